@@ -0,0 +1,344 @@
+//! # Arrow export
+//! Export components as [Apache Arrow](https://arrow.apache.org) record batches.
+//! # Details
+//! Every component in a batch must implement [ArrowComponent].
+//! A component is flattened into some columns named ```Component.field```,
+//! ```Component``` is [ArrowComponent::column_name] and ```field``` is the name in [ArrowComponent::fields].
+//! The first column is always ```entity_id``` whose type is ```UInt64```,
+//! it is the packed bits of [EntityId](crate::EntityId::to_bits).
+//! # Example
+//! ```
+//! use arrow::{array::{ArrayBuilder, Float32Builder}, datatypes::{DataType, Field}};
+//! use xecs::{World, arrow::{ArrowComponent, builder}};
+//! struct Position {
+//!     x : f32,
+//!     y : f32
+//! }
+//! impl ArrowComponent for Position {
+//!     fn fields() -> Vec<Field> {
+//!         vec![Field::new("x",DataType::Float32,false),Field::new("y",DataType::Float32,false)]
+//!     }
+//!     fn append(&self,builders : &mut [Box<dyn ArrayBuilder>]) {
+//!         builder::<Float32Builder>(builders,0).append_value(self.x);
+//!         builder::<Float32Builder>(builders,1).append_value(self.y);
+//!     }
+//! }
+//!
+//! let mut world = World::new();
+//! world.register::<Position>();
+//! world.create_entity().attach(Position { x : 1.0, y : 2.0 });
+//!
+//! let batch = world.to_arrow::<&Position>();
+//! assert_eq!(batch.num_rows(),1);
+//! assert_eq!(batch.schema().field(2).name(),"Position.y");
+//! ```
+use ::arrow::{
+    array::{make_builder, ArrayBuilder, ArrayRef, UInt64Builder},
+    datatypes::{Field, Schema},
+    record_batch::RecordBatch
+};
+use std::sync::Arc;
+use crate::{
+    component::Component,
+    group::{FullOwning, NonOwning, PartialOwning},
+    query::Queryable,
+    world::World
+};
+
+/// A component which can be flattened into some Arrow columns
+pub trait ArrowComponent : Component + Sized {
+    /// The name of this component in schema.
+    /// # Details
+    /// It's the last segment of type name by default.
+    fn column_name() -> &'static str {
+        std::any::type_name::<Self>()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default()
+    }
+    /// The fields of this component,their names are not prefixed
+    fn fields() -> Vec<Field>;
+    /// Append this component to ```builders``` in the order of [fields](ArrowComponent::fields).
+    /// # Details
+    /// ```builders``` are made from the data types of [fields](ArrowComponent::fields)
+    /// by [make_builder](arrow::array::make_builder),use [builder] to downcast them.
+    fn append(&self,builders : &mut [Box<dyn ArrayBuilder>]);
+}
+
+/// Downcast the builder at ```index``` to ```B```
+/// # Panics
+/// * Panic if the builder is not ```B```.
+pub fn builder<B : ArrayBuilder>(builders : &mut [Box<dyn ArrayBuilder>],index : usize) -> &mut B {
+    builders[index].as_any_mut()
+        .downcast_mut::<B>()
+        .expect("ArrowComponent: The type of builder does not match the field")
+}
+
+fn push_fields<T : ArrowComponent>(fields : &mut Vec<Field>) {
+    let name = T::column_name();
+    fields.extend(T::fields().into_iter()
+        .map(|field| {
+            let column = format!("{}.{}",name,field.name());
+            field.with_name(column)
+        }));
+}
+
+fn append_component<T : ArrowComponent>(component : &T,
+                                        builders : &mut [Box<dyn ArrayBuilder>],
+                                        offset : &mut usize) {
+    let count = T::fields().len();
+    let start = *offset;
+    *offset += count;
+    component.append(&mut builders[start..start + count]);
+}
+
+/// A query whose items can be written as Arrow rows.
+/// # Details
+/// It's implemented for ```&T```,tuples of them whose arities are from 2 to 8,
+/// and the group queries of ```(&A,&B)``` which iterate the packed part of group.
+pub trait ArrowQuery<'a> : Queryable<'a> {
+    /// Push the fields to ```fields```
+    fn fields(fields : &mut Vec<Field>);
+    /// Append the components of ```item``` to ```builders``` from ```offset```
+    fn append_item(item : &Self::Item,builders : &mut [Box<dyn ArrayBuilder>],offset : &mut usize);
+}
+
+impl<'a,T : ArrowComponent> ArrowQuery<'a> for &'a T {
+    fn fields(fields : &mut Vec<Field>) {
+        push_fields::<T>(fields);
+    }
+
+    fn append_item(item : &Self::Item,builders : &mut [Box<dyn ArrayBuilder>],offset : &mut usize) {
+        append_component(*item,builders,offset);
+    }
+}
+
+macro_rules! impl_arrow_query {
+    ($($name:ident $index:tt),*) => {
+        impl<'a,$($name : ArrowQuery<'a> + 'a),*> ArrowQuery<'a> for ($($name,)*) {
+            fn fields(fields : &mut Vec<Field>) {
+                $($name::fields(fields);)*
+            }
+
+            fn append_item(item : &Self::Item,builders : &mut [Box<dyn ArrayBuilder>],offset : &mut usize) {
+                $($name::append_item(&item.$index,builders,offset);)*
+            }
+        }
+    };
+}
+
+macro_rules! impl_arrow_group_query {
+    ($group:ident) => {
+        impl<'a,A : ArrowComponent,B : ArrowComponent> ArrowQuery<'a> for $group<&'a A,&'a B> {
+            fn fields(fields : &mut Vec<Field>) {
+                push_fields::<A>(fields);
+                push_fields::<B>(fields);
+            }
+
+            fn append_item(item : &Self::Item,builders : &mut [Box<dyn ArrayBuilder>],offset : &mut usize) {
+                append_component(item.0,builders,offset);
+                append_component(item.1,builders,offset);
+            }
+        }
+    };
+}
+
+impl_arrow_query!(A 0,B 1);
+impl_arrow_query!(A 0,B 1,C 2);
+impl_arrow_query!(A 0,B 1,C 2,D 3);
+impl_arrow_query!(A 0,B 1,C 2,D 3,E 4);
+impl_arrow_query!(A 0,B 1,C 2,D 3,E 4,F 5);
+impl_arrow_query!(A 0,B 1,C 2,D 3,E 4,F 5,G 6);
+impl_arrow_query!(A 0,B 1,C 2,D 3,E 4,F 5,G 6,H 7);
+
+impl_arrow_group_query!(FullOwning);
+impl_arrow_group_query!(PartialOwning);
+impl_arrow_group_query!(NonOwning);
+
+impl World {
+    /// Build a record batch from the items of query ```Q```.
+    /// # Details
+    /// The query is walked once,one row per entity.
+    /// The first column is ```entity_id```,then the columns of components.
+    /// Use [FullOwning](crate::group::FullOwning) or other group queries
+    /// to take the fast path of a group.
+    /// # Panics
+    /// * Panic if any component of ```Q``` is not registered.
+    /// * Panic if an [ArrowComponent] doesn't append exactly one value to each of its builders.
+    pub fn to_arrow<'a,Q : ArrowQuery<'a>>(&'a self) -> RecordBatch {
+        let mut fields = vec![Field::new("entity_id",::arrow::datatypes::DataType::UInt64,false)];
+        Q::fields(&mut fields);
+        let mut iter = self.query::<Q>();
+        let capacity = iter.size_hint().0;
+        let mut ids = UInt64Builder::with_capacity(capacity);
+        let mut builders = fields[1..].iter()
+            .map(|field|make_builder(field.data_type(),capacity))
+            .collect::<Vec<_>>();
+        while let Some((id,item)) = iter.next_with_id() {
+            ids.append_value(id.to_bits());
+            let mut offset = 0;
+            Q::append_item(&item,&mut builders,&mut offset);
+        }
+        let mut columns : Vec<ArrayRef> = vec![Arc::new(ids.finish())];
+        columns.extend(builders.iter_mut().map(|builder|builder.finish()));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)),columns)
+            .expect("World:Cannot export arrow because columns are not consistent with schema")
+    }
+
+    /// Build a record batch from all components of ```T``` with their IDs.
+    /// # Details
+    /// It's the same as ```to_arrow::<&T>()```.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn storage_to_arrow<T : ArrowComponent>(&self) -> RecordBatch {
+        self.to_arrow::<&T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arrow::{
+        array::{Array, ArrayBuilder, AsArray, Float32Builder, StringBuilder, UInt32Builder},
+        datatypes::{DataType, Field, Float32Type, UInt32Type, UInt64Type}
+    };
+    use crate::{entity::EntityId, group::{full_owning, FullOwning}, query::{QueryIterator, WithId}, world::World};
+    use super::{builder, ArrowComponent};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Position {
+        x : f32,
+        y : f32
+    }
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug,Clone,PartialEq)]
+    struct Name(Option<String>);
+
+    impl ArrowComponent for Position {
+        fn fields() -> Vec<Field> {
+            vec![Field::new("x",DataType::Float32,false),Field::new("y",DataType::Float32,false)]
+        }
+
+        fn append(&self,builders : &mut [Box<dyn ArrayBuilder>]) {
+            builder::<Float32Builder>(builders,0).append_value(self.x);
+            builder::<Float32Builder>(builders,1).append_value(self.y);
+        }
+    }
+
+    impl ArrowComponent for Health {
+        fn column_name() -> &'static str {
+            "hp"
+        }
+
+        fn fields() -> Vec<Field> {
+            vec![Field::new("value",DataType::UInt32,false)]
+        }
+
+        fn append(&self,builders : &mut [Box<dyn ArrayBuilder>]) {
+            builder::<UInt32Builder>(builders,0).append_value(self.0);
+        }
+    }
+
+    impl ArrowComponent for Name {
+        fn fields() -> Vec<Field> {
+            vec![Field::new("value",DataType::Utf8,true)]
+        }
+
+        fn append(&self,builders : &mut [Box<dyn ArrayBuilder>]) {
+            builder::<StringBuilder>(builders,0).append_option(self.0.as_deref());
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>().register::<Health>().register::<Name>();
+        for i in 0..10_u32 {
+            let entity = world.create_entity()
+                .attach(Position { x : i as f32, y : -(i as f32) });
+            if i % 3 != 0 {
+                entity.attach(Health(i * 10));
+            }
+        }
+        world.create_entity().attach(Name(Some("slime".to_string())));
+        world.create_entity().attach(Name(None));
+        world
+    }
+
+    #[test]
+    fn schema_test() {
+        let world = world();
+        let batch = world.to_arrow::<(&Position,&Health)>();
+        let schema = batch.schema();
+        let columns = schema.fields().iter()
+            .map(|field|(field.name().as_str(),field.data_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(columns,vec![
+            ("entity_id",DataType::UInt64),
+            ("Position.x",DataType::Float32),
+            ("Position.y",DataType::Float32),
+            ("hp.value",DataType::UInt32)
+        ]);
+        assert!(!schema.field(0).is_nullable());
+
+        let batch = world.storage_to_arrow::<Name>();
+        assert_eq!(batch.num_columns(),2);
+        assert!(batch.schema().field(1).is_nullable());
+    }
+
+    #[test]
+    fn rows_test() {
+        let world = world();
+        assert_eq!(world.to_arrow::<&Position>().num_rows(),world.query::<&Position>().count());
+        assert_eq!(world.to_arrow::<(&Position,&Health)>().num_rows(),
+                   world.query::<(&Position,&Health)>().count());
+        assert_eq!(world.to_arrow::<(&Health,&Position)>().num_rows(),6);
+        assert_eq!(world.storage_to_arrow::<Name>().num_rows(),2);
+
+        let mut world = World::new();
+        world.register::<Health>();
+        let batch = world.storage_to_arrow::<Health>();
+        assert_eq!((batch.num_rows(),batch.num_columns()),(0,2));
+    }
+
+    #[test]
+    fn values_test() {
+        let world = world();
+        let batch = world.to_arrow::<(&Position,&Health)>();
+        let ids = batch.column(0).as_primitive::<UInt64Type>();
+        let xs = batch.column(1).as_primitive::<Float32Type>();
+        let ys = batch.column(2).as_primitive::<Float32Type>();
+        let hps = batch.column(3).as_primitive::<UInt32Type>();
+        let expected = world.query::<(&Position,&Health)>()
+            .with_id()
+            .map(|(id,(pos,hp))|(id.to_bits(),pos.x,pos.y,hp.0))
+            .collect::<Vec<_>>();
+        let actual = (0..batch.num_rows())
+            .map(|row|(ids.value(row),xs.value(row),ys.value(row),hps.value(row)))
+            .collect::<Vec<_>>();
+        assert_eq!(actual,expected);
+
+        let batch = world.storage_to_arrow::<Name>();
+        let names = batch.column(1).as_string::<i32>();
+        assert_eq!(names.value(0),"slime");
+        assert!(names.is_null(1));
+    }
+
+    #[test]
+    fn group_test() {
+        let mut world = world();
+        world.make_group(full_owning::<Position,Health>());
+        let batch = world.to_arrow::<FullOwning<&Position,&Health>>();
+        assert_eq!(batch.num_rows(),6);
+        assert_eq!(batch.num_columns(),4);
+
+        let hps = batch.column(3).as_primitive::<UInt32Type>();
+        let ids = batch.column(0).as_primitive::<UInt64Type>();
+        let mut iter = world.query::<&Health>();
+        for row in 0..batch.num_rows() {
+            let id = EntityId::from_bits(ids.value(row)).unwrap();
+            assert_eq!(iter.from_id(id).unwrap().0,hps.value(row));
+        }
+    }
+}
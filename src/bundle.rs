@@ -0,0 +1,94 @@
+use std::{any::{Any, TypeId}, error::Error, fmt::{Display, Formatter}};
+use crate::{component::Component, entity::Entity, world::World};
+
+/// Type-erased component
+pub type AnyComponent = Box<dyn Any + Send + Sync>;
+
+/// A set of components which are handled together.
+/// # Details
+/// It's implemented for tuples of [Component](crate::component::Component)s,
+/// the arities are from 1 to 8.
+/// # Example
+/// ```
+/// use xecs::World;
+/// struct Transform(f32);
+/// struct Visibility(bool);
+///
+/// let mut world = World::new();
+/// world.register_bundle::<(Transform,Visibility)>();
+/// let id = world.create_entity_with_bundle((Transform(1.0),Visibility(true))).into_id();
+/// assert!(world.has_bundle::<(Transform,Visibility)>(id));
+/// ```
+pub trait Bundle : Sized + Send + Sync + 'static {
+    /// Push the type IDs and names of components to ```types``` in declared order
+    fn component_types(types : &mut Vec<(TypeId,&'static str)>);
+    /// Build the bundle from type-erased components in declared order.
+    /// # Details
+    /// Return ```None``` if the count or any type of ```components``` is mismatched.
+    fn from_components(components : Vec<AnyComponent>) -> Option<Self>;
+    /// Register the components which have not been registered
+    fn register(world : &mut World);
+    /// Attach all components to ```entity``` in declared order
+    fn attach(self,entity : Entity<'_>) -> Entity<'_>;
+}
+
+macro_rules! impl_bundle {
+    ($($name:ident),*) => {
+        impl<$($name : Component),*> Bundle for ($($name,)*) {
+            fn component_types(types : &mut Vec<(TypeId,&'static str)>) {
+                $(types.push((TypeId::of::<$name>(),std::any::type_name::<$name>()));)*
+            }
+
+            #[allow(non_snake_case)]
+            fn from_components(components : Vec<AnyComponent>) -> Option<Self> {
+                let mut components = components.into_iter();
+                $(let $name = *components.next()?.downcast::<$name>().ok()?;)*
+                if components.next().is_some() {
+                    return None;
+                }
+                Some(($($name,)*))
+            }
+
+            fn register(world : &mut World) {
+                $(if !world.has_registered::<$name>() {
+                    world.register::<$name>();
+                })*
+            }
+
+            #[allow(non_snake_case)]
+            fn attach(self,entity : Entity<'_>) -> Entity<'_> {
+                let ($($name,)*) = self;
+                entity$(.attach($name))*
+            }
+        }
+    };
+}
+
+impl_bundle!(A);
+impl_bundle!(A,B);
+impl_bundle!(A,B,C);
+impl_bundle!(A,B,C,D);
+impl_bundle!(A,B,C,D,E);
+impl_bundle!(A,B,C,D,E,F);
+impl_bundle!(A,B,C,D,E,F,G);
+impl_bundle!(A,B,C,D,E,F,G,H);
+
+/// The error when entity doesn't have all components of a bundle
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct RemoveBundleError {
+    /// The type names of missing components,
+    /// it's empty if the entity doesn't exist
+    pub missing : Vec<&'static str>
+}
+
+impl Display for RemoveBundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.missing.is_empty() {
+            write!(f,"Cannot remove bundle from a non-existence entity")
+        } else {
+            write!(f,"Cannot remove bundle because components are missing: {}",self.missing.join(","))
+        }
+    }
+}
+
+impl Error for RemoveBundleError {}
@@ -0,0 +1,272 @@
+//! # Command buffer
+//! Structural changes cannot be made while a query holds the locks of storages.
+//! [Commands](crate::command::Commands) records them as [WorldMutation](crate::command::WorldMutation)s,
+//! and applies them after all iterators are dropped.
+//! # Example
+//! ```
+//! use xecs::{World, command::Commands, query::WithId};
+//! let mut world = World::new();
+//! world.register::<u32>();
+//! world.insert_resource(Commands::new());
+//! for i in 0..4_u32 {
+//!     world.create_entity().attach(i);
+//! }
+//!
+//! {
+//!     let mut commands = world.resource_mut::<Commands>().unwrap();
+//!     for (id,x) in world.query::<&u32>().with_id() {
+//!         if *x % 2 == 0 {
+//!             commands.despawn(id);
+//!         }
+//!     }
+//! }
+//! world.flush_commands();
+//! assert_eq!(world.query::<&u32>().count(),2);
+//! ```
+use std::{any::TypeId, marker::PhantomData};
+use crate::{bundle::AnyComponent, component::Component, entity::EntityId, world::World};
+
+/// A deferred structural change
+pub trait WorldMutation : Send + Sync {
+    /// Apply this change to world
+    fn apply(self : Box<Self>,world : &mut World);
+}
+
+/// Create an entity with some components
+#[derive(Default)]
+pub struct SpawnEntity {
+    components : Vec<(TypeId,AnyComponent)>
+}
+
+impl SpawnEntity {
+    /// Create an empty entity
+    pub fn new() -> Self {
+        SpawnEntity::default()
+    }
+
+    /// Attach ```component``` to the created entity
+    pub fn with<T : Component>(mut self,component : T) -> Self {
+        self.components.push((TypeId::of::<T>(),Box::new(component)));
+        self
+    }
+}
+
+impl WorldMutation for SpawnEntity {
+    /// # Panics
+    /// * Panic if any component is not registered.
+    fn apply(self : Box<Self>,world : &mut World) {
+        let id = world.create_entity().into_id();
+        for (type_id,component) in self.components {
+            world.attach_any(id,type_id,component);
+        }
+    }
+}
+
+/// Remove an entity,nothing happens if it's not alive
+pub struct DespawnEntity(pub EntityId);
+
+impl WorldMutation for DespawnEntity {
+    fn apply(self : Box<Self>,world : &mut World) {
+        world.remove_entity(self.0);
+    }
+}
+
+/// Attach a component to an entity,nothing happens if it's not alive
+pub struct AddComponent<T> {
+    pub entity_id : EntityId,
+    pub component : T
+}
+
+impl<T : Component> WorldMutation for AddComponent<T> {
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    fn apply(self : Box<Self>,world : &mut World) {
+        if world.exist(self.entity_id) {
+            world.attach_component(self.entity_id,self.component);
+        }
+    }
+}
+
+/// Detach a component from an entity,nothing happens if it's not alive
+pub struct RemoveComponent<T> {
+    pub entity_id : EntityId,
+    _marker : PhantomData<T>
+}
+
+impl<T> RemoveComponent<T> {
+    pub fn new(entity_id : EntityId) -> Self {
+        RemoveComponent {
+            entity_id,
+            _marker : PhantomData
+        }
+    }
+}
+
+impl<T : Component> WorldMutation for RemoveComponent<T> {
+    fn apply(self : Box<Self>,world : &mut World) {
+        world.remove_component::<T>(self.entity_id);
+    }
+}
+
+/// A buffer of [WorldMutation]s.
+/// # Details
+/// The mutations are applied in recording order by [flush](Commands::flush).
+/// It can be a resource of world,then [flush_commands](crate::world::World::flush_commands) applies it,
+/// or be applied by [apply_commands](crate::world::World::apply_commands) directly.
+#[derive(Default)]
+pub struct Commands {
+    mutations : Vec<Box<dyn WorldMutation>>
+}
+
+impl Commands {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Commands::default()
+    }
+
+    /// Record a mutation
+    pub fn push<M : WorldMutation + 'static>(&mut self,mutation : M) -> &mut Self {
+        self.mutations.push(Box::new(mutation));
+        self
+    }
+
+    /// Record a [SpawnEntity]
+    pub fn spawn(&mut self,spawn : SpawnEntity) -> &mut Self {
+        self.push(spawn)
+    }
+
+    /// Record a [DespawnEntity]
+    pub fn despawn(&mut self,entity_id : EntityId) -> &mut Self {
+        self.push(DespawnEntity(entity_id))
+    }
+
+    /// Record an [AddComponent]
+    pub fn add_component<T : Component>(&mut self,entity_id : EntityId,component : T) -> &mut Self {
+        self.push(AddComponent { entity_id, component })
+    }
+
+    /// Record a [RemoveComponent]
+    pub fn remove_component<T : Component>(&mut self,entity_id : EntityId) -> &mut Self {
+        self.push(RemoveComponent::<T>::new(entity_id))
+    }
+
+    /// Get the count of recorded mutations
+    pub fn len(&self) -> usize {
+        self.mutations.len()
+    }
+
+    /// Check if nothing is recorded
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+
+    /// Apply all mutations to ```world``` in recording order,then clear the buffer
+    pub fn flush(&mut self,world : &mut World) {
+        for mutation in self.mutations.drain(..) {
+            mutation.apply(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{query::WithId, world::World};
+    use super::{Commands, SpawnEntity, WorldMutation};
+
+    #[test]
+    fn commands_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..5_u32).map(|i|world.create_entity().attach(i).into_id())
+            .collect::<Vec<_>>();
+
+        let mut commands = Commands::new();
+        for (id,x) in world.query::<&mut u32>().with_id() {
+            *x += 10;
+            match *x % 3 {
+                0 => { commands.despawn(id); },
+                1 => { commands.add_component(id,'a'); },
+                _ => { commands.remove_component::<u32>(id); }
+            }
+        }
+        commands.spawn(SpawnEntity::new().with(100_u32).with('z'));
+        assert_eq!(commands.len(),6);
+        commands.flush(&mut world);
+        assert!(commands.is_empty());
+
+        // 10:'a' 11:removed 12:despawned 13:'a' 14:removed
+        // the index of despawned entity is reused by the spawned one
+        assert!(!world.is_alive(ids[2]));
+        assert_eq!(world.query::<&u32>().count(),3);
+        let mut chars = world.query::<(&u32,&char)>()
+            .map(|(x,c)|(*x,*c))
+            .collect::<Vec<_>>();
+        chars.sort();
+        assert_eq!(chars,vec![(10,'a'),(13,'a'),(100,'z')]);
+    }
+
+    #[test]
+    fn order_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        let id = world.create_entity().into_id();
+        let mut commands = Commands::new();
+        // the entity is dead when the component is added
+        commands.despawn(id)
+            .add_component(id,1_u32)
+            .remove_component::<u32>(id)
+            .despawn(id);
+        commands.flush(&mut world);
+        assert!(!world.exist(id));
+        assert_eq!(world.query::<&u32>().count(),0);
+
+        struct Count(u32);
+        impl WorldMutation for Count {
+            fn apply(self : Box<Self>,world : &mut World) {
+                for _ in 0..self.0 {
+                    world.create_entity().attach(self.0);
+                }
+            }
+        }
+        commands.push(Count(3));
+        commands.flush(&mut world);
+        assert_eq!(world.query::<&u32>().count(),3);
+    }
+
+    #[test]
+    fn apply_commands_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let id = world.create_entity().attach(1_u32).into_id();
+        let mut commands = Commands::new();
+        for (id,x) in world.query::<&u32>().with_id() {
+            commands.add_component(id,'a')
+                .spawn(SpawnEntity::new().with(*x + 1));
+        }
+        world.apply_commands(commands);
+        assert_eq!(world.get::<char>(id).as_deref(),Some(&'a'));
+        let mut xs = world.query::<&u32>().copied().collect::<Vec<_>>();
+        xs.sort();
+        assert_eq!(xs,vec![1,2]);
+    }
+
+    #[test]
+    fn resource_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        world.flush_commands();
+        world.insert_resource(Commands::new());
+        let id = world.create_entity().attach(1_u32).into_id();
+        {
+            let mut commands = world.fetch_resource::<&mut Commands>().unwrap();
+            for (id,_) in world.query::<&u32>().with_id() {
+                commands.despawn(id);
+                commands.push(SpawnEntity::new().with(2_u32));
+            }
+        }
+        world.flush_commands();
+        assert!(!world.is_alive(id));
+        assert_eq!(world.query::<&u32>().copied().collect::<Vec<_>>(),vec![2]);
+        assert!(world.resource::<Commands>().unwrap().is_empty());
+    }
+}
@@ -1,11 +1,23 @@
-use std::ops::{Deref, DerefMut};
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
-use crate::{entity::EntityId, sparse_set::SparseSet};
+use std::{any::{Any, TypeId}, fmt::{Debug, Formatter}, marker::PhantomData, mem::ManuallyDrop, ops::{Deref, DerefMut}, sync::Arc};
+use crate::world::World;
+use crate::lock::{StorageReadGuard, StorageWriteGuard};
+use crate::{entity::EntityId, sparse_set::{Entry, SparseSet}};
 
 /// The Component trait  
 pub trait Component : Send + Sync + 'static {}
 impl<T : Send + Sync + 'static> Component for T {}
 
+/// The kind of a component storage
+#[non_exhaustive]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum StorageKind {
+    /// A [SparseSet](crate::sparse_set::SparseSet) with ```Vec``` as sparse indices
+    SparseSet,
+    /// A [SparseSet](crate::sparse_set::SparseSet) with a back buffer,
+    /// see [register_double_buffered](crate::world::World::register_double_buffered)
+    DoubleBuffered
+}
+
 /// A trait to make sparse set dynamic  
 pub trait ComponentStorage : Send + Sync{
     /// Check if storage has ```entity_id```
@@ -16,10 +28,59 @@ pub trait ComponentStorage : Send + Sync{
     fn id(&self,index : usize) -> Option<EntityId>;
     /// Remove entity by ```entity_id```
     fn remove(&mut self,entity_id : EntityId);
+    /// Get the type-erased component of ```entity_id```
+    fn get_any(&self,entity_id : EntityId) -> Option<&dyn Any>;
+    /// Remove entity by ```entity_id``` and return its type-erased component
+    fn remove_any(&mut self,entity_id : EntityId) -> Option<Box<dyn Any + Send + Sync>>;
+    /// Insert a type-erased component to ```entity_id```
+    /// # Panics
+    /// Panic if the type of ```component``` is not the type of storage
+    fn insert_any(&mut self,entity_id : EntityId,component : Box<dyn Any + Send + Sync>);
+    /// Overwrite the type-erased component of ```entity_id``` in place and set its change tick to ```tick```
+    /// # Details
+    /// The position,the added tick and the attach stamp are kept.
+    /// Return the old component with its old change tick,
+    /// or ```None``` if ```entity_id``` is not in storage,the component is dropped in this case.
+    /// # Panics
+    /// Panic if the type of ```component``` is not the type of storage
+    fn replace_any(&mut self,entity_id : EntityId,component : Box<dyn Any + Send + Sync>,tick : u64)
+        -> Option<(Box<dyn Any + Send + Sync>,u64)>;
+    /// Move a type-erased component to ```entity_id``` without allocation
+    /// # Details
+    /// Return ```false``` if the type of ```value``` is not the type of storage,
+    /// the component is dropped in this case.
+    fn insert_erased(&mut self,entity_id : EntityId,value : ErasedValue<'_>) -> bool;
+    /// Keep the entities which ```f``` returns ```true```,remove the others.
+    /// # Details
+    /// Like [remove](ComponentStorage::remove),groups are not maintained.
+    fn retain_by(&mut self,f : &mut dyn FnMut(EntityId) -> bool);
+    /// Remove all entities and return their type-erased components in dense order.
+    /// # Details
+    /// Like [remove](ComponentStorage::remove),groups are not maintained.
+    fn drain(&mut self) -> Vec<(EntityId,Box<dyn Any + Send + Sync>)>;
+    /// Remove all entities and drop their components.
+    /// # Details
+    /// Like [remove](ComponentStorage::remove),groups are not maintained.
+    fn clear(&mut self);
     /// Swap two items by their indices
     fn swap_by_index(&mut self,index_a : usize,index_b : usize);
     /// Get how many item in storage
     fn count(&self) -> usize;
+    /// Get the structural version,
+    /// it's increased when an entity is added,removed or moved in storage
+    fn version(&self) -> u64;
+    /// Set the structural version,
+    /// it's used to keep the version increasing when the storage is replaced
+    fn set_version(&mut self,version : u64);
+    /// Get the presence bitmap indexed by entity ID,
+    /// return ```None``` if it's not enabled
+    fn presence(&self) -> Option<&[u64]> {
+        None
+    }
+    /// Mark the component of ```entity_id``` is added at ```tick```,
+    /// see [Added](crate::query::Added).
+    /// Do nothing by default.
+    fn mark_added(&mut self,_entity_id : EntityId,_tick : u64) {}
     /// Check if storage is empty
     fn is_empty(&self) -> bool{
         self.count() == 0
@@ -43,6 +104,64 @@ impl<T : Component> ComponentStorage for SparseSet<EntityId,T>{
         self.remove(entity_id).unwrap();
     }
 
+    fn get_any(&self, entity_id: EntityId) -> Option<&dyn Any> {
+        self.get(entity_id)
+            .map(|component|component as &dyn Any)
+    }
+
+    fn remove_any(&mut self, entity_id: EntityId) -> Option<Box<dyn Any + Send + Sync>> {
+        self.remove(entity_id)
+            .map(|component| Box::new(component) as Box<dyn Any + Send + Sync>)
+    }
+
+    fn insert_any(&mut self, entity_id: EntityId, component: Box<dyn Any + Send + Sync>) {
+        let component = component.downcast::<T>()
+            .expect("ComponentStorage:Cannot insert a component with mismatched type");
+        self.add(entity_id,*component);
+    }
+
+    fn replace_any(&mut self, entity_id: EntityId, component: Box<dyn Any + Send + Sync>, tick: u64)
+        -> Option<(Box<dyn Any + Send + Sync>,u64)> {
+        let component = component.downcast::<T>()
+            .expect("ComponentStorage:Cannot replace a component with mismatched type");
+        let index = self.get_index(entity_id)?;
+        // Unwrap here never fails
+        // index is in range of data
+        let old_tick = self.tick(index).unwrap();
+        let old = match self.entry(entity_id) {
+            Entry::Occupied(mut entry) => entry.insert(*component),
+            // get_index ensures the entity is in sparse set
+            Entry::Vacant(_) => unreachable!()
+        };
+        self.mark_changed(index,tick);
+        Some((Box::new(old),old_tick))
+    }
+
+    fn insert_erased(&mut self, entity_id: EntityId, value: ErasedValue<'_>) -> bool {
+        if value.type_id() != TypeId::of::<T>() {
+            return false;
+        }
+        // Safety:
+        // the type of value is checked above
+        let component = unsafe { value.take::<T>() };
+        self.add(entity_id,component);
+        true
+    }
+
+    fn retain_by(&mut self, f: &mut dyn FnMut(EntityId) -> bool) {
+        self.retain(|entity_id,_|f(entity_id));
+    }
+
+    fn drain(&mut self) -> Vec<(EntityId,Box<dyn Any + Send + Sync>)> {
+        self.drain()
+            .map(|(entity_id,component)|(entity_id,Box::new(component) as Box<dyn Any + Send + Sync>))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+
     fn swap_by_index(&mut self, index_a: usize, index_b: usize) {
         self.swap_by_index(index_a,index_b);
     }
@@ -51,6 +170,23 @@ impl<T : Component> ComponentStorage for SparseSet<EntityId,T>{
         self.len()
     }
 
+    fn version(&self) -> u64 {
+        self.version()
+    }
+
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.presence()
+    }
+
+    fn mark_added(&mut self, entity_id: EntityId, tick: u64) {
+        if let Some(index) = self.get_index(entity_id) {
+            self.mark_added_at(index,tick);
+        }
+    }
 }
 
 impl dyn 'static + ComponentStorage {
@@ -62,14 +198,201 @@ impl dyn 'static + ComponentStorage {
     }
 }
 
+/// Swap dense positions in several component storages together.
+/// # Details
+/// All indices are validated before any swap,
+/// so the storages are never left partially swapped.  
+/// It's used by [groups](crate::group) to keep the owned storages aligned.
+/// # Example
+/// ```
+/// use xecs::{ComponentStorage, MultiStorageSwap};
+/// fn swap_front(a : &mut dyn ComponentStorage,b : &mut dyn ComponentStorage,index : usize) {
+///     MultiStorageSwap::new(&mut [a,b]).swap(index,0);
+/// }
+/// ```
+pub struct MultiStorageSwap<'s,'a> {
+    storages : &'s mut [&'a mut dyn ComponentStorage]
+}
+
+impl<'s,'a> MultiStorageSwap<'s,'a> {
+    /// Create a swap over ```storages```
+    pub fn new(storages : &'s mut [&'a mut dyn ComponentStorage]) -> Self {
+        MultiStorageSwap {
+            storages
+        }
+    }
+
+    /// Check if ```pair(i)``` is in the bounds of ```i```th storage for all storages
+    fn validate<F : Fn(usize) -> (usize,usize)>(&self,pair : &F) -> bool {
+        self.storages.iter()
+            .enumerate()
+            .all(|(i,storage)| {
+                let (index_a,index_b) = pair(i);
+                index_a < storage.count() && index_b < storage.count()
+            })
+    }
+
+    fn apply<F : Fn(usize) -> (usize,usize)>(&mut self,pair : F) {
+        assert!(self.validate(&pair),
+                "MultiStorageSwap:Cannot swap because index is out of bounds");
+        for (i,storage) in self.storages.iter_mut().enumerate() {
+            let (index_a,index_b) = pair(i);
+            storage.swap_by_index(index_a,index_b);
+        }
+    }
+
+    /// Swap ```index_a``` and ```index_b``` in all storages
+    /// # Panics
+    /// Panic if any index is out of bounds,no storage is swapped in this case
+    pub fn swap(&mut self,index_a : usize,index_b : usize) {
+        self.apply(|_|(index_a,index_b))
+    }
+
+    /// Swap ```pairs[i]``` in ```i```th storage
+    /// # Panics
+    /// * Panic if ```pairs.len()``` is not equal to the count of storages
+    /// * Panic if any index is out of bounds,no storage is swapped in this case
+    pub fn swap_each(&mut self,pairs : &[(usize,usize)]) {
+        assert_eq!(pairs.len(),self.storages.len(),
+                   "MultiStorageSwap:The count of pairs is not equal to the count of storages");
+        self.apply(|i|pairs[i])
+    }
+}
+
+/// A type-erased component borrowed from caller
+/// # Details
+/// The component is moved out when it's inserted into a storage,
+/// otherwise it's dropped when ```ErasedValue``` is dropped.
+/// So the component is always dropped exactly once and no heap allocation is needed.
+/// # Example
+/// ```
+/// use std::mem::ManuallyDrop;
+/// use xecs::{World, ErasedValue};
+/// let mut world = World::new();
+/// world.register::<String>();
+/// let id = world.create_entity().into_id();
+///
+/// let mut value = ManuallyDrop::new(String::from("xecs"));
+/// // Safety:
+/// // value is never used again
+/// world.attach_erased(id,unsafe { ErasedValue::new(&mut value) });
+/// assert_eq!(*world.entity_component_read::<String>(id).unwrap(),"xecs");
+/// ```
+pub struct ErasedValue<'a> {
+    ptr : *mut u8,
+    type_id : TypeId,
+    drop : unsafe fn(*mut u8),
+    moved : bool,
+    _marker : PhantomData<&'a mut ()>
+}
+
+/// Drop a ```T``` in place
+unsafe fn drop_erased<T>(ptr : *mut u8) {
+    std::ptr::drop_in_place(ptr as *mut T)
+}
+
+impl<'a> ErasedValue<'a> {
+    /// Borrow ```value``` as a type-erased component.
+    /// # Details
+    /// ```value``` is always moved out or dropped by ```ErasedValue```.
+    /// # Safety
+    /// * ```value``` must not be read,dropped or borrowed again after ```ErasedValue``` is created,
+    ///   otherwise it's a use-after-move or a double drop.
+    pub unsafe fn new<T : Component>(value : &'a mut ManuallyDrop<T>) -> Self {
+        ErasedValue {
+            ptr : &mut **value as *mut T as *mut u8,
+            type_id : TypeId::of::<T>(),
+            drop : drop_erased::<T>,
+            moved : false,
+            _marker : PhantomData
+        }
+    }
+
+    /// Get the type ID of component
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Move the component out
+    /// # Safety
+    /// * ```T``` must be the type of component
+    pub(in crate) unsafe fn take<T : Component>(mut self) -> T {
+        self.moved = true;
+        std::ptr::read(self.ptr as *const T)
+    }
+}
+
+impl<'a> Drop for ErasedValue<'a> {
+    fn drop(&mut self) {
+        if !self.moved {
+            // Safety:
+            // ptr points to a valid component which has not been moved out
+            unsafe { (self.drop)(self.ptr) }
+        }
+    }
+}
+
+impl<'a> Debug for ErasedValue<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ErasedValue")
+            .field("type_id",&self.type_id)
+            .field("moved",&self.moved)
+            .finish()
+    }
+}
+
+/// A snapshot of the entity IDs in a component storage
+/// # Details
+/// It's got from [ids_snapshot](crate::world::World::ids_snapshot).
+/// The snapshot is immutable and cheap to clone,
+/// it's still valid after the storage is changed.
+#[derive(Debug,Clone)]
+pub struct IdsSnapshot {
+    type_id : TypeId,
+    version : u64,
+    ids : Arc<[EntityId]>
+}
+
+impl IdsSnapshot {
+    pub(in crate) fn new(type_id : TypeId,version : u64,ids : Arc<[EntityId]>) -> Self {
+        IdsSnapshot {
+            type_id,
+            version,
+            ids
+        }
+    }
+
+    /// Get the IDs
+    pub fn as_slice(&self) -> &[EntityId] {
+        &self.ids
+    }
+
+    /// Get the structural version of storage when snapshot was taken
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Check if storage has not been structurally changed since snapshot was taken
+    pub fn is_current(&self,world : &World) -> bool {
+        world.raw_storage_read(self.type_id)
+            .map(|storage|storage.version() == self.version)
+            .unwrap_or(false)
+    }
+
+    #[cfg(test)]
+    pub(in crate) fn arc(&self) -> &Arc<[EntityId]> {
+        &self.ids
+    }
+}
+
 /// A read gurad for component storage
 pub struct StorageRead<'a,T>{
-    _lock : RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    _lock : StorageReadGuard<'a>,
     ptr : *const SparseSet<EntityId,T>
 }
 
 impl<'a,T : Component> StorageRead<'a,T> {
-    pub(in crate) fn from_lock(lock : RwLockReadGuard<'a,Box<dyn ComponentStorage>>) -> Self {
+    pub(in crate) fn from_lock(lock : StorageReadGuard<'a>) -> Self {
         // Safety:
         // 1.box has type SparseSet<EntityId,T>
         let ptr = unsafe {
@@ -121,18 +444,36 @@ impl<'a,T : Component> StorageRead<'a,T> {
         sparse_set.data()
     }
 
+    /// Get the entity IDs slice,it has the same order as [data](crate::component::StorageRead::data)
+    pub fn ids(&self) -> &[EntityId] {
+        let sparse_set = unsafe { &*self.ptr };
+        sparse_set.entities()
+    }
+
+    /// Get the structural version of storage
+    pub fn version(&self) -> u64 {
+        let sparse_set = unsafe { &*self.ptr };
+        sparse_set.version()
+    }
+
+    /// Get the version when entity got its component
+    pub(in crate) fn stamp(&self,id : EntityId) -> Option<u64> {
+        let sparse_set = unsafe { &*self.ptr };
+        sparse_set.stamp(id)
+    }
+
 }
 
 
 
 /// A write gurad for component storage
 pub struct StorageWrite<'a,T>{
-    _lock : RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    _lock : StorageWriteGuard<'a>,
     ptr : *mut SparseSet<EntityId,T>
 }
 
 impl<'a,T : Component> StorageWrite<'a,T> {
-    pub(in crate) fn from_lock(mut lock : RwLockWriteGuard<'a,Box<dyn ComponentStorage>>) -> Self {
+    pub(in crate) fn from_lock(mut lock : StorageWriteGuard<'a>) -> Self {
         // Safety:
         // 1.box has type SparseSet<EntityId,T>
         let ptr = unsafe {
@@ -156,6 +497,12 @@ impl<'a,T : Component> StorageWrite<'a,T> {
         sparse_set.exist(id)
     }
 
+    /// Get the version when entity got its component
+    pub(in crate) fn stamp(&self,id : EntityId) -> Option<u64> {
+        let sparse_set = unsafe { &*self.ptr };
+        sparse_set.stamp(id)
+    }
+
     /// Get component from storage by id
     /// # Details
     /// * Return None if id is not in storage
@@ -205,6 +552,15 @@ impl<'a,T : Component> StorageWrite<'a,T> {
         let sparse_set = unsafe { &mut *self.ptr };
         sparse_set.data_mut()
     }
+
+    /// Mark the component of ```id``` is changed at ```tick```,
+    /// do nothing if id is not in storage
+    pub(in crate) fn mark_changed(&mut self,id : EntityId,tick : u64) {
+        let sparse_set = unsafe { &mut *self.ptr };
+        if let Some(index) = sparse_set.get_index(id) {
+            sparse_set.mark_changed(index,tick);
+        }
+    }
 }
 
 
@@ -240,19 +596,54 @@ impl<'a,T : Component> Deref for ComponentRead<'a,T> {
 
 
 
+/// A read guard for a type-erased component,
+/// see [get_component_dynamic](crate::world::World::get_component_dynamic).
+pub struct AnyComponentRead<'a> {
+    id : EntityId,
+    storage : StorageReadGuard<'a>
+}
+
+impl<'a> AnyComponentRead<'a> {
+    /// Safety:
+    /// Safe only id is in storage
+    pub(in crate) unsafe fn new(id : EntityId,storage : StorageReadGuard<'a>) -> Self {
+        AnyComponentRead {
+            id,
+            storage
+        }
+    }
+}
+
+impl<'a> Deref for AnyComponentRead<'a> {
+    type Target = dyn Any;
+
+    fn deref(&self) -> &Self::Target {
+        // Unwrap here never fails
+        // new() method ensures the id is in storage,
+        // and it cannot be removed while storage is borrowed
+        self.storage.get_any(self.id).unwrap()
+    }
+}
+
+
+
 /// A write guard for component
+/// # Details
+/// The component is marked as [changed](crate::query::Changed) when it's dereferenced mutably.
 pub struct ComponentWrite<'a,T> {
     id : EntityId,
-    storage : StorageWrite<'a,T>
+    storage : StorageWrite<'a,T>,
+    tick : u64
 }
 
 impl<'a,T : Component> ComponentWrite<'a,T> {
     /// Safety:
     /// Safe only id is valid
-    pub(in crate) unsafe fn new(id : EntityId,storage : StorageWrite<'a,T>) -> Self {
+    pub(in crate) unsafe fn new(id : EntityId,storage : StorageWrite<'a,T>,tick : u64) -> Self {
         ComponentWrite{
             id,
             storage,
+            tick
         }
     }
 }
@@ -270,6 +661,7 @@ impl<'a,T : Component> Deref for ComponentWrite<'a,T> {
 }
 impl<'a,T : Component> DerefMut for ComponentWrite<'a,T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.storage.mark_changed(self.id,self.tick);
         // Safety:
         // new() method ensures this action is safe
         unsafe {
@@ -277,3 +669,138 @@ impl<'a,T : Component> DerefMut for ComponentWrite<'a,T> {
         }
     }
 }
+
+
+
+/// A weak reference to the component of an entity.
+/// # Details
+/// It does not hold any lock, so it can be stored across frames.
+/// Use [resolve](crate::world::World::resolve) or [resolve_mut](crate::world::World::resolve_mut)
+/// to access the component.
+/// ## Staleness
+/// The pointer records the [EntityId](crate::entity::EntityId) with its generation.
+/// * If the entity was removed,resolving returns ```None```,
+///   even if its ID was reused by another entity.
+/// * If the entity lost the component,resolving returns ```None```.
+/// * If the entity lost the component and then got a new one,
+///   resolving returns ```None``` too.
+///   The pointer records when the component was attached to check this.
+/// * If the component was replaced in place by [attach](crate::entity::Entity::attach),
+///   resolving returns the new value.
+pub struct ComponentPtr<T> {
+    pub(in crate) id : EntityId,
+    // the storage version when the component was attached
+    pub(in crate) stamp : u64,
+    _marker : PhantomData<fn() -> T>
+}
+
+impl<T : Component> ComponentPtr<T> {
+    pub(in crate) fn new(id : EntityId,stamp : u64) -> Self {
+        ComponentPtr {
+            id,
+            stamp,
+            _marker : PhantomData
+        }
+    }
+
+    /// Get the ID of entity which owns the component
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+}
+
+impl<T> Clone for ComponentPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ComponentPtr<T> {}
+
+impl<T> Debug for ComponentPtr<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentPtr")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{mem::ManuallyDrop, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use crate::{entity::EntityId, sparse_set::SparseSet};
+    use super::{ComponentStorage, ErasedValue, MultiStorageSwap};
+
+    struct DropCounter(Arc<AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1,Ordering::SeqCst);
+        }
+    }
+
+    fn id(id : usize) -> EntityId {
+        EntityId::new(id).unwrap()
+    }
+
+    #[test]
+    fn insert_erased_test() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut storage : Box<dyn ComponentStorage> = Box::new(SparseSet::<EntityId,DropCounter>::new());
+
+        // success
+        let mut value = ManuallyDrop::new(DropCounter(drops.clone()));
+        assert!(storage.insert_erased(id(1),unsafe { ErasedValue::new(&mut value) }));
+        assert_eq!(drops.load(Ordering::SeqCst),0);
+        assert!(storage.has(id(1)));
+
+        // replace existing: the old component is dropped once
+        let mut value = ManuallyDrop::new(DropCounter(drops.clone()));
+        assert!(storage.insert_erased(id(1),unsafe { ErasedValue::new(&mut value) }));
+        assert_eq!(drops.load(Ordering::SeqCst),1);
+        assert_eq!(storage.count(),1);
+
+        // failure: mismatched type is dropped once
+        let mut value = ManuallyDrop::new((DropCounter(drops.clone()),0_u32));
+        assert!(!storage.insert_erased(id(2),unsafe { ErasedValue::new(&mut value) }));
+        assert_eq!(drops.load(Ordering::SeqCst),2);
+        assert!(!storage.has(id(2)));
+
+        // not inserted at all
+        let mut value = ManuallyDrop::new(DropCounter(drops.clone()));
+        drop(unsafe { ErasedValue::new(&mut value) });
+        assert_eq!(drops.load(Ordering::SeqCst),3);
+
+        drop(storage);
+        assert_eq!(drops.load(Ordering::SeqCst),4);
+    }
+
+    #[test]
+    fn multi_storage_swap_test() {
+        let mut a = SparseSet::<EntityId,char>::new();
+        let mut b = SparseSet::<EntityId,u32>::new();
+        for (i,c) in ['a','b','c'].iter().enumerate() {
+            a.add(id(i + 1),*c);
+        }
+        b.add(id(1),1);
+        b.add(id(2),2);
+
+        MultiStorageSwap::new(&mut [&mut a,&mut b]).swap(0,1);
+        assert_eq!(a.data(),&['b','a','c']);
+        assert_eq!(b.data(),&[2,1]);
+
+        MultiStorageSwap::new(&mut [&mut a,&mut b]).swap_each(&[(0,2),(0,1)]);
+        assert_eq!(a.data(),&['c','a','b']);
+        assert_eq!(b.data(),&[1,2]);
+
+        // index 2 is out of bounds in b,a must not be swapped
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            MultiStorageSwap::new(&mut [&mut a,&mut b]).swap(0,2);
+        }));
+        assert!(result.is_err());
+        assert_eq!(a.data(),&['c','a','b']);
+        assert_eq!(b.data(),&[1,2]);
+        assert_eq!(a.get_index(id(3)),Some(0));
+    }
+}
@@ -1,9 +1,127 @@
-use std::{any::TypeId, num::NonZeroUsize, ops::Range};
-use parking_lot::RwLockReadGuard;
-use crate::{component::{Component, ComponentRead, ComponentWrite}, group::Group, sparse_set::SparseSet, world::World};
+use std::{any::TypeId, cmp::Ordering, error::Error, fmt::{Debug, Display, Formatter}, num::{NonZeroU32, NonZeroU64}, ops::Range};
+use crate::lock::EntityManagerReadGuard;
+use crate::{bundle::Bundle, component::{Component, ComponentRead, ComponentStorage, ComponentWrite}, sparse_set::{Entry, SparseSet}, world::World};
 
-/// The type of ID of entity which starts from 1 and can be recycled automatically
-pub type EntityId = NonZeroUsize;
+/// The generation of an entity index.
+/// # Details
+/// It starts from 1 and increases every time the index is recycled,
+/// so an [EntityId](crate::entity::EntityId) of a removed entity never
+/// refers to the entity which reuses its index.
+/// An index is retired and never reused after its generation reaches ```u32::MAX```.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Generation(NonZeroU32);
+
+impl Generation {
+    /// The generation of an index which has never been recycled
+    // Unwrap here never fails
+    // 1 is not zero
+    pub const FIRST : Generation = Generation(NonZeroU32::new(1).unwrap());
+
+    /// Get the generation number
+    pub fn get(&self) -> u32 {
+        self.0.get()
+    }
+
+    /// Get the next generation,
+    /// return ```None``` if the generation reaches ```u32::MAX```
+    pub(in crate) fn next(self) -> Option<Self> {
+        self.0.checked_add(1).map(Generation)
+    }
+}
+
+impl Display for Generation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}",self.0)
+    }
+}
+
+/// The ID of entity.
+/// # Details
+/// It packs an index and a [Generation](crate::entity::Generation) into a ```u64```.
+/// The index starts from 1 and is recycled automatically when the entity is removed,
+/// and the generation of index increases at the same time.
+/// So an ID of a removed entity is never [alive](crate::world::World::is_alive) again.
+/// # Ordering
+/// IDs are ordered by index first,then by generation.
+#[derive(Clone,Copy,PartialEq,Eq,Hash)]
+pub struct EntityId(NonZeroU64);
+
+impl EntityId {
+    /// Create an ID with ```index``` and the [first](Generation::FIRST) generation.
+    /// # Details
+    /// Return ```None``` if ```index``` is 0 or larger than ```u32::MAX```.
+    pub fn new(index : usize) -> Option<Self> {
+        EntityId::from_parts(index,Generation::FIRST)
+    }
+
+    /// Create an ID with ```index``` and ```generation```.
+    /// # Details
+    /// Return ```None``` if ```index``` is 0 or larger than ```u32::MAX```.
+    pub fn from_parts(index : usize,generation : Generation) -> Option<Self> {
+        let index = u32::try_from(index).ok()?;
+        let bits = (generation.get() as u64) << 32 | index as u64;
+        if index == 0 {
+            None
+        } else {
+            NonZeroU64::new(bits).map(EntityId)
+        }
+    }
+
+    /// Get the index of entity,it's used to index the storages
+    pub fn index(&self) -> usize {
+        (self.0.get() & u32::MAX as u64) as usize
+    }
+
+    /// Get the generation of index
+    pub fn generation(&self) -> Generation {
+        // Safety:
+        // the generation part is always from a Generation
+        Generation(unsafe { NonZeroU32::new_unchecked((self.0.get() >> 32) as u32) })
+    }
+
+    /// Get the packed bits,it can be restored by [from_bits](EntityId::from_bits)
+    pub fn to_bits(&self) -> u64 {
+        self.0.get()
+    }
+
+    /// Restore an ID from [to_bits](EntityId::to_bits).
+    /// # Details
+    /// Return ```None``` if the index or generation part is 0.
+    pub fn from_bits(bits : u64) -> Option<Self> {
+        let generation = NonZeroU32::new((bits >> 32) as u32)?;
+        EntityId::from_parts((bits & u32::MAX as u64) as usize,Generation(generation))
+    }
+}
+
+impl PartialOrd for EntityId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EntityId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.index(),self.generation()).cmp(&(other.index(),other.generation()))
+    }
+}
+
+impl From<EntityId> for usize {
+    fn from(id : EntityId) -> usize {
+        id.index()
+    }
+}
+
+impl Debug for EntityId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"EntityId({}v{})",self.index(),self.generation())
+    }
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}v{}",self.index(),self.generation())
+    }
+}
 
 /// A useful struct for building a entity
 // #[derive(Debug)]
@@ -13,12 +131,12 @@ pub struct Entity<'a>{
     // To avoid remove this ID from world
     // The ID must be valid during Entity is alive
     #[allow(unused)]
-    borrow_entity_manager : RwLockReadGuard<'a,EntityManager>
+    borrow_entity_manager : EntityManagerReadGuard<'a>
 }
 
 impl<'a> Entity<'a>{
     pub(in crate) fn new(world : &'a World,
-                         borrow_entity_manager : RwLockReadGuard<'a,EntityManager>,
+                         borrow_entity_manager : EntityManagerReadGuard<'a>,
                          entity_id : EntityId) -> Self{
         Entity{
             world,
@@ -32,6 +150,11 @@ impl<'a> Entity<'a>{
         self.id
     }
 
+    /// Get the ID without consuming Entity
+    pub fn id(&self) -> EntityId{
+        self.id
+    }
+
     /// Attach a component to entity
     /// # Panics
     /// * Panics if ```T``` has not been registered
@@ -48,33 +171,27 @@ impl<'a> Entity<'a>{
             let sparse_set = unsafe {
                 storage.downcast_mut::<SparseSet<EntityId,T>>()
             };
-            sparse_set.add(self.id,component);
-        }
-        for mut group in world.groups(type_id) {
-            match &mut *group {
-                Group::FullOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let mut comp_a = world.raw_storage_write(type_a).unwrap();
-                    let mut comp_b = world.raw_storage_write(type_b).unwrap();
-                    data.add(self.id,&mut comp_a,&mut comp_b);
-                },
-                Group::PartialOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let mut comp_a = world.raw_storage_write(type_a).unwrap();
-                    let comp_b = world.raw_storage_read(type_b).unwrap();
-                    data.add(self.id,&mut comp_a,&comp_b);
+            match sparse_set.entry(self.id) {
+                Entry::Occupied(mut entry) => {
+                    entry.insert(component);
                 },
-                Group::NonOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let comp_a = world.raw_storage_read(type_a).unwrap();
-                    let comp_b = world.raw_storage_read(type_b).unwrap();
-                    data.add(self.id,&comp_a,&comp_b);
+                Entry::Vacant(entry) => {
+                    entry.insert(component);
+                    sparse_set.mark_added(self.id,world.change_tick());
                 }
             }
         }
+        world.add_to_groups(self.id,type_id);
         self
     }
 
+    /// Attach all components of a [Bundle](crate::bundle::Bundle) to entity
+    /// # Panics
+    /// * Panics if any component of ```B``` has not been registered
+    pub fn attach_bundle<B : Bundle>(self,bundle : B) -> Self{
+        bundle.attach(self)
+    }
+
     /// Detach a component from entity
     /// # Panics
     /// * Panics if ```T``` has not been registered
@@ -83,28 +200,7 @@ impl<'a> Entity<'a>{
         assert!(world.has_registered::<T>(),
                 "World:Cannot detach component because components has not been registered.");
         let type_id = TypeId::of::<T>();
-        for mut group in world.groups(type_id) {
-            match &mut *group{
-                Group::FullOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let mut comp_a = world.raw_storage_write(type_a).unwrap();
-                    let mut comp_b = world.raw_storage_write(type_b).unwrap();
-                    data.remove(self.id,&mut comp_a,&mut comp_b);
-                },
-                Group::PartialOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let mut comp_a = world.raw_storage_write(type_a).unwrap();
-                    let comp_b = world.raw_storage_read(type_b).unwrap();
-                    data.remove(self.id,&mut comp_a,&comp_b);
-                },
-                Group::NonOwning(data) => {
-                    let (type_a,type_b) = data.types();
-                    let comp_a = world.raw_storage_read(type_a).unwrap();
-                    let comp_b = world.raw_storage_read(type_b).unwrap();
-                    data.remove(self.id,&comp_a,&comp_b);
-                }
-            }
-        }
+        world.remove_from_groups(self.id,type_id);
 
         // Unwrap never fails because assert ensures this
         let mut storage = world.raw_storage_write(type_id).unwrap();
@@ -126,11 +222,37 @@ impl<'a> Entity<'a>{
         self.world.entity_component_write(self.id)
     }
 
-    /// remove this entity from the world
-    pub fn manaully_drop(self) {
+    /// Get component of this entity.
+    /// # Details
+    /// The returned guard keeps the storage read lock until it is dropped.  
+    /// Return ```None``` if ```T``` is not registered or this entity doesn't have it.
+    pub fn get<T : Component>(&self) -> Option<ComponentRead<'_,T>> {
+        self.world.get(self.id)
+    }
+
+    /// Get mutable component of this entity.
+    /// # Details
+    /// The returned guard keeps the storage write lock until it is dropped.
+    pub fn get_mut<T : Component>(&self) -> Option<ComponentWrite<'_,T>> {
+        self.world.get_mut(self.id)
+    }
+
+    /// Check if this entity has component ```T```
+    pub fn has<T : Component>(&self) -> bool {
+        self.world.has_component::<T>(self.id)
+    }
+
+    /// Despawn this entity,remove it and all of its components from the world
+    pub fn despawn(self) {
         drop(self.borrow_entity_manager);
         self.world.remove_entity(self.id);
     }
+
+    /// remove this entity from the world
+    #[deprecated(note = "use despawn instead")]
+    pub fn manaully_drop(self) {
+        self.despawn();
+    }
 }
 
 /// A useful struct for building a lot of entities
@@ -140,13 +262,13 @@ pub struct Entities<'a>{
     // To avoid remove this ID from world
     // The ID must be valid during Entity is alive
     #[allow(unused)]
-    borrow_entity_manager : RwLockReadGuard<'a,EntityManager>
+    borrow_entity_manager : EntityManagerReadGuard<'a>
 }
 
 impl<'a> Entities<'a> {
     pub(in crate) fn new(world : &'a World,
                          ids : Range<EntityId>,
-                         borrow_entity_manager : RwLockReadGuard<'a,EntityManager>,) -> Self{
+                         borrow_entity_manager : EntityManagerReadGuard<'a>,) -> Self{
         Entities{
             world,
             ids,
@@ -162,42 +284,91 @@ impl<'a> Entities<'a> {
     where T : Component,
           C : Into<Vec<T>>{
         // ensure the slice length is equal to the entity count
-        let count = self.ids.end.get() - self.ids.start.get();
+        let count = self.ids.end.index() - self.ids.start.index();
         let components : Vec<T> = components.into();
         assert_eq!(components.len(),count);
         let type_id = TypeId::of::<T>();
-        let mut sparse_set = self.world.raw_storage_write(type_id)
-            .expect("Entities:Cannot attach component because components has not been registered.");
-        // Safety:
-        // sparse_set is SparseSet<EntityId,T>
-        let sparse_set = unsafe {
-            sparse_set.downcast_mut::<SparseSet<EntityId,T>>()
-        };
         // create Id slice
-        let ids = (self.ids.start.get()..self.ids.end.get())
-            // Safety:
-            // Safe here id cannot be zero 
-            .map(|id|unsafe{EntityId::new_unchecked(id)})
+        // the indices of created entities have never been recycled
+        // Unwrap here never fails
+        // the indices are allocated by EntityManager
+        let ids = (self.ids.start.index()..self.ids.end.index())
+            .map(|index|EntityId::new(index).unwrap())
             .collect::<Vec<_>>();
-        sparse_set.add_batch(&ids,components);
+        {
+            let mut sparse_set = self.world.raw_storage_write(type_id)
+                .expect("Entities:Cannot attach component because components has not been registered.");
+            // Safety:
+            // sparse_set is SparseSet<EntityId,T>
+            let sparse_set = unsafe {
+                sparse_set.downcast_mut::<SparseSet<EntityId,T>>()
+            };
+            sparse_set.add_batch(&ids,components);
+            let tick = self.world.change_tick();
+            for id in &ids {
+                sparse_set.mark_added(*id,tick);
+            }
+        }
+        for id in ids {
+            self.world.add_to_groups(id,type_id);
+        }
         self
     }
 
     /// Get ID range
     /// # Details
     /// Because create_entites() ensure the id is continuous,
-    /// so we can just return the range of EntityId for optimization
+    /// so we can just return the range of EntityId for optimization.
+    /// All IDs in range have the [first](crate::entity::Generation::FIRST) generation.
     pub fn into_ids(self) -> Range<EntityId> {
         self.ids
     }
 }
 
+/// The error when the entity ID reaches the
+/// [maximum](crate::world::World::set_max_entity_id)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct EntityIdExhausted {
+    /// The maximum entity ID
+    pub max_entity_id : usize
+}
+
+impl Display for EntityIdExhausted {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Entity ID exhausted,the maximum entity ID is {}",self.max_entity_id)
+    }
+}
+
+impl Error for EntityIdExhausted {}
+
+/// The statistics of entities
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct EntityStats {
+    /// The count of alive entities
+    pub alive : usize,
+    /// The largest ID which has ever been allocated
+    pub high_water_mark : usize,
+    /// The maximum entity ID,```None``` means no limit
+    pub max_entity_id : Option<usize>,
+    /// How many entities can still be created,```None``` means no limit
+    pub headroom : Option<usize>
+}
+
+/// The maximum index of entity
+/// # Details
+/// The index is packed into ```u32```,
+/// and the end of ID range returned by allocating must be valid too.
+const MAX_INDEX : usize = u32::MAX as usize - 1;
+
 #[derive(Debug,Copy,Clone)]
 enum EntityFlag{
     /// store the next available EntityID
     Available(EntityId),
     /// store the index of EntityID in entities array
-    Unavailable(usize)
+    Unavailable(usize),
+    /// the generations of this index are used up,
+    /// it stores the last removed EntityID
+    Retired(EntityId)
 }
 
 #[derive(Debug,Clone)]
@@ -208,90 +379,277 @@ pub(in crate) struct EntityManager {
     //     Available(EntityID) -> the EntityID
     entity_flags : Vec<EntityFlag>,
     entities : Vec<EntityId>,
+    // generations[index] : The generation of the next ID with this index
+    generations : Vec<Generation>,
+    // The maximum ID can be allocated
+    max_id : Option<usize>
 }
 
 impl EntityManager {
     pub(in crate) fn new() -> EntityManager {
         EntityManager {
             entity_flags: vec![EntityFlag::Unavailable(0)],
-            entities: vec![]
+            entities: vec![],
+            generations: vec![Generation::FIRST],
+            max_id: None
         }
     }
 
     pub(in crate) fn allocate(&mut self) -> EntityId {
+        match self.try_allocate() {
+            Ok(id) => id,
+            Err(err) => panic!("EntityManager:Cannot allocate entity ID. {}",err)
+        }
+    }
+
+    pub(in crate) fn try_allocate(&mut self) -> Result<EntityId,EntityIdExhausted> {
         //safe here:
         // the entity_flags[0] cannot be removed
         if let EntityFlag::Available(last_id) = self.entity_flags.first().unwrap() {
-            let last_id = *last_id;
-            //we got an id can be reused
-            let new_id = self.entity_flags[last_id.get()];
-            self.entities.push(last_id);
-            self.entity_flags[last_id.get()] = EntityFlag::Unavailable(self.entities.len() - 1);
+            let index = last_id.index();
+            //we got an index can be reused with a new generation
+            // Unwrap here never fails
+            // the index was allocated before
+            let id = EntityId::from_parts(index,self.generations[index]).unwrap();
+            let new_id = self.entity_flags[index];
+            self.entities.push(id);
+            self.entity_flags[index] = EntityFlag::Unavailable(self.entities.len() - 1);
             self.entity_flags[0] = new_id;
-            last_id
+            Ok(id)
         }else{
             //full
-            let id = self.entity_flags.len();
-            // safe here because id cannot be zero
-            let id = unsafe { EntityId::new_unchecked(id) };
+            let index = self.entity_flags.len();
+            self.check_max_id(index)?;
+            // Unwrap here never fails
+            // check_max_id() ensures the index is in range of u32
+            let id = EntityId::new(index).unwrap();
             self.entities.push(id);
             self.entity_flags.push(EntityFlag::Unavailable(self.entities.len() - 1));
-            //safe here because this id can't be 0
-            id
+            self.generations.push(Generation::FIRST);
+            Ok(id)
+        }
+    }
+
+    /// Re-allocate the removed index of ```entity_id``` with its old generation.
+    /// # Details
+    /// The index is taken out of the removed chain wherever it is.
+    /// If the index is not removed,a new ID is allocated.
+    pub(in crate) fn restore(&mut self,entity_id : EntityId) -> EntityId {
+        let index = entity_id.index();
+        if let Some(EntityFlag::Retired(retired)) = self.entity_flags.get(index) {
+            if *retired == entity_id {
+                self.entities.push(entity_id);
+                self.entity_flags[index] = EntityFlag::Unavailable(self.entities.len() - 1);
+                return entity_id;
+            }
+        }
+        // the index in entity_flags which links to the current removed ID
+        let mut prev = 0;
+        while let EntityFlag::Available(removed) = self.entity_flags[prev] {
+            if removed.index() == index {
+                // unlink index from the removed chain
+                self.entity_flags[prev] = self.entity_flags[index];
+                // undo the generations increased since entity_id was removed
+                self.generations[index] = entity_id.generation();
+                self.entities.push(entity_id);
+                self.entity_flags[index] = EntityFlag::Unavailable(self.entities.len() - 1);
+                return entity_id;
+            }
+            prev = removed.index();
         }
+        self.allocate()
     }
 
     /// Allocate ```n``` entities
     /// This ensure the entity id is continuous
     pub(in crate) fn allocate_n(&mut self, n : usize) -> Range<EntityId> {
+        match self.try_allocate_n(n) {
+            Ok(ids) => ids,
+            Err(err) => panic!("EntityManager:Cannot allocate entity IDs. {}",err)
+        }
+    }
+
+    /// Allocate ```n``` entities
+    /// This ensure the entity id is continuous
+    pub(in crate) fn try_allocate_n(&mut self, n : usize) -> Result<Range<EntityId>,EntityIdExhausted> {
         // Get the range of entity id
         let start_id = self.entity_flags.len();
         let end_id = start_id + n;   
+        if n > 0 {
+            self.check_max_id(end_id - 1)?;
+        }
         // Get the range of entity index
         let start_index = self.entities.len();
         let end_index = start_index + n;
         // record indecies to self.entity_flags
         for i in start_index..end_index {
             self.entity_flags.push(EntityFlag::Unavailable(i));
+            self.generations.push(Generation::FIRST);
         }
-        for id in start_id..end_id {
-            let id = unsafe { EntityId::new_unchecked(id) };
-            self.entities.push(id);
+        // Unwrap here never fails
+        // check_max_id() ensures the indices are in range of u32
+        for index in start_id..end_id {
+            self.entities.push(EntityId::new(index).unwrap());
+        }
+        let start_id = EntityId::new(start_id).unwrap();
+        let end_id = EntityId::new(end_id).unwrap();
+        Ok(start_id..end_id)
+    }
+
+    fn check_max_id(&self,id : usize) -> Result<(),EntityIdExhausted> {
+        let max_entity_id = self.max_id.unwrap_or(MAX_INDEX);
+        if id > max_entity_id {
+            Err(EntityIdExhausted { max_entity_id })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the maximum ID can be allocated
+    /// # Panics
+    /// Panic if an ID larger than ```max_id``` has been allocated
+    pub(in crate) fn set_max_id(&mut self,max_id : usize) {
+        assert!(self.high_water_mark() <= max_id,
+                "EntityManager:Cannot set the maximum entity ID less than an allocated ID");
+        self.max_id = Some(max_id);
+    }
+
+    /// Get the largest ID which has ever been allocated
+    pub(in crate) fn high_water_mark(&self) -> usize {
+        self.entity_flags.len() - 1
+    }
+
+    pub(in crate) fn stats(&self) -> EntityStats {
+        EntityStats {
+            alive : self.len(),
+            high_water_mark : self.high_water_mark(),
+            max_entity_id : self.max_id,
+            headroom : self.max_id.map(|max_id| max_id - self.len() - self.retired().count())
         }
-        let start_id = unsafe { EntityId::new_unchecked(start_id) };
-        let end_id = unsafe { EntityId::new_unchecked(end_id) };
-        start_id..end_id
     }
     // remove entity id
     // Do nothing if entity_id not exist
     pub(in crate) fn remove(&mut self,entity_id : EntityId) {
-        let entity_id_ = entity_id.get();
+        let entity_id_ = entity_id.index();
+        if !self.has(entity_id) {
+            return;
+        }
         if let EntityFlag::Unavailable(index) = self.entity_flags[entity_id_] {
             // unwrap safe: in this branch, we must have one entity at least
             let the_last_one_id = self.entities.last().unwrap();
             // move this entity to the end of entities
-            self.entity_flags[the_last_one_id.get()] = EntityFlag::Unavailable(index);
+            self.entity_flags[the_last_one_id.index()] = EntityFlag::Unavailable(index);
             self.entities.swap_remove(index);
-            // keep these destroyed ids being a chain
-            self.entity_flags[entity_id_] = self.entity_flags[0];
-            self.entity_flags[0] = EntityFlag::Available(entity_id);
+            match self.generations[entity_id_].next() {
+                Some(generation) => {
+                    // keep these destroyed ids being a chain
+                    self.entity_flags[entity_id_] = self.entity_flags[0];
+                    self.entity_flags[0] = EntityFlag::Available(entity_id);
+                    // the index will be reused by another entity with the next generation
+                    self.generations[entity_id_] = generation;
+                },
+                None => {
+                    // reusing the index would make an old ID alive again
+                    self.entity_flags[entity_id_] = EntityFlag::Retired(entity_id);
+                }
+            }
         }
     }
 
+    /// Get the retired IDs in order of index
+    fn retired(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.entity_flags.iter()
+            .filter_map(|flag| match flag {
+                EntityFlag::Retired(id) => Some(*id),
+                _ => None
+            })
+    }
+
     pub(in crate) fn has(&self,entity_id : EntityId) -> bool {
-        if let EntityFlag::Unavailable(_) = self.entity_flags[entity_id.get()] {
-            true
-        } else {
-            false
+        // The end of removed chain is a copy of entity_flags[0],
+        // it can be Unavailable(0) too,so the index must be checked.
+        // The stored ID has the generation too,so a stale ID is never found
+        match self.entity_flags.get(entity_id.index()) {
+            Some(EntityFlag::Unavailable(index)) => self.entities.get(*index) == Some(&entity_id),
+            _ => false
         }
     }
 
-    #[allow(dead_code)]
     pub(in crate) fn entities(&self) -> &[EntityId] {
         &self.entities
     }
 
-    #[allow(dead_code)]
+    /// Get the removed IDs in the order they will be reused,
+    /// the retired IDs follow them
+    #[cfg(feature = "serde")]
+    pub(in crate) fn removed(&self) -> Vec<EntityId> {
+        let mut removed = vec![];
+        let mut flag = self.entity_flags[0];
+        while let EntityFlag::Available(id) = flag {
+            removed.push(id);
+            flag = self.entity_flags[id.index()];
+        }
+        removed.extend(self.retired());
+        removed
+    }
+
+    /// Get the maximum ID can be allocated
+    #[cfg(feature = "serde")]
+    pub(in crate) fn max_id(&self) -> Option<usize> {
+        self.max_id
+    }
+
+    /// Rebuild from the alive ```entities``` and the ```removed``` IDs in the order they will be reused.
+    /// # Details
+    /// Return ```None``` if the indices of them are not exactly ```1..=n```,
+    /// or an index is larger than ```max_id```.
+    #[cfg(feature = "serde")]
+    pub(in crate) fn from_raw(entities : Vec<EntityId>,removed : &[EntityId],max_id : Option<usize>) -> Option<EntityManager> {
+        let len = entities.len() + removed.len();
+        let mut manager = EntityManager {
+            entity_flags : vec![EntityFlag::Unavailable(0);len + 1],
+            entities : vec![],
+            generations : vec![Generation::FIRST;len + 1],
+            max_id
+        };
+        manager.check_max_id(len).ok()?;
+        let mut visited = vec![false;len + 1];
+        let mut visit = |index : usize| {
+            if index > len || visited[index] {
+                return false;
+            }
+            visited[index] = true;
+            true
+        };
+        for (dense,id) in entities.iter().enumerate() {
+            if !visit(id.index()) {
+                return None;
+            }
+            manager.entity_flags[id.index()] = EntityFlag::Unavailable(dense);
+            manager.generations[id.index()] = id.generation();
+        }
+        // rebuild the chain from the end,
+        // the end of chain is a copy of the initial entity_flags[0]
+        for id in removed.iter().rev() {
+            if !visit(id.index()) {
+                return None;
+            }
+            match id.generation().next() {
+                Some(generation) => {
+                    manager.entity_flags[id.index()] = manager.entity_flags[0];
+                    manager.entity_flags[0] = EntityFlag::Available(*id);
+                    manager.generations[id.index()] = generation;
+                },
+                None => {
+                    manager.entity_flags[id.index()] = EntityFlag::Retired(*id);
+                    manager.generations[id.index()] = id.generation();
+                }
+            }
+        }
+        manager.entities = entities;
+        Some(manager)
+    }
+
     pub(in crate) fn len(&self) -> usize {
         self.entities.len()
     }
@@ -299,7 +657,10 @@ impl EntityManager {
 
 #[cfg(test)]
 mod tests{
-    use crate::entity::{EntityId, EntityManager};
+    use std::num::NonZeroU32;
+    use crate::entity::{EntityId, EntityIdExhausted, EntityManager, Generation};
+    use crate::group::full_owning;
+    use crate::world::World;
 
     #[test]
     fn manager_test() {
@@ -330,34 +691,127 @@ mod tests{
         println!("flags :{:?}",manager.entity_flags.as_slice());
         println!("entities :{:?}",manager.entities.as_slice());
         println!();
-        assert_eq!(manager.allocate(),EntityId::new(1).unwrap());
+        assert_eq!(manager.allocate().index(),1);
         println!("#create a new entity, id = 1");
         println!("flags :{:?}",manager.entity_flags.as_slice());
         println!("entities :{:?}",manager.entities.as_slice());
         println!();
-        assert_eq!(manager.allocate(),EntityId::new(5).unwrap());
+        assert_eq!(manager.allocate().index(),5);
         println!("#create a new entity, id = 5");
         println!("flags :{:?}",manager.entity_flags.as_slice());
         println!("entities :{:?}",manager.entities.as_slice());
         println!();
-        assert_eq!(manager.allocate(),EntityId::new(3).unwrap());
+        assert_eq!(manager.allocate().index(),3);
         println!("#create a new entity, id = 3");
         println!("flags :{:?}",manager.entity_flags.as_slice());
         println!("entities :{:?}",manager.entities.as_slice());
         println!();
-        assert_eq!(manager.allocate(),EntityId::new(6).unwrap());
+        assert_eq!(manager.allocate().index(),6);
         println!("#create a new entity, id = 6");
         println!("flags :{:?}",manager.entity_flags.as_slice());
         println!("entities :{:?}",manager.entities.as_slice());
         println!();
     }
 
+    #[test]
+    fn generation_test() {
+        let mut manager = EntityManager::new();
+
+        let id1 = manager.allocate();
+        let id2 = manager.allocate();
+        manager.allocate_n(2);
+        assert_eq!(id1.generation(),Generation::FIRST);
+        manager.remove(id1);
+        assert!(!manager.has(id1));
+        let reused = manager.allocate();
+        assert_eq!(reused.index(),id1.index());
+        assert_eq!(reused.generation().get(),2);
+        assert!(manager.has(reused));
+        assert!(!manager.has(id1));
+        assert!(manager.has(id2));
+        assert_eq!(id2.generation(),Generation::FIRST);
+        // a stale id cannot be removed again
+        manager.remove(id1);
+        assert!(manager.has(reused));
+    }
+
+    #[test]
+    fn retire_test() {
+        let mut manager = EntityManager::new();
+        manager.set_max_id(3);
+        let id = manager.allocate();
+        let other = manager.allocate();
+        let last = Generation(NonZeroU32::new(u32::MAX).unwrap());
+        manager.generations[id.index()] = Generation(NonZeroU32::new(u32::MAX - 1).unwrap());
+        manager.remove(id);
+        let id = manager.allocate();
+        assert_eq!(id.generation(),last);
+        assert_eq!(last.next(),None);
+
+        // the index is retired instead of wrapping to the first generation
+        manager.remove(id);
+        assert!(!manager.has(id));
+        assert_eq!(manager.stats().headroom,Some(1));
+        let new = manager.allocate();
+        assert_ne!(new.index(),id.index());
+        assert!(manager.try_allocate().is_err());
+        assert!(manager.has(other));
+        assert!(!manager.has(EntityId::new(id.index()).unwrap()));
+
+        // a retired ID can be restored
+        assert_eq!(manager.restore(id),id);
+        assert!(manager.has(id));
+    }
+
+    #[test]
+    fn restore_test() {
+        let mut manager = EntityManager::new();
+        let ids = (0..4).map(|_|manager.allocate()).collect::<Vec<_>>();
+        manager.remove(ids[1]);
+        manager.remove(ids[2]);
+        // ids[1] is not the last removed ID
+        assert_eq!(manager.restore(ids[1]),ids[1]);
+        assert!(manager.has(ids[1]));
+        assert_eq!(manager.restore(ids[2]),ids[2]);
+        assert!(manager.has(ids[2]));
+        assert_eq!(manager.len(),4);
+
+        // the other removed IDs are still in chain
+        manager.remove(ids[0]);
+        manager.remove(ids[3]);
+        manager.remove(ids[2]);
+        assert_eq!(manager.restore(ids[3]),ids[3]);
+        let reused = manager.allocate();
+        assert_eq!(reused.index(),ids[2].index());
+        assert_eq!(reused.generation().get(),2);
+        let reused = manager.allocate();
+        assert_eq!(reused.index(),ids[0].index());
+        assert_eq!(reused.generation().get(),2);
+        assert_eq!(manager.allocate().index(),5);
+    }
+
+    #[test]
+    fn max_id_test() {
+        let mut manager = EntityManager::new();
+        manager.allocate_n(2);
+        manager.set_max_id(4);
+        let id3 = manager.try_allocate().unwrap();
+        assert_eq!(manager.try_allocate_n(2),Err(EntityIdExhausted { max_entity_id : 4 }));
+        manager.try_allocate().unwrap();
+        assert_eq!(manager.try_allocate(),Err(EntityIdExhausted { max_entity_id : 4 }));
+        // removed id can be reused
+        manager.remove(id3);
+        assert_eq!(manager.try_allocate().map(|id|id.index()),Ok(id3.index()));
+        assert!(manager.try_allocate().is_err());
+        assert!(manager.try_allocate_n(0).map(|ids|ids.is_empty()).unwrap());
+    }
+
     #[test]
     fn create_entities() {
         let mut manager = EntityManager::new();
 
         let range = manager.allocate_n(5);
-        let range = range.start.get()..range.end.get();
+        let range = range.start.index()..range.end.index();
         let entities = range.map(|id|EntityId::new(id).unwrap())
             .collect::<Vec<_>>();
         assert_eq!(manager.entities(),&entities);
@@ -365,7 +819,7 @@ mod tests{
         println!("entities:{:?}",manager.entities.as_slice());
 
         let range = manager.allocate_n(3);
-        let range = range.start.get()..range.end.get();
+        let range = range.start.index()..range.end.index();
         let entities = range.map(|id|EntityId::new(id).unwrap())
             .collect::<Vec<_>>();
         assert_eq!(&manager.entities()[5..8],&entities);
@@ -373,4 +827,69 @@ mod tests{
         println!("entities:{:?}",manager.entities.as_slice());
     }
     
+
+    #[test]
+    fn entity_accessor_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        world.make_group(full_owning::<u32,char>());
+        let a = world.create_entity().attach(1_u32).attach('a').into_id();
+        world.create_entity().attach(2_u32).attach('b');
+
+        let entity = world.entity(a).unwrap();
+        assert_eq!(entity.id(),a);
+        assert!(entity.has::<u32>());
+        assert_eq!(*entity.get::<char>().unwrap(),'a');
+        *entity.get_mut::<u32>().unwrap() += 10;
+        assert_eq!(*entity.get::<u32>().unwrap(),11);
+
+        assert_eq!(entity.detach::<char>(),Some('a'));
+        assert!(!entity.has::<char>());
+        assert!(entity.get::<char>().is_none());
+        assert_eq!(entity.detach::<char>(),None);
+        drop(entity);
+
+        assert_eq!(world.group_len_of::<u32,char>(),Some(1));
+        assert_eq!(world.query::<(&u32,&char)>().count(),1);
+    }
+
+
+    #[test]
+    fn has_test() {
+        let mut manager = EntityManager::new();
+        let ids = (0..4).map(|_|manager.allocate()).collect::<Vec<_>>();
+        manager.remove(ids[0]);
+        manager.remove(ids[2]);
+        assert!(!manager.has(ids[0]));
+        assert!(manager.has(ids[1]));
+        assert!(!manager.has(ids[2]));
+        assert!(manager.has(ids[3]));
+        // never allocated
+        assert!(!manager.has(EntityId::new(100).unwrap()));
+
+        // removing a dead entity does nothing
+        manager.remove(ids[0]);
+        assert_eq!(manager.len(),2);
+        assert_eq!(manager.allocate().index(),ids[2].index());
+        assert_eq!(manager.allocate().index(),ids[0].index());
+        assert_eq!(manager.len(),4);
+    }
+
+    #[test]
+    fn world_entity_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        let a = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        world.remove_entity(a);
+        assert!(world.entity(a).is_none());
+        assert!(!world.exist(a));
+        assert!(!world.despawn(a));
+        assert!(world.entity(EntityId::new(1000).unwrap()).is_none());
+        if let Some(entity) = world.entity(b) {
+            entity.attach(10_u32);
+        }
+        assert_eq!(*world.get::<u32>(b).unwrap(),10);
+    }
+
 }
@@ -0,0 +1,47 @@
+use std::{any::TypeId, error::Error, fmt::{Display, Formatter}};
+
+/// An opaque token of the structural versions of component storages.
+/// # Details
+/// It's got from [epoch](crate::world::World::epoch) or
+/// [query_with_epoch](crate::world::World::query_with_epoch),and checked by
+/// [check_epoch](crate::world::World::check_epoch) to ensure that no entity
+/// was added,removed or moved in these storages between two passes.
+/// Modifying the data of components is not a structural change.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Epoch {
+    versions : Vec<(TypeId,u64)>
+}
+
+impl Epoch {
+    pub(in crate) fn new(versions : Vec<(TypeId,u64)>) -> Self {
+        Epoch {
+            versions
+        }
+    }
+
+    pub(in crate) fn versions(&self) -> &[(TypeId,u64)] {
+        &self.versions
+    }
+}
+
+/// The error when some storages were structurally changed since an [Epoch](crate::epoch::Epoch)
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct EpochChanged {
+    /// The type IDs and names of changed storages
+    pub changed_storages : Vec<(TypeId,&'static str)>
+}
+
+impl Display for EpochChanged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Epoch changed in storages:")?;
+        for (index,(_,name)) in self.changed_storages.iter().enumerate() {
+            if index != 0 {
+                write!(f,",")?;
+            }
+            write!(f," {}",name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for EpochChanged {}
@@ -0,0 +1,121 @@
+use std::{any::TypeId, error::Error, fmt::{Display, Formatter}};
+use crate::entity::EntityId;
+
+/// The error of structural operations in [World](crate::world::World)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum XecsError {
+    /// The component has not been registered,it stores the type name
+    ComponentNotRegistered(&'static str),
+    /// The component has been registered,it stores the type name
+    ComponentAlreadyRegistered(&'static str),
+    /// The entity is not alive
+    EntityNotFound(EntityId),
+    /// The entity doesn't have this component
+    ComponentNotFound {
+        entity_id : EntityId,
+        component : &'static str
+    },
+    /// Another [Transaction](crate::transaction::Transaction) is alive
+    NestedTransaction
+}
+
+impl Display for XecsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XecsError::ComponentNotRegistered(name) =>
+                write!(f,"Component {} has not been registered",name),
+            XecsError::ComponentAlreadyRegistered(name) =>
+                write!(f,"Component {} has been registered",name),
+            XecsError::EntityNotFound(entity_id) =>
+                write!(f,"Entity {} does not exist",entity_id),
+            XecsError::ComponentNotFound { entity_id, component } =>
+                write!(f,"Entity {} does not have component {}",entity_id,component),
+            XecsError::NestedTransaction =>
+                write!(f,"Another transaction is alive")
+        }
+    }
+}
+
+impl Error for XecsError {}
+
+/// The error of [try_query](crate::world::World::try_query)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum QueryError {
+    /// The component has not been registered,it stores the type ID
+    UnregisteredComponent(TypeId),
+    /// The group is not in world
+    MissingGroup {
+        /// The first 2 types of group
+        types : (TypeId,TypeId),
+        /// The type names of ```types```
+        names : (&'static str,&'static str)
+    },
+    /// A storage touched by query is locked for writing
+    StorageBorrowConflict
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::UnregisteredComponent(type_id) =>
+                write!(f,"Component {:?} has not been registered",type_id),
+            QueryError::MissingGroup { names : (name_a,name_b), .. } =>
+                write!(f,"Group of {} and {} is not in world",name_a,name_b),
+            QueryError::StorageBorrowConflict =>
+                write!(f,"Storage is borrowed mutably by another query")
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+/// The error of [try_make_group](crate::world::World::try_make_group)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum GroupError {
+    /// The same group is already in world
+    ExistingGroup,
+    /// The component is owned by another group,it stores the type ID
+    OwnedByAnotherGroup(TypeId),
+    /// The component is double-buffered,it stores the type ID
+    DoubleBuffered(TypeId)
+}
+
+impl Display for GroupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupError::ExistingGroup =>
+                write!(f,"World has a same group"),
+            GroupError::OwnedByAnotherGroup(type_id) =>
+                write!(f,"Component {:?} is owned by another group",type_id),
+            GroupError::DoubleBuffered(type_id) =>
+                write!(f,"Component {:?} is double-buffered and cannot be owned by group",type_id)
+        }
+    }
+}
+
+impl Error for GroupError {}
+
+/// The error of [set_parent](crate::world::World::set_parent)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum HierarchyError {
+    /// The entity is not alive
+    EntityNotFound(EntityId),
+    /// The parent is the child itself or a descendant of child
+    Cycle {
+        child : EntityId,
+        parent : EntityId
+    }
+}
+
+impl Display for HierarchyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HierarchyError::EntityNotFound(entity_id) =>
+                write!(f,"Entity {} does not exist",entity_id),
+            HierarchyError::Cycle { child, parent } =>
+                write!(f,"Entity {} cannot be the child of its descendant {}",child,parent)
+        }
+    }
+}
+
+impl Error for HierarchyError {}
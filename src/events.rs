@@ -0,0 +1,250 @@
+//! # Events
+//! Send typed events between systems through [World](crate::world::World).
+//! # Details
+//! The events of type ```T``` are stored in a double-buffered [Events<T>](crate::events::Events) resource
+//! which is created by [register_event](crate::world::World::register_event).
+//! Every [EventReader] has its own cursor,so each reader sees every event exactly once.
+//! [update_events](crate::world::World::update_events) should be called once per frame,
+//! an event is dropped after 2 updates,so the readers must read it in this frame or the next frame.
+//! # Example
+//! ```
+//! use xecs::{World, events::EventReader};
+//! #[derive(Debug,PartialEq)]
+//! struct Collision(u32,u32);
+//!
+//! let mut world = World::new();
+//! world.register_event::<Collision>();
+//! let mut sound = EventReader::<Collision>::new();
+//! let mut score = EventReader::<Collision>::new();
+//!
+//! world.send_event(Collision(1,2));
+//! assert_eq!(world.read_events(&mut sound).collect::<Vec<_>>(),vec![&Collision(1,2)]);
+//!
+//! world.update_events();
+//! world.send_event(Collision(3,4));
+//! assert_eq!(world.read_events(&mut sound).collect::<Vec<_>>(),vec![&Collision(3,4)]);
+//! assert_eq!(world.read_events(&mut score).count(),2);
+//! assert_eq!(world.read_events(&mut score).count(),0);
+//! ```
+use std::{iter::FusedIterator, marker::PhantomData};
+use crate::lock::ResourceReadGuard;
+
+/// The event trait
+pub trait Event : Send + Sync + 'static {}
+impl<T : Send + Sync + 'static> Event for T {}
+
+/// The double-buffered events of type ```T```
+/// # Details
+/// Every event has a sequence number,
+/// the events in ```older``` start from ```start```,and ```newer``` follows ```older```.
+pub struct Events<T> {
+    older : Vec<T>,
+    newer : Vec<T>,
+    start : usize
+}
+
+impl<T : Event> Default for Events<T> {
+    fn default() -> Self {
+        Events::new()
+    }
+}
+
+impl<T : Event> Events<T> {
+    /// Create an empty events buffer
+    pub fn new() -> Self {
+        Events {
+            older : vec![],
+            newer : vec![],
+            start : 0
+        }
+    }
+
+    /// Send an event
+    pub fn send(&mut self,event : T) {
+        self.newer.push(event);
+    }
+
+    /// Drop the older buffer and make the newer buffer older
+    pub fn update(&mut self) {
+        self.start += self.older.len();
+        self.older.clear();
+        std::mem::swap(&mut self.older,&mut self.newer);
+    }
+
+    /// Drop all events
+    /// # Details
+    /// The readers skip the dropped events.
+    pub fn clear(&mut self) {
+        self.start += self.older.len() + self.newer.len();
+        self.older.clear();
+        self.newer.clear();
+    }
+
+    /// Get the count of events in both buffers
+    pub fn len(&self) -> usize {
+        self.older.len() + self.newer.len()
+    }
+
+    /// Check if there is no event in both buffers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sequence number of the next event
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+
+    fn get(&self,sequence : usize) -> Option<&T> {
+        let index = sequence.checked_sub(self.start)?;
+        if index < self.older.len() {
+            self.older.get(index)
+        } else {
+            self.newer.get(index - self.older.len())
+        }
+    }
+}
+
+/// The cursor of a reader of events of type ```T```
+/// # Details
+/// A new reader reads all events in buffers.
+pub struct EventReader<T> {
+    cursor : usize,
+    _marker : PhantomData<T>
+}
+
+impl<T : Event> Default for EventReader<T> {
+    fn default() -> Self {
+        EventReader::new()
+    }
+}
+
+impl<T : Event> EventReader<T> {
+    /// Create a reader which reads all events in buffers
+    pub fn new() -> Self {
+        EventReader {
+            cursor : 0,
+            _marker : PhantomData
+        }
+    }
+}
+
+/// The iterator of unread events,see [read_events](crate::world::World::read_events)
+pub struct EventIter<'a,T> {
+    sequence : usize,
+    end : usize,
+    events : *const Events<T>,
+    #[allow(unused)]
+    borrow : ResourceReadGuard<'a>
+}
+
+impl<'a,T : Event> EventIter<'a,T> {
+    /// Read the events after ```reader``` and move ```reader``` to the end
+    pub(in crate) fn new(lock : ResourceReadGuard<'a>,reader : &mut EventReader<T>) -> Self {
+        // Safety:
+        // lock is the resource of Events<T>
+        let events = unsafe { lock.downcast_ref::<Events<T>>() };
+        let ptr = events as *const Events<T>;
+        // the events before start are dropped
+        let sequence = reader.cursor.max(events.start);
+        let end = events.end();
+        reader.cursor = end;
+        EventIter {
+            sequence,
+            end,
+            events : ptr,
+            borrow : lock
+        }
+    }
+}
+
+impl<'a,T : Event> Iterator for EventIter<'a,T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sequence < self.end {
+            // Safety:
+            // Safe here, because self.events is
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let events = unsafe { &*self.events };
+            let event = events.get(self.sequence);
+            self.sequence += 1;
+            event
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.end - self.sequence;
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,T : Event> ExactSizeIterator for EventIter<'a,T> {}
+impl<'a,T : Event> FusedIterator for EventIter<'a,T> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::World;
+    use super::{EventReader, Events};
+
+    fn read(world : &World,reader : &mut EventReader<u32>) -> Vec<u32> {
+        world.read_events(reader).copied().collect()
+    }
+
+    #[test]
+    fn reader_test() {
+        let mut world = World::new();
+        world.register_event::<u32>()
+            .register_event::<char>()
+            // registering twice keeps the events
+            .register_event::<u32>();
+        let mut a = EventReader::new();
+        let mut b = EventReader::new();
+
+        world.send_event(1_u32);
+        world.send_event(2_u32);
+        world.send_event('x');
+        assert_eq!(read(&world,&mut a),vec![1,2]);
+        assert!(read(&world,&mut a).is_empty());
+
+        world.update_events();
+        world.send_event(3_u32);
+        assert_eq!(world.read_events(&mut a).len(),1);
+        // b reads the older buffer too
+        assert_eq!(read(&world,&mut b),vec![1,2,3]);
+
+        world.update_events();
+        world.send_event(4_u32);
+        world.update_events();
+        world.update_events();
+        // a reader which missed 2 updates skips the dropped events
+        let mut c = EventReader::new();
+        world.send_event(5_u32);
+        assert_eq!(read(&world,&mut a),vec![5]);
+        assert_eq!(read(&world,&mut b),vec![5]);
+        assert_eq!(read(&world,&mut c),vec![5]);
+
+        world.resource_mut::<Events<u32>>().unwrap().clear();
+        world.send_event(6_u32);
+        assert_eq!(read(&world,&mut a),vec![6]);
+        assert_eq!(world.resource::<Events<char>>().unwrap().len(),0);
+    }
+
+    #[test]
+    fn send_sync_test() {
+        // the buffers can be shared by the systems in different threads
+        fn send_sync<T : Send + Sync>() {}
+        send_sync::<Events<u32>>();
+        send_sync::<EventReader<u32>>();
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_registered_test() {
+        let world = World::new();
+        world.send_event(1_u32);
+    }
+}
@@ -0,0 +1,263 @@
+//! # Despawn finalizer
+//! A despawn finalizer cleans up a component when its entity is removed.
+//! Unlike ```Drop```,it receives the component by value with a
+//! [FinalizerCtx](crate::finalizer::FinalizerCtx) which can see the rest of the entity.
+//! ## Ordering
+//! When [remove_entity](crate::world::World::remove_entity) is called:
+//! 1. Finalizers of the components present on the entity run in ascending priority.
+//!    Finalizers with the same priority run in registration order.
+//!    Before each finalizer runs,its component is removed from groups and taken out of storage.
+//! 2. The entity is removed from the remaining groups and storages.
+//! 3. The entity ID is freed.
+//! 4. The commands [deferred](crate::finalizer::FinalizerCtx::defer) by finalizers run in order.
+//!
+//! ## Transaction
+//! [Transaction](crate::transaction::Transaction) keeps the components of removed entities
+//! for undoing,so the finalizers of an entity removed by a transaction run when the transaction is
+//! [committed](crate::transaction::Transaction::commit),and never run if it's rolled back.
+//! The components have been taken out of world at that time,
+//! so [read](crate::finalizer::FinalizerCtx::read) always returns ```None```.
+use std::any::{Any, TypeId};
+use crate::{component::{Component, ComponentRead}, entity::EntityId, lock::RwLock, sparse_set::SparseSet, world::World};
+
+/// Type-erased component
+type AnyComponent = Box<dyn Any + Send + Sync>;
+
+/// The type-erased finalizer stored in world,
+/// the component is taken out of storage if it's not given
+type ErasedFinalizer = Box<dyn FnMut(&World,EntityId,Option<AnyComponent>,&mut FinalizerCtx<'_>) + Send + Sync>;
+
+/// A deferred command
+type Deferred = Box<dyn FnOnce(&World)>;
+
+pub(in crate) struct Finalizer {
+    type_id : TypeId,
+    priority : i32,
+    run : RwLock<ErasedFinalizer>
+}
+
+impl Finalizer {
+    pub(in crate) fn new<T,F>(priority : i32,mut f : F) -> Self
+        where T : Component,
+              F : FnMut(EntityId,T,&mut FinalizerCtx<'_>) + Send + Sync + 'static {
+        let type_id = TypeId::of::<T>();
+        let run : ErasedFinalizer = Box::new(move |world,id,component,ctx| {
+            let component = match component {
+                // downcast never fails because the component is T
+                Some(component) => Some(*component.downcast::<T>().unwrap()),
+                None => {
+                    world.remove_from_groups(id,type_id);
+                    // unwrap never fails because the finalizer is registered after T
+                    let mut storage = world.raw_storage_write(type_id).unwrap();
+                    // Safety:
+                    // storage is SparseSet<EntityId,T>
+                    let sparse_set = unsafe {
+                        storage.downcast_mut::<SparseSet<EntityId,T>>()
+                    };
+                    sparse_set.remove(id)
+                }
+            };
+            if let Some(component) = component {
+                f(id,component,ctx);
+            }
+        });
+        Finalizer {
+            type_id,
+            priority,
+            run : RwLock::new(run)
+        }
+    }
+
+    pub(in crate) fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub(in crate) fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub(in crate) fn run(&self,world : &World,id : EntityId,ctx : &mut FinalizerCtx<'_>) {
+        let mut run = self.run.write();
+        (*run)(world,id,None,ctx)
+    }
+
+    /// Run with a component which has been taken out of world
+    pub(in crate) fn run_with(&self,world : &World,id : EntityId,component : AnyComponent,ctx : &mut FinalizerCtx<'_>) {
+        let mut run = self.run.write();
+        (*run)(world,id,Some(component),ctx)
+    }
+}
+
+/// The limited world access for despawn finalizers
+/// # Details
+/// Structural changes cannot be made in finalizers,
+/// they can be [deferred](crate::finalizer::FinalizerCtx::defer) until the entity is removed.
+pub struct FinalizerCtx<'a> {
+    world : &'a World,
+    id : EntityId,
+    deferred : Vec<Deferred>
+}
+
+impl<'a> FinalizerCtx<'a> {
+    pub(in crate) fn new(world : &'a World,id : EntityId) -> Self {
+        FinalizerCtx {
+            world,
+            id,
+            deferred : vec![]
+        }
+    }
+
+    pub(in crate) fn into_deferred(self) -> Vec<Deferred> {
+        self.deferred
+    }
+
+    /// Get the ID of the dying entity
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    /// Read a component of the dying entity.
+    /// # Details
+    /// Return ```None``` if entity doesn't have this component
+    /// or the component has been finalized.
+    pub fn read<T : Component>(&self) -> Option<ComponentRead<'a,T>> {
+        self.world.entity_component_read::<T>(self.id)
+    }
+
+    /// Defer a command until the entity is removed
+    pub fn defer<F : FnOnce(&World) + 'static>(&mut self,command : F) {
+        self.deferred.push(Box::new(command));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+    use crate::{entity::EntityId, group::{full_owning, FullOwning}, world::World};
+
+    #[derive(Clone,Copy)]
+    struct PhysicsBody(usize);
+    #[derive(Clone,Copy)]
+    struct Mesh(&'static str);
+
+    struct DropCounter(Arc<AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1,Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn ordering_test() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut world = World::new();
+        world.register::<PhysicsBody>()
+            .register::<Mesh>()
+            .register::<u32>();
+        let log_ = log.clone();
+        world.register_despawn_finalizer::<Mesh,_>(10,move |_,mesh,ctx| {
+            // the body has been finalized
+            assert!(ctx.read::<PhysicsBody>().is_none());
+            log_.lock().unwrap().push(format!("mesh {}",mesh.0));
+        });
+        let log_ = log.clone();
+        world.register_despawn_finalizer::<PhysicsBody,_>(-5,move |_,body,ctx| {
+            // read a sibling component
+            let mesh = ctx.read::<Mesh>().unwrap();
+            log_.lock().unwrap().push(format!("body {} of {}",body.0,mesh.0));
+        });
+        world.make_group(full_owning::<PhysicsBody,Mesh>());
+
+        let a = world.create_entity()
+            .attach(PhysicsBody(1))
+            .attach(Mesh("cube"))
+            .attach(7_u32)
+            .into_id();
+        let b = world.create_entity()
+            .attach(Mesh("sphere"))
+            .into_id();
+        world.remove_entity(a);
+        world.remove_entity(b);
+
+        assert_eq!(*log.lock().unwrap(),vec![
+            "body 1 of cube".to_string(),
+            "mesh cube".to_string(),
+            "mesh sphere".to_string()
+        ]);
+        assert_eq!(world.query::<FullOwning<&PhysicsBody,&Mesh>>().count(),0);
+        assert_eq!(world.query::<&u32>().count(),0);
+    }
+
+    #[test]
+    fn by_value_test() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::new();
+        world.register::<DropCounter>();
+        let drops_ = drops.clone();
+        world.register_despawn_finalizer::<DropCounter,_>(0,move |_,counter,_| {
+            assert_eq!(drops_.load(Ordering::SeqCst),0);
+            drop(counter);
+            assert_eq!(drops_.load(Ordering::SeqCst),1);
+        });
+        let id = world.create_entity()
+            .attach(DropCounter(drops.clone()))
+            .into_id();
+        world.remove_entity(id);
+        assert_eq!(drops.load(Ordering::SeqCst),1);
+    }
+
+    #[test]
+    fn defer_test() {
+        let mut world = World::new();
+        world.register::<PhysicsBody>()
+            .register::<EntityId>();
+        world.register_despawn_finalizer::<EntityId,_>(0,|_,child,ctx| {
+            ctx.defer(move |world| { world.remove_entity(child); });
+        });
+        let child = world.create_entity().attach(PhysicsBody(2)).into_id();
+        let parent = world.create_entity().attach(child).into_id();
+        world.remove_entity(parent);
+        assert_eq!(world.query::<&EntityId>().count(),0);
+        assert_eq!(world.query::<&PhysicsBody>().count(),0);
+        assert!(!world.exist(child));
+    }
+
+    #[test]
+    fn transaction_test() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let finalized = Arc::new(AtomicUsize::new(0));
+        let mut world = World::new();
+        world.register::<DropCounter>()
+            .register::<Mesh>();
+        let finalized_ = finalized.clone();
+        world.register_despawn_finalizer::<DropCounter,_>(0,move |_,_,ctx| {
+            // the components have been taken out of world
+            assert!(ctx.read::<Mesh>().is_none());
+            finalized_.fetch_add(1,Ordering::SeqCst);
+        });
+        let id = world.create_entity()
+            .attach(DropCounter(drops.clone()))
+            .attach(Mesh("cube"))
+            .into_id();
+
+        // a rolled-back despawn never finalizes
+        let mut tx = world.begin_transaction();
+        tx.despawn(id);
+        tx.rollback();
+        assert!(world.exist(id));
+        assert_eq!(finalized.load(Ordering::SeqCst),0);
+        assert_eq!(drops.load(Ordering::SeqCst),0);
+
+        // a committed despawn finalizes exactly once
+        let mut tx = world.begin_transaction();
+        tx.despawn(id);
+        assert_eq!(finalized.load(Ordering::SeqCst),0);
+        tx.commit();
+        assert!(!world.exist(id));
+        assert_eq!(finalized.load(Ordering::SeqCst),1);
+        assert_eq!(drops.load(Ordering::SeqCst),1);
+        world.remove_entity(id);
+        assert_eq!(finalized.load(Ordering::SeqCst),1);
+    }
+}
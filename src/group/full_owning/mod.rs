@@ -1,5 +1,5 @@
 use std::{any::TypeId, marker::PhantomData};
-use crate::{component::{Component, ComponentStorage}, entity::EntityId};
+use crate::{component::{Component, ComponentStorage, MultiStorageSwap}, entity::EntityId};
 
 mod query;
 
@@ -7,11 +7,13 @@ pub use query::{
     IterRefRef,
     IterRefMut,
     IterMutRef,
-    IterMutMut
+    IterMutMut,
+    IndexedIter
 };
 
 use super::Group;
 
+#[derive(Clone)]
 pub struct FullOwningData{
     length : usize,
     type_a : TypeId,
@@ -83,8 +85,8 @@ impl FullOwningData {
         let index_a = comp_a.index(id).unwrap();
         let index_b = comp_b.index(id).unwrap();
 
-        comp_a.swap_by_index(index_a,self.length);
-        comp_b.swap_by_index(index_b,self.length);
+        MultiStorageSwap::new(&mut [&mut **comp_a,&mut **comp_b])
+            .swap_each(&[(index_a,self.length),(index_b,self.length)]);
 
         self.length += 1;
     }
@@ -104,8 +106,8 @@ impl FullOwningData {
 
         self.length -= 1;
 
-        comp_a.swap_by_index(index_a,self.length);
-        comp_b.swap_by_index(index_b,self.length);
+        MultiStorageSwap::new(&mut [&mut **comp_a,&mut **comp_b])
+            .swap_each(&[(index_a,self.length),(index_b,self.length)]);
     }
 
     pub(in crate) fn make(&mut self,
@@ -122,8 +124,8 @@ impl FullOwningData {
                     // the for loop ensure this
                     let id = comp_a.id(index_a).unwrap();
                     if let Some(index_b) = comp_b.index(id) {
-                        comp_a.swap_by_index(index_a,self.length);
-                        comp_b.swap_by_index(index_b,self.length);
+                        MultiStorageSwap::new(&mut [&mut **comp_a,&mut **comp_b])
+                            .swap_each(&[(index_a,self.length),(index_b,self.length)]);
                         self.length += 1;
                     }
                 }
@@ -133,8 +135,8 @@ impl FullOwningData {
                     // the for loop ensure this
                     let id = comp_b.id(index_b).unwrap();
                     if let Some(index_a) = comp_a.index(id) {
-                        comp_a.swap_by_index(index_a,self.length);
-                        comp_b.swap_by_index(index_b,self.length);
+                        MultiStorageSwap::new(&mut [&mut **comp_a,&mut **comp_b])
+                            .swap_each(&[(index_a,self.length),(index_b,self.length)]);
                         self.length += 1;
                     }
                 }
@@ -166,3 +168,29 @@ impl<A : Component,B : Component> Into<Group> for FullOwning<A,B> {
         })
     }
 }
+
+/// Query the full-owning group with the indices of items in group.
+/// # Details
+/// The item is ```(index,a,b)```,```index``` is the same in both storages.
+/// It's useful to build some auxiliary arrays which are sized to the
+/// [length](crate::world::World::group_len_of) of group.  
+/// **The indices are invalidated by any structural change to either storage.**
+/// # Example
+/// ```
+/// use xecs::{World, group::{full_owning, GroupIndexed}};
+/// let mut world = World::new();
+/// world.register::<u32>().register::<f32>();
+/// world.create_entity().attach(1_u32).attach(2.0_f32);
+/// world.make_group(full_owning::<u32,f32>());
+///
+/// let mut impulses = vec![0.0;world.group_len_of::<u32,f32>().unwrap()];
+/// for (index,a,b) in world.query::<GroupIndexed<&u32,&f32>>() {
+///     impulses[index] += *a as f32 * b;
+/// }
+/// assert_eq!(impulses,vec![2.0]);
+/// ```
+#[derive(Clone,Copy)]
+pub struct GroupIndexed<A,B>{
+    _marker_a : PhantomData<A>,
+    _marker_b : PhantomData<B>
+}
@@ -1,25 +1,26 @@
-use std::any::TypeId;
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
-use crate::{component::{Component, ComponentStorage}, entity::EntityId, group::{Group, full_owning}, query::{QueryIterator, Queryable}, sparse_set::SparseSet, world::World};
-use super::FullOwning;
+use std::{any::TypeId, iter::FusedIterator};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, group::full_owning, query::{DoubleEndedQueryable, QueryGet, QueryIterator, Queryable, Taken}, sparse_set::SparseSet, world::World};
+use crate::group::{GroupQueryable, LockedGroup};
+use super::{FullOwning, GroupIndexed};
 
 pub struct IterRefRef<'a,A,B> {
     index: usize,
     length: usize,
+    // the index after the last item,it's decreased by next_back
+    end: usize,
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_group : RwLockReadGuard<'a,Group>
+    borrow_group : GroupReadGuard<'a>
 }
 
-impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a B> {
-    type Item = (&'a A,&'a B);
-
-    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+impl<'a,A : Component,B : Component> IterRefRef<'a,A,B> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for FullOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -38,18 +39,69 @@ impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a B> {
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(full_owning::<A,B>()),"Queryable for FullOwning: Group is not in world");
-        let group = world.group(full_owning::<A,B>());
         let length = group.len();
-        Box::new(IterRefRef{
+        IterRefRef{
             index: 0,
             length,
+            end: length,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
             borrow_a: storage_a,
             borrow_b: storage_b,
             borrow_group: group,
-        })
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupIndexIter for IterRefRef<'a,A,B> {
+    fn next_index(&self) -> usize {
+        self.index
+    }
+
+    fn group_index(&self,id : EntityId) -> Option<usize> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        sparse_set_a.get_index(id)
+            .filter(|index| *index < self.length)
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a B> {
+    type Item = (&'a A,&'a B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(full_owning::<A,B>(),"Queryable for FullOwning: Group is not in world");
+        Box::new(IterRefRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for FullOwning<&'a A,&'a B> {
+    type Group = FullOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component> DoubleEndedQueryable<'a> for FullOwning<&'a A,&'a B> {
+    type Iter = IterRefRef<'a,A,B>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        let group = world.lock_group(full_owning::<A,B>(),"DoubleEndedQueryable for FullOwning: Group is not in world");
+        IterRefRef::new(world,group)
     }
 }
 
@@ -57,7 +109,7 @@ impl<'a,A : Component,B : Component> Iterator for IterRefRef<'a,A,B> {
     type Item = (&'a A,&'a B);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.length {
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -80,32 +132,46 @@ impl<'a,A : Component,B : Component> Iterator for IterRefRef<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.end - self.index;
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterRefRef<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterRefRef<'a,A,B>{ }
+
+impl<'a,A : Component,B : Component> DoubleEndedIterator for IterRefRef<'a,A,B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.end)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.end)
+            };
+            Some((data_a,data_b))
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
-        // Safety:
-        // Safe here, because self.sparse_set is 
-        // a pointer from borrow,
-        // This pointer is valid now.
-        let sparse_set_a = unsafe { &*self.sparse_set_a };
-        let sparse_set_b = unsafe { &*self.sparse_set_b };
-        
-        if let Some(a) = sparse_set_a.get(id) {
-            if let Some(b) = sparse_set_b.get(id) {
-                return Some((a,b))
-            }
-        }
-        None
+        self.peek_from_id(id)
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.length {
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -132,6 +198,24 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
     }
 }
 
+impl<'a,A : Component,B : Component> QueryGet for IterRefRef<'a,A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        
+        if let Some(a) = sparse_set_a.get(id) {
+            if let Some(b) = sparse_set_b.get(id) {
+                return Some((a,b))
+            }
+        }
+        None
+    }
+}
+
 
 
 
@@ -139,20 +223,22 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
 pub struct IterRefMut<'a,A,B> {
     index: usize,
     length: usize,
+    // the index after the last item,it's decreased by next_back
+    end: usize,
+    // the indices in index..end which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
-impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a mut B> {
-    type Item = (&'a A,&'a mut B);
-
-    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+impl<'a,A : Component,B : Component> IterRefMut<'a,A,B> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for FullOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -171,18 +257,70 @@ impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a mut
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(full_owning::<A,B>()),"Queryable for FullOwning: Group is not in world");
-        let group = world.group(full_owning::<A,B>());
         let length = group.len();
-        Box::new(IterRefMut{
+        IterRefMut{
             index: 0,
             length,
+            end: length,
+            taken: Taken::default(),
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
             borrow_a: storage_a,
             borrow_b: storage_b,
             borrow_group: group,
-        })
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupIndexIter for IterRefMut<'a,A,B> {
+    fn next_index(&self) -> usize {
+        self.index
+    }
+
+    fn group_index(&self,id : EntityId) -> Option<usize> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        sparse_set_a.get_index(id)
+            .filter(|index| *index < self.length)
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a A,&'a mut B> {
+    type Item = (&'a A,&'a mut B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(full_owning::<A,B>(),"Queryable for FullOwning: Group is not in world");
+        Box::new(IterRefMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for FullOwning<&'a A,&'a mut B> {
+    type Group = FullOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component> DoubleEndedQueryable<'a> for FullOwning<&'a A,&'a mut B> {
+    type Iter = IterRefMut<'a,A,B>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        let group = world.lock_group(full_owning::<A,B>(),"DoubleEndedQueryable for FullOwning: Group is not in world");
+        IterRefMut::new(world,group)
     }
 }
 
@@ -190,7 +328,8 @@ impl<'a,A : Component,B : Component> Iterator for IterRefMut<'a,A,B> {
     type Item = (&'a A,&'a mut B);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -213,12 +352,39 @@ impl<'a,A : Component,B : Component> Iterator for IterRefMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.end - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterRefMut<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterRefMut<'a,A,B>{ }
+
+impl<'a,A : Component,B : Component> DoubleEndedIterator for IterRefMut<'a,A,B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.taken.skip_back(self.index,&mut self.end);
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.end)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.end)
+            };
+            Some((data_a,data_b))
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -228,17 +394,26 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &*self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
-        
-        if let Some(a) = sparse_set_a.get(id) {
-            if let Some(b) = sparse_set_b.get_mut(id) {
-                return Some((a,b))
-            }
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.end) {
+            return None;
         }
-        None
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data().get_unchecked(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -273,20 +448,22 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
 pub struct IterMutRef<'a,A,B> {
     index: usize,
     length: usize,
+    // the index after the last item,it's decreased by next_back
+    end: usize,
+    // the indices in index..end which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
-impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a B> {
-    type Item = (&'a mut A,&'a B);
-
-    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+impl<'a,A : Component,B : Component> IterMutRef<'a,A,B> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for FullOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -305,18 +482,70 @@ impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(full_owning::<A,B>()),"Queryable for FullOwning: Group is not in world");
-        let group = world.group(full_owning::<A,B>());
         let length = group.len();
-        Box::new(IterMutRef{
+        IterMutRef{
             index: 0,
             length,
+            end: length,
+            taken: Taken::default(),
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
             borrow_a: storage_a,
             borrow_b: storage_b,
             borrow_group: group,
-        })
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupIndexIter for IterMutRef<'a,A,B> {
+    fn next_index(&self) -> usize {
+        self.index
+    }
+
+    fn group_index(&self,id : EntityId) -> Option<usize> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        sparse_set_a.get_index(id)
+            .filter(|index| *index < self.length)
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a B> {
+    type Item = (&'a mut A,&'a B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(full_owning::<A,B>(),"Queryable for FullOwning: Group is not in world");
+        Box::new(IterMutRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for FullOwning<&'a mut A,&'a B> {
+    type Group = FullOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component> DoubleEndedQueryable<'a> for FullOwning<&'a mut A,&'a B> {
+    type Iter = IterMutRef<'a,A,B>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        let group = world.lock_group(full_owning::<A,B>(),"DoubleEndedQueryable for FullOwning: Group is not in world");
+        IterMutRef::new(world,group)
     }
 }
 
@@ -324,7 +553,8 @@ impl<'a,A : Component,B : Component> Iterator for IterMutRef<'a,A,B> {
     type Item = (&'a mut A,&'a B);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -347,12 +577,39 @@ impl<'a,A : Component,B : Component> Iterator for IterMutRef<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.end - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterMutRef<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterMutRef<'a,A,B>{ }
+
+impl<'a,A : Component,B : Component> DoubleEndedIterator for IterMutRef<'a,A,B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.taken.skip_back(self.index,&mut self.end);
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.end)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.end)
+            };
+            Some((data_a,data_b))
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -362,17 +619,26 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &*self.sparse_set_b };
-        
-        if let Some(a) = sparse_set_a.get_mut(id) {
-            if let Some(b) = sparse_set_b.get(id) {
-                return Some((a,b))
-            }
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.end) {
+            return None;
         }
-        None
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data().get_unchecked(index)
+        };
+        Some((data_a,data_b))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -406,20 +672,22 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
 pub struct IterMutMut<'a,A,B> {
     index: usize,
     length: usize,
+    // the index after the last item,it's decreased by next_back
+    end: usize,
+    // the indices in index..end which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
-impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a mut B> {
-    type Item = (&'a mut A,&'a mut B);
-
-    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+impl<'a,A : Component,B : Component> IterMutMut<'a,A,B> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for FullOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -438,18 +706,70 @@ impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(full_owning::<A,B>()),"Queryable for FullOwning: Group is not in world");
-        let group = world.group(full_owning::<A,B>());
         let length = group.len();
-        Box::new(IterMutMut{
+        IterMutMut{
             index: 0,
             length,
+            end: length,
+            taken: Taken::default(),
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
             borrow_a: storage_a,
             borrow_b: storage_b,
             borrow_group: group,
-        })
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupIndexIter for IterMutMut<'a,A,B> {
+    fn next_index(&self) -> usize {
+        self.index
+    }
+
+    fn group_index(&self,id : EntityId) -> Option<usize> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        sparse_set_a.get_index(id)
+            .filter(|index| *index < self.length)
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for FullOwning<&'a mut A,&'a mut B> {
+    type Item = (&'a mut A,&'a mut B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(full_owning::<A,B>(),"Queryable for FullOwning: Group is not in world");
+        Box::new(IterMutMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for FullOwning<&'a mut A,&'a mut B> {
+    type Group = FullOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component> DoubleEndedQueryable<'a> for FullOwning<&'a mut A,&'a mut B> {
+    type Iter = IterMutMut<'a,A,B>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        let group = world.lock_group(full_owning::<A,B>(),"DoubleEndedQueryable for FullOwning: Group is not in world");
+        IterMutMut::new(world,group)
     }
 }
 
@@ -457,7 +777,8 @@ impl<'a,A : Component,B : Component> Iterator for IterMutMut<'a,A,B> {
     type Item = (&'a mut A,&'a mut B);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -480,12 +801,39 @@ impl<'a,A : Component,B : Component> Iterator for IterMutMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.end - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterMutMut<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterMutMut<'a,A,B>{ }
+
+impl<'a,A : Component,B : Component> DoubleEndedIterator for IterMutMut<'a,A,B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.taken.skip_back(self.index,&mut self.end);
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.end)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.end)
+            };
+            Some((data_a,data_b))
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -495,17 +843,26 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
-        
-        if let Some(a) = sparse_set_a.get_mut(id) {
-            if let Some(b) = sparse_set_b.get_mut(id) {
-                return Some((a,b))
-            }
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.end) {
+            return None;
         }
-        None
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.length {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -531,3 +888,133 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
         }
     }
 }
+
+
+
+
+/// The iterators of full-owning group which know the indices of items in group
+pub trait GroupIndexIter : QueryIterator {
+    /// Get the index of the next item in group
+    fn next_index(&self) -> usize;
+    /// Get the index of ```id``` in group
+    fn group_index(&self,id : EntityId) -> Option<usize>;
+}
+
+/// An iterator yields items with their indices in full-owning group
+pub struct IndexedIter<I> {
+    iter : I
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for GroupIndexed<&'a A,&'a B> {
+    type Item = (usize,&'a A,&'a B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IndexedIter{
+            iter : IterRefRef::new(world,world.lock_group(full_owning::<A,B>(),"Queryable for GroupIndexed: Group is not in world"))
+        })
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for GroupIndexed<&'a A,&'a mut B> {
+    type Item = (usize,&'a A,&'a mut B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IndexedIter{
+            iter : IterRefMut::new(world,world.lock_group(full_owning::<A,B>(),"Queryable for GroupIndexed: Group is not in world"))
+        })
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for GroupIndexed<&'a mut A,&'a B> {
+    type Item = (usize,&'a mut A,&'a B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IndexedIter{
+            iter : IterMutRef::new(world,world.lock_group(full_owning::<A,B>(),"Queryable for GroupIndexed: Group is not in world"))
+        })
+    }
+}
+
+impl<'a,A : Component,B : Component> Queryable<'a> for GroupIndexed<&'a mut A,&'a mut B> {
+    type Item = (usize,&'a mut A,&'a mut B);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(full_owning::<A,B>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IndexedIter{
+            iter : IterMutMut::new(world,world.lock_group(full_owning::<A,B>(),"Queryable for GroupIndexed: Group is not in world"))
+        })
+    }
+}
+
+impl<A,B,I> Iterator for IndexedIter<I>
+    where I : GroupIndexIter + Iterator<Item = (A,B)> {
+    type Item = (usize,A,B);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<A,B,I> ExactSizeIterator for IndexedIter<I>
+    where I : GroupIndexIter + ExactSizeIterator<Item = (A,B)> {}
+
+impl<A,B,I> QueryIterator for IndexedIter<I>
+    where I : GroupIndexIter + Iterator<Item = (A,B)> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        let index = self.iter.group_index(id)?;
+        let (a,b) = self.iter.from_id(id)?;
+        Some((index,a,b))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let (id,(a,b)) = self.iter.next_with_id()?;
+        // the items yielded by from_id are skipped,
+        // so the index is got after next_with_id
+        let index = self.iter.next_index() - 1;
+        Some((id,(index,a,b)))
+    }
+}
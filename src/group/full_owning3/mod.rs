@@ -0,0 +1,309 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::{Component, ComponentStorage, MultiStorageSwap}, entity::EntityId};
+
+mod query;
+
+pub use query::{
+    IterRefRefRef,
+    IterRefRefMut,
+    IterRefMutRef,
+    IterRefMutMut,
+    IterMutRefRef,
+    IterMutRefMut,
+    IterMutMutRef,
+    IterMutMutMut
+};
+
+use super::Group;
+
+#[derive(Clone)]
+pub struct FullOwningData3{
+    length : usize,
+    type_a : TypeId,
+    type_b : TypeId,
+    type_c : TypeId
+}
+
+impl PartialEq for FullOwningData3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_a == other.type_a && self.type_b == other.type_b && self.type_c == other.type_c
+    }
+}
+
+impl FullOwningData3 {
+    pub(in crate) fn len(&self) -> usize {
+        self.length
+    }
+
+    pub(in crate) fn types(&self) -> (TypeId,TypeId,TypeId) {
+        (self.type_a,self.type_b,self.type_c)
+    }
+
+    pub(in crate) fn owned(&self,type_id : TypeId) -> bool {
+        type_id == self.type_a || type_id == self.type_b || type_id == self.type_c
+    }
+
+    pub(in crate) fn owning(&self) -> Vec<TypeId> {
+        vec![self.type_a,self.type_b,self.type_c]
+    }
+
+    pub(in crate) fn in_components( &self,
+                id : EntityId,
+                comp_a : &dyn ComponentStorage,
+                comp_b : &dyn ComponentStorage,
+                comp_c : &dyn ComponentStorage) -> bool {
+        comp_a.has(id) && comp_b.has(id) && comp_c.has(id)
+    }
+
+    pub(in crate) fn in_group(&self,
+                id : EntityId,
+                comp_a : &dyn ComponentStorage,
+                comp_b : &dyn ComponentStorage,
+                comp_c : &dyn ComponentStorage) -> bool {
+        if !self.in_components(id,comp_a,comp_b,comp_c) {
+            return false;
+        }
+
+        // get indexes in all component storages
+        // This unwrap never fails because the in_components() ensures that it's already in components
+        let index_a = comp_a.index(id).unwrap();
+        let index_b = comp_b.index(id).unwrap();
+        let index_c = comp_c.index(id).unwrap();
+        index_a < self.length && index_b < self.length && index_c < self.length
+    }
+
+    pub(in crate) fn add(&mut self,
+           id : EntityId,
+           comp_a : &mut dyn ComponentStorage,
+           comp_b : &mut dyn ComponentStorage,
+           comp_c : &mut dyn ComponentStorage) {
+        if !self.in_components(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+        if self.in_group(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+
+        // get indexes in all component storages
+        // This unwrap never fails because the in_components() ensures that it's already in components
+        let index_a = comp_a.index(id).unwrap();
+        let index_b = comp_b.index(id).unwrap();
+        let index_c = comp_c.index(id).unwrap();
+
+        MultiStorageSwap::new(&mut [&mut *comp_a,&mut *comp_b,&mut *comp_c])
+            .swap_each(&[(index_a,self.length),(index_b,self.length),(index_c,self.length)]);
+
+        self.length += 1;
+    }
+
+    pub(in crate) fn remove(&mut self,
+              id : EntityId,
+              comp_a : &mut dyn ComponentStorage,
+              comp_b : &mut dyn ComponentStorage,
+              comp_c : &mut dyn ComponentStorage) {
+        if !self.in_group(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+
+        // get indexes in all component storages
+        // This unwrap never fails because the in_group() ensure that it's already in components
+        let index_a = comp_a.index(id).unwrap();
+        let index_b = comp_b.index(id).unwrap();
+        let index_c = comp_c.index(id).unwrap();
+
+        self.length -= 1;
+
+        MultiStorageSwap::new(&mut [&mut *comp_a,&mut *comp_b,&mut *comp_c])
+            .swap_each(&[(index_a,self.length),(index_b,self.length),(index_c,self.length)]);
+    }
+
+    pub(in crate) fn make(&mut self,
+            comp_a : &mut dyn ComponentStorage,
+            comp_b : &mut dyn ComponentStorage,
+            comp_c : &mut dyn ComponentStorage) {
+        self.length = 0;
+
+        // iterate the smallest storage,
+        // the entities before index are never moved after it
+        let counts = [comp_a.count(),comp_b.count(),comp_c.count()];
+        // Unwrap here never fails
+        // counts is not empty
+        let smallest = (0..3).min_by_key(|index| counts[*index]).unwrap();
+        for index in 0..counts[smallest] {
+            // Unwrap here never fails
+            // the for loop ensures this
+            let id = [&*comp_a,&*comp_b,&*comp_c][smallest].id(index).unwrap();
+            if self.in_components(id,comp_a,comp_b,comp_c) {
+                // This unwrap never fails because the in_components() ensures that it's already in components
+                let index_a = comp_a.index(id).unwrap();
+                let index_b = comp_b.index(id).unwrap();
+                let index_c = comp_c.index(id).unwrap();
+                MultiStorageSwap::new(&mut [&mut *comp_a,&mut *comp_b,&mut *comp_c])
+                    .swap_each(&[(index_a,self.length),(index_b,self.length),(index_c,self.length)]);
+                self.length += 1;
+            }
+        }
+    }
+}
+
+/// A full-owning group of 3 components.
+/// # Details
+/// It owns all 3 storages and keeps them aligned,
+/// the first ```length``` items of each storage are the entities in group.
+#[derive(Clone,Copy)]
+pub struct FullOwning3<A,B,C>{
+    _marker_a : PhantomData<A>,
+    _marker_b : PhantomData<B>,
+    _marker_c : PhantomData<C>
+}
+
+impl<A : Component,B : Component,C : Component> FullOwning3<A,B,C> {
+    pub(in crate) fn new() -> Self {
+        FullOwning3 {
+            _marker_a: PhantomData,
+            _marker_b: PhantomData,
+            _marker_c: PhantomData
+        }
+    }
+}
+
+impl<A : Component,B : Component,C : Component> From<FullOwning3<A,B,C>> for Group {
+    fn from(_ : FullOwning3<A,B,C>) -> Group {
+        Group::FullOwning3(FullOwningData3 {
+            length: 0,
+            type_a: TypeId::of::<A>(),
+            type_b: TypeId::of::<B>(),
+            type_c: TypeId::of::<C>()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, full_owning3, non_owning, FullOwning3}, query::{QueryIterator, WithId}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Transform(f32);
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Velocity(f32);
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Collider(u32);
+
+    fn world() -> (World,Vec<EntityId>) {
+        let mut world = World::new();
+        world.register::<Transform>()
+            .register::<Velocity>()
+            .register::<Collider>()
+            .register::<char>();
+        let ids = (0..30).map(|i| {
+            let id = world.create_entity().attach(Transform(i as f32)).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,Velocity(1.0));
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,Collider(i));
+            }
+            id
+        }).collect::<Vec<_>>();
+        (world,ids)
+    }
+
+    // Check the packed part of all storages is exactly the group
+    fn check(world : &World) {
+        let expected = {
+            let mut ids = world.query::<(&Transform,&Velocity,&Collider)>()
+                .with_id()
+                .map(|(id,_)|id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let iter = world.query::<FullOwning3<&Transform,&Velocity,&Collider>>();
+        assert_eq!(iter.len_hint(),Some(expected.len()));
+        let mut ids = vec![];
+        for (id,(t,v,c)) in iter.with_id() {
+            assert_eq!(world.get::<Transform>(id).as_deref(),Some(t));
+            assert_eq!(world.get::<Velocity>(id).as_deref(),Some(v));
+            assert_eq!(world.get::<Collider>(id).as_deref(),Some(c));
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids,expected);
+    }
+
+    #[test]
+    fn make_test() {
+        let (mut world,_) = world();
+        world.make_group(full_owning3::<Transform,Velocity,Collider>());
+        assert_eq!(world.query::<FullOwning3<&Transform,&Velocity,&Collider>>().count(),5);
+        check(&world);
+        world.remake_group(full_owning3::<Transform,Velocity,Collider>());
+        check(&world);
+    }
+
+    #[test]
+    fn add_remove_test() {
+        let (mut world,ids) = world();
+        world.make_group(full_owning3::<Transform,Velocity,Collider>());
+        world.make_group(non_owning::<Velocity,char>());
+
+        world.attach_component(ids[3],Velocity(2.0));
+        world.attach_component(ids[4],Collider(4));
+        check(&world);
+        world.detach_component::<Velocity>(ids[6]);
+        world.remove_entity(ids[0]);
+        world.remove_component::<Collider>(ids[12]);
+        check(&world);
+        let new = world.create_entity()
+            .attach(Collider(100))
+            .attach(Velocity(3.0))
+            .attach(Transform(100.0))
+            .into_id();
+        world.create_entities(3)
+            .attach(vec![Transform(0.0);3])
+            .attach(vec![Velocity(0.0);3])
+            .attach(vec![Collider(0);3]);
+        check(&world);
+        assert_eq!(world.query::<FullOwning3<&Transform,&Velocity,&Collider>>().count(),8);
+
+        let mut iter = world.query::<FullOwning3<&Transform,&Velocity,&Collider>>();
+        assert_eq!(iter.from_id(new),Some((&Transform(100.0),&Velocity(3.0),&Collider(100))));
+        assert_eq!(iter.from_id(ids[6]),None);
+    }
+
+    #[test]
+    fn mut_test() {
+        let (mut world,_) = world();
+        world.make_group(full_owning3::<Transform,Velocity,Collider>());
+        for (t,v,_) in world.query::<FullOwning3<&mut Transform,&Velocity,&Collider>>() {
+            t.0 += v.0;
+        }
+        for (_,v,c) in world.query::<FullOwning3<&Transform,&mut Velocity,&mut Collider>>() {
+            v.0 *= 2.0;
+            c.0 += 1;
+        }
+        for (t,v,c) in world.query::<FullOwning3<&mut Transform,&mut Velocity,&mut Collider>>() {
+            t.0 += v.0;
+            c.0 += 1;
+        }
+        let mut items = world.query::<FullOwning3<&Transform,&Velocity,&Collider>>()
+            .map(|(t,_,c)|(t.0 as u32,c.0))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,vec![(3,2),(9,8),(15,14),(21,20),(27,26)]);
+        assert_eq!(world.query::<FullOwning3<&mut Transform,&Velocity,&mut Collider>>().count(),5);
+        assert_eq!(world.query::<FullOwning3<&Transform,&mut Velocity,&Collider>>().count(),5);
+        assert_eq!(world.query::<FullOwning3<&mut Transform,&mut Velocity,&Collider>>().count(),5);
+        assert_eq!(world.query::<FullOwning3<&Transform,&Velocity,&mut Collider>>().count(),5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn owned_panic_test() {
+        let (mut world,_) = world();
+        world.make_group(full_owning::<Velocity,char>());
+        world.make_group(full_owning3::<Transform,Velocity,Collider>());
+    }
+}
@@ -0,0 +1,1538 @@
+use std::{any::TypeId, iter::FusedIterator};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, group::full_owning3, query::{QueryGet, QueryIterator, Queryable, Taken}, sparse_set::SparseSet, world::World};
+use crate::group::{GroupQueryable, LockedGroup};
+use super::FullOwning3;
+
+pub struct IterRefRefRef<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    sparse_set_a: *const SparseSet<EntityId,A>,
+    sparse_set_b: *const SparseSet<EntityId,B>,
+    sparse_set_c: *const SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterRefRefRef<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let storage_a = world.raw_storage_read(type_id_a).unwrap();
+        let storage_b = world.raw_storage_read(type_id_b).unwrap();
+        let storage_c = world.raw_storage_read(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_ref::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_ref::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_ref::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = sparse_set_a;
+        let ptr_b = sparse_set_b;
+        let ptr_c = sparse_set_c;
+        let length = group.len();
+        IterRefRefRef{
+            index: 0,
+            length,
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a A,&'a B,&'a C> {
+    type Item = (&'a A,&'a B,&'a C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterRefRefRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a A,&'a B,&'a C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefRefRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterRefRefRef<'a,A,B,C> {
+    type Item = (&'a A,&'a B,&'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index;
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterRefRefRef<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterRefRefRef<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterRefRefRef<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> QueryGet for IterRefRefRef<'a,A,B,C> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        let sparse_set_c = unsafe { &*self.sparse_set_c };
+        
+        if let Some(a) = sparse_set_a.get(id) {
+            if let Some(b) = sparse_set_b.get(id) {
+                if let Some(c) = sparse_set_c.get(id) {
+                    return Some((a,b,c))
+                }
+            }
+        }
+        None
+    }
+}
+
+
+
+
+pub struct IterRefRefMut<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *const SparseSet<EntityId,A>,
+    sparse_set_b: *const SparseSet<EntityId,B>,
+    sparse_set_c: *mut SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterRefRefMut<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let storage_a = world.raw_storage_read(type_id_a).unwrap();
+        let storage_b = world.raw_storage_read(type_id_b).unwrap();
+        let mut storage_c = world.raw_storage_write(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_ref::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_ref::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_mut::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = sparse_set_a;
+        let ptr_b = sparse_set_b;
+        let ptr_c = &mut *sparse_set_c;
+        let length = group.len();
+        IterRefRefMut{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a A,&'a B,&'a mut C> {
+    type Item = (&'a A,&'a B,&'a mut C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterRefRefMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a A,&'a B,&'a mut C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefRefMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterRefRefMut<'a,A,B,C> {
+    type Item = (&'a A,&'a B,&'a mut C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterRefRefMut<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterRefRefMut<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterRefRefMut<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data().get_unchecked(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data().get_unchecked(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterRefMutRef<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *const SparseSet<EntityId,A>,
+    sparse_set_b: *mut SparseSet<EntityId,B>,
+    sparse_set_c: *const SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterRefMutRef<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let storage_a = world.raw_storage_read(type_id_a).unwrap();
+        let mut storage_b = world.raw_storage_write(type_id_b).unwrap();
+        let storage_c = world.raw_storage_read(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_ref::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_mut::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_ref::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = sparse_set_a;
+        let ptr_b = &mut *sparse_set_b;
+        let ptr_c = sparse_set_c;
+        let length = group.len();
+        IterRefMutRef{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a A,&'a mut B,&'a C> {
+    type Item = (&'a A,&'a mut B,&'a C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterRefMutRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a A,&'a mut B,&'a C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefMutRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterRefMutRef<'a,A,B,C> {
+    type Item = (&'a A,&'a mut B,&'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterRefMutRef<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterRefMutRef<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterRefMutRef<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let sparse_set_c = unsafe { &*self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data().get_unchecked(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data().get_unchecked(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterRefMutMut<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *const SparseSet<EntityId,A>,
+    sparse_set_b: *mut SparseSet<EntityId,B>,
+    sparse_set_c: *mut SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterRefMutMut<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let storage_a = world.raw_storage_read(type_id_a).unwrap();
+        let mut storage_b = world.raw_storage_write(type_id_b).unwrap();
+        let mut storage_c = world.raw_storage_write(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_ref::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_mut::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_mut::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = sparse_set_a;
+        let ptr_b = &mut *sparse_set_b;
+        let ptr_c = &mut *sparse_set_c;
+        let length = group.len();
+        IterRefMutMut{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a A,&'a mut B,&'a mut C> {
+    type Item = (&'a A,&'a mut B,&'a mut C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterRefMutMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a A,&'a mut B,&'a mut C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterRefMutMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterRefMutMut<'a,A,B,C> {
+    type Item = (&'a A,&'a mut B,&'a mut C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterRefMutMut<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterRefMutMut<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterRefMutMut<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data().get_unchecked(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &*self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data().get_unchecked(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterMutRefRef<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *mut SparseSet<EntityId,A>,
+    sparse_set_b: *const SparseSet<EntityId,B>,
+    sparse_set_c: *const SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterMutRefRef<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let mut storage_a = world.raw_storage_write(type_id_a).unwrap();
+        let storage_b = world.raw_storage_read(type_id_b).unwrap();
+        let storage_c = world.raw_storage_read(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_mut::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_ref::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_ref::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = &mut *sparse_set_a;
+        let ptr_b = sparse_set_b;
+        let ptr_c = sparse_set_c;
+        let length = group.len();
+        IterMutRefRef{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a mut A,&'a B,&'a C> {
+    type Item = (&'a mut A,&'a B,&'a C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterMutRefRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a mut A,&'a B,&'a C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutRefRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterMutRefRef<'a,A,B,C> {
+    type Item = (&'a mut A,&'a B,&'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterMutRefRef<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterMutRefRef<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterMutRefRef<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        let sparse_set_c = unsafe { &*self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data().get_unchecked(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data().get_unchecked(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterMutRefMut<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *mut SparseSet<EntityId,A>,
+    sparse_set_b: *const SparseSet<EntityId,B>,
+    sparse_set_c: *mut SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterMutRefMut<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let mut storage_a = world.raw_storage_write(type_id_a).unwrap();
+        let storage_b = world.raw_storage_read(type_id_b).unwrap();
+        let mut storage_c = world.raw_storage_write(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_mut::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_ref::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_mut::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = &mut *sparse_set_a;
+        let ptr_b = sparse_set_b;
+        let ptr_c = &mut *sparse_set_c;
+        let length = group.len();
+        IterMutRefMut{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a mut A,&'a B,&'a mut C> {
+    type Item = (&'a mut A,&'a B,&'a mut C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterMutRefMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a mut A,&'a B,&'a mut C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutRefMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterMutRefMut<'a,A,B,C> {
+    type Item = (&'a mut A,&'a B,&'a mut C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterMutRefMut<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterMutRefMut<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterMutRefMut<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data().get_unchecked(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &*self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data().get_unchecked(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterMutMutRef<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *mut SparseSet<EntityId,A>,
+    sparse_set_b: *mut SparseSet<EntityId,B>,
+    sparse_set_c: *const SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageReadGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterMutMutRef<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let mut storage_a = world.raw_storage_write(type_id_a).unwrap();
+        let mut storage_b = world.raw_storage_write(type_id_b).unwrap();
+        let storage_c = world.raw_storage_read(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_mut::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_mut::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_ref::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = &mut *sparse_set_a;
+        let ptr_b = &mut *sparse_set_b;
+        let ptr_c = sparse_set_c;
+        let length = group.len();
+        IterMutMutRef{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a mut A,&'a mut B,&'a C> {
+    type Item = (&'a mut A,&'a mut B,&'a C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterMutMutRef::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a mut A,&'a mut B,&'a C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutMutRef::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterMutMutRef<'a,A,B,C> {
+    type Item = (&'a mut A,&'a mut B,&'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterMutMutRef<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterMutMutRef<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterMutMutRef<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+        let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let sparse_set_c = unsafe { &*self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data().get_unchecked(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &*self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data().get_unchecked(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
+
+
+
+
+pub struct IterMutMutMut<'a,A,B,C> {
+    index: usize,
+    length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
+    sparse_set_a: *mut SparseSet<EntityId,A>,
+    sparse_set_b: *mut SparseSet<EntityId,B>,
+    sparse_set_c: *mut SparseSet<EntityId,C>,
+    #[allow(unused)]
+    borrow_a: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_b: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_c: StorageWriteGuard<'a>,
+    #[allow(unused)]
+    borrow_group: GroupReadGuard<'a>
+}
+
+impl<'a,A : Component,B : Component,C : Component> IterMutMutMut<'a,A,B,C> {
+    pub(in crate) fn new(world : &'a World,group : GroupReadGuard<'a>) -> Self {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>() && world.has_registered::<C>(),
+                "Queryable for FullOwning3: Component was not registered in world");
+        let type_id_a = TypeId::of::<A>();
+        let type_id_b = TypeId::of::<B>();
+        let type_id_c = TypeId::of::<C>();
+        // Unwrap here
+        // assert before ensures this
+        let mut storage_a = world.raw_storage_write(type_id_a).unwrap();
+        let mut storage_b = world.raw_storage_write(type_id_b).unwrap();
+        let mut storage_c = world.raw_storage_write(type_id_c).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,...>
+        let sparse_set_a = unsafe {
+            storage_a.downcast_mut::<SparseSet<EntityId,A>>()
+        };
+        let sparse_set_b = unsafe {
+            storage_b.downcast_mut::<SparseSet<EntityId,B>>()
+        };
+        let sparse_set_c = unsafe {
+            storage_c.downcast_mut::<SparseSet<EntityId,C>>()
+        };
+        let ptr_a = &mut *sparse_set_a;
+        let ptr_b = &mut *sparse_set_b;
+        let ptr_c = &mut *sparse_set_c;
+        let length = group.len();
+        IterMutMutMut{
+            index: 0,
+            length,
+            taken: Taken::default(),
+            sparse_set_a: ptr_a,
+            sparse_set_b: ptr_b,
+            sparse_set_c: ptr_c,
+            borrow_a: storage_a,
+            borrow_b: storage_b,
+            borrow_c: storage_c,
+            borrow_group: group,
+        }
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Queryable<'a> for FullOwning3<&'a mut A,&'a mut B,&'a mut C> {
+    type Item = (&'a mut A,&'a mut B,&'a mut C);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+        types.push(TypeId::of::<C>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>(),TypeId::of::<C>()])?;
+        world.check_group(full_owning3::<A,B,C>())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let group = world.lock_group(full_owning3::<A,B,C>(),"Queryable for FullOwning3: Group is not in world");
+        Box::new(IterMutMutMut::new(world,group))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> GroupQueryable<'a> for FullOwning3<&'a mut A,&'a mut B,&'a mut C> {
+    type Group = FullOwning3<A,B,C>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterMutMutMut::new(world,group.0))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> Iterator for IterMutMutMut<'a,A,B,C> {
+    type Item = (&'a mut A,&'a mut B,&'a mut C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((data_a,data_b,data_c))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.length - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,A : Component,B : Component,C : Component> ExactSizeIterator for IterMutMutMut<'a,A,B,C>{ }
+impl<'a,A : Component,B : Component,C : Component> FusedIterator for IterMutMutMut<'a,A,B,C>{ }
+
+impl<'a,A : Component,B : Component,C : Component> QueryIterator for IterMutMutMut<'a,A,B,C> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+        let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
+        // Safety:
+        // Safe here, because the items in group have the same index in all storages.
+        let data_a = unsafe {
+            sparse_set_a.data_mut().get_unchecked_mut(index)
+        };
+        let data_b = unsafe {
+            sparse_set_b.data_mut().get_unchecked_mut(index)
+        };
+        let data_c = unsafe {
+            sparse_set_c.data_mut().get_unchecked_mut(index)
+        };
+        Some((data_a,data_b,data_c))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
+        if self.index < self.length {
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set_a = unsafe { &mut *self.sparse_set_a };
+            let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+            let sparse_set_c = unsafe { &mut *self.sparse_set_c };
+            // Safety:
+            // Safe here, because we checked before.
+            let id = *unsafe {
+                sparse_set_a.entities()
+                    .get_unchecked(self.index)
+            };
+            let data_a = unsafe {
+                sparse_set_a.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_b = unsafe {
+                sparse_set_b.data_mut().get_unchecked_mut(self.index)
+            };
+            let data_c = unsafe {
+                sparse_set_c.data_mut().get_unchecked_mut(self.index)
+            };
+            self.index += 1;
+            Some((id,(data_a,data_b,data_c)))
+        } else {
+            None
+        }
+    }
+}
@@ -9,6 +9,7 @@
 //! ### Full-Owning Group
 //! Full-owning group owns 2 component storages as its name.It's the fastest group type 
 //! because its can rearrange these 2 component storages to make them aligned.
+//! [FullOwning3](crate::group::FullOwning3) does the same to 3 component storages.
 //! ### Partial-Owning Group
 //! Partial-Owning only owns the first storage.It's not faster than Full-Owning group but 
 //! it can stil make iteration fast
@@ -16,85 +17,124 @@
 //! This group does not own any storage.It use an extra sparse set to 
 //! record the entities owned by all storage.Although it's the slowest group and it need more 
 //! memory to accelerate the iteration,it sill fast than raw query iteration.
-use std::any::TypeId;
-use crate::{component::{Component, ComponentStorage}, entity::EntityId};
+//! [NonOwning3](crate::group::NonOwning3) does the same to 3 component storages.
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::{Component, ComponentStorage}, entity::EntityId, lock::GroupReadGuard, query::{QueryIterator, Queryable}, world::World};
 
 /// Full-owning group and its [Queryable](crate::query::Queryable) impls
 pub mod full_owning;
+/// Full-owning group of 3 components and its [Queryable](crate::query::Queryable) impls
+pub mod full_owning3;
 /// Partial-owning group and its [Queryable](crate::query::Queryable) impls
 pub mod partial_owning;
 /// Non-owning group and its [Queryable](crate::query::Queryable) impls
 pub mod non_owning;
+/// Non-owning group of 3 components and its [Queryable](crate::query::Queryable) impls
+pub mod non_owning3;
 
-pub use full_owning::FullOwning;
+pub use full_owning::{FullOwning, GroupIndexed};
+pub use full_owning3::FullOwning3;
 pub use partial_owning::PartialOwning;
 pub use non_owning::NonOwning;
+pub use non_owning3::NonOwning3;
 
 use self::{
     full_owning::FullOwningData,
+    full_owning3::FullOwningData3,
     non_owning::NonOwningData,
+    non_owning3::NonOwningData3,
     partial_owning::PartialOwningData
 };
 
-#[derive(PartialEq)]
+#[derive(Clone,PartialEq)]
 pub enum Group {
     FullOwning(FullOwningData),
+    FullOwning3(FullOwningData3),
     PartialOwning(PartialOwningData),
-    NonOwning(NonOwningData)
+    NonOwning(NonOwningData),
+    NonOwning3(NonOwningData3)
 }
 
 impl Group {
     pub fn len(&self) -> usize {
         match self {
             Group::FullOwning(data) => data.len(),
+            Group::FullOwning3(data) => data.len(),
             Group::PartialOwning(data) => data.len(),
             Group::NonOwning(data) => data.len(),
+            Group::NonOwning3(data) => data.len(),
         }
     }
 
-    pub fn types(&self) -> (TypeId,TypeId) {
+    /// Get the types of group in order of storages
+    pub fn types(&self) -> Vec<TypeId> {
         match &self {
-            Group::FullOwning(data) => data.types(),
-            Group::PartialOwning(data) => data.types(),
-            Group::NonOwning(data) => data.types(),
+            Group::FullOwning(data) => {
+                let (type_a,type_b) = data.types();
+                vec![type_a,type_b]
+            },
+            Group::FullOwning3(data) => {
+                let (type_a,type_b,type_c) = data.types();
+                vec![type_a,type_b,type_c]
+            },
+            Group::PartialOwning(data) => {
+                let (type_a,type_b) = data.types();
+                vec![type_a,type_b]
+            },
+            Group::NonOwning(data) => {
+                let (type_a,type_b) = data.types();
+                vec![type_a,type_b]
+            },
+            Group::NonOwning3(data) => {
+                let (type_a,type_b,type_c) = data.types();
+                vec![type_a,type_b,type_c]
+            },
         }
     }
 
     pub fn owned(&self,type_id : TypeId) -> bool {
         match self {
             Group::FullOwning(data) => data.owned(type_id),
+            Group::FullOwning3(data) => data.owned(type_id),
             Group::PartialOwning(data) => data.owned(type_id),
             Group::NonOwning(data) => data.owned(type_id),
+            Group::NonOwning3(data) => data.owned(type_id),
         }
     }
 
     pub fn owning(&self) -> Vec<TypeId> {
         match self {
             Group::FullOwning(data) => data.owning(),
+            Group::FullOwning3(data) => data.owning(),
             Group::PartialOwning(data) => data.owning(),
             Group::NonOwning(data) => data.owning(),
+            Group::NonOwning3(data) => data.owning(),
         }
     }
 
+    /// ```storages``` are in order of [types](Group::types)
     pub fn in_components(&self,
                 id : EntityId,
-                comp_a : &Box<dyn ComponentStorage>,
-                comp_b : &Box<dyn ComponentStorage>) -> bool {
+                storages : &[&Box<dyn ComponentStorage>]) -> bool {
         match self {
-            Group::FullOwning(data) => data.in_components(id,comp_a,comp_b),
-            Group::PartialOwning(data) => data.in_components(id, comp_a, comp_b),
-            Group::NonOwning(data) => data.in_components(id, comp_a, comp_b),
+            Group::FullOwning(data) => data.in_components(id,storages[0],storages[1]),
+            Group::FullOwning3(data) => data.in_components(id,&**storages[0],&**storages[1],&**storages[2]),
+            Group::PartialOwning(data) => data.in_components(id,storages[0],storages[1]),
+            Group::NonOwning(data) => data.in_components(id,storages[0],storages[1]),
+            Group::NonOwning3(data) => data.in_components(id,&**storages[0],&**storages[1],&**storages[2]),
         }
     }
 
+    /// ```storages``` are in order of [types](Group::types)
     pub fn in_group(&self,
                 id : EntityId,
-                comp_a : &Box<dyn ComponentStorage>,
-                comp_b : &Box<dyn ComponentStorage>) -> bool {
+                storages : &[&Box<dyn ComponentStorage>]) -> bool {
         match self {
-            Group::FullOwning(data) => data.in_group(id, comp_a, comp_b),
-            Group::PartialOwning(data) => data.in_group(id, comp_a, comp_b),
-            Group::NonOwning(data) => data.in_group(id, comp_a, comp_b),
+            Group::FullOwning(data) => data.in_group(id,storages[0],storages[1]),
+            Group::FullOwning3(data) => data.in_group(id,&**storages[0],&**storages[1],&**storages[2]),
+            Group::PartialOwning(data) => data.in_group(id,storages[0],storages[1]),
+            Group::NonOwning(data) => data.in_group(id,storages[0],storages[1]),
+            Group::NonOwning3(data) => data.in_group(id,&**storages[0],&**storages[1],&**storages[2]),
         }
     }
 }
@@ -104,6 +144,11 @@ pub fn full_owning<A : Component,B : Component>() -> FullOwning<A,B> {
     FullOwning::<A,B>::new()
 }
 
+/// A useful function to create FullOwning3 group
+pub fn full_owning3<A : Component,B : Component,C : Component>() -> FullOwning3<A,B,C> {
+    FullOwning3::<A,B,C>::new()
+}
+
 /// A useful function to create PartialOwning group
 pub fn partial_owning<A : Component,B : Component>() -> PartialOwning<A,B> {
     PartialOwning::<A,B>::new()
@@ -114,3 +159,64 @@ pub fn non_owning<A : Component,B : Component>() -> NonOwning<A,B> {
     NonOwning::<A,B>::new()
 }
 
+/// A useful function to create NonOwning3 group
+pub fn non_owning3<A : Component,B : Component,C : Component>() -> NonOwning3<A,B,C> {
+    NonOwning3::<A,B,C>::new()
+}
+
+/// A resolved group in world,see [group_handle](crate::world::World::group_handle)
+/// # Details
+/// It's invalidated when any group is removed from world.
+pub struct GroupHandle<G> {
+    index : usize,
+    generation : u64,
+    _marker : PhantomData<G>
+}
+
+impl<G> GroupHandle<G> {
+    pub(in crate) fn new(index : usize,generation : u64) -> Self {
+        GroupHandle {
+            index,
+            generation,
+            _marker : PhantomData
+        }
+    }
+
+    pub(in crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(in crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl<G> Clone for GroupHandle<G> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<G> Copy for GroupHandle<G> {}
+
+impl<G> std::fmt::Debug for GroupHandle<G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GroupHandle")
+            .field("index",&self.index)
+            .field("generation",&self.generation)
+            .finish()
+    }
+}
+
+/// The read lock of a group which is resolved already
+pub struct LockedGroup<'a>(pub(in crate) GroupReadGuard<'a>);
+
+/// A group query which can be made from a [GroupHandle],
+/// see [query_group](crate::world::World::query_group)
+pub trait GroupQueryable<'a> : Queryable<'a> {
+    /// The group descriptor like [FullOwning<A,B>](crate::group::FullOwning)
+    type Group;
+
+    /// Query with the locked group,the group lookup is skipped
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a>;
+}
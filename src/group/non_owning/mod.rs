@@ -12,6 +12,7 @@ pub use query::{
 };
 
 
+#[derive(Clone)]
 pub struct NonOwningData {
     sparse_set : SparseSet<EntityId,(usize,usize)>,
     type_a : TypeId,
@@ -1,6 +1,6 @@
-use std::any::TypeId;
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
-use crate::{component::{Component, ComponentStorage}, entity::EntityId, group::{Group, non_owning}, query::{QueryIterator, Queryable}, sparse_set::SparseSet, world::World};
+use std::{any::TypeId, iter::FusedIterator};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, group::{Group, GroupQueryable, LockedGroup, non_owning}, query::{QueryGet, QueryIterator, Queryable, Taken}, sparse_set::SparseSet, world::World};
 use super::NonOwning;
 
 pub struct IterRefRef<'a,A,B> {
@@ -9,17 +9,36 @@ pub struct IterRefRef<'a,A,B> {
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>,
+    borrow_group: GroupReadGuard<'a>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>
+    borrow_b: StorageReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a A,&'a B> {
     type Item = (&'a A,&'a B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(non_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(non_owning::<A,B>(),"Queryable for NonOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for NonOwning<&'a A,&'a B> {
+    type Group = NonOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for NonOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -38,8 +57,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a A,&'a B> {
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(non_owning::<A,B>()),"Queryable for NonOwning: Group is not in world");
-        let group = world.group(non_owning::<A,B>());
+        let group = group.0;
         let group_data = match &*group {
             Group::NonOwning(data) => data,
             _ => unreachable!()
@@ -70,8 +88,8 @@ impl<'a,A: Component,B : Component> Iterator for IterRefRef<'a,A,B> {
         if self.index < group.len() {
             // Safety:
             // we checked in if condition
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
+            let id = *unsafe {
+                group.entities().get_unchecked(self.index)
             };
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -80,12 +98,12 @@ impl<'a,A: Component,B : Component> Iterator for IterRefRef<'a,A,B> {
             let sparse_set_a = unsafe { &*self.sparse_set_a };
             let sparse_set_b = unsafe { &*self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
+                sparse_set_a.get_unchecked(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
+                sparse_set_b.get_unchecked(id)
             };
             self.index += 1;
             Some((data_a,data_b))
@@ -101,29 +119,11 @@ impl<'a,A: Component,B : Component> Iterator for IterRefRef<'a,A,B> {
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterRefRef<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterRefRef<'a,A,B>{ }
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
-    fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
-        // Safety:
-        // Safe here, because these are
-        // pointers from borrow,
-        // This pointer is valid now.
-        let group = unsafe { &*self.sparse_set_group };
-        let sparse_set_a = unsafe { &*self.sparse_set_a };
-        let sparse_set_b = unsafe { &*self.sparse_set_b };
-        if let Some((index_a,index_b)) = group.get(id) {
-            // Safety:
-            // Safe here, because the index stored in group is valid.
-            let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
-            };
-            let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
-            };
-            Some((data_a,data_b))
-        } else {
-            None
-        }
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
@@ -138,9 +138,6 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
             let id = *unsafe {
                 group.entities().get_unchecked(self.index)
             };
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
-            };
             // Safety:
             // Safe here, because these are
             // pointers from borrow,
@@ -148,12 +145,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
             let sparse_set_a = unsafe { &*self.sparse_set_a };
             let sparse_set_b = unsafe { &*self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
+                sparse_set_a.get_unchecked(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
+                sparse_set_b.get_unchecked(id)
             };
             self.index += 1;
             Some((id,(data_a,data_b)))
@@ -163,6 +160,31 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
     }
 }
 
+impl<'a,A : Component,B : Component> QueryGet for IterRefRef<'a,A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because these are
+        // pointers from borrow,
+        // This pointer is valid now.
+        let group = unsafe { &*self.sparse_set_group };
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        if group.exist(id) {
+            // Safety:
+            // Safe here, because the entities in group are in both storages.
+            let data_a = unsafe {
+                sparse_set_a.get_unchecked(id)
+            };
+            let data_b = unsafe {
+                sparse_set_b.get_unchecked(id)
+            };
+            Some((data_a,data_b))
+        } else {
+            None
+        }
+    }
+}
+
 
 
 
@@ -170,21 +192,42 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
 
 pub struct IterRefMut<'a,A,B> {
     index: usize,
+    // the indices in group which are yielded by from_id
+    taken: Taken,
     sparse_set_group: *const SparseSet<EntityId,(usize,usize)>,
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>,
+    borrow_group: GroupReadGuard<'a>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>
+    borrow_b: StorageWriteGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a A,&'a mut B> {
     type Item = (&'a A,&'a mut B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(non_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(non_owning::<A,B>(),"Queryable for NonOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for NonOwning<&'a A,&'a mut B> {
+    type Group = NonOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for NonOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -203,8 +246,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a A,&'a mut B
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(non_owning::<A,B>()),"Queryable for NonOwning: Group is not in world");
-        let group = world.group(non_owning::<A,B>());
+        let group = group.0;
         let group_data = match &*group {
             Group::NonOwning(data) => data,
             _ => unreachable!()
@@ -213,6 +255,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a A,&'a mut B
         let ptr_group = &*group_data;
         Box::new(IterRefMut{
             index: 0,
+            taken: Taken::default(),
             sparse_set_group: ptr_group,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -232,11 +275,12 @@ impl<'a,A: Component,B : Component> Iterator for IterRefMut<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // we checked in if condition
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
+            let id = *unsafe {
+                group.entities().get_unchecked(self.index)
             };
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -245,12 +289,12 @@ impl<'a,A: Component,B : Component> Iterator for IterRefMut<'a,A,B> {
             let sparse_set_a = unsafe { &*self.sparse_set_a };
             let sparse_set_b = unsafe { &mut *self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
+                sparse_set_a.get_unchecked(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             self.index += 1;
             Some((data_a,data_b))
@@ -260,12 +304,13 @@ impl<'a,A: Component,B : Component> Iterator for IterRefMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.borrow_group.len() - self.index;
+        let rem = self.borrow_group.len() - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterRefMut<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterRefMut<'a,A,B>{ }
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
     fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
@@ -276,14 +321,20 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
         let group = unsafe { &*self.sparse_set_group };
         let sparse_set_a = unsafe { &*self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
-        if let Some((index_a,index_b)) = group.get(id) {
+        let index = group.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,group.len()) {
+            return None;
+        }
+        if group.exist(id) {
             // Safety:
             // Safe here, because index stored in group is valid.
             let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
+                sparse_set_a.get_unchecked(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             Some((data_a,data_b))
         } else {
@@ -297,15 +348,13 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // Safe here, because if condition ensures this.
             let id = *unsafe {
                 group.entities().get_unchecked(self.index)
             };
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
-            };
             // Safety:
             // Safe here, because these are
             // pointers from borrow,
@@ -313,12 +362,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
             let sparse_set_a = unsafe { &*self.sparse_set_a };
             let sparse_set_b = unsafe { &mut *self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data().get_unchecked(*index_a)
+                sparse_set_a.get_unchecked(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             self.index += 1;
             Some((id,(data_a,data_b)))
@@ -335,21 +384,42 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
 
 pub struct IterMutRef<'a,A,B> {
     index: usize,
+    // the indices in group which are yielded by from_id
+    taken: Taken,
     sparse_set_group: *const SparseSet<EntityId,(usize,usize)>,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>,
+    borrow_group: GroupReadGuard<'a>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>
+    borrow_b: StorageReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a B> {
     type Item = (&'a mut A,&'a B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(non_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(non_owning::<A,B>(),"Queryable for NonOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for NonOwning<&'a mut A,&'a B> {
+    type Group = NonOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for NonOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -368,8 +438,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a B
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(non_owning::<A,B>()),"Queryable for NonOwning: Group is not in world");
-        let group = world.group(non_owning::<A,B>());
+        let group = group.0;
         let group_data = match &*group {
             Group::NonOwning(data) => data,
             _ => unreachable!()
@@ -378,6 +447,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a B
         let ptr_group = &*group_data;
         Box::new(IterMutRef{
             index: 0,
+            taken: Taken::default(),
             sparse_set_group: ptr_group,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -397,11 +467,12 @@ impl<'a,A: Component,B : Component> Iterator for IterMutRef<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // we checked in if condition
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
+            let id = *unsafe {
+                group.entities().get_unchecked(self.index)
             };
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -410,12 +481,12 @@ impl<'a,A: Component,B : Component> Iterator for IterMutRef<'a,A,B> {
             let sparse_set_a = unsafe { &mut *self.sparse_set_a };
             let sparse_set_b = unsafe { &*self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
+                sparse_set_b.get_unchecked(id)
             };
             self.index += 1;
             Some((data_a,data_b))
@@ -425,12 +496,13 @@ impl<'a,A: Component,B : Component> Iterator for IterMutRef<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.borrow_group.len() - self.index;
+        let rem = self.borrow_group.len() - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterMutRef<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterMutRef<'a,A,B>{ }
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
     fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
@@ -441,14 +513,20 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
         let group = unsafe { &*self.sparse_set_group };
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &*self.sparse_set_b };
-        if let Some((index_a,index_b)) = group.get(id) {
+        let index = group.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,group.len()) {
+            return None;
+        }
+        if group.exist(id) {
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
+                sparse_set_b.get_unchecked(id)
             };
             Some((data_a,data_b))
         } else {
@@ -462,15 +540,13 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // Safe here, because if condition ensure this
             let id = *unsafe {
                 group.entities().get_unchecked(self.index)
             };
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
-            };
             // Safety:
             // Safe here, because these are
             // pointers from borrow,
@@ -478,12 +554,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
             let sparse_set_a = unsafe { &mut *self.sparse_set_a };
             let sparse_set_b = unsafe { &*self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data().get_unchecked(*index_b)
+                sparse_set_b.get_unchecked(id)
             };
             self.index += 1;
             Some((id,(data_a,data_b)))
@@ -501,21 +577,42 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
 
 pub struct IterMutMut<'a,A,B> {
     index: usize,
+    // the indices in group which are yielded by from_id
+    taken: Taken,
     sparse_set_group: *const SparseSet<EntityId,(usize,usize)>,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>,
+    borrow_group: GroupReadGuard<'a>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>
+    borrow_b: StorageWriteGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a mut B> {
     type Item = (&'a mut A,&'a mut B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(non_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(non_owning::<A,B>(),"Queryable for NonOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for NonOwning<&'a mut A,&'a mut B> {
+    type Group = NonOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for NonOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -534,8 +631,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a m
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(non_owning::<A,B>()),"Queryable for NonOwning: Group is not in world");
-        let group = world.group(non_owning::<A,B>());
+        let group = group.0;
         let group_data = match &*group {
             Group::NonOwning(data) => data,
             _ => unreachable!()
@@ -544,6 +640,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for NonOwning<&'a mut A,&'a m
         let ptr_group = &*group_data;
         Box::new(IterMutMut{
             index: 0,
+            taken: Taken::default(),
             sparse_set_group: ptr_group,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -563,11 +660,12 @@ impl<'a,A: Component,B : Component> Iterator for IterMutMut<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // we checked in if condition
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
+            let id = *unsafe {
+                group.entities().get_unchecked(self.index)
             };
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -576,12 +674,12 @@ impl<'a,A: Component,B : Component> Iterator for IterMutMut<'a,A,B> {
             let sparse_set_a = unsafe { &mut *self.sparse_set_a };
             let sparse_set_b = unsafe { &mut *self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             self.index += 1;
             Some((data_a,data_b))
@@ -591,12 +689,13 @@ impl<'a,A: Component,B : Component> Iterator for IterMutMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.borrow_group.len() - self.index;
+        let rem = self.borrow_group.len() - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A : Component,B : Component> ExactSizeIterator for IterMutMut<'a,A,B>{ }
+impl<'a,A : Component,B : Component> FusedIterator for IterMutMut<'a,A,B>{ }
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
     fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
@@ -607,14 +706,20 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
         let group = unsafe { &*self.sparse_set_group };
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
-        if let Some((index_a,index_b)) = group.get(id) {
+        let index = group.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,group.len()) {
+            return None;
+        }
+        if group.exist(id) {
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             Some((data_a,data_b))
         } else {
@@ -628,15 +733,13 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
         // a pointer from borrow,
         // This pointer is valid now.
         let group = unsafe { &*self.sparse_set_group };
+        self.taken.skip_front(&mut self.index,group.len());
         if self.index < group.len() {
             // Safety:
             // Safe here, because if condition ensure this
             let id = *unsafe {
                 group.entities().get_unchecked(self.index)
             };
-            let (index_a,index_b) = unsafe {
-                group.data().get_unchecked(self.index)
-            };
             // Safety:
             // Safe here, because these are
             // pointers from borrow,
@@ -644,12 +747,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
             let sparse_set_a = unsafe { &mut *self.sparse_set_a };
             let sparse_set_b = unsafe { &mut *self.sparse_set_b };
             // Safety:
-            // Safe here, because the index stored in group is valid.
+            // Safe here, because the entities in group are in both storages.
             let data_a = unsafe {
-                sparse_set_a.data_mut().get_unchecked_mut(*index_a)
+                sparse_set_a.get_unchecked_mut(id)
             };
             let data_b = unsafe {
-                sparse_set_b.data_mut().get_unchecked_mut(*index_b)
+                sparse_set_b.get_unchecked_mut(id)
             };
             self.index += 1;
             Some((id,(data_a,data_b)))
@@ -0,0 +1,281 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::{Component, ComponentStorage}, entity::EntityId, sparse_set::SparseSet};
+
+mod query;
+
+pub use query::{
+    IterRefRefRef,
+    IterRefRefMut,
+    IterRefMutRef,
+    IterRefMutMut,
+    IterMutRefRef,
+    IterMutRefMut,
+    IterMutMutRef,
+    IterMutMutMut
+};
+
+use super::Group;
+
+#[derive(Clone)]
+pub struct NonOwningData3 {
+    sparse_set : SparseSet<EntityId,(usize,usize,usize)>,
+    type_a : TypeId,
+    type_b : TypeId,
+    type_c : TypeId
+}
+
+impl PartialEq for NonOwningData3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_a == other.type_a && self.type_b == other.type_b && self.type_c == other.type_c
+    }
+}
+
+impl NonOwningData3 {
+    pub(in crate) fn len(&self) -> usize {
+        self.sparse_set.len()
+    }
+
+    pub(in crate) fn types(&self) -> (TypeId,TypeId,TypeId) {
+        (self.type_a,self.type_b,self.type_c)
+    }
+
+    pub(in crate) fn owned(&self,_type_id : TypeId) -> bool {
+        false
+    }
+
+    pub(in crate) fn owning(&self) -> Vec<TypeId> {
+        vec![]
+    }
+
+    pub(in crate) fn in_components( &self,
+                id : EntityId,
+                comp_a : &dyn ComponentStorage,
+                comp_b : &dyn ComponentStorage,
+                comp_c : &dyn ComponentStorage) -> bool {
+        comp_a.has(id) && comp_b.has(id) && comp_c.has(id)
+    }
+
+    pub(in crate) fn in_group(&self,
+                id : EntityId,
+                comp_a : &dyn ComponentStorage,
+                comp_b : &dyn ComponentStorage,
+                comp_c : &dyn ComponentStorage) -> bool {
+        if !self.in_components(id,comp_a,comp_b,comp_c) {
+            return false;
+        }
+
+        self.sparse_set.exist(id)
+    }
+
+    pub(in crate) fn add(&mut self,
+           id : EntityId,
+           comp_a : &dyn ComponentStorage,
+           comp_b : &dyn ComponentStorage,
+           comp_c : &dyn ComponentStorage) {
+        if !self.in_components(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+        if self.in_group(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+
+        // get indexes in all component storages
+        // This unwrap never fails because the in_components() ensures that it's already in components
+        let index_a = comp_a.index(id).unwrap();
+        let index_b = comp_b.index(id).unwrap();
+        let index_c = comp_c.index(id).unwrap();
+
+        self.sparse_set.add(id,(index_a,index_b,index_c));
+    }
+
+    pub(in crate) fn remove(&mut self,
+              id : EntityId,
+              comp_a : &dyn ComponentStorage,
+              comp_b : &dyn ComponentStorage,
+              comp_c : &dyn ComponentStorage) {
+        if !self.in_group(id,comp_a,comp_b,comp_c) {
+            return;
+        }
+
+        // Unwrap here
+        // This never fails because in_group ensures that it's already in group.
+        self.sparse_set.remove(id).unwrap();
+    }
+
+    pub(in crate) fn make(&mut self,
+            comp_a : &dyn ComponentStorage,
+            comp_b : &dyn ComponentStorage,
+            comp_c : &dyn ComponentStorage) {
+        self.sparse_set.clear();
+
+        // iterate the smallest storage
+        let storages = [comp_a,comp_b,comp_c];
+        // Unwrap here never fails
+        // storages is not empty
+        let smallest = storages.iter()
+            .min_by_key(|storage|storage.count())
+            .unwrap();
+        for index in 0..smallest.count() {
+            // Unwrap here never fails
+            // the for loop ensures this
+            let id = smallest.id(index).unwrap();
+            if let (Some(index_a),Some(index_b),Some(index_c)) = (comp_a.index(id),comp_b.index(id),comp_c.index(id)) {
+                self.sparse_set.add(id,(index_a,index_b,index_c));
+            }
+        }
+    }
+}
+
+/// A non-owning group of 3 components.
+/// # Details
+/// It owns no storage,an extra sparse set records the entities
+/// which have all 3 components.
+#[derive(Clone,Copy)]
+pub struct NonOwning3<A,B,C>{
+    _marker_a : PhantomData<A>,
+    _marker_b : PhantomData<B>,
+    _marker_c : PhantomData<C>
+}
+
+impl<A : Component,B : Component,C : Component> NonOwning3<A,B,C> {
+    pub(in crate) fn new() -> Self {
+        NonOwning3 {
+            _marker_a: PhantomData,
+            _marker_b: PhantomData,
+            _marker_c: PhantomData
+        }
+    }
+}
+
+impl<A : Component,B : Component,C : Component> From<NonOwning3<A,B,C>> for Group {
+    fn from(_ : NonOwning3<A,B,C>) -> Group {
+        Group::NonOwning3(NonOwningData3 {
+            sparse_set: SparseSet::new(),
+            type_a: TypeId::of::<A>(),
+            type_b: TypeId::of::<B>(),
+            type_c: TypeId::of::<C>()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, non_owning3, NonOwning3}, query::{QueryIterator, WithId}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Transform(f32);
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Mesh(u32);
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Visible;
+
+    fn world() -> (World,Vec<EntityId>) {
+        let mut world = World::new();
+        world.register::<Transform>()
+            .register::<Mesh>()
+            .register::<Visible>()
+            .register::<char>();
+        let ids = (0..30).map(|i| {
+            let id = world.create_entity().attach(Transform(i as f32)).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,Mesh(i));
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,Visible);
+            }
+            id
+        }).collect::<Vec<_>>();
+        (world,ids)
+    }
+
+    // Check the group is exactly the entities with all 3 components
+    fn check(world : &World) {
+        let expected = {
+            let mut ids = world.query::<(&Transform,&Mesh,&Visible)>()
+                .with_id()
+                .map(|(id,_)|id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let iter = world.query::<NonOwning3<&Transform,&Mesh,&Visible>>();
+        assert_eq!(iter.len_hint(),Some(expected.len()));
+        let mut ids = vec![];
+        for (id,(t,m,v)) in iter.with_id() {
+            assert_eq!(world.get::<Transform>(id).as_deref(),Some(t));
+            assert_eq!(world.get::<Mesh>(id).as_deref(),Some(m));
+            assert_eq!(world.get::<Visible>(id).as_deref(),Some(v));
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids,expected);
+    }
+
+    #[test]
+    fn make_test() {
+        let (mut world,_) = world();
+        world.make_group(non_owning3::<Transform,Mesh,Visible>());
+        assert_eq!(world.query::<NonOwning3<&Transform,&Mesh,&Visible>>().count(),5);
+        check(&world);
+        world.remake_group(non_owning3::<Transform,Mesh,Visible>());
+        check(&world);
+    }
+
+    #[test]
+    fn add_remove_test() {
+        let (mut world,ids) = world();
+        world.make_group(non_owning3::<Transform,Mesh,Visible>());
+        // the storages can still be owned by another group
+        world.make_group(full_owning::<Mesh,Visible>());
+
+        world.attach_component(ids[3],Mesh(3));
+        world.attach_component(ids[4],Visible);
+        check(&world);
+        world.detach_component::<Mesh>(ids[6]);
+        world.remove_entity(ids[0]);
+        world.remove_component::<Visible>(ids[12]);
+        check(&world);
+        let new = world.create_entity()
+            .attach(Visible)
+            .attach(Mesh(100))
+            .attach(Transform(100.0))
+            .into_id();
+        world.create_entities(3)
+            .attach(vec![Transform(0.0);3])
+            .attach(vec![Mesh(0);3])
+            .attach(vec![Visible;3]);
+        check(&world);
+        assert_eq!(world.query::<NonOwning3<&Transform,&Mesh,&Visible>>().count(),8);
+
+        let mut iter = world.query::<NonOwning3<&Transform,&Mesh,&Visible>>();
+        assert_eq!(iter.from_id(new),Some((&Transform(100.0),&Mesh(100),&Visible)));
+        assert_eq!(iter.from_id(ids[6]),None);
+    }
+
+    #[test]
+    fn mut_test() {
+        let (mut world,_) = world();
+        world.make_group(non_owning3::<Transform,Mesh,Visible>());
+        for (t,m,_) in world.query::<NonOwning3<&mut Transform,&Mesh,&Visible>>() {
+            t.0 += m.0 as f32;
+        }
+        for (_,m,_) in world.query::<NonOwning3<&Transform,&mut Mesh,&mut Visible>>() {
+            m.0 += 1;
+        }
+        for (t,m,_) in world.query::<NonOwning3<&mut Transform,&mut Mesh,&Visible>>() {
+            t.0 += 1.0;
+            m.0 *= 2;
+        }
+        let mut items = world.query::<NonOwning3<&Transform,&Mesh,&Visible>>()
+            .map(|(t,m,_)|(t.0 as u32,m.0))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,vec![(1,2),(13,14),(25,26),(37,38),(49,50)]);
+        assert_eq!(world.query::<NonOwning3<&Transform,&Mesh,&mut Visible>>().count(),5);
+        assert_eq!(world.query::<NonOwning3<&mut Transform,&Mesh,&mut Visible>>().count(),5);
+        assert_eq!(world.query::<NonOwning3<&Transform,&mut Mesh,&Visible>>().count(),5);
+        assert_eq!(world.query::<NonOwning3<&mut Transform,&mut Mesh,&mut Visible>>().count(),5);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{any::TypeId, iter::FusedIterator};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, group::{Group, GroupQueryable, LockedGroup, non_owning3}, query::{QueryGet, QueryIterator, Queryable, Taken}, sparse_set::SparseSet, world::World};
+use super::NonOwning3;
+
+// Every permutation of & and &mut is generated by this macro,
+// ```$unique``` is true if any item is ```&mut```,
+// each component is described by
+// (type,index in tuple,item type,pointer mutability,guard,storage getter,downcast,data getter)
+macro_rules! impl_non_owning3_query {
+    ($a:lifetime,$name:ident,$unique:literal,
+     $(($t:ident,$idx:tt,$item:ty,$ptr:tt,$guard:ident,$storage:ident,$downcast:ident,$get:ident)),+) => {
+        pub struct $name<$a,A,B,C> {
+            index: usize,
+            // the indices in group which are yielded by from_id
+            taken: Taken,
+            sparse_set_group: *const SparseSet<EntityId,(usize,usize,usize)>,
+            sparse_sets: ($(*$ptr SparseSet<EntityId,$t>),+),
+            #[allow(unused)]
+            borrow_group: GroupReadGuard<$a>,
+            #[allow(unused)]
+            borrows: ($($guard<$a>),+)
+        }
+
+        impl<$a,A : Component,B : Component,C : Component> $name<$a,A,B,C> {
+            /// # Safety
+            /// ```id``` must be in group,so it's in all 3 storages.
+            unsafe fn data(&self,id : EntityId) -> ($($item),+) {
+                // Safety:
+                // Safe here, because these are
+                // pointers from borrow,
+                // This pointer is valid now.
+                unsafe {
+                    ($((*self.sparse_sets.$idx).$get(id)),+)
+                }
+            }
+        }
+
+        impl<$a,A : Component,B : Component,C : Component> Queryable<$a> for NonOwning3<$($item),+> {
+            type Item = ($($item),+);
+
+            fn component_types(types : &mut Vec<TypeId>) {
+                $(types.push(TypeId::of::<$t>());)+
+            }
+
+            fn check(world : &World) -> Result<(),QueryError> {
+                world.check_registered(&[$(TypeId::of::<$t>()),+])?;
+                world.check_group(non_owning3::<A,B,C>())
+            }
+
+            fn query(world : &$a World) -> Box<dyn QueryIterator<Item = Self::Item> + $a> {
+                let group = world.lock_group(non_owning3::<A,B,C>(),"Queryable for NonOwning3: Group is not in world");
+                <Self as GroupQueryable<$a>>::query_in(world,LockedGroup(group))
+            }
+        }
+
+        impl<$a,A : Component,B : Component,C : Component> GroupQueryable<$a> for NonOwning3<$($item),+> {
+            type Group = NonOwning3<A,B,C>;
+
+            fn query_in(world : &$a World,group : LockedGroup<$a>) -> Box<dyn QueryIterator<Item = Self::Item> + $a> {
+                assert!($(world.has_registered::<$t>())&&+,
+                        "Queryable for NonOwning3: Component was not registered in world");
+                // Unwrap here
+                // assert before ensures this
+                #[allow(unused_mut)]
+                let mut borrows = ($(world.$storage(TypeId::of::<$t>()).unwrap()),+);
+                // Safety:
+                // storage is SparseSet<EntityId,...>
+                let sparse_sets = ($(unsafe {
+                    borrows.$idx.$downcast::<SparseSet<EntityId,$t>>()
+                } as *$ptr SparseSet<EntityId,$t>),+);
+                let group = group.0;
+                let group_data = match &*group {
+                    Group::NonOwning3(data) => data,
+                    _ => unreachable!()
+                };
+                let ptr_group = &group_data.sparse_set as *const _;
+                Box::new($name{
+                    index: 0,
+                    taken: Taken::default(),
+                    sparse_set_group: ptr_group,
+                    sparse_sets,
+                    borrow_group: group,
+                    borrows
+                })
+            }
+        }
+
+        impl<$a,A : Component,B : Component,C : Component> Iterator for $name<$a,A,B,C> {
+            type Item = ($($item),+);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.next_with_id().map(|(_,data)|data)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let rem = self.borrow_group.len() - self.index - self.taken.len();
+                (rem,Some(rem))
+            }
+        }
+
+        impl<$a,A : Component,B : Component,C : Component> ExactSizeIterator for $name<$a,A,B,C>{ }
+        impl<$a,A : Component,B : Component,C : Component> FusedIterator for $name<$a,A,B,C>{ }
+
+        impl<$a,A : Component,B : Component,C : Component> QueryIterator for $name<$a,A,B,C> {
+            fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+                // Safety:
+                // Safe here, because self.group is
+                // a pointer from borrow,
+                // This pointer is valid now.
+                let group = unsafe { &*self.sparse_set_group };
+                let index = group.get_index(id)?;
+                // never yield the same mutable item twice,
+                // otherwise there are two mutable references to it
+                if $unique && !self.taken.take(index,self.index,group.len()) {
+                    return None;
+                }
+                // Safety:
+                // Safe here, because the entities in group are in all storages.
+                Some(unsafe { self.data(id) })
+            }
+
+            fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+                // Safety:
+                // Safe here, because self.group is
+                // a pointer from borrow,
+                // This pointer is valid now.
+                let group = unsafe { &*self.sparse_set_group };
+                self.taken.skip_front(&mut self.index,group.len());
+                if self.index < group.len() {
+                    // Safety:
+                    // Safe here, because if condition ensures this.
+                    let id = *unsafe {
+                        group.entities().get_unchecked(self.index)
+                    };
+                    self.index += 1;
+                    // Safety:
+                    // Safe here, because the entities in group are in all storages.
+                    Some((id,unsafe { self.data(id) }))
+                } else {
+                    None
+                }
+            }
+        }
+    };
+}
+
+impl_non_owning3_query!('a,IterRefRefRef,false,
+    (A,0,&'a A,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (B,1,&'a B,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (C,2,&'a C,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked));
+impl_non_owning3_query!('a,IterRefRefMut,true,
+    (A,0,&'a A,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (B,1,&'a B,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (C,2,&'a mut C,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut));
+impl_non_owning3_query!('a,IterRefMutRef,true,
+    (A,0,&'a A,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (B,1,&'a mut B,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (C,2,&'a C,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked));
+impl_non_owning3_query!('a,IterRefMutMut,true,
+    (A,0,&'a A,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (B,1,&'a mut B,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (C,2,&'a mut C,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut));
+impl_non_owning3_query!('a,IterMutRefRef,true,
+    (A,0,&'a mut A,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (B,1,&'a B,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (C,2,&'a C,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked));
+impl_non_owning3_query!('a,IterMutRefMut,true,
+    (A,0,&'a mut A,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (B,1,&'a B,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked),
+    (C,2,&'a mut C,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut));
+impl_non_owning3_query!('a,IterMutMutRef,true,
+    (A,0,&'a mut A,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (B,1,&'a mut B,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (C,2,&'a C,const,StorageReadGuard,raw_storage_read,downcast_ref,get_unchecked));
+impl_non_owning3_query!('a,IterMutMutMut,true,
+    (A,0,&'a mut A,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (B,1,&'a mut B,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut),
+    (C,2,&'a mut C,mut,StorageWriteGuard,raw_storage_write,downcast_mut,get_unchecked_mut));
+
+impl<'a,A : Component,B : Component,C : Component> QueryGet for IterRefRefRef<'a,A,B,C> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.group is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let group = unsafe { &*self.sparse_set_group };
+        if group.exist(id) {
+            // Safety:
+            // Safe here, because the entities in group are in all storages.
+            Some(unsafe { self.data(id) })
+        } else {
+            None
+        }
+    }
+}
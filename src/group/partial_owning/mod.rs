@@ -1,9 +1,10 @@
 use std::{any::TypeId, marker::PhantomData};
-use crate::{component::{Component, ComponentStorage}, entity::EntityId};
+use crate::{component::{Component, ComponentStorage, MultiStorageSwap}, entity::EntityId};
 use super::Group;
 
 mod query;
 
+#[derive(Clone)]
 pub struct PartialOwningData {
     length : usize,
     type_a : TypeId,
@@ -74,7 +75,7 @@ impl PartialOwningData {
         // because in_components() ensures that id is in comp_a
         let index_a = comp_a.index(id).unwrap();
 
-        comp_a.swap_by_index(index_a,self.length);
+        MultiStorageSwap::new(&mut [&mut **comp_a]).swap(index_a,self.length);
 
         self.length += 1;
     }
@@ -93,7 +94,7 @@ impl PartialOwningData {
 
         self.length -= 1;
 
-        comp_a.swap_by_index(index_a,self.length);
+        MultiStorageSwap::new(&mut [&mut **comp_a]).swap(index_a,self.length);
     }
 
     pub(in crate) fn make(&mut self,
@@ -106,7 +107,7 @@ impl PartialOwningData {
             // for loop ensures the range is valid
             let entity_id = comp_a.id(index).unwrap();
             if comp_b.has(entity_id) {
-                comp_a.swap_by_index(index,self.length);
+                MultiStorageSwap::new(&mut [&mut **comp_a]).swap(index,self.length);
                 self.length += 1;
             }
         }
@@ -1,6 +1,6 @@
-use std::any::TypeId;
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
-use crate::{component::{Component, ComponentStorage}, entity::EntityId, group::{Group, partial_owning}, query::{QueryIterator, Queryable}, sparse_set::SparseSet, world::World};
+use std::{any::TypeId, iter::FusedIterator};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, group::{partial_owning, GroupQueryable, LockedGroup}, query::{QueryGet, QueryIterator, Queryable, Taken}, sparse_set::SparseSet, world::World};
 use super::PartialOwning;
 
 pub struct IterRefRef<'a,A,B> {
@@ -9,17 +9,36 @@ pub struct IterRefRef<'a,A,B> {
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a A,&'a B> {
     type Item = (&'a A,&'a B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(partial_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(partial_owning::<A,B>(),"Queryable for PartialOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for PartialOwning<&'a A,&'a B> {
+    type Group = PartialOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for PartialOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -38,8 +57,7 @@ impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a A,&'a B
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(partial_owning::<A,B>()),"Queryable for PartialOwning: Group is not in world");
-        let group = world.group(partial_owning::<A,B>());
+        let group = group.0;
         let length = group.len();
         Box::new(IterRefRef {
             index: 0,
@@ -92,21 +110,11 @@ impl<'a,A : Component,B : Component> Iterator for IterRefRef<'a,A,B> {
 }
 
 impl<'a,A: Component,B: Component> ExactSizeIterator for IterRefRef<'a,A,B> {}
+impl<'a,A: Component,B: Component> FusedIterator for IterRefRef<'a,A,B> {}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
-        // Safety:
-        // Safe here, because self.sparse_set is 
-        // a pointer from borrow,
-        // This pointer is valid now.
-        let sparse_set_a = unsafe { &*self.sparse_set_a };
-        let sparse_set_b = unsafe { &*self.sparse_set_b };
-        if let Some(a) = sparse_set_a.get(id) {
-            if let Some(b) = sparse_set_b.get(id) {
-                return Some((a,b))
-            }
-        }
-        None
+        self.peek_from_id(id)
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
@@ -139,6 +147,23 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
     }
 }
 
+impl<'a,A : Component,B : Component> QueryGet for IterRefRef<'a,A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set_a = unsafe { &*self.sparse_set_a };
+        let sparse_set_b = unsafe { &*self.sparse_set_b };
+        if let Some(a) = sparse_set_a.get(id) {
+            if let Some(b) = sparse_set_b.get(id) {
+                return Some((a,b))
+            }
+        }
+        None
+    }
+}
+
 
 
 
@@ -147,20 +172,41 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefRef<'a,A,B> {
 pub struct IterRefMut<'a,A,B> {
     index: usize,
     length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *const SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a A,&'a mut B> {
     type Item = (&'a A,&'a mut B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(partial_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(partial_owning::<A,B>(),"Queryable for PartialOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for PartialOwning<&'a A,&'a mut B> {
+    type Group = PartialOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for PartialOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -179,11 +225,11 @@ impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a A,&'a m
         };
         let ptr_a = &*sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(partial_owning::<A,B>()),"Queryable for PartialOwning: Group is not in world");
-        let group = world.group(partial_owning::<A,B>());
+        let group = group.0;
         let length = group.len();
         Box::new(IterRefMut {
             index: 0,
+            taken: Taken::default(),
             length,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -198,6 +244,7 @@ impl<'a,A : Component,B : Component> Iterator for IterRefMut<'a,A,B> {
     type Item = (&'a A,&'a mut B);
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -227,12 +274,13 @@ impl<'a,A : Component,B : Component> Iterator for IterRefMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.length - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A: Component,B: Component> ExactSizeIterator for IterRefMut<'a,A,B> {}
+impl<'a,A: Component,B: Component> FusedIterator for IterRefMut<'a,A,B> {}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -242,6 +290,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &*self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
         if let Some(a) = sparse_set_a.get(id) {
             if let Some(b) = sparse_set_b.get_mut(id) {
                 return Some((a,b))
@@ -251,6 +305,7 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -290,20 +345,41 @@ impl<'a,A : Component,B : Component> QueryIterator for IterRefMut<'a,A,B> {
 pub struct IterMutRef<'a,A,B> {
     index: usize,
     length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *const SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockReadGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageReadGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a mut A,&'a B> {
     type Item = (&'a mut A,&'a B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(partial_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(partial_owning::<A,B>(),"Queryable for PartialOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for PartialOwning<&'a mut A,&'a B> {
+    type Group = PartialOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for PartialOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -322,11 +398,11 @@ impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a mut A,&
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &*sparse_set_b;
-        assert!(world.has_group(partial_owning::<A,B>()),"Queryable for PartialOwning: Group is not in world");
-        let group = world.group(partial_owning::<A,B>());
+        let group = group.0;
         let length = group.len();
         Box::new(IterMutRef {
             index: 0,
+            taken: Taken::default(),
             length,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -341,6 +417,7 @@ impl<'a,A : Component,B : Component> Iterator for IterMutRef<'a,A,B> {
     type Item = (&'a mut A,&'a B);
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -370,12 +447,13 @@ impl<'a,A : Component,B : Component> Iterator for IterMutRef<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.length - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A: Component,B: Component> ExactSizeIterator for IterMutRef<'a,A,B> {}
+impl<'a,A: Component,B: Component> FusedIterator for IterMutRef<'a,A,B> {}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -385,6 +463,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &*self.sparse_set_b };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
         if let Some(a) = sparse_set_a.get_mut(id) {
             if let Some(b) = sparse_set_b.get(id) {
                 return Some((a,b))
@@ -394,6 +478,7 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -431,20 +516,41 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutRef<'a,A,B> {
 pub struct IterMutMut<'a,A,B> {
     index: usize,
     length: usize,
+    // the indices in index..length which are yielded by from_id
+    taken: Taken,
     sparse_set_a: *mut SparseSet<EntityId,A>,
     sparse_set_b: *mut SparseSet<EntityId,B>,
     #[allow(unused)]
-    borrow_a: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_a: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_b: RwLockWriteGuard<'a,Box<dyn ComponentStorage>>,
+    borrow_b: StorageWriteGuard<'a>,
     #[allow(unused)]
-    borrow_group: RwLockReadGuard<'a,Group>
+    borrow_group: GroupReadGuard<'a>
 }
 
 impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a mut A,&'a mut B> {
     type Item = (&'a mut A,&'a mut B);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<A>());
+        types.push(TypeId::of::<B>());
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        world.check_registered(&[TypeId::of::<A>(),TypeId::of::<B>()])?;
+        world.check_group(partial_owning::<A,B>())
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        let group = world.lock_group(partial_owning::<A,B>(),"Queryable for PartialOwning: Group is not in world");
+        <Self as GroupQueryable<'a>>::query_in(world,LockedGroup(group))
+    }
+}
+
+impl<'a,A : Component,B : Component> GroupQueryable<'a> for PartialOwning<&'a mut A,&'a mut B> {
+    type Group = PartialOwning<A,B>;
+
+    fn query_in(world : &'a World,group : LockedGroup<'a>) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
         assert!(world.has_registered::<A>() && world.has_registered::<B>(),
                 "Queryable for PartialOwning: Component was not registered in world");
         let type_id_a = TypeId::of::<A>();
@@ -463,11 +569,11 @@ impl<'a,A : Component,B : Component> Queryable<'a> for PartialOwning<&'a mut A,&
         };
         let ptr_a = &mut *sparse_set_a;
         let ptr_b = &mut *sparse_set_b;
-        assert!(world.has_group(partial_owning::<A,B>()),"Queryable for PartialOwning: Group is not in world");
-        let group = world.group(partial_owning::<A,B>());
+        let group = group.0;
         let length = group.len();
         Box::new(IterMutMut {
             index: 0,
+            taken: Taken::default(),
             length,
             sparse_set_a: ptr_a,
             sparse_set_b: ptr_b,
@@ -482,6 +588,7 @@ impl<'a,A : Component,B : Component> Iterator for IterMutMut<'a,A,B> {
     type Item = (&'a mut A,&'a mut B);
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
@@ -511,12 +618,13 @@ impl<'a,A : Component,B : Component> Iterator for IterMutMut<'a,A,B> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.length - self.index;
+        let rem = self.length - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 
 impl<'a,A: Component,B: Component> ExactSizeIterator for IterMutMut<'a,A,B> {}
+impl<'a,A: Component,B: Component> FusedIterator for IterMutMut<'a,A,B> {}
 
 impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
@@ -526,6 +634,12 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
         // This pointer is valid now.
         let sparse_set_a = unsafe { &mut *self.sparse_set_a };
         let sparse_set_b = unsafe { &mut *self.sparse_set_b };
+        let index = sparse_set_a.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.length) {
+            return None;
+        }
         if let Some(a) = sparse_set_a.get_mut(id) {
             if let Some(b) = sparse_set_b.get_mut(id) {
                 return Some((a,b))
@@ -535,6 +649,7 @@ impl<'a,A : Component,B : Component> QueryIterator for IterMutMut<'a,A,B> {
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.length);
         if self.index < self.length {
             // Safety:
             // Safe here, because self.sparse_set is 
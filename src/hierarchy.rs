@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use crate::entity::EntityId;
+
+/// The parent and children of entities in [World](crate::world::World)
+/// # Details
+/// The links of removed entities may be kept in a [Transaction](crate::transaction::Transaction),
+/// so the readers of world filter the dead IDs.
+#[derive(Debug,Clone,Default)]
+pub(in crate) struct Hierarchy {
+    parents : HashMap<EntityId,EntityId>,
+    // in order of set_parent
+    children : HashMap<EntityId,Vec<EntityId>>
+}
+
+impl Hierarchy {
+    pub(in crate) fn parent(&self,child : EntityId) -> Option<EntityId> {
+        self.parents.get(&child).copied()
+    }
+
+    pub(in crate) fn children(&self,parent : EntityId) -> &[EntityId] {
+        self.children.get(&parent)
+            .map(|children|children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Check if ```ancestor``` is ```entity_id``` or any ancestor of it
+    pub(in crate) fn is_ancestor(&self,ancestor : EntityId,entity_id : EntityId) -> bool {
+        let mut current = Some(entity_id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+        false
+    }
+
+    /// Set the parent of ```child```,the old parent is replaced.
+    /// # Details
+    /// The caller ensures this doesn't make a cycle.
+    pub(in crate) fn set_parent(&mut self,child : EntityId,parent : EntityId) {
+        self.remove_parent(child);
+        self.parents.insert(child,parent);
+        self.children.entry(parent)
+            .or_default()
+            .push(child);
+    }
+
+    /// Remove ```child``` from its parent,return the old parent
+    pub(in crate) fn remove_parent(&mut self,child : EntityId) -> Option<EntityId> {
+        let parent = self.parents.remove(&child)?;
+        if let Some(children) = self.children.get_mut(&parent) {
+            children.retain(|id|*id != child);
+            if children.is_empty() {
+                self.children.remove(&parent);
+            }
+        }
+        Some(parent)
+    }
+
+    /// Remove ```entity_id``` from its parent and detach all its children
+    pub(in crate) fn remove(&mut self,entity_id : EntityId) {
+        self.remove_parent(entity_id);
+        for child in self.children.remove(&entity_id).unwrap_or_default() {
+            self.parents.remove(&child);
+        }
+    }
+
+    /// Get all descendants of ```entity_id``` in depth-first order
+    pub(in crate) fn descendants(&self,entity_id : EntityId) -> Vec<EntityId> {
+        let mut descendants = vec![];
+        let mut stack = self.children(entity_id)
+            .iter()
+            .rev()
+            .copied()
+            .collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            descendants.push(id);
+            stack.extend(self.children(id).iter().rev());
+        }
+        descendants
+    }
+
+    /// Get all links as ```(child,parent)``` in order of children
+    pub(in crate) fn links(&self) -> Vec<(EntityId,EntityId)> {
+        self.children.iter()
+            .flat_map(|(parent,children)|children.iter().map(move |child|(*child,*parent)))
+            .collect()
+    }
+
+    pub(in crate) fn clear(&mut self) {
+        self.parents.clear();
+        self.children.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::HierarchyError, world::World};
+
+    #[test]
+    fn set_parent_test() {
+        let world = World::new();
+        let root = world.create_entity().into_id();
+        let a = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        let c = world.create_entity().into_id();
+        assert_eq!(world.set_parent(a,root),Ok(()));
+        assert_eq!(world.set_parent(b,root),Ok(()));
+        assert_eq!(world.set_parent(c,a),Ok(()));
+        assert_eq!(world.parent(c),Some(a));
+        assert_eq!(world.parent(root),None);
+        assert_eq!(world.children(root).collect::<Vec<_>>(),vec![a,b]);
+
+        // cycles are rejected
+        assert_eq!(world.set_parent(root,c),Err(HierarchyError::Cycle { child : root, parent : c }));
+        assert_eq!(world.set_parent(a,a),Err(HierarchyError::Cycle { child : a, parent : a }));
+        assert_eq!(world.parent(root),None);
+
+        // reparenting updates both sides
+        assert_eq!(world.set_parent(a,b),Ok(()));
+        assert_eq!(world.children(root).collect::<Vec<_>>(),vec![b]);
+        assert_eq!(world.children(b).collect::<Vec<_>>(),vec![a]);
+        assert_eq!(world.remove_parent(a),Some(b));
+        assert_eq!(world.children(b).count(),0);
+        assert_eq!(world.parent(a),None);
+
+        world.remove_entity(b);
+        assert_eq!(world.set_parent(a,b),Err(HierarchyError::EntityNotFound(b)));
+        assert_eq!(world.set_parent(b,a),Err(HierarchyError::EntityNotFound(b)));
+    }
+
+    #[test]
+    fn despawn_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        let ids = (0..7_u32).map(|i|world.create_entity().attach(i).into_id()).collect::<Vec<_>>();
+        // 0 -> 1 -> 2
+        //   -> 3 -> 4
+        // 5 -> 6
+        world.set_parent(ids[1],ids[0]).unwrap();
+        world.set_parent(ids[2],ids[1]).unwrap();
+        world.set_parent(ids[3],ids[0]).unwrap();
+        world.set_parent(ids[4],ids[3]).unwrap();
+        world.set_parent(ids[6],ids[5]).unwrap();
+
+        // removing a parent detaches its children
+        world.remove_entity(ids[3]);
+        assert_eq!(world.parent(ids[4]),None);
+        assert_eq!(world.children(ids[0]).collect::<Vec<_>>(),vec![ids[1]]);
+
+        assert!(world.despawn_recursive(ids[0]));
+        assert!(!world.despawn_recursive(ids[0]));
+        assert!(!world.is_alive(ids[1]));
+        assert!(!world.is_alive(ids[2]));
+        assert!(world.is_alive(ids[4]));
+        let mut rest = world.query::<&u32>().copied().collect::<Vec<_>>();
+        rest.sort();
+        assert_eq!(rest,vec![4,5,6]);
+
+        world.clear_entities();
+        let parent = world.create_entity().into_id();
+        assert_eq!(world.children(parent).count(),0);
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut world = World::new();
+        world.create_entity();
+        let level = World::new();
+        let parent = level.create_entity().into_id();
+        let child = level.create_entity().into_id();
+        level.set_parent(child,parent).unwrap();
+
+        let ids = world.merge(level);
+        assert_eq!(world.parent(ids[&child]),Some(ids[&parent]));
+        assert_eq!(world.children(ids[&parent]).collect::<Vec<_>>(),vec![ids[&child]]);
+    }
+}
@@ -50,18 +50,28 @@
 //! ```
 //! 
 //! # About entity
-//! Entity in XECS is just an number ID.In XECS, it's just a 
-//! [NonZeroUsize](std::num::NonZeroUsize).
-//! The ID is allocated from 1 by world automatically. The ```id=0``` 
+//! Entity in XECS is just an number ID.In XECS, it's an [EntityId](crate::entity::EntityId)
+//! which packs an index and a [Generation](crate::entity::Generation) into a ```u64```.
+//! The index is allocated from 1 by world automatically. The ```index=0``` 
 //! represents a recycled ID without any other flags through ```Option<EntityId>```.
 //! 
 //! # ID recycling
 //! When you call ```world.create_entity()```, an ID will be allocated automatically. 
-//! If you call ```world.remove_entity(id)```, this ID will be a pit. If the 
-//! next ```world.create_entity()``` is called, it will allocate this ID to fill 
-//! the pit.Thanks to sparse set, it's still fast to 
+//! If you call ```world.remove_entity(id)```, this index will be a pit. If the 
+//! next ```world.create_entity()``` is called, it will allocate this index with
+//! the next generation to fill the pit.So a removed ID never refers to the new entity,
+//! see [is_alive](crate::world::World::is_alive).Thanks to sparse set, it's still fast to 
 //! iterate all components no matter how random of ID
 //! 
+//! # Migration from NonZeroUsize IDs
+//! * ```EntityId``` was ```NonZeroUsize```,now it's a struct.
+//!   Use ```id.index()``` instead of ```id.get()```,and ```EntityId::new(index)```
+//!   still creates an ID of the first generation.
+//! * ```EntityHandle```,```world.handle()```,```with_handle()``` and the ```*_by_handle()```
+//!   functions are removed,because ```EntityId``` itself carries the generation.
+//!   Use ```EntityId``` and ```world.is_alive(id)``` instead.
+//! * An ID is not equal to the ID reusing its index,compare ```id.index()``` if it's needed.
+//! 
 //! # Concurrency Safety
 //! Because [Component](crate::component::Component) is just ```T : Send + Sync```.
 //! [World](crate::world::World) can use [RwLock](std::sync::RwLock) to 
@@ -81,31 +91,84 @@ mod entity;
 mod component;
 mod system;
 mod resource;
+mod epoch;
+mod bundle;
+mod error;
+mod lifetime;
+mod hierarchy;
 /// Some things to accelerate the iteration
 pub mod group;
 /// The query functions
 pub mod query;
+/// Make structural changes atomically
+pub mod transaction;
+/// Clean up components when entities are removed
+pub mod finalizer;
+/// Defer structural changes until iterators are dropped
+pub mod command;
+/// Send typed events between systems
+pub mod events;
+/// The commonly used items
+pub mod prelude;
+/// Copy and restore the whole world
+pub mod snapshot;
 pub(in crate) mod sparse_set;
+mod lock;
+/// Deterministic scenarios for tests
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "tabular")]
+pub mod tabular;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "serde")]
+pub mod serde;
 /// The resource type
 
 pub use world::World;
 pub use entity::{
     EntityId,
+    Generation,
+    EntityIdExhausted,
+    EntityStats,
     Entity,
     Entities,
 };
 pub use component::{
     Component,
+    AnyComponentRead,
     ComponentRead,
     ComponentWrite,
+    ComponentPtr,
+    ErasedValue,
+    IdsSnapshot,
+    MultiStorageSwap,
     ComponentStorage,
+    StorageKind,
     StorageRead,
     StorageWrite
 };
+pub use bundle::{
+    AnyComponent,
+    Bundle,
+    RemoveBundleError
+};
+pub use error::{GroupError, HierarchyError, QueryError, XecsError};
+pub use lifetime::{
+    Lifetime,
+    BoundTo
+};
+pub use epoch::{
+    Epoch,
+    EpochChanged
+};
 pub use system::System;
 pub use resource::{
     Resource,
     ResourceRead,
-    ResourceWrite
+    ResourceWrite,
+    ResourceRef,
+    ResourceMut,
+    ResourceParam
 };
 
@@ -0,0 +1,269 @@
+use crate::entity::EntityId;
+
+/// A component which despawns its entity when time is up.
+/// # Details
+/// It's counted down by [despawn_expired](crate::world::World::despawn_expired),
+/// the entity is despawned when the remaining time reaches zero.
+/// # Example
+/// ```
+/// use xecs::{World, Lifetime};
+/// let mut world = World::new();
+/// world.register::<Lifetime>();
+/// let particle = world.create_entity().attach(Lifetime::seconds(1.0)).into_id();
+///
+/// assert!(world.despawn_expired(0.6).is_empty());
+/// assert_eq!(world.despawn_expired(0.6),vec![particle]);
+/// assert!(!world.exist(particle));
+/// ```
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Lifetime {
+    remaining : Remaining
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Remaining {
+    Seconds(f32),
+    Frames(u32)
+}
+
+impl Lifetime {
+    /// Despawn after ```seconds```
+    pub fn seconds(seconds : f32) -> Self {
+        Lifetime {
+            remaining : Remaining::Seconds(seconds)
+        }
+    }
+
+    /// Despawn after ```frames``` calls of [despawn_expired](crate::world::World::despawn_expired)
+    pub fn frames(frames : u32) -> Self {
+        Lifetime {
+            remaining : Remaining::Frames(frames)
+        }
+    }
+
+    /// Get the remaining seconds,return ```None``` if it's counted by frames
+    pub fn remaining_seconds(&self) -> Option<f32> {
+        match self.remaining {
+            Remaining::Seconds(seconds) => Some(seconds),
+            Remaining::Frames(_) => None
+        }
+    }
+
+    /// Get the remaining frames,return ```None``` if it's counted by seconds
+    pub fn remaining_frames(&self) -> Option<u32> {
+        match self.remaining {
+            Remaining::Seconds(_) => None,
+            Remaining::Frames(frames) => Some(frames)
+        }
+    }
+
+    /// Count down by ```delta``` seconds or one frame,
+    /// return ```true``` if it's expired
+    pub(in crate) fn advance(&mut self,delta : f32) -> bool {
+        match &mut self.remaining {
+            Remaining::Seconds(seconds) => {
+                *seconds -= delta;
+                *seconds <= 0.0
+            },
+            Remaining::Frames(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+        }
+    }
+}
+
+/// A component which despawns its entity when the target entity is despawned.
+/// # Details
+/// It's attached by [bind_to](crate::world::World::bind_to).
+/// It holds the [EntityId](crate::entity::EntityId) of target with its generation,
+/// so an entity which reuses the index of target is never treated as the target.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct BoundTo {
+    target : EntityId
+}
+
+impl BoundTo {
+    pub(in crate) fn new(target : EntityId) -> Self {
+        BoundTo {
+            target
+        }
+    }
+
+    /// Get the ID of target
+    pub fn target(&self) -> EntityId {
+        self.target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::XecsError, world::World};
+    use super::{BoundTo, Lifetime};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Lifetime>()
+            .register::<BoundTo>()
+            .register::<u32>();
+        world
+    }
+
+    #[test]
+    fn seconds_test() {
+        let world = world();
+        let a = world.create_entity().attach(Lifetime::seconds(2.5)).attach(1_u32).into_id();
+        let b = world.create_entity().attach(Lifetime::seconds(1.0)).into_id();
+        let c = world.create_entity().attach(2_u32).into_id();
+
+        assert!(world.despawn_expired(0.5).is_empty());
+        assert_eq!(world.get::<Lifetime>(a).unwrap().remaining_seconds(),Some(2.0));
+        assert_eq!(world.despawn_expired(0.5),vec![b]);
+        assert!(world.despawn_expired(1.0).is_empty());
+        assert_eq!(world.despawn_expired(0.5),vec![a]);
+        assert!(!world.exist(a) && !world.exist(b));
+        assert!(world.exist(c));
+        assert_eq!(world.query::<&u32>().count(),1);
+    }
+
+    #[test]
+    fn frames_test() {
+        let world = world();
+        let ids = (0..4).map(|frames| {
+            world.create_entity().attach(Lifetime::frames(frames)).into_id()
+        }).collect::<Vec<_>>();
+        assert_eq!(world.get::<Lifetime>(ids[3]).unwrap().remaining_frames(),Some(3));
+
+        // zero frame and one frame both expire in the first pass
+        let mut expired = world.despawn_expired(100.0);
+        expired.sort();
+        assert_eq!(expired,vec![ids[0],ids[1]]);
+        assert_eq!(world.despawn_expired(0.0),vec![ids[2]]);
+        assert_eq!(world.despawn_expired(0.0),vec![ids[3]]);
+        assert_eq!(world.query::<&Lifetime>().count(),0);
+    }
+
+    #[test]
+    fn bound_to_test() {
+        let world = world();
+        let c = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        let a = world.create_entity().attach(7_u32).into_id();
+        let other = world.create_entity().into_id();
+        world.bind_to(b,c).unwrap();
+        world.bind_to(a,b).unwrap();
+        world.bind_to(other,b).unwrap();
+        assert_eq!(world.get::<BoundTo>(a).unwrap().target(),b);
+
+        // rebinding leaves the old target
+        world.bind_to(other,a).unwrap();
+        world.remove_entity(other);
+        world.bind_to(other,a).unwrap_err();
+
+        // the whole chain is despawned at once
+        assert!(world.despawn(c));
+        assert!(!world.exist(b));
+        assert!(!world.exist(a));
+        assert_eq!(world.query::<&u32>().count(),0);
+        assert_eq!(world.query::<&BoundTo>().count(),0);
+
+        // the lifetime of target expires
+        let target = world.create_entity().attach(Lifetime::frames(1)).into_id();
+        let bound = world.create_entity().into_id();
+        world.bind_to(bound,target).unwrap();
+        assert_eq!(world.despawn_expired(0.0),vec![target]);
+        assert!(!world.exist(bound));
+    }
+
+    #[test]
+    fn bind_error_test() {
+        let world = world();
+        let a = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        world.remove_entity(b);
+        assert_eq!(world.bind_to(a,b),Err(XecsError::EntityNotFound(b)));
+        assert_eq!(world.bind_to(b,a),Err(XecsError::EntityNotFound(b)));
+
+        let mut world = World::new();
+        let a = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        assert!(matches!(world.bind_to(a,b),Err(XecsError::ComponentNotRegistered(_))));
+        world.register::<BoundTo>();
+        world.bind_to(a,b).unwrap();
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut world = world();
+        let old = (0..3).map(|_|world.create_entity().attach(0_u32).into_id()).collect::<Vec<_>>();
+
+        let level = self::world();
+        let owner = level.create_entity().into_id();
+        let bound = level.create_entity().attach(1_u32).into_id();
+        let chained = level.create_entity().attach(2_u32).into_id();
+        level.bind_to(bound,owner).unwrap();
+        level.bind_to(chained,bound).unwrap();
+        // the target of this binding is not merged
+        let removed = level.create_entity().into_id();
+        level.remove_entity(removed);
+        let orphan = level.create_entity()
+            .attach(3_u32)
+            .attach(BoundTo::new(removed))
+            .into_id();
+
+        let ids = world.merge(level);
+        assert_eq!(world.get::<BoundTo>(ids[&bound]).unwrap().target(),ids[&owner]);
+        assert_eq!(world.get::<BoundTo>(ids[&chained]).unwrap().target(),ids[&bound]);
+        assert!(world.get::<BoundTo>(ids[&orphan]).is_none());
+
+        assert!(world.despawn(ids[&owner]));
+        assert!(!world.exist(ids[&bound]));
+        assert!(!world.exist(ids[&chained]));
+        assert!(world.exist(ids[&orphan]));
+        // the entities with the old IDs are not touched
+        assert!(old.iter().all(|id|world.exist(*id)));
+        assert_eq!(world.query::<&u32>().count(),4);
+    }
+
+    #[test]
+    fn id_reuse_test() {
+        let world = world();
+        let target = world.create_entity().into_id();
+        let bound = world.create_entity().attach(1_u32).into_id();
+        world.bind_to(bound,target).unwrap();
+
+        // the binding is kept until the transaction is committed
+        let mut tx = world.begin_transaction();
+        tx.despawn(target);
+        assert!(world.exist(bound));
+        tx.commit();
+        assert!(!world.exist(bound));
+        // the bound entity is removed last,so its ID is reused first
+        let reused = world.create_entity().into_id();
+        assert_eq!(reused.index(),bound.index());
+        assert_ne!(reused,bound);
+        world.despawn_expired(0.0);
+        assert!(world.exist(reused));
+
+        // the reused ID doesn't resurrect the binding
+        let bound = world.create_entity().into_id();
+        let new_target = world.create_entity().into_id();
+        world.bind_to(bound,new_target).unwrap();
+        world.remove_entity(reused);
+        assert!(world.exist(bound));
+        world.despawn_expired(0.0);
+        assert!(world.exist(bound));
+        world.remove_entity(new_target);
+        assert!(!world.exist(bound));
+
+        // a bound entity reusing an ID is not despawned by the old target
+        let target = world.create_entity().into_id();
+        let bound = world.create_entity().into_id();
+        world.bind_to(bound,target).unwrap();
+        world.remove_entity(bound);
+        let reused = world.create_entity().into_id();
+        assert_eq!(reused.index(),bound.index());
+        world.remove_entity(target);
+        assert!(world.exist(reused));
+    }
+}
@@ -0,0 +1,74 @@
+//! # Lock
+//! The locks used by [World](crate::world::World) to guard its states.
+//! ## Backends
+//! * By default, the locks are from [parking_lot](parking_lot).
+//! * With ```refcell``` feature, the locks are [RefCell](std::cell::RefCell)
+//!   wrappers for single thread environment. Conflicting borrows panic
+//!   instead of blocking, and [World](crate::world::World) is not ```Sync```.
+//!
+//! All query iterators and guards use the aliases below,
+//! so they do not depend on which backend is chosen.
+use crate::{component::ComponentStorage, entity::EntityManager, group::Group, resource::Resource};
+
+#[cfg(not(feature = "refcell"))]
+mod backend {
+    pub(in crate) type RwLock<T> = parking_lot::RwLock<T>;
+    pub(in crate) type ReadGuard<'a,T> = parking_lot::RwLockReadGuard<'a,T>;
+    pub(in crate) type WriteGuard<'a,T> = parking_lot::RwLockWriteGuard<'a,T>;
+}
+
+#[cfg(feature = "refcell")]
+mod backend {
+    use std::cell::{Ref, RefCell, RefMut};
+
+    pub(in crate) type ReadGuard<'a,T> = Ref<'a,T>;
+    pub(in crate) type WriteGuard<'a,T> = RefMut<'a,T>;
+
+    /// A [RefCell](std::cell::RefCell) with the interfaces of RwLock
+    #[derive(Debug,Default)]
+    pub(in crate) struct RwLock<T>(RefCell<T>);
+
+    impl<T> RwLock<T> {
+        pub(in crate) fn new(value : T) -> Self {
+            RwLock(RefCell::new(value))
+        }
+
+        /// # Panics
+        /// Panic if it is borrowed mutably
+        pub(in crate) fn read(&self) -> ReadGuard<'_,T> {
+            self.0.borrow()
+        }
+
+        /// Return ```None``` if it is borrowed mutably
+        pub(in crate) fn try_read(&self) -> Option<ReadGuard<'_,T>> {
+            self.0.try_borrow().ok()
+        }
+
+        /// # Panics
+        /// Panic if it is borrowed
+        pub(in crate) fn write(&self) -> WriteGuard<'_,T> {
+            self.0.borrow_mut()
+        }
+
+        pub(in crate) fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+    }
+}
+
+pub(in crate) use backend::{RwLock, ReadGuard, WriteGuard};
+
+/// The read guard of component storage
+pub(in crate) type StorageReadGuard<'a> = ReadGuard<'a,Box<dyn ComponentStorage>>;
+/// The write guard of component storage
+pub(in crate) type StorageWriteGuard<'a> = WriteGuard<'a,Box<dyn ComponentStorage>>;
+/// The read guard of group
+pub(in crate) type GroupReadGuard<'a> = ReadGuard<'a,Group>;
+/// The write guard of group
+pub(in crate) type GroupWriteGuard<'a> = WriteGuard<'a,Group>;
+/// The read guard of entity manager
+pub(in crate) type EntityManagerReadGuard<'a> = ReadGuard<'a,EntityManager>;
+/// The read guard of resource
+pub(in crate) type ResourceReadGuard<'a> = ReadGuard<'a,Box<dyn Resource>>;
+/// The write guard of resource
+pub(in crate) type ResourceWriteGuard<'a> = WriteGuard<'a,Box<dyn Resource>>;
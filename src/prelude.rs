@@ -0,0 +1,34 @@
+//! # Prelude
+//! The commonly used items of XECS.
+//! ```
+//! use xecs::prelude::*;
+//! #[derive(Clone,Copy)]
+//! struct Position(f32);
+//! #[derive(Clone,Copy)]
+//! struct Velocity(f32);
+//!
+//! let mut world = World::new();
+//! world.register::<Position>().register::<Velocity>();
+//! world.create_entity().attach(Position(0.0)).attach(Velocity(1.0));
+//! world.make_group(full_owning::<Position,Velocity>());
+//!
+//! for (id,(pos,vel)) in world.query::<FullOwning<&mut Position,&Velocity>>().with_id() {
+//!     pos.0 += vel.0;
+//!     assert!(world.is_alive(id));
+//! }
+//! ```
+pub use crate::{
+    world::World,
+    entity::{Entity, EntityId},
+    component::Component,
+    system::System,
+    query::{Queryable, QueryIterator, WithId, Without},
+    group::{
+        full_owning,
+        partial_owning,
+        non_owning,
+        FullOwning,
+        PartialOwning,
+        NonOwning
+    }
+};
@@ -0,0 +1,168 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::Component, entity::EntityId, sparse_set::SparseSet, world::World};
+use crate::lock::StorageReadGuard;
+use super::{QueryGet, QueryIterator, Queryable};
+
+/// A query element that yields ```&T``` only if ```T``` was attached
+/// in last frame or current frame.
+/// # Details
+/// Overwriting an existing component is not an addition.
+/// The frame is advanced by [increment_tick](crate::world::World::increment_tick).
+/// # Example
+/// ```
+/// use xecs::{World, query::Added};
+/// let mut world = World::new();
+/// world.register::<u32>();
+/// world.create_entity().attach(1_u32);
+/// assert_eq!(world.query::<Added<u32>>().count(),1);
+///
+/// world.increment_tick();
+/// world.create_entity().attach(2_u32);
+/// assert_eq!(world.query::<Added<u32>>().count(),2);
+/// world.increment_tick();
+/// assert_eq!(world.query::<Added<u32>>().copied().collect::<Vec<_>>(),vec![2]);
+/// world.increment_tick();
+/// assert_eq!(world.query::<Added<u32>>().count(),0);
+/// ```
+pub struct Added<T> {
+    _marker : PhantomData<T>
+}
+
+pub struct IterAdded<'a,T> {
+    index : usize,
+    sparse_set : *const SparseSet<EntityId,T>,
+    // the oldest tick can be yielded
+    since : u64,
+    borrow : StorageReadGuard<'a>
+}
+
+impl<'a,T : Component> Queryable<'a> for Added<T> {
+    type Item = &'a T;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let storage = world.raw_storage_read(TypeId::of::<T>())
+            .expect("Queryable for Added<T>: Component was not registered in world");
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe {
+            storage.downcast_ref::<SparseSet<EntityId,T>>()
+        };
+        let ptr : *const SparseSet<EntityId,T> = sparse_set;
+        // tick 0 means unknown
+        let since = world.change_tick().saturating_sub(1).max(1);
+        Box::new(IterAdded {
+            index : 0,
+            sparse_set : ptr,
+            since,
+            borrow : storage
+        })
+    }
+}
+
+impl<'a,T> IterAdded<'a,T> {
+    fn sparse_set(&self) -> &'a SparseSet<EntityId,T> {
+        // Safety:
+        // the pointer is from borrow,it's valid in 'a
+        unsafe { &*self.sparse_set }
+    }
+
+    fn is_added(&self,index : usize) -> bool {
+        self.sparse_set()
+            .added_tick(index)
+            .map(|tick|tick >= self.since)
+            .unwrap_or(false)
+    }
+}
+
+impl<'a,T : Component> Iterator for IterAdded<'a,T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.borrow.count() - self.index;
+        (0,Some(rem))
+    }
+}
+
+impl<'a,T : Component> QueryIterator for IterAdded<'a,T> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let sparse_set = self.sparse_set();
+        while self.index < sparse_set.len() {
+            let index = self.index;
+            self.index += 1;
+            if self.is_added(index) {
+                return Some((sparse_set.entities()[index],&sparse_set.data()[index]));
+            }
+        }
+        None
+    }
+}
+
+impl<'a,T : Component> QueryGet for IterAdded<'a,T> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        let sparse_set = self.sparse_set();
+        let index = sparse_set.get_index(id)?;
+        if self.is_added(index) {
+            sparse_set.data().get(index)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{query::{Added, Changed, QueryIterator, WithId}, world::World};
+
+    #[test]
+    fn added_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..4_u32).map(|i|world.create_entity().attach(i).into_id())
+            .collect::<Vec<_>>();
+        world.increment_tick();
+        world.increment_tick();
+        assert_eq!(world.query::<Added<u32>>().count(),0);
+
+        // overwriting is not an addition
+        world.attach_component(ids[0],10_u32);
+        world.attach_component(ids[1],'a');
+        world.create_entities(2).attach(vec![20_u32,21]);
+        let new = world.create_entity().attach(30_u32).into_id();
+        let mut added = world.query::<Added<u32>>()
+            .copied()
+            .collect::<Vec<_>>();
+        added.sort();
+        assert_eq!(added,vec![20,21,30]);
+        assert_eq!(world.query::<(Added<char>,&u32)>().map(|(_,x)|*x).collect::<Vec<_>>(),vec![1]);
+        let mut iter = world.query::<Added<u32>>();
+        assert_eq!(iter.from_id(new),Some(&30));
+        assert_eq!(iter.from_id(ids[0]),None);
+        drop(iter);
+
+        // the removed entity is swapped out
+        world.remove_entity(ids[2]);
+        world.detach_component::<u32>(ids[3]);
+        world.attach_component(ids[3],40_u32);
+        let mut added = world.query::<Added<u32>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        added.sort();
+        assert_eq!(added.len(),4);
+        assert!(added.contains(&ids[3]));
+        assert_eq!(world.query::<Changed<u32>>().count(),0);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::Component, entity::EntityId, sparse_set::SparseSet, world::World};
+use crate::lock::StorageReadGuard;
+use super::{QueryGet, QueryIterator, Queryable};
+
+/// A query element that yields ```&T``` only if ```T``` was changed
+/// in last frame or current frame.
+/// # Details
+/// A component is changed when it's accessed by ```&mut T``` query
+/// or dereferenced mutably by [ComponentWrite](crate::component::ComponentWrite).
+/// Attaching a new component is not a change,
+/// so nothing is yielded in the first frame after registration.
+/// The frame is advanced by [tick](crate::world::World::tick).
+/// # Example
+/// ```
+/// use xecs::{World, query::Changed};
+/// let mut world = World::new();
+/// world.register::<u32>();
+/// let id = world.create_entity().attach(1_u32).into_id();
+/// assert_eq!(world.query::<Changed<u32>>().count(),0);
+///
+/// *world.get_mut::<u32>(id).unwrap() += 1;
+/// assert_eq!(world.query::<Changed<u32>>().count(),1);
+/// world.tick();
+/// assert_eq!(world.query::<Changed<u32>>().count(),1);
+/// world.tick();
+/// assert_eq!(world.query::<Changed<u32>>().count(),0);
+/// ```
+pub struct Changed<T> {
+    _marker : PhantomData<T>
+}
+
+pub struct IterChanged<'a,T> {
+    index : usize,
+    sparse_set : *const SparseSet<EntityId,T>,
+    // the oldest tick can be yielded
+    since : u64,
+    borrow : StorageReadGuard<'a>
+}
+
+impl<'a,T : Component> Queryable<'a> for Changed<T> {
+    type Item = &'a T;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let storage = world.raw_storage_read(TypeId::of::<T>())
+            .expect("Queryable for Changed<T>: Component was not registered in world");
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe {
+            storage.downcast_ref::<SparseSet<EntityId,T>>()
+        };
+        let ptr : *const SparseSet<EntityId,T> = sparse_set;
+        // tick 0 means never changed
+        let since = world.change_tick().saturating_sub(1).max(1);
+        Box::new(IterChanged {
+            index : 0,
+            sparse_set : ptr,
+            since,
+            borrow : storage
+        })
+    }
+}
+
+impl<'a,T> IterChanged<'a,T> {
+    fn sparse_set(&self) -> &'a SparseSet<EntityId,T> {
+        // Safety:
+        // the pointer is from borrow,it's valid in 'a
+        unsafe { &*self.sparse_set }
+    }
+
+    fn is_changed(&self,index : usize) -> bool {
+        self.sparse_set()
+            .tick(index)
+            .map(|tick|tick >= self.since)
+            .unwrap_or(false)
+    }
+}
+
+impl<'a,T : Component> Iterator for IterChanged<'a,T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.borrow.count() - self.index;
+        (0,Some(rem))
+    }
+}
+
+impl<'a,T : Component> QueryIterator for IterChanged<'a,T> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let sparse_set = self.sparse_set();
+        while self.index < sparse_set.len() {
+            let index = self.index;
+            self.index += 1;
+            if self.is_changed(index) {
+                return Some((sparse_set.entities()[index],&sparse_set.data()[index]));
+            }
+        }
+        None
+    }
+}
+
+impl<'a,T : Component> QueryGet for IterChanged<'a,T> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        let sparse_set = self.sparse_set();
+        let index = sparse_set.get_index(id)?;
+        if self.is_changed(index) {
+            sparse_set.data().get(index)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{query::{Changed, QueryIterator, WithId}, world::World};
+
+    #[test]
+    fn changed_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..5_u32).map(|i| {
+            world.create_entity().attach(i).attach('a').into_id()
+        }).collect::<Vec<_>>();
+        // attaching is not a change
+        assert_eq!(world.query::<Changed<u32>>().count(),0);
+        world.tick();
+        assert_eq!(world.query::<Changed<u32>>().count(),0);
+
+        for x in world.query::<&mut u32>() {
+            if *x % 2 == 0 {
+                *x += 10;
+            }
+        }
+        // every accessed component is changed
+        assert_eq!(world.query::<Changed<u32>>().count(),5);
+
+        world.tick();
+        let mut iter = world.query::<(&char,&mut u32)>();
+        iter.from_id(ids[3]);
+        drop(iter);
+        world.tick();
+        // only ids[3] was changed in last frame
+        let changed = world.query::<Changed<u32>>()
+            .with_id()
+            .map(|(id,x)|(id,*x))
+            .collect::<Vec<_>>();
+        assert_eq!(changed,vec![(ids[3],3)]);
+
+        // composition with other queries
+        *world.get_mut::<char>(ids[1]).unwrap() = 'b';
+        let composed = world.query::<(&u32,Changed<char>)>()
+            .map(|(x,c)|(*x,*c))
+            .collect::<Vec<_>>();
+        assert_eq!(composed,vec![(1,'b')]);
+        assert_eq!(world.query::<(Changed<u32>,Changed<char>)>().count(),0);
+
+        // reading is not a change
+        let _ = *world.get::<char>(ids[2]).unwrap();
+        world.tick();
+        world.tick();
+        assert_eq!(world.query::<Changed<char>>().count(),0);
+    }
+
+    #[test]
+    fn changed_half_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..100_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 4 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.increment_tick();
+        world.increment_tick();
+
+        // only the items yielded by &mut u32 are changed
+        for (_,x) in world.query::<(&char,&mut u32)>() {
+            *x += 1000;
+        }
+        for (id,x) in world.query::<&u32>().with_id() {
+            assert_eq!(*x >= 1000,id.index() % 4 == 1);
+        }
+        let mut iter = world.query::<&mut u32>();
+        for id in ids.iter().skip(2).step_by(4) {
+            *iter.from_id(*id).unwrap() += 1000;
+        }
+        drop(iter);
+        let mut changed = world.query::<Changed<u32>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        changed.sort();
+        assert_eq!(changed,ids.iter().step_by(2).cloned().collect::<Vec<_>>());
+        assert!(world.query::<Changed<u32>>().all(|x|*x >= 1000));
+    }
+
+    #[test]
+    fn changed_structural_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        let ids = (0..4_u32).map(|i|world.create_entity().attach(i).into_id())
+            .collect::<Vec<_>>();
+        *world.get_mut::<u32>(ids[0]).unwrap() = 100;
+        // ids[3] is moved to the removed position
+        world.remove_entity(ids[0]);
+        assert_eq!(world.query::<Changed<u32>>().count(),0);
+        *world.get_mut::<u32>(ids[3]).unwrap() = 30;
+        world.attach_component(ids[1],7_u32);
+        let changed = world.query::<Changed<u32>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(changed,vec![ids[3]]);
+    }
+}
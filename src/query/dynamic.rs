@@ -0,0 +1,70 @@
+use std::any::Any;
+use crate::{component::ComponentStorage, entity::EntityId};
+use crate::lock::StorageReadGuard;
+
+/// The iterator of [query_dynamic](crate::world::World::query_dynamic)
+/// # Details
+/// It yields the entities which have all components,
+/// with the type-erased components in the order of requested types.
+pub struct DynQueryIter<'a> {
+    index : usize,
+    // the storage with the fewest entities drives the iteration
+    driver : usize,
+    storages : Vec<*const dyn ComponentStorage>,
+    #[allow(unused)]
+    borrows : Vec<StorageReadGuard<'a>>
+}
+
+impl<'a> DynQueryIter<'a> {
+    pub(in crate) fn new(borrows : Vec<StorageReadGuard<'a>>) -> Self {
+        let storages = borrows.iter()
+            .map(|storage|&***storage as *const dyn ComponentStorage)
+            .collect::<Vec<_>>();
+        let driver = borrows.iter()
+            .enumerate()
+            .min_by_key(|(_,storage)|storage.count())
+            .map(|(driver,_)|driver)
+            .unwrap_or(0);
+        DynQueryIter {
+            index : 0,
+            driver,
+            storages,
+            borrows
+        }
+    }
+
+    fn storage(&self,index : usize) -> &'a dyn ComponentStorage {
+        // Safety:
+        // Safe here, because the pointers are from borrows,
+        // the boxed storages are never moved while they are borrowed.
+        unsafe { &*self.storages[index] }
+    }
+}
+
+impl<'a> Iterator for DynQueryIter<'a> {
+    type Item = (EntityId,Vec<&'a dyn Any>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.storages.is_empty() {
+            return None;
+        }
+        let driver = self.storage(self.driver);
+        while let Some(id) = driver.id(self.index) {
+            self.index += 1;
+            let components = (0..self.storages.len())
+                .map(|index|self.storage(index).get_any(id))
+                .collect::<Option<Vec<_>>>();
+            if let Some(components) = components {
+                return Some((id,components));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = self.storages.get(self.driver)
+            .map(|_|self.storage(self.driver).count() - self.index)
+            .unwrap_or(0);
+        (0,Some(upper))
+    }
+}
@@ -0,0 +1,129 @@
+use std::any::TypeId;
+use crate::lock::EntityManagerReadGuard;
+use crate::{entity::EntityId, error::QueryError, world::World};
+use super::{QueryIterator, Queryable};
+
+pub struct IterEntityId<'a> {
+    index : usize,
+    world : &'a World,
+    // locked at the first iteration
+    entities : Option<EntityManagerReadGuard<'a>>
+}
+
+impl<'a> Queryable<'a> for EntityId {
+    type Item = EntityId;
+
+    /// No component is touched
+    fn component_types(_ : &mut Vec<TypeId>) {}
+
+    fn check(_ : &World) -> Result<(),QueryError> {
+        Ok(())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        Box::new(IterEntityId {
+            index : 0,
+            world,
+            entities : None
+        })
+    }
+}
+
+impl<'a> IterEntityId<'a> {
+    fn entities(&mut self) -> &EntityManagerReadGuard<'a> {
+        let world = self.world;
+        self.entities.get_or_insert_with(|| world.entity_manager_read())
+    }
+}
+
+impl<'a> Iterator for IterEntityId<'a> {
+    type Item = EntityId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(id,_)|id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.entities {
+            Some(entities) => {
+                let rem = entities.entities().len() - self.index;
+                (rem,Some(rem))
+            },
+            // all entities may be yielded,
+            // so it should be probed at last
+            None => (0,None)
+        }
+    }
+}
+
+impl<'a> QueryIterator for IterEntityId<'a> {
+    fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
+        if self.entities().has(id) {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let index = self.index;
+        let id = *self.entities().entities().get(index)?;
+        self.index += 1;
+        Some((id,id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, FullOwning}, query::{QueryIterator, WithId, Without}, world::World};
+
+    #[test]
+    fn entity_id_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..8_u32).map(|i| {
+            let id = world.create_entity().into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,i);
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.remove_entity(ids[6]);
+
+        let alive = world.query::<EntityId>().collect::<Vec<_>>();
+        assert_eq!(alive,world.entity_manager_read().entities().to_vec());
+        assert_eq!(alive.len(),7);
+
+        // the same as with_id
+        let expected = world.query::<&u32>()
+            .with_id()
+            .map(|(id,x)|(id,*x))
+            .collect::<Vec<_>>();
+        // entities drive the iteration,so it's in the order of entities
+        let mut items = world.query::<(EntityId,&u32)>()
+            .map(|(id,x)|(id,*x))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,expected);
+        let items = world.query::<(&u32,EntityId)>()
+            .map(|(x,id)|(id,*x))
+            .collect::<Vec<_>>();
+        assert_eq!(items,expected);
+
+        let mut iter = world.query::<(EntityId,Without<&char>)>();
+        assert_eq!(iter.from_id(ids[1]),Some(ids[1]));
+        assert_eq!(iter.from_id(ids[3]),None);
+        assert_eq!(iter.from_id(ids[6]),None);
+        assert_eq!(iter.count(),5);
+
+        world.make_group(full_owning::<u32,char>());
+        let grouped = world.query::<(FullOwning<&u32,&char>,EntityId)>()
+            .map(|((x,_),id)|(*x,id))
+            .collect::<Vec<_>>();
+        assert_eq!(grouped,vec![(0,ids[0])]);
+    }
+}
@@ -7,13 +7,17 @@
 //! The result of [query](crate::world::World::query) is a boxed [QueryIterator](crate::query::QueryIterator). 
 //! This trait is an extension of [Iterator](std::iter::Iterator). So it can be treat as 
 //! an [Iterator](std::iter::Iterator).
+//! The concrete iterators are [FusedIterator](std::iter::FusedIterator),
+//! they keep returning ```None``` after the first ```None```.
 //! # With Id
 //! Sometime we don't only need the borrow of components data, but we also interest in the ID of
 //! entity. The [with_id](crate::query::WithId::with_id) method from [WithId](crate::query::WithId) 
 //! will be helpful.
 //! ```no_run
 //! // query with id
-//! use xecs::query::WithId; // we need use this trait before using with_id
+//! use xecs::prelude::*; // we need use WithId before using with_id
+//! # struct A;
+//! # let world = World::new();
 //! for (id,data) in world.query::<&A>().with_id() {
 //!     // do sth with id and data
 //! }
@@ -22,18 +26,53 @@
 //! Sometime we want to query all entities with component ```A``` but ```B```.The
 //! [Without](crate::query::Without) can be useful in this situation.
 //! ```no_run
+//! # use xecs::prelude::*;
+//! # struct A;
+//! # struct B;
+//! # let world = World::new();
 //! for data in world.query::<(&A,Without<&B>)>() {
 //!    // do sth with data
 //! }
 //! ```
+//! The queried side can also be a [group](crate::group) query like
+//! ```(FullOwning<&A,&B>,Without<&C>)```,
+//! the packed part of group still drives the iteration.
+//! # Or
+//! [Or<A,B>](crate::query::Or) matches the entities matched by ```A```,```B``` or both,
+//! each entity is yielded once as an [OrItem](crate::query::OrItem).
+//! # Double-ended
+//! The boxed [QueryIterator](crate::query::QueryIterator) cannot be reversed.
+//! [query_double_ended](crate::world::World::query_double_ended) returns the unboxed iterator
+//! of a [DoubleEndedQueryable](crate::query::DoubleEndedQueryable),
+//! so ```world.query_double_ended::<&T>().rev()``` iterates from the last item.
+//! # Optional
+//! ```Option<&T>``` and ```Option<&mut T>``` match entities whether they have ```T``` or not.
+//! The item is ```None``` when the entity doesn't have ```T``` or ```T``` is not registered.
+//! They never drive a tuple query,so ```(&A,Option<&B>)``` yields every entity with ```A```.
+//! Queried alone,```world.query::<Option<&T>>()``` yields an item for every alive entity.
+//! ```
+//! use xecs::World;
+//! let mut world = World::new();
+//! world.register::<u32>().register::<char>();
+//! world.create_entity().attach(1_u32).attach('a');
+//! world.create_entity().attach(2_u32);
+//!
+//! let items = world.query::<(&u32,Option<&char>)>()
+//!     .map(|(n,c)|(*n,c.cloned()))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(items,vec![(1,Some('a')),(2,None)]);
+//! ```
+//! # Entity ID
+//! ```world.query::<EntityId>()``` yields the IDs of all alive entities,
+//! it can be a part of tuple like ```(EntityId,&T)``` instead of [with_id](crate::query::WithId::with_id).
 //! # Safety
 //! Query Iterator internal has a lot of ```*const _```or```*mut _``` 
 //! to avoid borrow-checker warnings like this
-//! ```no_run
+//! ```ignore
 //! pub struct IterRef<'a,T> {
 //!     index : usize,
 //!     sparse_set : *const SparseSet<EntityId,T>,
-//!     borrow : RwLockReadGuard<'a,Box<dyn ComponentStorage>>
+//!     borrow : StorageReadGuard<'a>
 //! }
 //! ```
 //! This struct is NOT a Self-Reference struct! Moving this struct 
@@ -44,18 +83,83 @@
 //! is boxed by ```Box<dyn ComponentStorage>```. And the 
 //! ```sparse_set``` field's lifetime equals to borrow's ```'a```. 
 //! So the pointer is valid when this struct is alive.
-use std::any::TypeId;
-use crate::{component::{Component, ComponentStorage}, entity::EntityId, sparse_set::SparseSet, world::World};
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
+use std::{any::TypeId, iter::FusedIterator};
+use crate::{component::Component, entity::EntityId, error::QueryError, sparse_set::SparseSet, world::World};
+use crate::lock::{StorageReadGuard, StorageWriteGuard};
 
 mod with;
 mod without;
+mod or;
+mod optional;
+mod with_bundle;
+mod stats;
+mod next;
+mod changed;
+mod added;
+mod presence;
+mod entity_id;
+mod dynamic;
+#[cfg(feature = "rayon")]
+mod par;
+
+pub(in crate) use presence::{bit_of, PresenceBits};
 
 pub use with::{
     WithIter,
     WithIter3,
     WithIter4,
-    WithIter5
+    WithIter5,
+    WithIter6,
+    WithIter7,
+    WithIter8
+};
+
+pub use optional::{
+    OptionRef,
+    OptionMut
+};
+
+pub use entity_id::IterEntityId;
+
+pub use dynamic::DynQueryIter;
+
+pub use with_bundle::{
+    WithBundle,
+    WithBundleIter
+};
+
+pub use changed::{
+    Changed,
+    IterChanged
+};
+
+pub use added::{
+    Added,
+    IterAdded
+};
+
+pub use next::{
+    Next,
+    IterNext
+};
+
+#[cfg(feature = "rayon")]
+pub use par::{
+    Collected,
+    ParQuery,
+    ParQueryable
+};
+
+pub use stats::{
+    NumericStats,
+    QueryStats,
+    ToOwnedItem
+};
+
+pub use or::{
+    Or,
+    OrItem,
+    OrIter
 };
 
 pub use without::{
@@ -68,6 +172,18 @@ pub use without::{
 pub trait Queryable<'a> {
     type Item;
 
+    /// Push the type IDs of components which are touched by this query to ```types```
+    fn component_types(types : &mut Vec<TypeId>);
+
+    /// Check if this query can be made from ```world``` without panic.
+    /// # Details
+    /// By default,all components in [component_types](Queryable::component_types) must be registered.
+    fn check(world : &World) -> Result<(),QueryError> {
+        let mut types = vec![];
+        Self::component_types(&mut types);
+        world.check_registered(&types)
+    }
+
     /// Get the [QueryIterator](crate::query::QueryIterator) from the world
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)>;
 }
@@ -75,9 +191,80 @@ pub trait Queryable<'a> {
 /// The result of query
 pub trait QueryIterator : Iterator {
     /// Get item from ```id```
+    /// # Details
+    /// The item of ```&mut T``` is yielded at most once,
+    /// so it returns ```None``` for the item which has been yielded by ```next``` or ```from_id```,
+    /// and ```next``` skips the items yielded by ```from_id```.
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item>;
     /// Just like [next](std::iter::Iterator::next), but it yield data with ID
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)>;
+    /// Get the exact count of remaining items without iterating.
+    /// # Details
+    /// It's exact for single storages and groups.
+    /// Return ```None``` if it's unknown,like the combinations of storages
+    /// or [Without](crate::query::Without).
+    fn len_hint(&self) -> Option<usize> {
+        match self.size_hint() {
+            (lower,Some(upper)) if lower == upper => Some(lower),
+            _ => None
+        }
+    }
+    /// Get the presence bitmap of the IDs which can be got by [from_id](crate::query::QueryIterator::from_id).
+    /// # Details
+    /// The bit of ```id.index()``` is set if ```from_id(id)``` returns ```Some```.
+    /// Return ```None``` if it's unknown,
+    /// see [enable_presence_bitmap](crate::world::World::enable_presence_bitmap).
+    fn presence(&self) -> Option<&[u64]> {
+        None
+    }
+    /// Get the ID of entity with ```index``` which can be got by [from_id](crate::query::QueryIterator::from_id).
+    /// # Details
+    /// It restores the IDs from [presence](crate::query::QueryIterator::presence) bitmap,
+    /// so it must be implemented if the bitmap is known.
+    fn entity_of(&self,_index : usize) -> Option<EntityId> {
+        None
+    }
+}
+
+/// The [QueryIterator] which can get items by a shared reference.
+/// # Details
+/// It's implemented by the read-only iterators and their combinations,
+/// so one query result can be shared by the helper functions which only need random access.
+/// ```
+/// use xecs::{World, EntityId, query::{QueryGet, QueryIterator}};
+/// let mut world = World::new();
+/// world.register::<u32>();
+/// let id = world.create_entity().attach(1_u32).into_id();
+///
+/// fn value<'a>(iter : &impl QueryGet<Item = &'a u32>,id : EntityId) -> u32 {
+///     iter.peek_from_id(id).copied().unwrap_or(0)
+/// }
+/// let iter = world.query_double_ended::<&u32>();
+/// assert_eq!(value(&iter,id) + value(&iter,id),2);
+/// ```
+pub trait QueryGet : QueryIterator {
+    /// Like [from_id](crate::query::QueryIterator::from_id),but it doesn't need ```&mut self```
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item>;
+}
+
+/// Something can be queried as a [DoubleEndedIterator](std::iter::DoubleEndedIterator).
+/// # Details
+/// [query](crate::world::World::query) returns a boxed [QueryIterator](crate::query::QueryIterator)
+/// which cannot be reversed,
+/// use [query_double_ended](crate::world::World::query_double_ended) instead.
+/// It's only implemented for the queries whose items are contiguous:
+/// * ```&T``` and ```&mut T```
+/// * [FullOwning](crate::group::FullOwning) with all combinations of ```&``` and ```&mut```
+///
+/// The combinations of storages like ```(&A,&B)``` are not double-ended,
+/// because the items of ```B``` are looked up from the items of ```A```,
+/// reversing them would need both sides to be double-ended and the order of result is ambiguous.
+pub trait DoubleEndedQueryable<'a> : Queryable<'a> {
+    /// The unboxed iterator
+    type Iter : QueryIterator<Item = Self::Item> + DoubleEndedIterator + ExactSizeIterator + 'a;
+
+    /// Get the double-ended iterator from the world
+    fn query_double_ended(world : &'a World) -> Self::Iter;
 }
 
 impl<T : QueryIterator + ?Sized> QueryIterator for Box<T> {
@@ -90,6 +277,24 @@ impl<T : QueryIterator + ?Sized> QueryIterator for Box<T> {
         (**self)
             .next_with_id()
     }
+
+    fn len_hint(&self) -> Option<usize> {
+        (**self).len_hint()
+    }
+
+    fn presence(&self) -> Option<&[u64]> {
+        (**self).presence()
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        (**self).entity_of(index)
+    }
+}
+
+impl<T : QueryGet + ?Sized> QueryGet for Box<T> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        (**self).peek_from_id(id)
+    }
 }
 
 
@@ -97,14 +302,35 @@ impl<T : QueryIterator + ?Sized> QueryIterator for Box<T> {
 
 pub struct IterRef<'a,T> {
     index : usize,
+    // the index after the last item,it's decreased by next_back
+    end : usize,
     sparse_set : *const SparseSet<EntityId,T>,
-    borrow : RwLockReadGuard<'a,Box<dyn ComponentStorage>>
+    #[allow(unused)]
+    borrow : StorageReadGuard<'a>
 }
 
 impl<'a,T : Component> Queryable<'a> for &'a T {
     type Item = Self;
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        Box::new(IterRef::new(world))
+    }
+}
+
+impl<'a,T : Component> DoubleEndedQueryable<'a> for &'a T {
+    type Iter = IterRef<'a,T>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        IterRef::new(world)
+    }
+}
+
+impl<'a,T : Component> IterRef<'a,T> {
+    fn new(world : &'a World) -> Self {
         assert!(world.has_registered::<T>(),
                 "Queryable for &'a T: Component was not registered in world");
         let type_id = TypeId::of::<T>();
@@ -116,12 +342,14 @@ impl<'a,T : Component> Queryable<'a> for &'a T {
         let sparse_set = unsafe {
             storage.downcast_ref::<SparseSet<EntityId,T>>()
         };
+        let end = sparse_set.len();
         let ptr = &*sparse_set;
-        Box::new(IterRef{
+        IterRef{
             index : 0,
+            end,
             sparse_set : ptr,
             borrow : storage
-        })
+        }
     }
 }
 
@@ -130,7 +358,7 @@ impl<'a,T : Component> Iterator for IterRef<'a,T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.borrow.count() {
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -149,25 +377,42 @@ impl<'a,T : Component> Iterator for IterRef<'a,T> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.borrow.count() - self.index;
+        let rem = self.end - self.index;
         (rem,Some(rem))
     }
 }
 impl<'a,T : Component> ExactSizeIterator for IterRef<'a,T>{}
+impl<'a,T : Component> FusedIterator for IterRef<'a,T>{}
+
+impl<'a,T : Component> DoubleEndedIterator for IterRef<'a,T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set = unsafe { &*self.sparse_set };
+            // Safety:
+            // Safe here, because we checked before.
+            let data = unsafe {
+                sparse_set.data().get_unchecked(self.end)
+            };
+            Some(data)
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,T : Component> QueryIterator for IterRef<'a,T> {
 
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
-        // Safety:
-        // Safe here, because self.sparse_set is 
-        // a pointer from borrow:Ref<'a,SparseSet<...>>,
-        // This pointer is valid now.
-        let sparse_set = unsafe { &*self.sparse_set };
-        sparse_set.get(id)
+        self.peek_from_id(id)
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.borrow.count() {
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -189,21 +434,144 @@ impl<'a,T : Component> QueryIterator for IterRef<'a,T> {
             None
         }
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.presence()
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.entity_at(index)
+    }
+}
+
+impl<'a,T : Component> QueryGet for IterRef<'a,T> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // Safety:
+        // Safe here, because self.sparse_set is 
+        // a pointer from borrow:Ref<'a,SparseSet<...>>,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.get(id)
+    }
+}
+
+
+
+
+/// The items yielded by the [QueryIterator] of ```&mut T```
+/// # Details
+/// A mutable item must be yielded at most once,otherwise there are two mutable references to it.
+/// The items are marked by their positions in iterator,
+/// [from_id](crate::query::QueryIterator::from_id) marks them and ```next``` skips them.
+#[derive(Debug,Default)]
+pub(in crate) struct Taken {
+    bits : Vec<u64>,
+    // the count of bits
+    count : usize
 }
 
+impl Taken {
+    pub(in crate) fn contains(&self,position : usize) -> bool {
+        self.count > 0 && self.bits
+            .get(position / 64)
+            .map(|bits|bits & (1 << (position % 64)) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Mark ```position``` as yielded,return ```false``` if it has been yielded
+    pub(in crate) fn insert(&mut self,position : usize) -> bool {
+        if self.contains(position) {
+            return false;
+        }
+        if self.bits.len() <= position / 64 {
+            self.bits.resize(position / 64 + 1,0);
+        }
+        self.bits[position / 64] |= 1 << (position % 64);
+        self.count += 1;
+        true
+    }
+
+    /// Unmark ```position```,return ```false``` if it's not marked
+    pub(in crate) fn remove(&mut self,position : usize) -> bool {
+        if !self.contains(position) {
+            return false;
+        }
+        self.bits[position / 64] &= !(1 << (position % 64));
+        self.count -= 1;
+        true
+    }
+
+    /// Mark ```position``` as yielded if it's in ```index..end``` which is not iterated yet,
+    /// return ```false``` if it has been yielded by ```next```,```next_back``` or ```from_id```
+    pub(in crate) fn take(&mut self,position : usize,index : usize,end : usize) -> bool {
+        index <= position && position < end && self.insert(position)
+    }
+
+    /// Skip the positions yielded by ```from_id``` at front of ```index..end```
+    pub(in crate) fn skip_front(&mut self,index : &mut usize,end : usize) {
+        while *index < end && self.remove(*index) {
+            *index += 1;
+        }
+    }
 
+    /// Skip the positions yielded by ```from_id``` at back of ```index..end```
+    pub(in crate) fn skip_back(&mut self,index : usize,end : &mut usize) {
+        while index < *end && self.remove(*end - 1) {
+            *end -= 1;
+        }
+    }
 
+    /// Get the count of marked positions
+    pub(in crate) fn len(&self) -> usize {
+        self.count
+    }
+}
 
 pub struct IterMut<'a,T> {
     index : usize,
+    // the index after the last item,it's decreased by next_back
+    end : usize,
     sparse_set : *mut SparseSet<EntityId,T>,
-    borrow : RwLockWriteGuard<'a,Box<dyn ComponentStorage>>
+    #[allow(unused)]
+    borrow : StorageWriteGuard<'a>,
+    // the yielded components are marked as changed at this tick
+    tick : u64,
+    // the indices in index..end which are yielded by from_id
+    taken : Taken
 }
 
 impl<'a,T : Component> Queryable<'a> for &'a mut T {
     type Item = Self;
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
+        Box::new(IterMut::new(world))
+    }
+}
+
+impl<'a,T : Component> DoubleEndedQueryable<'a> for &'a mut T {
+    type Iter = IterMut<'a,T>;
+
+    fn query_double_ended(world : &'a World) -> Self::Iter {
+        IterMut::new(world)
+    }
+}
+
+impl<'a,T : Component> IterMut<'a,T> {
+    fn new(world : &'a World) -> Self {
         assert!(world.has_registered::<T>(),
                 "Queryable for &'a mut T: Component was not registered in world");
         let type_id = TypeId::of::<T>();
@@ -215,26 +583,31 @@ impl<'a,T : Component> Queryable<'a> for &'a mut T {
         let sparse_set = unsafe {
             storage.downcast_mut::<SparseSet<EntityId,T>>()
         };
+        let end = sparse_set.len();
         let ptr = &mut *sparse_set;
-        Box::new(IterMut{
+        IterMut{
             index : 0,
+            end,
             sparse_set : ptr,
-            borrow : storage
-        })
+            borrow : storage,
+            tick : world.change_tick(),
+            taken : Taken::default()
+        }
     }
 }
 
-
 impl<'a,T : Component> Iterator for IterMut<'a,T> {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.borrow.count() {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
             // This pointer is valid now.
             let sparse_set = unsafe { &mut *self.sparse_set };
+            sparse_set.mark_changed(self.index,self.tick);
             // Safety:
             // Safe here, because we checked before.
             let data = unsafe {
@@ -248,11 +621,35 @@ impl<'a,T : Component> Iterator for IterMut<'a,T> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let rem = self.borrow.count() - self.index;
+        let rem = self.end - self.index - self.taken.len();
         (rem,Some(rem))
     }
 }
 impl<'a,T : Component> ExactSizeIterator for IterMut<'a,T>{}
+impl<'a,T : Component> FusedIterator for IterMut<'a,T>{}
+
+impl<'a,T : Component> DoubleEndedIterator for IterMut<'a,T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.taken.skip_back(self.index,&mut self.end);
+        if self.index < self.end {
+            self.end -= 1;
+            // Safety:
+            // Safe here, because self.sparse_set is 
+            // a pointer from borrow,
+            // This pointer is valid now.
+            let sparse_set = unsafe { &mut *self.sparse_set };
+            sparse_set.mark_changed(self.end,self.tick);
+            // Safety:
+            // Safe here, because we checked before.
+            let data = unsafe {
+                sparse_set.data_mut().get_unchecked_mut(self.end)
+            };
+            Some(data)
+        } else {
+            None
+        }
+    }
+}
 
 impl<'a,T : Component> QueryIterator for IterMut<'a,T> {
 
@@ -262,11 +659,19 @@ impl<'a,T : Component> QueryIterator for IterMut<'a,T> {
         // a pointer from borrow:Ref<'a,SparseSet<...>>,
         // This pointer is valid now.
         let sparse_set = unsafe { &mut *self.sparse_set };
-        sparse_set.get_mut(id)
+        let index = sparse_set.get_index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.end) {
+            return None;
+        }
+        sparse_set.mark_changed(index,self.tick);
+        sparse_set.data_mut().get_mut(index)
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
-        if self.index < self.borrow.count() {
+        self.taken.skip_front(&mut self.index,self.end);
+        if self.index < self.end {
             // Safety:
             // Safe here, because self.sparse_set is 
             // a pointer from borrow,
@@ -277,6 +682,7 @@ impl<'a,T : Component> QueryIterator for IterMut<'a,T> {
             let id = *unsafe {
                 sparse_set.entities().get_unchecked(self.index)
             };
+            sparse_set.mark_changed(self.index,self.tick);
             // Safety:
             // Safe here, because we have already checked.
             let data = unsafe {
@@ -288,6 +694,24 @@ impl<'a,T : Component> QueryIterator for IterMut<'a,T> {
             None
         }
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.presence()
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.entity_at(index)
+    }
 }
 
 
@@ -331,12 +755,61 @@ impl<A : QueryIterator> Iterator for IdIter<A> {
 
 #[cfg(test)]
 mod tests{
-    use std::num::NonZeroUsize;
-    use crate::{query::{WithId, Without}, world::World};
+    use crate::{entity::EntityId, query::{WithId, Without}, world::World};
 
     #[derive(Debug,Clone,Copy,PartialEq)]
     struct Tag;
 
+    #[test]
+    fn len_hint_test() {
+        use crate::group::{full_owning, non_owning, partial_owning, FullOwning, NonOwning, PartialOwning};
+        use super::QueryIterator;
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<Tag>();
+        for i in 0..10_u32 {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+                world.attach_component(id,i as i8);
+            }
+            if i % 5 == 0 {
+                world.attach_component(id,Tag);
+            }
+        }
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<char,Tag>());
+
+        let mut iter = world.query::<&u32>();
+        assert_eq!(iter.len_hint(),Some(10));
+        iter.next();
+        assert_eq!(iter.len_hint(),Some(9));
+        assert_eq!(world.query::<&mut u64>().len_hint(),Some(10));
+        // the entities are locked lazily
+        assert_eq!(world.query::<Option<&Tag>>().len_hint(),None);
+        drop(iter);
+
+        let mut iter = world.query::<FullOwning<&u32,&char>>();
+        assert_eq!(iter.len_hint(),Some(world.group_len_of::<u32,char>().unwrap()));
+        assert_eq!(iter.len_hint(),Some(5));
+        iter.next();
+        assert_eq!(iter.len_hint(),Some(4));
+        drop(iter);
+        assert_eq!(world.query::<PartialOwning<&u64,&i8>>().len_hint(),Some(5));
+        assert_eq!(world.query::<NonOwning<&char,&Tag>>().len_hint(),Some(1));
+
+        assert_eq!(world.query::<(&u32,&Tag)>().len_hint(),None);
+        assert_eq!(world.query::<(&u32,Without<&Tag>)>().len_hint(),None);
+        // nothing can be yielded
+        world.register::<u8>();
+        assert_eq!(world.query::<(&u32,&u8)>().len_hint(),Some(0));
+    }
+
     #[test]
     fn basic_test() {
 
@@ -375,8 +848,273 @@ mod tests{
             .map(|(id,(a,(b,c)))|(id,*a,*b,*c))
             .collect::<Vec<_>>();
         assert_eq!(&res,&[
-                   (NonZeroUsize::new(5).unwrap(),5,'q',Tag),
-                   (NonZeroUsize::new(8).unwrap(),8,'s',Tag)]);
+                   (EntityId::new(5).unwrap(),5,'q',Tag),
+                   (EntityId::new(8).unwrap(),8,'s',Tag)]);
+    }
+
+    #[test]
+    fn double_ended_test() {
+        use crate::group::{full_owning, FullOwning};
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        for i in 0..6_u32 {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,i as u64);
+            }
+        }
+
+        let mut iter = world.query_double_ended::<&u32>();
+        assert_eq!(iter.next_back().copied(),Some(5));
+        assert_eq!(iter.next().copied(),Some(0));
+        assert_eq!(iter.len(),4);
+        assert_eq!(iter.rev().copied().collect::<Vec<_>>(),vec![4,3,2,1]);
+
+        world.make_group(full_owning::<u32,u64>());
+        for (i,x) in world.query_double_ended::<&mut u32>().rev().enumerate() {
+            *x += i as u32 * 10;
+        }
+        // the group is packed at the front
+        let mut items = world.query_double_ended::<FullOwning<&u32,&u64>>()
+            .rev()
+            .map(|(a,b)|(*a,*b))
+            .collect::<Vec<_>>();
+        assert_eq!(items.len(),3);
+        let forward = world.query::<FullOwning<&u32,&u64>>()
+            .map(|(a,b)|(*a,*b))
+            .collect::<Vec<_>>();
+        items.reverse();
+        assert_eq!(items,forward);
+
+        // both ends meet in the middle
+        let mut iter = world.query_double_ended::<FullOwning<&mut u32,&mut u64>>();
+        let (_,last) = iter.next_back().unwrap();
+        *last = 100;
+        assert!(iter.next().is_some());
+        assert!(iter.next_back().is_some());
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+        drop(iter);
+        assert_eq!(world.query::<&u64>().filter(|x|**x == 100).count(),1);
+        assert_eq!(world.query_double_ended::<FullOwning<&u32,&mut u64>>().rev().count(),3);
+        assert_eq!(world.query_double_ended::<FullOwning<&mut u32,&u64>>().rev().count(),3);
+    }
+
+    #[test]
+    fn from_id_mut_test() {
+        use super::QueryIterator;
+
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..6_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i != 4 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.enable_presence_bitmap::<u32>();
+        world.enable_presence_bitmap::<char>();
+
+        let mut iter = world.query_double_ended::<&mut u32>();
+        let first = iter.from_id(ids[2]).unwrap();
+        // the same item is never yielded twice
+        assert!(iter.from_id(ids[2]).is_none());
+        let last = iter.next_back().unwrap();
+        assert!(iter.from_id(ids[5]).is_none());
+        *first += 100;
+        *last += 100;
+        assert_eq!(iter.len(),4);
+        assert!(iter.from_id(ids[0]).is_some());
+        assert_eq!(iter.len(),3);
+        // next skips the items yielded by from_id
+        assert_eq!(iter.map(|x|*x).collect::<Vec<_>>(),vec![1,3,4]);
+        assert_eq!(world.query::<&u32>().copied().collect::<Vec<_>>(),vec![0,1,102,3,4,105]);
+
+        // the same in combinations,with or without presence bitmaps
+        let mut iter = world.query::<(&mut u32,&char)>();
+        assert!(iter.from_id(ids[1]).is_some());
+        assert!(iter.from_id(ids[1]).is_none());
+        assert_eq!(iter.count(),4);
+        let mut iter = world.query::<(&char,&mut u32)>();
+        assert!(iter.from_id(ids[3]).is_some());
+        assert!(iter.from_id(ids[3]).is_none());
+        assert_eq!(iter.map(|(_,x)|*x).collect::<Vec<_>>(),vec![0,1,102,105]);
+        let mut iter = world.query::<(&mut u32,Without<&char>)>();
+        assert!(iter.from_id(ids[4]).is_some());
+        assert!(iter.from_id(ids[4]).is_none());
+        assert_eq!(iter.count(),0);
+    }
+
+    // Check the mutable items of query are never yielded twice
+    fn check_yield_once<'a,Q : super::Queryable<'a>>(world : &'a World,ids : &[EntityId]) {
+        use super::QueryIterator;
+
+        let mut iter = world.query::<Q>();
+        let count = iter.len_hint().unwrap();
+        assert!(iter.from_id(ids[1]).is_some());
+        assert!(iter.from_id(ids[1]).is_none());
+        let (id,_) = iter.next_with_id().unwrap();
+        assert!(iter.from_id(id).is_none());
+        assert_eq!(iter.len_hint(),Some(count - 2));
+        // next skips the items yielded by from_id
+        let rest = iter.with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(rest.len(),count - 2);
+        assert!(!rest.contains(&ids[1]));
+    }
+
+    #[test]
+    fn from_id_mut_group_test() {
+        use crate::group::{
+            full_owning, full_owning3, non_owning, non_owning3, partial_owning,
+            FullOwning, FullOwning3, GroupIndexed, NonOwning, NonOwning3, PartialOwning
+        };
+        use super::QueryIterator;
+
+        fn spawn() -> (World,Vec<EntityId>) {
+            let mut world = World::new();
+            world.register::<u32>()
+                .register::<char>()
+                .register::<u64>();
+            let ids = (0..6_u32).map(|i| {
+                world.create_entity()
+                    .attach(i)
+                    .attach('a')
+                    .attach(i as u64)
+                    .into_id()
+            }).collect::<Vec<_>>();
+            (world,ids)
+        }
+
+        let (mut world,ids) = spawn();
+        world.make_group(full_owning::<u32,char>());
+        check_yield_once::<FullOwning<&u32,&mut char>>(&world,&ids);
+        check_yield_once::<FullOwning<&mut u32,&char>>(&world,&ids);
+        check_yield_once::<FullOwning<&mut u32,&mut char>>(&world,&ids);
+        check_yield_once::<GroupIndexed<&u32,&mut char>>(&world,&ids);
+        check_yield_once::<GroupIndexed<&mut u32,&char>>(&world,&ids);
+        check_yield_once::<GroupIndexed<&mut u32,&mut char>>(&world,&ids);
+        // the items yielded by next_back
+        let mut iter = world.query_double_ended::<FullOwning<&mut u32,&mut char>>();
+        let (last,_) = iter.next_back().unwrap();
+        assert!(iter.from_id(ids[5]).is_none());
+        assert!(iter.from_id(ids[0]).is_some());
+        assert_eq!(iter.len(),4);
+        *last += 100;
+        drop(iter);
+        assert_eq!(*world.get::<u32>(ids[5]).unwrap(),105);
+        // the indices in group are still right
+        let mut iter = world.query::<GroupIndexed<&u32,&mut char>>();
+        assert!(iter.from_id(ids[0]).is_some());
+        assert!(iter.all(|(index,x,_)|index == *x as usize || index == 5));
+        drop(iter);
+
+        let (mut world,ids) = spawn();
+        world.make_group(partial_owning::<u32,char>());
+        check_yield_once::<PartialOwning<&u32,&mut char>>(&world,&ids);
+        check_yield_once::<PartialOwning<&mut u32,&char>>(&world,&ids);
+        check_yield_once::<PartialOwning<&mut u32,&mut char>>(&world,&ids);
+
+        let (mut world,ids) = spawn();
+        world.make_group(non_owning::<u32,char>());
+        check_yield_once::<NonOwning<&u32,&mut char>>(&world,&ids);
+        check_yield_once::<NonOwning<&mut u32,&char>>(&world,&ids);
+        check_yield_once::<NonOwning<&mut u32,&mut char>>(&world,&ids);
+
+        let (mut world,ids) = spawn();
+        world.make_group(full_owning3::<u32,char,u64>());
+        check_yield_once::<FullOwning3<&u32,&char,&mut u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&u32,&mut char,&u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&u32,&mut char,&mut u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&mut u32,&char,&u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&mut u32,&char,&mut u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&mut u32,&mut char,&u64>>(&world,&ids);
+        check_yield_once::<FullOwning3<&mut u32,&mut char,&mut u64>>(&world,&ids);
+
+        let (mut world,ids) = spawn();
+        world.make_group(non_owning3::<u32,char,u64>());
+        check_yield_once::<NonOwning3<&u32,&char,&mut u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&u32,&mut char,&u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&u32,&mut char,&mut u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&mut u32,&char,&u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&mut u32,&char,&mut u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&mut u32,&mut char,&u64>>(&world,&ids);
+        check_yield_once::<NonOwning3<&mut u32,&mut char,&mut u64>>(&world,&ids);
+        // the read-only items can still be got many times
+        let mut iter = world.query::<NonOwning3<&u32,&char,&u64>>();
+        assert!(iter.from_id(ids[1]).is_some());
+        assert!(iter.from_id(ids[1]).is_some());
+    }
+
+    #[test]
+    fn peek_from_id_test() {
+        use crate::group::{full_owning, FullOwning};
+        use super::{QueryGet, WithIter, WithoutIterRight};
+
+        fn sum<'a>(iter : &impl QueryGet<Item = &'a u32>,ids : &[EntityId]) -> u32 {
+            ids.iter().filter_map(|id|iter.peek_from_id(*id)).sum()
+        }
+
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..6_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+
+        let iter = world.query_double_ended::<&u32>();
+        // the same query result is shared by helpers
+        assert_eq!(sum(&iter,&ids) + sum(&iter,&ids[..2]),16);
+        assert_eq!(iter.count(),6);
+
+        let iter = WithIter::new(world.query_double_ended::<&u32>(),world.query_double_ended::<&char>());
+        let shared = &iter;
+        assert_eq!(shared.peek_from_id(ids[2]),Some((&2,&'a')));
+        assert_eq!(shared.peek_from_id(ids[3]),None);
+        assert_eq!(iter.count(),3);
+
+        let iter = WithoutIterRight::new(world.query_double_ended::<&u32>(),world.query_double_ended::<&char>());
+        assert_eq!(sum(&iter,&ids),9);
+        drop(iter);
+
+        world.make_group(full_owning::<u32,char>());
+        let iter = world.query_double_ended::<FullOwning<&u32,&char>>();
+        assert_eq!(iter.peek_from_id(ids[4]),Some((&4,&'a')));
+        assert_eq!(iter.peek_from_id(ids[5]),None);
+    }
+
+    #[test]
+    fn fused_test() {
+        use std::iter::FusedIterator;
+        use crate::group::{full_owning, FullOwning};
+
+        fn exhaust<I : Iterator>(mut iter : I) {
+            assert!(iter.next().is_some());
+            for _ in 0..50 {
+                assert!(iter.next().is_none());
+            }
+        }
+        fn fused<I : FusedIterator>(iter : I) -> I { iter }
+
+        let mut world = World::new();
+        world.register::<u32>().register::<char>().register::<Tag>();
+        world.create_entity().attach(1_u32).attach('a');
+        exhaust(fused(world.query_double_ended::<&u32>()));
+        exhaust(fused(world.query_double_ended::<&mut u32>()));
+        exhaust(world.query::<(&u32,&char)>());
+        exhaust(world.query::<(&mut u32,Without<&Tag>)>());
+
+        world.make_group(full_owning::<u32,char>());
+        exhaust(fused(world.query_double_ended::<FullOwning<&u32,&mut char>>()));
+        exhaust(world.query::<FullOwning<&u32,&char>>());
     }
 
     #[test]
@@ -0,0 +1,288 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::{component::Component, entity::EntityId, sparse_set::SparseSet, world::World};
+use crate::lock::StorageReadGuard;
+use super::{QueryIterator, Queryable, Taken};
+
+/// A query element that writes the back buffer of a
+/// [double-buffered](crate::world::World::register_double_buffered) component.
+/// # Details
+/// The item is ```&mut T``` in the back buffer.
+/// It only takes the read lock of storage,
+/// so it can be used with ```&T``` of the same component to read last frame's values.
+/// Call [swap_buffers](crate::world::World::swap_buffers) to make the written values visible.
+/// # Panics
+/// * Panic if ```T``` is not double-buffered.
+/// * Panic if another ```Next<T>``` is alive.
+/// # Example
+/// ```
+/// use xecs::{World, query::Next};
+/// #[derive(Clone,Copy,Debug,PartialEq)]
+/// struct Temperature(f32);
+///
+/// let mut world = World::new();
+/// world.register_double_buffered::<Temperature>();
+/// world.create_entity().attach(Temperature(10.0));
+///
+/// for (now,next) in world.query::<(&Temperature,Next<Temperature>)>() {
+///     next.0 = now.0 * 0.5;
+/// }
+/// assert_eq!(world.query::<&Temperature>().next(),Some(&Temperature(10.0)));
+/// world.swap_buffers::<Temperature>();
+/// assert_eq!(world.query::<&Temperature>().next(),Some(&Temperature(5.0)));
+/// ```
+pub struct Next<T> {
+    _marker : PhantomData<T>
+}
+
+pub struct IterNext<'a,T> {
+    index : usize,
+    sparse_set : *const SparseSet<EntityId,T>,
+    back : *mut T,
+    borrow : StorageReadGuard<'a>,
+    // the indices which are yielded by from_id
+    taken : Taken
+}
+
+impl<'a,T : Component> Queryable<'a> for Next<T> {
+    type Item = &'a mut T;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let storage = world.raw_storage_read(TypeId::of::<T>())
+            .expect("Queryable for Next<T>: Component was not registered in world");
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe {
+            storage.downcast_ref::<SparseSet<EntityId,T>>()
+        };
+        let back = sparse_set.borrow_back();
+        let ptr : *const SparseSet<EntityId,T> = sparse_set;
+        Box::new(IterNext {
+            index : 0,
+            sparse_set : ptr,
+            back,
+            borrow : storage,
+            taken : Taken::default()
+        })
+    }
+}
+
+impl<'a,T> IterNext<'a,T> {
+    /// # Safety
+    /// ```index``` must be less than the count of storage
+    unsafe fn back_at(&self,index : usize) -> &'a mut T {
+        &mut *self.back.add(index)
+    }
+}
+
+impl<'a,T> Drop for IterNext<'a,T> {
+    fn drop(&mut self) {
+        // Safety:
+        // the read guard is still alive here
+        let sparse_set = unsafe { &*self.sparse_set };
+        sparse_set.release_back();
+    }
+}
+
+impl<'a,T : Component> Iterator for IterNext<'a,T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.borrow.count() - self.index - self.taken.len();
+        (rem,Some(rem))
+    }
+}
+
+impl<'a,T : Component> QueryIterator for IterNext<'a,T> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        let index = self.borrow.index(id)?;
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.take(index,self.index,self.borrow.count()) {
+            return None;
+        }
+        // Safety:
+        // index is from storage,
+        // the back buffer has the same length as storage
+        Some(unsafe { self.back_at(index) })
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        self.taken.skip_front(&mut self.index,self.borrow.count());
+        let id = self.borrow.id(self.index)?;
+        // Safety:
+        // index is checked by id() above
+        let item = unsafe { self.back_at(self.index) };
+        self.index += 1;
+        Some((id,item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, non_owning, partial_owning}, query::{Next, QueryIterator, WithId}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Temperature(f32);
+
+    #[derive(Debug,Clone,Copy)]
+    struct Neighbors([EntityId;2]);
+
+    #[test]
+    fn read_old_write_new_test() {
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        let a = world.create_entity().attach(Temperature(1.0)).into_id();
+        let b = world.create_entity().attach(Temperature(2.0)).into_id();
+
+        for (now,next) in world.query::<(&Temperature,Next<Temperature>)>() {
+            next.0 = now.0 + 10.0;
+        }
+        // still the old values before swap
+        assert_eq!(*world.get::<Temperature>(a).unwrap(),Temperature(1.0));
+        world.swap_buffers::<Temperature>();
+        assert_eq!(*world.get::<Temperature>(a).unwrap(),Temperature(11.0));
+        assert_eq!(*world.get::<Temperature>(b).unwrap(),Temperature(12.0));
+
+        // the back buffer holds last frame
+        let back = world.query::<Next<Temperature>>()
+            .map(|next|*next)
+            .collect::<Vec<_>>();
+        assert_eq!(back,vec![Temperature(1.0),Temperature(2.0)]);
+    }
+
+    #[test]
+    fn structural_change_test() {
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        world.register::<char>();
+        world.make_group(non_owning::<Temperature,char>());
+        let ids = (0..4).map(|i| {
+            world.create_entity()
+                .attach(Temperature(i as f32))
+                .attach('a')
+                .into_id()
+        }).collect::<Vec<_>>();
+
+        for next in world.query::<Next<Temperature>>() {
+            next.0 += 100.0;
+        }
+        // mid-frame: detach and attach
+        world.detach_component::<Temperature>(ids[1]);
+        let e = world.create_entity().attach(Temperature(7.0)).into_id();
+        world.attach_component(ids[2],Temperature(-1.0));
+
+        world.swap_buffers::<Temperature>();
+        let mut front = world.query::<&Temperature>()
+            .with_id()
+            .map(|(id,t)|(id,t.0))
+            .collect::<Vec<_>>();
+        front.sort_by_key(|(id,_)|*id);
+        assert_eq!(front,vec![(ids[0],100.0),(ids[2],-1.0),(ids[3],103.0),(e,7.0)]);
+        assert_eq!(world.query::<(&Temperature,&char)>().count(),3);
+    }
+
+    #[test]
+    #[should_panic(expected = "double-buffered")]
+    fn full_owning_rejection_test() {
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        world.register::<char>();
+        world.make_group(full_owning::<char,Temperature>());
+    }
+
+    #[test]
+    #[should_panic(expected = "double-buffered")]
+    fn partial_owning_rejection_test() {
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        world.register::<char>();
+        world.make_group(partial_owning::<Temperature,char>());
+    }
+
+    #[test]
+    fn from_id_test() {
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        let ids = (0..3).map(|i|world.create_entity().attach(Temperature(i as f32)).into_id())
+            .collect::<Vec<_>>();
+
+        let mut iter = world.query::<Next<Temperature>>();
+        let next = iter.from_id(ids[1]).unwrap();
+        // the same item is never yielded twice
+        assert!(iter.from_id(ids[1]).is_none());
+        let (id,_) = iter.next_with_id().unwrap();
+        assert_eq!(id,ids[0]);
+        assert!(iter.from_id(ids[0]).is_none());
+        assert_eq!(iter.len_hint(),Some(1));
+        // next skips the items yielded by from_id
+        assert_eq!(iter.next_with_id().map(|(id,_)|id),Some(ids[2]));
+        next.0 = 10.0;
+        drop(iter);
+        world.swap_buffers::<Temperature>();
+        assert_eq!(*world.get::<Temperature>(ids[1]).unwrap(),Temperature(10.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_double_buffered_test() {
+        let mut world = World::new();
+        world.register::<Temperature>();
+        world.query::<Next<Temperature>>().count();
+    }
+
+    fn diffuse(world : &World) {
+        for (now,neighbors,next) in world.query::<(&Temperature,&Neighbors,Next<Temperature>)>() {
+            let sum = neighbors.0.iter()
+                .map(|id|world.get::<Temperature>(*id).unwrap().0)
+                .sum::<f32>();
+            next.0 = now.0 + 0.25 * (sum - 2.0 * now.0);
+        }
+    }
+
+    fn maintain(world : &World) {
+        world.swap_buffers::<Temperature>();
+    }
+
+    #[test]
+    fn diffusion_test() {
+        const N : usize = 16;
+        let mut world = World::new();
+        world.register_double_buffered::<Temperature>();
+        world.register::<Neighbors>();
+        let ids = (0..N).map(|_|world.create_entity().into_id())
+            .collect::<Vec<_>>();
+        let initial = (0..N).map(|i|if i == 0 { 100.0 } else { 0.0 }).collect::<Vec<f32>>();
+        for (i,id) in ids.iter().enumerate() {
+            world.attach_component(*id,Temperature(initial[i]));
+            world.attach_component(*id,Neighbors([ids[(i + N - 1) % N],ids[(i + 1) % N]]));
+        }
+
+        let mut front = initial.clone();
+        let mut back = initial;
+        for _ in 0..20 {
+            diffuse(&world);
+            maintain(&world);
+
+            for i in 0..N {
+                let sum = front[(i + N - 1) % N] + front[(i + 1) % N];
+                back[i] = front[i] + 0.25 * (sum - 2.0 * front[i]);
+            }
+            std::mem::swap(&mut front,&mut back);
+        }
+
+        for (i,id) in ids.iter().enumerate() {
+            assert_eq!(world.get::<Temperature>(*id).unwrap().0,front[i]);
+        }
+        let total = world.query::<&Temperature>().map(|t|t.0).sum::<f32>();
+        assert!((total - 100.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,332 @@
+use std::any::TypeId;
+use crate::lock::{EntityManagerReadGuard, StorageReadGuard, StorageWriteGuard};
+use crate::{component::Component, entity::EntityId, error::QueryError, sparse_set::SparseSet, world::World};
+use super::{QueryGet, QueryIterator, Queryable, Taken};
+
+pub struct OptionRef<'a,T> {
+    index : usize,
+    world : &'a World,
+    // None if T is not registered
+    sparse_set : Option<*const SparseSet<EntityId,T>>,
+    #[allow(unused)]
+    borrow : Option<StorageReadGuard<'a>>,
+    // locked at the first iteration
+    entities : Option<EntityManagerReadGuard<'a>>
+}
+
+impl<'a,T : Component> Queryable<'a> for Option<&'a T> {
+    type Item = Self;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
+    /// ```T``` is not required to be registered
+    fn check(_ : &World) -> Result<(),QueryError> {
+        Ok(())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        // the storage is not borrowed if T is not registered
+        let borrow = world.raw_storage_read(TypeId::of::<T>());
+        let sparse_set = borrow.as_ref().map(|storage| {
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            unsafe {
+                storage.downcast_ref::<SparseSet<EntityId,T>>() as *const _
+            }
+        });
+        Box::new(OptionRef {
+            index : 0,
+            world,
+            sparse_set,
+            borrow,
+            entities : None
+        })
+    }
+}
+
+impl<'a,T : Component> OptionRef<'a,T> {
+    fn get(&self,id : EntityId) -> Option<&'a T> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &*self.sparse_set? };
+        sparse_set.get(id)
+    }
+}
+
+impl<'a,T : Component> Iterator for OptionRef<'a,T> {
+    type Item = Option<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.entities {
+            Some(entities) => {
+                let rem = entities.entities().len() - self.index;
+                (rem,Some(rem))
+            },
+            // all entities may be yielded,
+            // so it should be probed at last
+            None => (0,None)
+        }
+    }
+}
+
+impl<'a,T : Component> QueryIterator for OptionRef<'a,T> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let world = self.world;
+        let entities = self.entities.get_or_insert_with(|| world.entity_manager_read());
+        let id = *entities.entities().get(self.index)?;
+        self.index += 1;
+        Some((id,self.get(id)))
+    }
+}
+
+impl<'a,T : Component> QueryGet for OptionRef<'a,T> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        // entity always matches whether it has component or not
+        Some(self.get(id))
+    }
+}
+
+
+
+
+pub struct OptionMut<'a,T> {
+    index : usize,
+    world : &'a World,
+    // None if T is not registered
+    sparse_set : Option<*mut SparseSet<EntityId,T>>,
+    #[allow(unused)]
+    borrow : Option<StorageWriteGuard<'a>>,
+    // locked at the first iteration
+    entities : Option<EntityManagerReadGuard<'a>>,
+    // the indices of entities yielded by next or from_id
+    taken : Taken
+}
+
+impl<'a,T : Component> Queryable<'a> for Option<&'a mut T> {
+    type Item = Self;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        types.push(TypeId::of::<T>());
+    }
+
+    /// ```T``` is not required to be registered
+    fn check(_ : &World) -> Result<(),QueryError> {
+        Ok(())
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        // the storage is not borrowed if T is not registered
+        let mut borrow = world.raw_storage_write(TypeId::of::<T>());
+        let sparse_set = borrow.as_mut().map(|storage| {
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            unsafe {
+                storage.downcast_mut::<SparseSet<EntityId,T>>() as *mut _
+            }
+        });
+        Box::new(OptionMut {
+            index : 0,
+            world,
+            sparse_set,
+            borrow,
+            entities : None,
+            taken : Taken::default()
+        })
+    }
+}
+
+impl<'a,T : Component> OptionMut<'a,T> {
+    fn get_mut(&mut self,id : EntityId) -> Option<&'a mut T> {
+        // Safety:
+        // Safe here, because self.sparse_set is
+        // a pointer from borrow,
+        // This pointer is valid now.
+        let sparse_set = unsafe { &mut *self.sparse_set? };
+        sparse_set.get_mut(id)
+    }
+}
+
+impl<'a,T : Component> Iterator for OptionMut<'a,T> {
+    type Item = Option<&'a mut T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.entities {
+            Some(entities) => {
+                let rem = entities.entities().len() - self.index;
+                // some of them may be yielded by from_id
+                (rem.saturating_sub(self.taken.len()),Some(rem))
+            },
+            // all entities may be yielded,
+            // so it should be probed at last
+            None => (0,None)
+        }
+    }
+}
+
+impl<'a,T : Component> QueryIterator for OptionMut<'a,T> {
+    fn from_id(&mut self, id : EntityId) -> Option<Self::Item> {
+        // never yield the same item twice,
+        // otherwise there are two mutable references to it
+        if !self.taken.insert(id.index()) {
+            return None;
+        }
+        // entity always matches whether it has component or not
+        Some(self.get_mut(id))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        let world = self.world;
+        loop {
+            let entities = self.entities.get_or_insert_with(|| world.entity_manager_read());
+            let id = *entities.entities().get(self.index)?;
+            self.index += 1;
+            // the entities yielded by from_id are skipped
+            if self.taken.insert(id.index()) {
+                return Some((id,self.get_mut(id)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{query::{QueryIterator, WithId, Without}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Transform(f32);
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Velocity(f32);
+
+    #[test]
+    fn optional_test() {
+        let mut world = World::new();
+        world.register::<Transform>().register::<Velocity>();
+        let a = world.create_entity().attach(Transform(0.0)).attach(Velocity(2.0)).into_id();
+        let b = world.create_entity().attach(Transform(1.0)).into_id();
+        let c = world.create_entity().attach(Velocity(3.0)).into_id();
+
+        let items = world.query::<(&Transform,Option<&Velocity>)>()
+            .with_id()
+            .map(|(id,(t,v))|(id,*t,v.cloned()))
+            .collect::<Vec<_>>();
+        assert_eq!(items,vec![
+            (a,Transform(0.0),Some(Velocity(2.0))),
+            (b,Transform(1.0),None)
+        ]);
+
+        // apply friction only to entities that have velocity
+        for (_,v) in world.query::<(&Transform,Option<&mut Velocity>)>() {
+            if let Some(v) = v {
+                v.0 *= 0.5;
+            }
+        }
+        assert_eq!(*world.entity_component_read::<Velocity>(a).unwrap(),Velocity(1.0));
+        assert_eq!(*world.entity_component_read::<Velocity>(c).unwrap(),Velocity(3.0));
+
+        // optional component drives the iteration
+        let items = world.query::<(Option<&Transform>,&Velocity)>()
+            .with_id()
+            .map(|(id,(t,v))|(id,t.cloned(),*v))
+            .collect::<Vec<_>>();
+        assert_eq!(items,vec![
+            (a,Some(Transform(0.0)),Velocity(1.0)),
+            (c,None,Velocity(3.0))
+        ]);
+    }
+
+    #[test]
+    fn without_test() {
+        let mut world = World::new();
+        world.register::<Transform>()
+            .register::<Velocity>()
+            .register::<u32>();
+        let ids = (0..8).map(|i| {
+            let id = world.create_entity().attach(Transform(i as f32)).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,Velocity(i as f32));
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,i as u32);
+            }
+            id
+        }).collect::<Vec<_>>();
+        let expected = (0..8)
+            .filter(|i|i % 3 != 0)
+            .map(|i|(ids[i],Transform(i as f32),Some(Velocity(i as f32)).filter(|_|i % 2 == 0)))
+            .collect::<Vec<_>>();
+
+        let items = world.query::<((&Transform,Option<&Velocity>),Without<&u32>)>()
+            .with_id()
+            .map(|(id,(t,v))|(id,*t,v.cloned()))
+            .collect::<Vec<_>>();
+        assert_eq!(items,expected);
+
+        let items = world.query::<(Option<&Velocity>,(&Transform,Without<&u32>))>()
+            .with_id()
+            .map(|(id,(v,t))|(id,*t,v.cloned()))
+            .collect::<Vec<_>>();
+        assert_eq!(items,expected);
+
+        let mut iter = world.query::<(&Transform,(Option<&Velocity>,Without<&u32>))>();
+        assert_eq!(iter.from_id(ids[1]).map(|(_,v)|v.cloned()),Some(None));
+        assert_eq!(iter.from_id(ids[2]).map(|(_,v)|v.cloned()),Some(Some(Velocity(2.0))));
+        assert!(iter.from_id(ids[3]).is_none());
+        assert_eq!(iter.count(),expected.len());
+    }
+
+    #[test]
+    fn from_id_mut_test() {
+        let mut world = World::new();
+        world.register::<Velocity>();
+        let a = world.create_entity().attach(Velocity(1.0)).into_id();
+        let b = world.create_entity().into_id();
+        let c = world.create_entity().attach(Velocity(3.0)).into_id();
+
+        let mut iter = world.query::<Option<&mut Velocity>>();
+        let first = iter.from_id(c).unwrap().unwrap();
+        // the same item is never yielded twice
+        assert!(iter.from_id(c).is_none());
+        assert_eq!(iter.from_id(b).map(|v|v.is_none()),Some(true));
+        assert!(iter.from_id(b).is_none());
+        let (id,_) = iter.next_with_id().unwrap();
+        assert_eq!(id,a);
+        assert!(iter.from_id(a).is_none());
+        // next skips the items yielded by from_id
+        assert!(iter.next().is_none());
+        first.0 += 1.0;
+        drop(iter);
+        assert_eq!(*world.get::<Velocity>(c).unwrap(),Velocity(4.0));
+    }
+
+    #[test]
+    fn unregistered_test() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.create_entity().attach(Transform(0.0));
+        world.create_entity();
+
+        assert_eq!(world.query::<Option<&Velocity>>().count(),2);
+        assert!(world.query::<(&Transform,Option<&mut Velocity>)>()
+            .all(|(_,v)|v.is_none()));
+        let (_,epoch) = world.query_with_epoch::<(&Transform,Option<&Velocity>)>();
+        assert!(world.check_epoch(&epoch).is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+use std::{any::TypeId, collections::HashSet, marker::PhantomData};
+use crate::{entity::EntityId, error::QueryError, world::World};
+use super::{QueryGet, QueryIterator, Queryable};
+
+/// Match the entities matched by ```A```,```B``` or both.
+/// # Details
+/// ```Or<A,B>``` iterates ```A``` first,then the entities in ```B``` but not in ```A```,
+/// so an entity matched by both is yielded only once as [OrItem::Both].
+/// It can be composed like ```(Or<&A,&B>,&C)```.
+/// # Example
+/// ```
+/// use xecs::{World, query::{Or, OrItem}};
+/// #[derive(Debug,PartialEq)]
+/// struct Poisoned(u32);
+/// #[derive(Debug,PartialEq)]
+/// struct Burning(u32);
+///
+/// let mut world = World::new();
+/// world.register::<Poisoned>().register::<Burning>();
+/// world.create_entity().attach(Poisoned(1));
+/// world.create_entity().attach(Poisoned(2)).attach(Burning(3));
+/// world.create_entity().attach(Burning(4));
+/// world.create_entity();
+///
+/// let damage = world.query::<Or<&Poisoned,&Burning>>()
+///     .map(|item| match item {
+///         OrItem::Left(p) => p.0,
+///         OrItem::Right(b) => b.0,
+///         OrItem::Both(p,b) => p.0 + b.0
+///     })
+///     .collect::<Vec<_>>();
+/// assert_eq!(damage,vec![1,5,4]);
+/// ```
+pub struct Or<A,B>{
+    _marker_a : PhantomData<A>,
+    _marker_b : PhantomData<B>
+}
+
+/// The item of [Or]
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum OrItem<A,B> {
+    /// Only matched by ```A```
+    Left(A),
+    /// Only matched by ```B```
+    Right(B),
+    /// Matched by both
+    Both(A,B)
+}
+
+impl<A,B> OrItem<A,B> {
+    /// Get the item of ```A``` if it's matched
+    pub fn left(self) -> Option<A> {
+        match self {
+            OrItem::Left(a) | OrItem::Both(a,_) => Some(a),
+            OrItem::Right(_) => None
+        }
+    }
+
+    /// Get the item of ```B``` if it's matched
+    pub fn right(self) -> Option<B> {
+        match self {
+            OrItem::Right(b) | OrItem::Both(_,b) => Some(b),
+            OrItem::Left(_) => None
+        }
+    }
+}
+
+impl<'a,A : 'a + Queryable<'a>,B : 'a + Queryable<'a>> Queryable<'a> for Or<A,B> {
+    type Item = OrItem<<A as Queryable<'a>>::Item,<B as Queryable<'a>>::Item>;
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let iter_a = world.query::<A>();
+        let iter_b = world.query::<B>();
+        Box::new(OrIter{
+            iter_a,
+            iter_b,
+            visited : HashSet::new()
+        })
+    }
+}
+
+pub struct OrIter<A,B>{
+    iter_a : A,
+    iter_b : B,
+    // the IDs yielded from A,they are skipped when iterating B
+    visited : HashSet<EntityId>
+}
+
+impl<A : QueryIterator,B : QueryIterator> Iterator for OrIter<A,B> {
+    type Item = OrItem<A::Item,B::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id().map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower_a,upper_a) = self.iter_a.size_hint();
+        let upper_b = self.iter_b.size_hint().1;
+        let upper = upper_a.zip(upper_b).map(|(a,b)|a + b);
+        (lower_a,upper)
+    }
+}
+
+impl<A : QueryIterator,B : QueryIterator> QueryIterator for OrIter<A,B> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        match (self.iter_a.from_id(id),self.iter_b.from_id(id)) {
+            (Some(a),Some(b)) => Some(OrItem::Both(a,b)),
+            (Some(a),None) => Some(OrItem::Left(a)),
+            (None,Some(b)) => Some(OrItem::Right(b)),
+            (None,None) => None
+        }
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if let Some((id,a)) = self.iter_a.next_with_id() {
+            self.visited.insert(id);
+            let item = match self.iter_b.from_id(id) {
+                Some(b) => OrItem::Both(a,b),
+                None => OrItem::Left(a)
+            };
+            return Some((id,item));
+        }
+        while let Some((id,b)) = self.iter_b.next_with_id() {
+            if !self.visited.contains(&id) {
+                return Some((id,OrItem::Right(b)));
+            }
+        }
+        None
+    }
+}
+
+impl<A : QueryGet,B : QueryGet> QueryGet for OrIter<A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        match (self.iter_a.peek_from_id(id),self.iter_b.peek_from_id(id)) {
+            (Some(a),Some(b)) => Some(OrItem::Both(a,b)),
+            (Some(a),None) => Some(OrItem::Left(a)),
+            (None,Some(b)) => Some(OrItem::Right(b)),
+            (None,None) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, FullOwning}, query::{QueryIterator, WithId, Without}, world::World};
+    use super::{Or, OrItem};
+
+    fn world() -> (World,Vec<EntityId>) {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        let ids = (0..12_u32).map(|i| {
+            let id = world.create_entity().into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,i);
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,'a');
+            }
+            if i % 4 != 0 {
+                world.attach_component(id,i as u64);
+            }
+            id
+        }).collect::<Vec<_>>();
+        (world,ids)
+    }
+
+    #[test]
+    fn or_test() {
+        let (world,ids) = world();
+        let mut items = world.query::<Or<&u32,&char>>()
+            .with_id()
+            .map(|(id,item)|(id,item.left().copied(),item.right().copied()))
+            .collect::<Vec<_>>();
+        items.sort();
+        let expected = (0..12_u32)
+            .filter(|i|i % 2 == 0 || i % 3 == 0)
+            .map(|i|(ids[i as usize],Some(i).filter(|i|i % 2 == 0),Some('a').filter(|_|i % 3 == 0)))
+            .collect::<Vec<_>>();
+        assert_eq!(items,expected);
+
+        let mut iter = world.query::<Or<&u32,&char>>();
+        assert_eq!(iter.from_id(ids[6]),Some(OrItem::Both(&6,&'a')));
+        assert_eq!(iter.from_id(ids[3]),Some(OrItem::Right(&'a')));
+        assert_eq!(iter.from_id(ids[1]),None);
+        assert_eq!(iter.count(),8);
+    }
+
+    #[test]
+    fn compose_test() {
+        let (mut world,ids) = world();
+        for (item,x) in world.query::<(Or<&mut u32,&char>,&mut u64)>() {
+            if let Some(i) = item.left() {
+                *i += 100;
+            }
+            *x += 1000;
+        }
+        let mut ids_with = world.query::<(Or<&u32,&char>,&u64)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        ids_with.sort();
+        assert_eq!(ids_with,vec![ids[2],ids[3],ids[6],ids[9],ids[10]]);
+        assert_eq!(world.get::<u32>(ids[2]).as_deref(),Some(&102));
+        assert_eq!(world.get::<u32>(ids[4]).as_deref(),Some(&4));
+        assert_eq!(world.get::<u64>(ids[9]).as_deref(),Some(&1009));
+        assert_eq!(world.get::<u64>(ids[1]).as_deref(),Some(&1));
+
+        assert_eq!(world.query::<(Or<&u32,&char>,Without<&u64>)>().count(),3);
+
+        world.make_group(full_owning::<u32,u64>());
+        let mut items = world.query::<Or<FullOwning<&u32,&u64>,&char>>()
+            .map(|item|item.left().map(|(i,_)|*i))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,vec![None,None,None,Some(102),Some(106),Some(110)]);
+    }
+}
@@ -0,0 +1,351 @@
+use rayon::{iter::{Copied, IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator, Zip}, slice};
+use crate::{component::Component, entity::EntityId, group::{full_owning, FullOwning, NonOwning, PartialOwning}, sparse_set::SparseSet, world::World};
+use crate::lock::{GroupReadGuard, StorageReadGuard, StorageWriteGuard};
+use super::{QueryIterator, Queryable};
+
+/// Something can be queried in parallel.
+/// # Details
+/// * ```&T``` and ```&mut T``` iterate the data of storage directly.
+/// * [FullOwning<&A,&B>](crate::group::FullOwning) and ```FullOwning<&mut A,&mut B>```
+///   iterate the packed part of group directly.
+/// * ```(&A,&B)```,[PartialOwning<&A,&B>](crate::group::PartialOwning) and
+///   [NonOwning<&A,&B>](crate::group::NonOwning) are not aligned,
+///   their items are collected sequentially before iterating in parallel.
+pub trait ParQueryable<'a> {
+    /// The locks and data held by [ParQuery]
+    type Borrow;
+    /// The parallel iterator
+    type Iter<'b> : ParallelIterator where Self : 'b, 'a : 'b;
+
+    /// Lock the storages
+    fn borrow(world : &'a World) -> Self::Borrow;
+
+    /// Get the parallel iterator from the borrow
+    fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b;
+}
+
+/// The result of [par_query](crate::world::World::par_query).
+/// # Details
+/// It holds the locks of storages,
+/// use [par_iter](ParQuery::par_iter) to iterate in parallel.
+pub struct ParQuery<'a,Q : ParQueryable<'a>> {
+    borrow : Q::Borrow
+}
+
+impl<'a,Q : ParQueryable<'a>> ParQuery<'a,Q> {
+    pub(in crate) fn new(world : &'a World) -> Self {
+        ParQuery {
+            borrow : Q::borrow(world)
+        }
+    }
+
+    /// Get the parallel iterator of items
+    pub fn par_iter(&mut self) -> Q::Iter<'_> {
+        Q::par_iter(&mut self.borrow)
+    }
+}
+
+fn sparse_set_of<'b,T : Component>(storage : &'b StorageReadGuard<'_>) -> &'b SparseSet<EntityId,T> {
+    // Safety:
+    // storage is SparseSet<EntityId,T>
+    unsafe {
+        storage.downcast_ref::<SparseSet<EntityId,T>>()
+    }
+}
+
+fn sparse_set_mut_of<'b,T : Component>(storage : &'b mut StorageWriteGuard<'_>) -> &'b mut SparseSet<EntityId,T> {
+    // Safety:
+    // storage is SparseSet<EntityId,T>
+    unsafe {
+        storage.downcast_mut::<SparseSet<EntityId,T>>()
+    }
+}
+
+impl<'a,T : Component> ParQueryable<'a> for &'a T {
+    type Borrow = StorageReadGuard<'a>;
+    type Iter<'b> = slice::Iter<'b,T> where Self : 'b, 'a : 'b;
+
+    fn borrow(world : &'a World) -> Self::Borrow {
+        world.raw_storage_read(std::any::TypeId::of::<T>())
+            .expect("ParQueryable for &'a T: Component was not registered in world")
+    }
+
+    fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b {
+        sparse_set_of::<T>(borrow).data().par_iter()
+    }
+}
+
+impl<'a,A : Component,B : Component> ParQueryable<'a> for FullOwning<&'a A,&'a B> {
+    type Borrow = (StorageReadGuard<'a>,StorageReadGuard<'a>,GroupReadGuard<'a>);
+    type Iter<'b> = Zip<slice::Iter<'b,A>,slice::Iter<'b,B>> where Self : 'b, 'a : 'b;
+
+    fn borrow(world : &'a World) -> Self::Borrow {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>(),
+                "ParQueryable for FullOwning: Component was not registered in world");
+        let group = world.lock_group(full_owning::<A,B>(),"ParQueryable for FullOwning: Group is not in world");
+        // Unwrap here
+        // assert before ensures this
+        let storage_a = world.raw_storage_read(std::any::TypeId::of::<A>()).unwrap();
+        let storage_b = world.raw_storage_read(std::any::TypeId::of::<B>()).unwrap();
+        (storage_a,storage_b,group)
+    }
+
+    fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b {
+        let (storage_a,storage_b,group) = borrow;
+        let length = group.len();
+        // the first length components of A and B are aligned
+        let data_a = &sparse_set_of::<A>(storage_a).data()[..length];
+        let data_b = &sparse_set_of::<B>(storage_b).data()[..length];
+        data_a.par_iter().zip(data_b.par_iter())
+    }
+}
+
+impl<'a,T : Component> ParQueryable<'a> for &'a mut T {
+    type Borrow = StorageWriteGuard<'a>;
+    type Iter<'b> = slice::IterMut<'b,T> where Self : 'b, 'a : 'b;
+
+    fn borrow(world : &'a World) -> Self::Borrow {
+        let mut storage = world.raw_storage_write(std::any::TypeId::of::<T>())
+            .expect("ParQueryable for &'a mut T: Component was not registered in world");
+        // all items can be changed by the parallel iterator
+        let sparse_set = sparse_set_mut_of::<T>(&mut storage);
+        sparse_set.mark_changed_until(sparse_set.len(),world.change_tick());
+        storage
+    }
+
+    fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b {
+        sparse_set_mut_of::<T>(borrow).data_mut().par_iter_mut()
+    }
+}
+
+impl<'a,A : Component,B : Component> ParQueryable<'a> for FullOwning<&'a mut A,&'a mut B> {
+    type Borrow = (StorageWriteGuard<'a>,StorageWriteGuard<'a>,GroupReadGuard<'a>);
+    type Iter<'b> = Zip<slice::IterMut<'b,A>,slice::IterMut<'b,B>> where Self : 'b, 'a : 'b;
+
+    fn borrow(world : &'a World) -> Self::Borrow {
+        assert!(world.has_registered::<A>() && world.has_registered::<B>(),
+                "ParQueryable for FullOwning: Component was not registered in world");
+        let group = world.lock_group(full_owning::<A,B>(),"ParQueryable for FullOwning: Group is not in world");
+        let length = group.len();
+        let tick = world.change_tick();
+        // Unwrap here
+        // assert before ensures this
+        let mut storage_a = world.raw_storage_write(std::any::TypeId::of::<A>()).unwrap();
+        let mut storage_b = world.raw_storage_write(std::any::TypeId::of::<B>()).unwrap();
+        // all items in group can be changed by the parallel iterator
+        sparse_set_mut_of::<A>(&mut storage_a).mark_changed_until(length,tick);
+        sparse_set_mut_of::<B>(&mut storage_b).mark_changed_until(length,tick);
+        (storage_a,storage_b,group)
+    }
+
+    fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b {
+        let (storage_a,storage_b,group) = borrow;
+        let length = group.len();
+        // the first length components of A and B are aligned
+        let (data_a,_) = sparse_set_mut_of::<A>(storage_a).data_mut().split_at_mut(length);
+        let (data_b,_) = sparse_set_mut_of::<B>(storage_b).data_mut().split_at_mut(length);
+        data_a.par_iter_mut().zip(data_b.par_iter_mut())
+    }
+}
+
+/// The items collected from a sequential query,
+/// the query is kept to hold the locks
+pub struct Collected<'a,T> {
+    #[allow(unused)]
+    iter : Box<dyn QueryIterator<Item = T> + 'a>,
+    items : Vec<T>
+}
+
+impl<'a,T> Collected<'a,T> {
+    fn new<Q : Queryable<'a,Item = T>>(world : &'a World) -> Self {
+        let mut iter = world.query::<Q>();
+        let items = iter.by_ref().collect();
+        Collected {
+            iter,
+            items
+        }
+    }
+}
+
+macro_rules! impl_collected_par_queryable {
+    ($($query:tt)*) => {
+        impl<'a,A : Component,B : Component> ParQueryable<'a> for $($query)* {
+            type Borrow = Collected<'a,(&'a A,&'a B)>;
+            type Iter<'b> = Copied<slice::Iter<'b,(&'a A,&'a B)>> where Self : 'b, 'a : 'b;
+
+            fn borrow(world : &'a World) -> Self::Borrow {
+                Collected::new::<Self>(world)
+            }
+
+            fn par_iter<'b>(borrow : &'b mut Self::Borrow) -> Self::Iter<'b> where 'a : 'b {
+                borrow.items.par_iter().copied()
+            }
+        }
+    };
+}
+
+impl_collected_par_queryable!((&'a A,&'a B));
+impl_collected_par_queryable!(PartialOwning<&'a A,&'a B>);
+impl_collected_par_queryable!(NonOwning<&'a A,&'a B>);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use rayon::iter::ParallelIterator;
+    use crate::{group::{full_owning, non_owning, partial_owning, FullOwning, NonOwning, PartialOwning}, query::Changed, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Position(f64);
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Velocity(f64);
+
+    fn world(count : u32) -> World {
+        let mut world = World::new();
+        world.register::<Position>()
+            .register::<Velocity>()
+            .register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>();
+        for i in 0..count {
+            let id = world.create_entity().attach(Position(i as f64)).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,Velocity(1.0));
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,i);
+                world.attach_component(id,'a');
+                world.attach_component(id,i as u64);
+                world.attach_component(id,(i % 100) as i8);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn par_query_test() {
+        let mut world = world(1000);
+        let sum = world.par_query::<&Position>()
+            .par_iter()
+            .map(|p|p.0)
+            .sum::<f64>();
+        assert_eq!(sum,world.query::<&Position>().map(|p|p.0).sum::<f64>());
+
+        let mut query = world.par_query::<(&Position,&Velocity)>();
+        assert_eq!(query.par_iter().count(),500);
+        let sum = query.par_iter().map(|(p,v)|p.0 * v.0).sum::<f64>();
+        assert_eq!(sum,world.query::<(&Position,&Velocity)>().map(|(p,v)|p.0 * v.0).sum::<f64>());
+        drop(query);
+
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<Position,Velocity>());
+        let sum = world.par_query::<FullOwning<&u32,&char>>()
+            .par_iter()
+            .map(|(x,_)|*x as u64)
+            .sum::<u64>();
+        assert_eq!(sum,(0..1000).filter(|i|i % 3 == 0).sum::<u64>());
+        assert_eq!(world.par_query::<PartialOwning<&u64,&i8>>().par_iter().count(),334);
+        assert_eq!(world.par_query::<NonOwning<&Position,&Velocity>>().par_iter().count(),500);
+
+        // reading is shared
+        let mut query = world.par_query::<&Position>();
+        assert_eq!(world.query::<&Position>().count(),query.par_iter().count());
+    }
+
+    #[test]
+    fn par_query_mut_test() {
+        let mut world = world(1000);
+        world.par_query::<&mut Position>()
+            .par_iter()
+            .for_each(|p|p.0 *= 2.0);
+        assert_eq!(world.query::<&Position>().map(|p|p.0).sum::<f64>(),(0..1000).map(|i|i as f64 * 2.0).sum::<f64>());
+
+        world.make_group(full_owning::<Position,Velocity>());
+        world.make_group(full_owning::<u32,char>());
+        // forget the changes of the first pass
+        world.tick();
+        world.tick();
+        world.par_query::<FullOwning<&mut Position,&mut Velocity>>()
+            .par_iter()
+            .for_each(|(p,v)|{
+                p.0 += v.0;
+                v.0 = 0.0;
+            });
+        world.par_query::<FullOwning<&mut u32,&mut char>>()
+            .par_iter()
+            .for_each(|(_,c)|*c = 'b');
+        let mut expected = (0..1000).map(|i|i as f64 * 2.0 + if i % 2 == 0 { 1.0 } else { 0.0 }).collect::<Vec<_>>();
+        let mut positions = world.query::<&Position>().map(|p|p.0).collect::<Vec<_>>();
+        expected.sort_by(f64::total_cmp);
+        positions.sort_by(f64::total_cmp);
+        assert_eq!(positions,expected);
+        assert!(world.query::<&Velocity>().all(|v|v.0 == 0.0));
+        assert!(world.query::<&char>().all(|c|*c == 'b'));
+        // only the items in group are changed
+        assert_eq!(world.query::<Changed<Position>>().count(),500);
+    }
+
+    #[test]
+    #[ignore]
+    fn par_sum_bench_test() {
+        let mut world = world(1_000_000);
+        world.make_group(full_owning::<Position,Velocity>());
+
+        let start = Instant::now();
+        let seq = world.query::<&Position>().map(|p|p.0).sum::<f64>();
+        let seq_time = start.elapsed();
+
+        let start = Instant::now();
+        let par = world.par_query::<&Position>()
+            .par_iter()
+            .map(|p|p.0)
+            .sum::<f64>();
+        let par_time = start.elapsed();
+        assert_eq!(par,seq);
+        println!("&T sequential:{:?} parallel:{:?}",seq_time,par_time);
+
+        let start = Instant::now();
+        for (p,v) in world.query::<FullOwning<&mut Position,&mut Velocity>>() {
+            v.0 += p.0;
+        }
+        let seq_time = start.elapsed();
+        let start = Instant::now();
+        world.par_query::<FullOwning<&mut Position,&mut Velocity>>()
+            .par_iter()
+            .for_each(|(p,v)|v.0 += p.0);
+        let par_time = start.elapsed();
+        let seq = world.query::<&Velocity>().map(|v|v.0).sum::<f64>();
+        let par = world.par_query::<&mut Velocity>()
+            .par_iter()
+            .map(|v|v.0)
+            .sum::<f64>();
+        assert_eq!(par,seq);
+        println!("FullOwning<&mut A,&mut B> sequential:{:?} parallel:{:?}",seq_time,par_time);
+    }
+
+    #[test]
+    #[ignore]
+    fn par_throughput_test() {
+        let mut world = world(1_000_000);
+        world.make_group(full_owning::<Position,Velocity>());
+        let work = |p : &Position,v : &Velocity| (0..64).fold(p.0,|acc,_|(acc * v.0).sin());
+
+        let start = Instant::now();
+        let seq = world.query::<FullOwning<&Position,&Velocity>>()
+            .map(|(p,v)|work(p,v))
+            .sum::<f64>();
+        let seq_time = start.elapsed();
+
+        let start = Instant::now();
+        let par = world.par_query::<FullOwning<&Position,&Velocity>>()
+            .par_iter()
+            .map(|(p,v)|work(p,v))
+            .sum::<f64>();
+        let par_time = start.elapsed();
+
+        assert!((seq - par).abs() < 1e-6 * seq.abs().max(1.0));
+        println!("sequential:{:?} parallel:{:?}",seq_time,par_time);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::entity::EntityId;
+
+/// Get the word and mask of entity ```index``` in a presence bitmap
+pub(in crate) fn bit_of(index : usize) -> (usize,u64) {
+    (index / 64,1 << (index % 64))
+}
+
+/// The entity indices computed from presence bitmaps.
+/// # Details
+/// It holds the combined bitmap and yields the set bits in ascending order.
+/// The full IDs are restored by [entity_of](crate::query::QueryIterator::entity_of).
+pub(in crate) struct PresenceBits {
+    bitmap : Vec<u64>,
+    // the word of next bit
+    word : usize,
+    // the unvisited bits of current word
+    bits : u64,
+    remaining : usize
+}
+
+impl PresenceBits {
+    fn new(bitmap : Vec<u64>) -> Self {
+        let remaining = bitmap.iter()
+            .map(|word|word.count_ones() as usize)
+            .sum();
+        let bits = bitmap.first().cloned().unwrap_or(0);
+        PresenceBits {
+            bitmap,
+            word : 0,
+            bits,
+            remaining
+        }
+    }
+
+    /// Intersect all ```bitmaps``` word by word.
+    /// Return ```None``` if any of them is ```None```.
+    pub(in crate) fn and(bitmaps : &[Option<&[u64]>]) -> Option<Self> {
+        let bitmaps = bitmaps.iter()
+            .cloned()
+            .collect::<Option<Vec<_>>>()?;
+        // the words beyond the shortest bitmap are all zero
+        let len = bitmaps.iter()
+            .map(|bitmap|bitmap.len())
+            .min()
+            .unwrap_or(0);
+        let bitmap = (0..len)
+            .map(|word| bitmaps.iter().fold(u64::MAX,|acc,bitmap|acc & bitmap[word]))
+            .collect();
+        Some(PresenceBits::new(bitmap))
+    }
+
+    /// Get the IDs in ```a``` but not in ```b```.
+    /// Return ```None``` if any of them is ```None```.
+    pub(in crate) fn and_not(a : Option<&[u64]>,b : Option<&[u64]>) -> Option<Self> {
+        let (a,b) = (a?,b?);
+        let bitmap = a.iter()
+            .enumerate()
+            .map(|(word,bits)|bits & !b.get(word).cloned().unwrap_or(0))
+            .collect();
+        Some(PresenceBits::new(bitmap))
+    }
+
+    /// Get the whole bitmap,including the yielded IDs
+    pub(in crate) fn bitmap(&self) -> &[u64] {
+        &self.bitmap
+    }
+
+    /// Check if the index of ```id``` is in the whole bitmap
+    pub(in crate) fn contains(&self,id : EntityId) -> bool {
+        let (word,mask) = bit_of(id.index());
+        self.bitmap.get(word).map(|bits|bits & mask != 0).unwrap_or(false)
+    }
+
+    /// Get the count of remaining IDs
+    pub(in crate) fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl Iterator for PresenceBits {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bits == 0 {
+            self.word += 1;
+            self.bits = *self.bitmap.get(self.word)?;
+        }
+        let bit = self.bits.trailing_zeros() as usize;
+        // clear the lowest set bit
+        self.bits &= self.bits - 1;
+        self.remaining -= 1;
+        Some(self.word * 64 + bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining,Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, time::Instant};
+    use crate::{component::Component, entity::EntityId, query::{QueryIterator, WithId, Without}, sparse_set::SparseSet, world::World};
+    use super::PresenceBits;
+
+    // xorshift,so the worlds are reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn chance(&mut self,percent : u64) -> bool {
+            self.next() % 100 < percent
+        }
+    }
+
+    fn world(bitmap : bool) -> World {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<u64>()
+            .register::<char>();
+        if bitmap {
+            world.enable_presence_bitmap::<u32>()
+                .enable_presence_bitmap::<u64>()
+                .enable_presence_bitmap::<char>();
+        }
+        world
+    }
+
+    // Build the same random world twice,with and without bitmaps
+    fn random_worlds(seed : u64,count : usize,density : [u64;3]) -> (World,World) {
+        let probe = world(false);
+        let bitmap = world(true);
+        for world in [&probe,&bitmap] {
+            let mut rng = Rng(seed);
+            let ids = (0..count).map(|i| {
+                let id = world.create_entity().into_id();
+                if rng.chance(density[0]) { world.attach_component(id,i as u32); }
+                if rng.chance(density[1]) { world.attach_component(id,i as u64); }
+                if rng.chance(density[2]) { world.attach_component(id,'a'); }
+                id
+            }).collect::<Vec<_>>();
+            for id in ids {
+                match rng.next() % 10 {
+                    0 => { world.remove_entity(id); },
+                    1 => { world.remove_component::<u64>(id); },
+                    _ => {}
+                }
+            }
+        }
+        (probe,bitmap)
+    }
+
+    fn sorted_ids<I : QueryIterator>(iter : I) -> Vec<EntityId> {
+        let mut ids = iter.with_id().map(|(id,_)|id).collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    fn assert_synced<T : Component>(world : &World) {
+        let storage = world.raw_storage_read(TypeId::of::<T>()).unwrap();
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe { storage.downcast_ref::<SparseSet<EntityId,T>>() };
+        let bitmap = sparse_set.presence().unwrap();
+        let count = bitmap.iter().map(|word|word.count_ones() as usize).sum::<usize>();
+        assert_eq!(count,sparse_set.len());
+        for id in sparse_set.entities() {
+            let (word,mask) = super::bit_of(id.index());
+            assert!(bitmap[word] & mask != 0);
+        }
+    }
+
+    fn bitmap(ids : &[usize]) -> Vec<u64> {
+        let mut bitmap = vec![];
+        for id in ids {
+            let (word,mask) = super::bit_of(*id);
+            if bitmap.len() <= word {
+                bitmap.resize(word + 1,0);
+            }
+            bitmap[word] |= mask;
+        }
+        bitmap
+    }
+
+    fn ids(bits : PresenceBits) -> Vec<usize> {
+        bits.collect()
+    }
+
+    #[test]
+    fn bits_test() {
+        let a = bitmap(&[1,5,63,64,65,200,1000]);
+        let b = bitmap(&[5,63,65,130,200]);
+        let c = bitmap(&[5,65,200,2000]);
+
+        let bits = PresenceBits::and(&[Some(&a),Some(&b),Some(&c)]).unwrap();
+        assert_eq!(bits.len(),3);
+        assert!(bits.contains(EntityId::new(65).unwrap()));
+        assert!(!bits.contains(EntityId::new(63).unwrap()));
+        assert!(!bits.contains(EntityId::new(5000).unwrap()));
+        assert_eq!(ids(bits),vec![5,65,200]);
+        assert!(PresenceBits::and(&[Some(&a),None]).is_none());
+
+        let bits = PresenceBits::and_not(Some(&a),Some(&c)).unwrap();
+        assert_eq!(bits.size_hint(),(4,Some(4)));
+        assert_eq!(ids(bits),vec![1,63,64,1000]);
+        assert_eq!(ids(PresenceBits::and_not(Some(&c),Some(&a)).unwrap()),vec![2000]);
+        assert!(PresenceBits::and_not(None,Some(&a)).is_none());
+
+        assert_eq!(ids(PresenceBits::and(&[Some(&[]),Some(&a)]).unwrap()),Vec::<usize>::new());
+        assert_eq!(ids(PresenceBits::and(&[Some(&[0,0,4])]).unwrap()),vec![130]);
+    }
+
+    #[test]
+    fn differential_test() {
+        for (seed,density) in [(1,[50,50,50]),(2,[90,10,90]),(3,[5,5,5]),(4,[100,100,1]),(5,[0,50,50])] {
+            let (probe,bitmap) = random_worlds(seed,2000,density);
+
+            let iter = bitmap.query::<(&u32,&u64,&char)>();
+            assert!(iter.presence().is_some());
+            assert_eq!(iter.len_hint(),Some(probe.query::<(&u32,&u64,&char)>().count()));
+            assert_eq!(sorted_ids(iter),sorted_ids(probe.query::<(&u32,&u64,&char)>()));
+
+            assert_eq!(sorted_ids(bitmap.query::<(&u32,Without<&u64>)>()),
+                       sorted_ids(probe.query::<(&u32,Without<&u64>)>()));
+            assert_eq!(sorted_ids(bitmap.query::<(Without<&char>,&u64)>()),
+                       sorted_ids(probe.query::<(Without<&char>,&u64)>()));
+            assert_eq!(sorted_ids(bitmap.query::<((&u32,&char),Without<&u64>)>()),
+                       sorted_ids(probe.query::<((&u32,&char),Without<&u64>)>()));
+            assert_eq!(sorted_ids(bitmap.query::<(&mut u32,(&u64,&char))>()),
+                       sorted_ids(probe.query::<(&mut u32,(&u64,&char))>()));
+
+            // from_id agrees with the probe-based path
+            let mut with_bitmap = bitmap.query::<((&u32,&u64),Without<&char>)>();
+            let mut without_bitmap = probe.query::<((&u32,&u64),Without<&char>)>();
+            for id in (1..=2000).filter_map(EntityId::new) {
+                let expected = without_bitmap.from_id(id).map(|(a,b)|(*a,*b));
+                assert_eq!(with_bitmap.from_id(id).map(|(a,b)|(*a,*b)),expected);
+            }
+        }
+    }
+
+    #[test]
+    fn sync_test() {
+        let world = world(true);
+        let ids = world.create_entities(200)
+            .attach((0..200).collect::<Vec<u32>>())
+            .attach(vec!['a';200])
+            .into_ids();
+        assert_synced::<u32>(&world);
+        assert_synced::<char>(&world);
+        for id in ids.start.index()..ids.end.index() {
+            let id = EntityId::new(id).unwrap();
+            match id.index() % 4 {
+                0 => { world.remove_entity(id); },
+                1 => { world.remove_component::<char>(id); },
+                2 => { world.attach_component(id,id.index() as u64); },
+                _ => {}
+            }
+        }
+        assert_synced::<u32>(&world);
+        assert_synced::<u64>(&world);
+        assert_synced::<char>(&world);
+        assert_eq!(world.query::<(&u32,&char)>().count(),100);
+        // the IDs are reused
+        world.create_entity().attach(1_u32).attach('b');
+        assert_synced::<u32>(&world);
+        assert_eq!(world.query::<(&u32,&char)>().count(),101);
+
+        let mut sparse_set = SparseSet::<EntityId,u32>::new();
+        sparse_set.add(EntityId::new(3).unwrap(),3);
+        sparse_set.enable_presence();
+        sparse_set.add(EntityId::new(100).unwrap(),100);
+        assert_eq!(sparse_set.presence().unwrap(),&[1 << 3,1 << 36]);
+        sparse_set.clear();
+        assert!(sparse_set.presence().unwrap().iter().all(|word|*word == 0));
+        sparse_set.add(EntityId::new(65).unwrap(),65);
+        assert_eq!(sparse_set.presence().unwrap(),&[0,2]);
+
+        assert!(world.presence_bitmap_bytes::<u32>().unwrap() >= 200 / 8);
+        assert_eq!(World::new().register::<u32>().presence_bitmap_bytes::<u32>(),None);
+    }
+
+    #[test]
+    #[ignore]
+    fn intersection_bench_test() {
+        let build = |bitmap : bool| {
+            let world = world(bitmap);
+            let count = 1_000_000;
+            world.create_entities(count).attach((0..count as u32).collect::<Vec<_>>());
+            // 1% of entities have all three components
+            for id in (1..=count).step_by(10) {
+                let id = EntityId::new(id).unwrap();
+                world.attach_component(id,0_u64);
+                if id.index() % 100 == 1 {
+                    world.attach_component(id,'a');
+                }
+            }
+            for id in (6..=count).step_by(10) {
+                world.attach_component(EntityId::new(id).unwrap(),'b');
+            }
+            world
+        };
+        let probe = build(false);
+        let bitmap = build(true);
+
+        let start = Instant::now();
+        let expected = probe.query::<(&u32,&u64,&char)>().count();
+        let probe_time = start.elapsed();
+        let start = Instant::now();
+        let count = bitmap.query::<(&u32,&u64,&char)>().count();
+        let bitmap_time = start.elapsed();
+
+        assert_eq!(count,expected);
+        assert_eq!(count,10_000);
+        println!("probe:{:?} bitmap:{:?}",probe_time,bitmap_time);
+    }
+}
@@ -0,0 +1,286 @@
+use std::{collections::HashMap, hash::Hash};
+use crate::entity::EntityId;
+use super::QueryIterator;
+
+/// The aggregate statistics of a query,see [stats_by](crate::query::QueryStats::stats_by)
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct NumericStats {
+    pub count : usize,
+    pub min : f64,
+    pub max : f64,
+    pub mean : f64,
+    pub p50 : f64,
+    pub p90 : f64,
+    pub p99 : f64
+}
+
+impl NumericStats {
+    /// Compute the exact statistics of ```values```
+    /// # Details
+    /// The percentiles use nearest-rank method.
+    /// All fields except ```count``` are ```NaN``` if ```values``` is empty.
+    fn from_values(mut values : Vec<f64>) -> NumericStats {
+        if values.is_empty() {
+            return NumericStats {
+                count : 0,
+                min : f64::NAN,
+                max : f64::NAN,
+                mean : f64::NAN,
+                p50 : f64::NAN,
+                p90 : f64::NAN,
+                p99 : f64::NAN
+            };
+        }
+        values.sort_by(f64::total_cmp);
+        let count = values.len();
+        let percentile = |p : usize| {
+            // nearest-rank: the smallest value which is greater than p percent of values
+            let rank = (p * count).div_ceil(100);
+            values[rank.max(1) - 1]
+        };
+        NumericStats {
+            count,
+            min : values[0],
+            max : values[count - 1],
+            mean : values.iter().sum::<f64>() / count as f64,
+            p50 : percentile(50),
+            p90 : percentile(90),
+            p99 : percentile(99)
+        }
+    }
+}
+
+/// Convert an item of query to an owned value by cloning components
+pub trait ToOwnedItem {
+    type Owned;
+
+    fn to_owned_item(&self) -> Self::Owned;
+}
+
+impl<T : Clone> ToOwnedItem for &T {
+    type Owned = T;
+
+    fn to_owned_item(&self) -> Self::Owned {
+        (*self).clone()
+    }
+}
+
+impl<T : Clone> ToOwnedItem for &mut T {
+    type Owned = T;
+
+    fn to_owned_item(&self) -> Self::Owned {
+        (**self).clone()
+    }
+}
+
+impl<T : ToOwnedItem> ToOwnedItem for Option<T> {
+    type Owned = Option<T::Owned>;
+
+    fn to_owned_item(&self) -> Self::Owned {
+        self.as_ref().map(|item|item.to_owned_item())
+    }
+}
+
+impl ToOwnedItem for () {
+    type Owned = ();
+
+    fn to_owned_item(&self) -> Self::Owned {}
+}
+
+macro_rules! impl_to_owned_item {
+    ($($name:ident $index:tt),*) => {
+        impl<$($name : ToOwnedItem),*> ToOwnedItem for ($($name,)*) {
+            type Owned = ($($name::Owned,)*);
+
+            fn to_owned_item(&self) -> Self::Owned {
+                ($(self.$index.to_owned_item(),)*)
+            }
+        }
+    };
+}
+
+impl_to_owned_item!(A 0,B 1);
+impl_to_owned_item!(A 0,B 1,C 2);
+impl_to_owned_item!(A 0,B 1,C 2,D 3);
+impl_to_owned_item!(A 0,B 1,C 2,D 3,E 4);
+impl_to_owned_item!(A 0,B 1,C 2,D 3,E 4,F 5);
+impl_to_owned_item!(A 0,B 1,C 2,D 3,E 4,F 5,G 6);
+impl_to_owned_item!(A 0,B 1,C 2,D 3,E 4,F 5,G 6,H 7);
+
+/// SplitMix64,it's small and good enough for sampling
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a random number in ```0..=max```
+    fn up_to(&mut self,max : u64) -> u64 {
+        // the bias is negligible because max is far less than u64::MAX
+        self.next_u64() % (max + 1)
+    }
+}
+
+/// A trait for aggregating the results of query
+/// # Details
+/// All methods consume the query,
+/// they work on any query including groups and [Without](crate::query::Without).
+pub trait QueryStats : QueryIterator + Sized {
+    /// Compute the statistics of the values mapped from items.
+    /// # Details
+    /// It's exact,all values are collected and sorted,
+    /// so it costs ```O(n log n)``` time and ```O(n)``` memory.
+    /// The percentiles use nearest-rank method.
+    /// All fields except ```count``` are ```NaN``` if there is no item.
+    fn stats_by<F>(self,f : F) -> NumericStats
+        where F : FnMut(Self::Item) -> f64 {
+        NumericStats::from_values(self.map(f).collect())
+    }
+
+    /// Count items grouped by the keys mapped from items
+    fn count_by_key<K,F>(self,mut f : F) -> HashMap<K,usize>
+        where K : Eq + Hash,
+              F : FnMut(Self::Item) -> K {
+        let mut counts = HashMap::new();
+        for item in self {
+            *counts.entry(f(item)).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Sample ```n``` items uniformly with their IDs.
+    /// # Details
+    /// It's reservoir sampling,only ```n``` items are kept at the same time.
+    /// The result is deterministic for the same ```seed``` and the same world.
+    /// Return all items if there are less than ```n``` items.
+    fn sample(mut self,n : usize,seed : u64) -> Vec<(EntityId,<Self::Item as ToOwnedItem>::Owned)>
+        where Self::Item : ToOwnedItem {
+        let mut rng = SplitMix64(seed);
+        let mut reservoir = Vec::with_capacity(n);
+        let mut seen = 0_u64;
+        while let Some((id,item)) = self.next_with_id() {
+            if reservoir.len() < n {
+                reservoir.push((id,item.to_owned_item()));
+            } else {
+                let index = rng.up_to(seen) as usize;
+                if index < n {
+                    reservoir[index] = (id,item.to_owned_item());
+                }
+            }
+            seen += 1;
+        }
+        reservoir
+    }
+}
+
+impl<A : QueryIterator> QueryStats for A {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::{group::full_owning, query::{QueryStats, Without}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+    enum Team {
+        Red,
+        Blue,
+        Green
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut world = World::new();
+        world.register::<Health>().register::<char>();
+        for hp in 1..=10 {
+            world.create_entity().attach(Health(hp));
+        }
+        world.create_entity().attach(Health(100)).attach('x');
+
+        let stats = world.query::<&Health>().stats_by(|h|h.0 as f64);
+        assert_eq!(stats.count,11);
+        assert_eq!(stats.min,1.0);
+        assert_eq!(stats.max,100.0);
+        assert_eq!(stats.mean,155.0 / 11.0);
+        assert_eq!(stats.p50,6.0);
+        assert_eq!(stats.p90,10.0);
+        assert_eq!(stats.p99,100.0);
+
+        let stats = world.query::<(&Health,Without<&char>)>().stats_by(|h|h.0 as f64);
+        assert_eq!(stats.count,10);
+        assert_eq!(stats.mean,5.5);
+        assert_eq!(stats.p50,5.0);
+        assert_eq!(stats.p90,9.0);
+        assert_eq!(stats.p99,10.0);
+
+        let stats = world.query::<(&Health,&char)>().stats_by(|(h,_)|h.0 as f64);
+        assert_eq!((stats.count,stats.min,stats.p99),(1,100.0,100.0));
+
+        world.register::<u8>();
+        let stats = world.query::<&u8>().stats_by(|x|*x as f64);
+        assert_eq!(stats.count,0);
+        assert!(stats.mean.is_nan());
+    }
+
+    #[test]
+    fn count_by_key_test() {
+        let mut world = World::new();
+        world.register::<Team>().register::<Health>();
+        world.make_group(full_owning::<Team,Health>());
+        let teams = [Team::Red,Team::Blue,Team::Red,Team::Green,Team::Red,Team::Blue];
+        for team in teams {
+            world.create_entity().attach(team).attach(Health(1));
+        }
+        world.create_entity().attach(Team::Green);
+
+        let counts = world.query::<&Team>().count_by_key(|team|*team);
+        assert_eq!(counts.len(),3);
+        assert_eq!(counts[&Team::Red],3);
+        assert_eq!(counts[&Team::Blue],2);
+        assert_eq!(counts[&Team::Green],2);
+
+        let counts = world.query::<crate::group::FullOwning<&Team,&Health>>()
+            .count_by_key(|(team,_)|*team);
+        assert_eq!(counts[&Team::Green],1);
+    }
+
+    #[test]
+    fn sample_test() {
+        let mut world = World::new();
+        world.register::<Health>().register::<Team>();
+        for hp in 0..10000 {
+            world.create_entity().attach(Health(hp)).attach(Team::Red);
+        }
+
+        let a = world.query::<(&Health,&Team)>().sample(10,42);
+        let b = world.query::<(&Health,&Team)>().sample(10,42);
+        let c = world.query::<(&Health,&Team)>().sample(10,43);
+        assert_eq!(a,b);
+        assert_ne!(a,c);
+        assert_eq!(a.len(),10);
+        assert!(a.iter().all(|(id,(health,_))|*world.get::<Health>(*id).unwrap() == *health));
+
+        // fewer items than n
+        world.register::<char>();
+        assert_eq!(world.query::<&char>().sample(10,42).len(),0);
+        world.create_entity().attach('a');
+        assert_eq!(world.query::<&char>().sample(10,42).len(),1);
+
+        // every decile should get about 200 of 2000 samples
+        let sample = world.query::<&Health>().sample(2000,7);
+        let ids = sample.iter().map(|(id,_)|*id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(),2000);
+        let mut deciles = [0_usize;10];
+        for (_,health) in &sample {
+            deciles[health.0 as usize / 1000] += 1;
+        }
+        assert!(deciles.iter().all(|count|(140..=260).contains(count)),"{:?}",deciles);
+    }
+}
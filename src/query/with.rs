@@ -1,28 +1,91 @@
-use crate::{entity::EntityId, world::World};
-use super::{QueryIterator, Queryable};
+use std::{any::TypeId, iter::FusedIterator};
+use crate::{entity::EntityId, error::QueryError, world::World};
+use super::{PresenceBits, QueryGet, QueryIterator, Queryable};
+
+/// Get the probing order of iterators in ```from_id```.
+/// # Details
+/// The indices are sorted by the upper bound of ```size_hint``` which is
+/// captured at construction, so the smallest storage will be probed first.
+/// Iterators without upper bound will be probed at last.
+fn probe_order<const N : usize>(hints : [Option<usize>;N]) -> [usize;N] {
+    let mut order = [0;N];
+    for (index,value) in order.iter_mut().enumerate() {
+        *value = index;
+    }
+    // sort_by_key is stable,
+    // so the iterators with same size keep the declared order
+    order.sort_by_key(|index| hints[*index].unwrap_or(usize::MAX));
+    order
+}
+
+/// Get the minimal upper bound from ```hints```
+fn min_upper_bound(hints : &[Option<usize>]) -> Option<usize> {
+    hints.iter()
+        .flatten()
+        .min()
+        .cloned()
+}
 
 impl<'a,A : 'a + Queryable<'a>,B :'a + Queryable<'a>> Queryable<'a> for (A,B) {
     type Item = (<A as Queryable<'a>>::Item,<B as Queryable<'a>>::Item);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
-        Box::new(WithIter{
-            iter_a,
-            iter_b
-        })
+        Box::new(WithIter::new(iter_a,iter_b))
     }
 }
 
 pub struct WithIter<A,B> {
     iter_a : A,
-    iter_b : B
+    iter_b : B,
+    order : [usize;2],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A : QueryIterator,B : QueryIterator> WithIter<A,B> {
+    pub(in crate) fn new(iter_a : A,iter_b : B) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence()
+        ]);
+        WithIter {
+            iter_a,
+            iter_b,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
 }
 
 impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithIter<A,B>{
     type Item = (A::Item,B::Item);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 return Some((a,b))
@@ -30,19 +93,51 @@ impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithIter<A,B>{
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1
+        ]);
+        (0,upper)
+    }
 }
 
+impl<A : QueryIterator + FusedIterator,B : QueryIterator + FusedIterator> FusedIterator for WithIter<A,B> {}
+
 impl<A : QueryIterator,B : QueryIterator> QueryIterator for WithIter<A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
-        if let Some(a) = self.iter_a.from_id(id) {
-            if let Some(b) = self.iter_b.from_id(id) {
-                return Some((a,b))
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
             }
         }
-        None
+        let mut a = None;
+        let mut b = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                _ => b = Some(self.iter_b.from_id(id)?)
+            }
+        }
+        Some((a?,b?))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 return Some((id,(a,b)))
@@ -50,6 +145,28 @@ impl<A : QueryIterator,B : QueryIterator> QueryIterator for WithIter<A,B> {
         }
         None
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A : QueryGet,B : QueryGet> QueryGet for WithIter<A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?
+        ))
+    }
 }
 
 
@@ -62,22 +179,64 @@ impl<'a,A,B,C> Queryable<'a> for (A,B,C)
                  <B as Queryable<'a>>::Item,
                  <C as Queryable<'a>>::Item);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
         let iter_c = world.query::<C>();
-        Box::new(WithIter3{
-            iter_a,
-            iter_b,
-            iter_c
-        })
+        Box::new(WithIter3::new(iter_a,iter_b,iter_c))
     }
 }
 
 pub struct WithIter3<A,B,C> {
     iter_a : A,
     iter_b : B,
-    iter_c : C
+    iter_c : C,
+    order : [usize;3],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C> WithIter3<A,B,C>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator{
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence()
+        ]);
+        WithIter3 {
+            iter_a,
+            iter_b,
+            iter_c,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
 }
 
 impl<'a,A,B,C> Iterator for WithIter3<A,B,C>
@@ -87,6 +246,9 @@ impl<'a,A,B,C> Iterator for WithIter3<A,B,C>
     type Item = (A::Item,B::Item,C::Item);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
@@ -96,24 +258,60 @@ impl<'a,A,B,C> Iterator for WithIter3<A,B,C>
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1
+        ]);
+        (0,upper)
+    }
 }
 
+impl<A,B,C> FusedIterator for WithIter3<A,B,C>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator {}
+
 impl<A,B,C> QueryIterator for WithIter3<A,B,C>
     where A : QueryIterator,
           B : QueryIterator,
           C : QueryIterator{
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
-        if let Some(a) = self.iter_a.from_id(id) {
-            if let Some(b) = self.iter_b.from_id(id) {
-                if let Some(c) = self.iter_c.from_id(id) {
-                    return Some((a,b,c))
-                }
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
             }
         }
-        None
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                _ => c = Some(self.iter_c.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
@@ -123,6 +321,32 @@ impl<A,B,C> QueryIterator for WithIter3<A,B,C>
         }
         None
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C> QueryGet for WithIter3<A,B,C>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?
+        ))
+    }
 }
 
 
@@ -138,17 +362,26 @@ impl<'a,A,B,C,D> Queryable<'a> for (A,B,C,D)
                  <C as Queryable<'a>>::Item,
                  <D as Queryable<'a>>::Item);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+        D::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)?;
+        D::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
         let iter_c = world.query::<C>();
         let iter_d = world.query::<D>();
-        Box::new(WithIter4{
-            iter_a,
-            iter_b,
-            iter_c,
-            iter_d
-        })
+        Box::new(WithIter4::new(iter_a,iter_b,iter_c,iter_d))
     }
 }
 
@@ -156,7 +389,45 @@ pub struct WithIter4<A,B,C,D> {
     iter_a : A,
     iter_b : B,
     iter_c : C,
-    iter_d : D
+    iter_d : D,
+    order : [usize;4],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C,D> WithIter4<A,B,C,D>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator{
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C,iter_d : D) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1,
+            iter_d.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence(),
+            iter_d.presence()
+        ]);
+        WithIter4 {
+            iter_a,
+            iter_b,
+            iter_c,
+            iter_d,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
 }
 
 impl<'a,A,B,C,D> Iterator for WithIter4<A,B,C,D>
@@ -167,6 +438,9 @@ impl<'a,A,B,C,D> Iterator for WithIter4<A,B,C,D>
     type Item = (A::Item,B::Item,C::Item,D::Item);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
@@ -178,27 +452,65 @@ impl<'a,A,B,C,D> Iterator for WithIter4<A,B,C,D>
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1,
+            self.iter_d.size_hint().1
+        ]);
+        (0,upper)
+    }
 }
 
+impl<A,B,C,D> FusedIterator for WithIter4<A,B,C,D>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator,
+          D : QueryIterator + FusedIterator {}
+
 impl<A,B,C,D> QueryIterator for WithIter4<A,B,C,D>
     where A : QueryIterator,
           B : QueryIterator,
           C : QueryIterator,
           D : QueryIterator{
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
-        if let Some(a) = self.iter_a.from_id(id) {
-            if let Some(b) = self.iter_b.from_id(id) {
-                if let Some(c) = self.iter_c.from_id(id) {
-                    if let Some(d) = self.iter_d.from_id(id) {
-                        return Some((a,b,c,d))
-                    }
-                }
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
             }
         }
-        None
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        let mut d = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                2 => c = Some(self.iter_c.from_id(id)?),
+                _ => d = Some(self.iter_d.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?,d?))
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
@@ -210,6 +522,34 @@ impl<A,B,C,D> QueryIterator for WithIter4<A,B,C,D>
         }
         None
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C,D> QueryGet for WithIter4<A,B,C,D>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet,
+          D : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?,
+            self.iter_d.peek_from_id(id)?
+        ))
+    }
 }
 
 
@@ -228,19 +568,29 @@ impl<'a,A,B,C,D,E> Queryable<'a> for (A,B,C,D,E)
                  <D as Queryable<'a>>::Item,
                  <E as Queryable<'a>>::Item);
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+        D::component_types(types);
+        E::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)?;
+        D::check(world)?;
+        E::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
         let iter_c = world.query::<C>();
         let iter_d = world.query::<D>();
         let iter_e = world.query::<E>();
-        Box::new(WithIter5{
-            iter_a,
-            iter_b,
-            iter_c,
-            iter_d,
-            iter_e
-        })
+        Box::new(WithIter5::new(iter_a,iter_b,iter_c,iter_d,iter_e))
     }
 }
 
@@ -249,7 +599,49 @@ pub struct WithIter5<A,B,C,D,E> {
     iter_b : B,
     iter_c : C,
     iter_d : D,
-    iter_e : E
+    iter_e : E,
+    order : [usize;5],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C,D,E> WithIter5<A,B,C,D,E>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator{
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C,iter_d : D,iter_e : E) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1,
+            iter_d.size_hint().1,
+            iter_e.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence(),
+            iter_d.presence(),
+            iter_e.presence()
+        ]);
+        WithIter5 {
+            iter_a,
+            iter_b,
+            iter_c,
+            iter_d,
+            iter_e,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
 }
 
 impl<'a,A,B,C,D,E> Iterator for WithIter5<A,B,C,D,E>
@@ -261,6 +653,9 @@ impl<'a,A,B,C,D,E> Iterator for WithIter5<A,B,C,D,E>
     type Item = (A::Item,B::Item,C::Item,D::Item,E::Item);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
@@ -274,8 +669,29 @@ impl<'a,A,B,C,D,E> Iterator for WithIter5<A,B,C,D,E>
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1,
+            self.iter_d.size_hint().1,
+            self.iter_e.size_hint().1
+        ]);
+        (0,upper)
+    }
 }
 
+impl<A,B,C,D,E> FusedIterator for WithIter5<A,B,C,D,E>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator,
+          D : QueryIterator + FusedIterator,
+          E : QueryIterator + FusedIterator {}
+
 impl<A,B,C,D,E> QueryIterator for WithIter5<A,B,C,D,E>
     where A : QueryIterator,
           B : QueryIterator,
@@ -283,12 +699,46 @@ impl<A,B,C,D,E> QueryIterator for WithIter5<A,B,C,D,E>
           D : QueryIterator,
           E : QueryIterator{
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
-        if let Some(a) = self.iter_a.from_id(id) {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        let mut d = None;
+        let mut e = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                2 => c = Some(self.iter_c.from_id(id)?),
+                3 => d = Some(self.iter_d.from_id(id)?),
+                _ => e = Some(self.iter_e.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?,d?,e?))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
                     if let Some(d) = self.iter_d.from_id(id) {
                         if let Some(e) = self.iter_e.from_id(id) {
-                            return Some((a,b,c,d,e))
+                            return Some((id,(a,b,c,d,e)))
                         }
                     }
                 }
@@ -297,13 +747,156 @@ impl<A,B,C,D,E> QueryIterator for WithIter5<A,B,C,D,E>
         None
     }
 
-    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C,D,E> QueryGet for WithIter5<A,B,C,D,E>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet,
+          D : QueryGet,
+          E : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?,
+            self.iter_d.peek_from_id(id)?,
+            self.iter_e.peek_from_id(id)?
+        ))
+    }
+}
+
+impl<'a,A,B,C,D,E,F> Queryable<'a> for (A,B,C,D,E,F)
+    where A : 'a + Queryable<'a>,
+          B : 'a + Queryable<'a>,
+          C : 'a + Queryable<'a>,
+          D : 'a + Queryable<'a>,
+          E : 'a + Queryable<'a>,
+          F : 'a + Queryable<'a>{
+    type Item = (<A as Queryable<'a>>::Item,
+                 <B as Queryable<'a>>::Item,
+                 <C as Queryable<'a>>::Item,
+                 <D as Queryable<'a>>::Item,
+                 <E as Queryable<'a>>::Item,
+                 <F as Queryable<'a>>::Item);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+        D::component_types(types);
+        E::component_types(types);
+        F::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)?;
+        D::check(world)?;
+        E::check(world)?;
+        F::check(world)
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let iter_a = world.query::<A>();
+        let iter_b = world.query::<B>();
+        let iter_c = world.query::<C>();
+        let iter_d = world.query::<D>();
+        let iter_e = world.query::<E>();
+        let iter_f = world.query::<F>();
+        Box::new(WithIter6::new(iter_a,iter_b,iter_c,iter_d,iter_e,iter_f))
+    }
+}
+
+pub struct WithIter6<A,B,C,D,E,F> {
+    iter_a : A,
+    iter_b : B,
+    iter_c : C,
+    iter_d : D,
+    iter_e : E,
+    iter_f : F,
+    order : [usize;6],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C,D,E,F> WithIter6<A,B,C,D,E,F>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator{
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C,iter_d : D,iter_e : E,iter_f : F) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1,
+            iter_d.size_hint().1,
+            iter_e.size_hint().1,
+            iter_f.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence(),
+            iter_d.presence(),
+            iter_e.presence(),
+            iter_f.presence()
+        ]);
+        WithIter6 {
+            iter_a,
+            iter_b,
+            iter_c,
+            iter_d,
+            iter_e,
+            iter_f,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
+}
+
+impl<A,B,C,D,E,F> Iterator for WithIter6<A,B,C,D,E,F>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator{
+    type Item = (A::Item,B::Item,C::Item,D::Item,E::Item,F::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let Some(b) = self.iter_b.from_id(id) {
                 if let Some(c) = self.iter_c.from_id(id) {
                     if let Some(d) = self.iter_d.from_id(id) {
                         if let Some(e) = self.iter_e.from_id(id) {
-                            return Some((id,(a,b,c,d,e)))
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                return Some((a,b,c,d,e,f))
+                            }
                         }
                     }
                 }
@@ -311,4 +904,840 @@ impl<A,B,C,D,E> QueryIterator for WithIter5<A,B,C,D,E>
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1,
+            self.iter_d.size_hint().1,
+            self.iter_e.size_hint().1,
+            self.iter_f.size_hint().1
+        ]);
+        (0,upper)
+    }
+}
+
+impl<A,B,C,D,E,F> FusedIterator for WithIter6<A,B,C,D,E,F>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator,
+          D : QueryIterator + FusedIterator,
+          E : QueryIterator + FusedIterator,
+          F : QueryIterator + FusedIterator {}
+
+impl<A,B,C,D,E,F> QueryIterator for WithIter6<A,B,C,D,E,F>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator{
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        let mut d = None;
+        let mut e = None;
+        let mut f = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                2 => c = Some(self.iter_c.from_id(id)?),
+                3 => d = Some(self.iter_d.from_id(id)?),
+                4 => e = Some(self.iter_e.from_id(id)?),
+                _ => f = Some(self.iter_f.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?,d?,e?,f?))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
+            if let Some(b) = self.iter_b.from_id(id) {
+                if let Some(c) = self.iter_c.from_id(id) {
+                    if let Some(d) = self.iter_d.from_id(id) {
+                        if let Some(e) = self.iter_e.from_id(id) {
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                return Some((id,(a,b,c,d,e,f)))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C,D,E,F> QueryGet for WithIter6<A,B,C,D,E,F>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet,
+          D : QueryGet,
+          E : QueryGet,
+          F : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?,
+            self.iter_d.peek_from_id(id)?,
+            self.iter_e.peek_from_id(id)?,
+            self.iter_f.peek_from_id(id)?
+        ))
+    }
+}
+
+impl<'a,A,B,C,D,E,F,G> Queryable<'a> for (A,B,C,D,E,F,G)
+    where A : 'a + Queryable<'a>,
+          B : 'a + Queryable<'a>,
+          C : 'a + Queryable<'a>,
+          D : 'a + Queryable<'a>,
+          E : 'a + Queryable<'a>,
+          F : 'a + Queryable<'a>,
+          G : 'a + Queryable<'a>{
+    type Item = (<A as Queryable<'a>>::Item,
+                 <B as Queryable<'a>>::Item,
+                 <C as Queryable<'a>>::Item,
+                 <D as Queryable<'a>>::Item,
+                 <E as Queryable<'a>>::Item,
+                 <F as Queryable<'a>>::Item,
+                 <G as Queryable<'a>>::Item);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+        D::component_types(types);
+        E::component_types(types);
+        F::component_types(types);
+        G::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)?;
+        D::check(world)?;
+        E::check(world)?;
+        F::check(world)?;
+        G::check(world)
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let iter_a = world.query::<A>();
+        let iter_b = world.query::<B>();
+        let iter_c = world.query::<C>();
+        let iter_d = world.query::<D>();
+        let iter_e = world.query::<E>();
+        let iter_f = world.query::<F>();
+        let iter_g = world.query::<G>();
+        Box::new(WithIter7::new(iter_a,iter_b,iter_c,iter_d,iter_e,iter_f,iter_g))
+    }
+}
+
+pub struct WithIter7<A,B,C,D,E,F,G> {
+    iter_a : A,
+    iter_b : B,
+    iter_c : C,
+    iter_d : D,
+    iter_e : E,
+    iter_f : F,
+    iter_g : G,
+    order : [usize;7],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C,D,E,F,G> WithIter7<A,B,C,D,E,F,G>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator{
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C,iter_d : D,iter_e : E,iter_f : F,iter_g : G) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1,
+            iter_d.size_hint().1,
+            iter_e.size_hint().1,
+            iter_f.size_hint().1,
+            iter_g.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence(),
+            iter_d.presence(),
+            iter_e.presence(),
+            iter_f.presence(),
+            iter_g.presence()
+        ]);
+        WithIter7 {
+            iter_a,
+            iter_b,
+            iter_c,
+            iter_d,
+            iter_e,
+            iter_f,
+            iter_g,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
+}
+
+impl<A,B,C,D,E,F,G> Iterator for WithIter7<A,B,C,D,E,F,G>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator{
+    type Item = (A::Item,B::Item,C::Item,D::Item,E::Item,F::Item,G::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
+            if let Some(b) = self.iter_b.from_id(id) {
+                if let Some(c) = self.iter_c.from_id(id) {
+                    if let Some(d) = self.iter_d.from_id(id) {
+                        if let Some(e) = self.iter_e.from_id(id) {
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                if let Some(g) = self.iter_g.from_id(id) {
+                                    return Some((a,b,c,d,e,f,g))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1,
+            self.iter_d.size_hint().1,
+            self.iter_e.size_hint().1,
+            self.iter_f.size_hint().1,
+            self.iter_g.size_hint().1
+        ]);
+        (0,upper)
+    }
+}
+
+impl<A,B,C,D,E,F,G> FusedIterator for WithIter7<A,B,C,D,E,F,G>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator,
+          D : QueryIterator + FusedIterator,
+          E : QueryIterator + FusedIterator,
+          F : QueryIterator + FusedIterator,
+          G : QueryIterator + FusedIterator {}
+
+impl<A,B,C,D,E,F,G> QueryIterator for WithIter7<A,B,C,D,E,F,G>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator{
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        let mut d = None;
+        let mut e = None;
+        let mut f = None;
+        let mut g = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                2 => c = Some(self.iter_c.from_id(id)?),
+                3 => d = Some(self.iter_d.from_id(id)?),
+                4 => e = Some(self.iter_e.from_id(id)?),
+                5 => f = Some(self.iter_f.from_id(id)?),
+                _ => g = Some(self.iter_g.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?,d?,e?,f?,g?))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
+            if let Some(b) = self.iter_b.from_id(id) {
+                if let Some(c) = self.iter_c.from_id(id) {
+                    if let Some(d) = self.iter_d.from_id(id) {
+                        if let Some(e) = self.iter_e.from_id(id) {
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                if let Some(g) = self.iter_g.from_id(id) {
+                                    return Some((id,(a,b,c,d,e,f,g)))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C,D,E,F,G> QueryGet for WithIter7<A,B,C,D,E,F,G>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet,
+          D : QueryGet,
+          E : QueryGet,
+          F : QueryGet,
+          G : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?,
+            self.iter_d.peek_from_id(id)?,
+            self.iter_e.peek_from_id(id)?,
+            self.iter_f.peek_from_id(id)?,
+            self.iter_g.peek_from_id(id)?
+        ))
+    }
+}
+
+impl<'a,A,B,C,D,E,F,G,H> Queryable<'a> for (A,B,C,D,E,F,G,H)
+    where A : 'a + Queryable<'a>,
+          B : 'a + Queryable<'a>,
+          C : 'a + Queryable<'a>,
+          D : 'a + Queryable<'a>,
+          E : 'a + Queryable<'a>,
+          F : 'a + Queryable<'a>,
+          G : 'a + Queryable<'a>,
+          H : 'a + Queryable<'a>{
+    type Item = (<A as Queryable<'a>>::Item,
+                 <B as Queryable<'a>>::Item,
+                 <C as Queryable<'a>>::Item,
+                 <D as Queryable<'a>>::Item,
+                 <E as Queryable<'a>>::Item,
+                 <F as Queryable<'a>>::Item,
+                 <G as Queryable<'a>>::Item,
+                 <H as Queryable<'a>>::Item);
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+        C::component_types(types);
+        D::component_types(types);
+        E::component_types(types);
+        F::component_types(types);
+        G::component_types(types);
+        H::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)?;
+        C::check(world)?;
+        D::check(world)?;
+        E::check(world)?;
+        F::check(world)?;
+        G::check(world)?;
+        H::check(world)
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let iter_a = world.query::<A>();
+        let iter_b = world.query::<B>();
+        let iter_c = world.query::<C>();
+        let iter_d = world.query::<D>();
+        let iter_e = world.query::<E>();
+        let iter_f = world.query::<F>();
+        let iter_g = world.query::<G>();
+        let iter_h = world.query::<H>();
+        Box::new(WithIter8::new(iter_a,iter_b,iter_c,iter_d,iter_e,iter_f,iter_g,iter_h))
+    }
+}
+
+pub struct WithIter8<A,B,C,D,E,F,G,H> {
+    iter_a : A,
+    iter_b : B,
+    iter_c : C,
+    iter_d : D,
+    iter_e : E,
+    iter_f : F,
+    iter_g : G,
+    iter_h : H,
+    order : [usize;8],
+    // the IDs in all iterators if every iterator has a presence bitmap
+    bits : Option<PresenceBits>
+}
+
+impl<A,B,C,D,E,F,G,H> WithIter8<A,B,C,D,E,F,G,H>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator,
+          H : QueryIterator{
+    #[allow(clippy::too_many_arguments)]
+    pub(in crate) fn new(iter_a : A,iter_b : B,iter_c : C,iter_d : D,iter_e : E,iter_f : F,iter_g : G,iter_h : H) -> Self {
+        let order = probe_order([
+            iter_a.size_hint().1,
+            iter_b.size_hint().1,
+            iter_c.size_hint().1,
+            iter_d.size_hint().1,
+            iter_e.size_hint().1,
+            iter_f.size_hint().1,
+            iter_g.size_hint().1,
+            iter_h.size_hint().1
+        ]);
+        let bits = PresenceBits::and(&[
+            iter_a.presence(),
+            iter_b.presence(),
+            iter_c.presence(),
+            iter_d.presence(),
+            iter_e.presence(),
+            iter_f.presence(),
+            iter_g.presence(),
+            iter_h.presence()
+        ]);
+        WithIter8 {
+            iter_a,
+            iter_b,
+            iter_c,
+            iter_d,
+            iter_e,
+            iter_f,
+            iter_g,
+            iter_h,
+            order,
+            bits
+        }
+    }
+
+    /// Get the order of probing in [from_id](crate::query::QueryIterator::from_id).
+    /// This is useful for debugging.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
+}
+
+impl<A,B,C,D,E,F,G,H> Iterator for WithIter8<A,B,C,D,E,F,G,H>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator,
+          H : QueryIterator{
+    type Item = (A::Item,B::Item,C::Item,D::Item,E::Item,F::Item,G::Item,H::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
+            if let Some(b) = self.iter_b.from_id(id) {
+                if let Some(c) = self.iter_c.from_id(id) {
+                    if let Some(d) = self.iter_d.from_id(id) {
+                        if let Some(e) = self.iter_e.from_id(id) {
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                if let Some(g) = self.iter_g.from_id(id) {
+                                    if let Some(h) = self.iter_h.from_id(id) {
+                                        return Some((a,b,c,d,e,f,g,h))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        let upper = min_upper_bound(&[
+            self.iter_a.size_hint().1,
+            self.iter_b.size_hint().1,
+            self.iter_c.size_hint().1,
+            self.iter_d.size_hint().1,
+            self.iter_e.size_hint().1,
+            self.iter_f.size_hint().1,
+            self.iter_g.size_hint().1,
+            self.iter_h.size_hint().1
+        ]);
+        (0,upper)
+    }
+}
+
+impl<A,B,C,D,E,F,G,H> FusedIterator for WithIter8<A,B,C,D,E,F,G,H>
+    where A : QueryIterator + FusedIterator,
+          B : QueryIterator + FusedIterator,
+          C : QueryIterator + FusedIterator,
+          D : QueryIterator + FusedIterator,
+          E : QueryIterator + FusedIterator,
+          F : QueryIterator + FusedIterator,
+          G : QueryIterator + FusedIterator,
+          H : QueryIterator + FusedIterator {}
+
+impl<A,B,C,D,E,F,G,H> QueryIterator for WithIter8<A,B,C,D,E,F,G,H>
+    where A : QueryIterator,
+          B : QueryIterator,
+          C : QueryIterator,
+          D : QueryIterator,
+          E : QueryIterator,
+          F : QueryIterator,
+          G : QueryIterator,
+          H : QueryIterator{
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item>{
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        let mut a = None;
+        let mut b = None;
+        let mut c = None;
+        let mut d = None;
+        let mut e = None;
+        let mut f = None;
+        let mut g = None;
+        let mut h = None;
+        for index in self.order {
+            match index {
+                0 => a = Some(self.iter_a.from_id(id)?),
+                1 => b = Some(self.iter_b.from_id(id)?),
+                2 => c = Some(self.iter_c.from_id(id)?),
+                3 => d = Some(self.iter_d.from_id(id)?),
+                4 => e = Some(self.iter_e.from_id(id)?),
+                5 => f = Some(self.iter_f.from_id(id)?),
+                6 => g = Some(self.iter_g.from_id(id)?),
+                _ => h = Some(self.iter_h.from_id(id)?)
+            }
+        }
+        Some((a?,b?,c?,d?,e?,f?,g?,h?))
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if self.bits.is_some() {
+            // the items taken by from_id before are skipped
+            while let Some(index) = self.bits.as_mut().and_then(|bits|bits.next()) {
+                // every iterator has the entity,the bitmap ensures this
+                let id = self.iter_a.entity_of(index)?;
+                if let Some(item) = self.from_id(id) {
+                    return Some((id,item));
+                }
+            }
+            return None;
+        }
+        while let Some((id,a)) = self.iter_a.next_with_id() {
+            if let Some(b) = self.iter_b.from_id(id) {
+                if let Some(c) = self.iter_c.from_id(id) {
+                    if let Some(d) = self.iter_d.from_id(id) {
+                        if let Some(e) = self.iter_e.from_id(id) {
+                            if let Some(f) = self.iter_f.from_id(id) {
+                                if let Some(g) = self.iter_g.from_id(id) {
+                                    if let Some(h) = self.iter_h.from_id(id) {
+                                        return Some((id,(a,b,c,d,e,f,g,h)))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A,B,C,D,E,F,G,H> QueryGet for WithIter8<A,B,C,D,E,F,G,H>
+    where A : QueryGet,
+          B : QueryGet,
+          C : QueryGet,
+          D : QueryGet,
+          E : QueryGet,
+          F : QueryGet,
+          G : QueryGet,
+          H : QueryGet {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+        }
+        Some((
+            self.iter_a.peek_from_id(id)?,
+            self.iter_b.peek_from_id(id)?,
+            self.iter_c.peek_from_id(id)?,
+            self.iter_d.peek_from_id(id)?,
+            self.iter_e.peek_from_id(id)?,
+            self.iter_f.peek_from_id(id)?,
+            self.iter_g.peek_from_id(id)?,
+            self.iter_h.peek_from_id(id)?
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+    use crate::{entity::EntityId, query::QueryIterator, world::World};
+    use super::{WithIter, WithIter3};
+
+    /// A wrapper to record the probing of ```from_id```
+    struct Probed<I> {
+        name : &'static str,
+        log : Rc<RefCell<Vec<&'static str>>>,
+        iter : I
+    }
+
+    impl<I : QueryIterator> Iterator for Probed<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.iter.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.iter.size_hint()
+        }
+    }
+
+    impl<I : QueryIterator> QueryIterator for Probed<I> {
+        fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+            self.log.borrow_mut().push(self.name);
+            self.iter.from_id(id)
+        }
+
+        fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+            self.iter.next_with_id()
+        }
+    }
+
+    fn skewed_world() -> World {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<u64>()
+            .register::<char>();
+        for i in 0..1000_u32 {
+            let entity = world.create_entity().attach(i);
+            if i % 10 == 0 {
+                entity.attach(i as u64);
+            } else if i % 250 == 1 {
+                entity.attach('c');
+            }
+        }
+        world.create_entity().attach(7_u64).attach('a');
+        world.create_entity().attach(8_u64).attach('b').attach(1000_u32);
+        world
+    }
+
+    #[test]
+    fn probe_order_test() {
+        let world = skewed_world();
+        let log = Rc::new(RefCell::new(vec![]));
+
+        let mut iter = WithIter3::new(
+            Probed { name : "u32", log : log.clone(), iter : world.query::<&u32>() },
+            Probed { name : "u64", log : log.clone(), iter : world.query::<&u64>() },
+            Probed { name : "char", log : log.clone(), iter : world.query::<&char>() });
+        assert_eq!(iter.probe_order(),&[2,1,0]);
+
+        // the smallest storage is probed first and fails fast
+        let ids = world.query::<&u32>()
+            .with_id_collect();
+        log.borrow_mut().clear();
+        for id in &ids {
+            iter.from_id(*id);
+        }
+        assert!(log.borrow().iter().filter(|name| **name == "char").count() == ids.len());
+        assert_eq!(log.borrow().iter().filter(|name| **name == "u32").count(),1);
+
+        // items are returned in declared order
+        let id = *ids.last().unwrap();
+        log.borrow_mut().clear();
+        let (a,b,c) = iter.from_id(id).unwrap();
+        assert_eq!((*a,*b,*c),(1000,8,'b'));
+        assert_eq!(log.borrow().as_slice(),&["char","u64","u32"]);
+    }
+
+    #[test]
+    fn skewed_query_test() {
+        let world = skewed_world();
+
+        let res = world.query::<(&u32,&u64,&char)>()
+            .map(|(a,b,c)|(*a,*b,*c))
+            .collect::<Vec<_>>();
+        assert_eq!(&res,&[(1000,8,'b')]);
+
+        let mut res = world.query::<(&char,(&u32,&u64))>()
+            .map(|(c,(a,b))|(*a,*b,*c))
+            .collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(&res,&[(1000,8,'b')]);
+
+        let iter = WithIter::new(world.query::<(&u32,&u64)>(),world.query::<&char>());
+        assert_eq!(iter.probe_order(),&[1,0]);
+    }
+
+    #[test]
+    fn large_tuple_test() {
+        let mut world = World::new();
+        world.register::<u8>()
+            .register::<u16>()
+            .register::<u32>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>()
+            .register::<i32>()
+            .register::<i64>();
+        for i in 0..10_u8 {
+            let entity = world.create_entity()
+                .attach(i)
+                .attach(i as u16)
+                .attach(i as u32)
+                .attach(i as u64)
+                .attach(i as i8)
+                .attach(i as i16)
+                .attach(i as i32);
+            if i % 2 == 0 {
+                entity.attach(i as i64);
+            }
+        }
+
+        let res = world.query::<(&u8,&u16,&u32,&u64,&i8,&i16)>()
+            .map(|(a,_,_,_,_,f)|(*a,*f))
+            .collect::<Vec<_>>();
+        assert_eq!(res.len(),10);
+        assert!(res.iter().all(|(a,f)|*a as i16 == *f));
+
+        let res = world.query::<(&u8,&u16,&u32,&u64,&i8,&i16,&mut i32)>()
+            .map(|(_,_,_,_,_,_,g)|{ *g += 1; *g })
+            .sum::<i32>();
+        assert_eq!(res,55);
+
+        let mut ids = world.query::<(&i64,&u16,&u32,&u64,&i8,&i16,&i32,&u8)>()
+            .with_id_collect();
+        ids.sort();
+        let mut expected = world.query::<&i64>().with_id_collect();
+        expected.sort();
+        assert_eq!(ids,expected);
+
+        // nested tuples still work
+        let count = world.query::<(&u8,(&u16,&u32,&u64,&i8,&i16),&i32)>().count();
+        assert_eq!(count,10);
+    }
+
+    trait WithIdCollect {
+        fn with_id_collect(self) -> Vec<EntityId>;
+    }
+
+    impl<I : QueryIterator> WithIdCollect for I {
+        fn with_id_collect(mut self) -> Vec<EntityId> {
+            let mut ids = vec![];
+            while let Some((id,_)) = self.next_with_id() {
+                ids.push(id);
+            }
+            ids
+        }
+    }
 }
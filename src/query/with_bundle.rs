@@ -0,0 +1,102 @@
+use std::{any::TypeId, marker::PhantomData};
+use crate::lock::StorageReadGuard;
+use crate::{bundle::Bundle, entity::EntityId, world::World};
+use super::{QueryGet, QueryIterator, Queryable};
+
+/// A query filter that matches the entities having every component of bundle ```B```.
+/// # Details
+/// The item is ```()```,it only checks the membership.
+/// # Example
+/// ```
+/// use xecs::{World, query::WithBundle};
+/// let mut world = World::new();
+/// world.register::<u32>().register::<char>().register::<f32>();
+/// world.create_entity().attach(1_u32).attach('a').attach(1.0_f32);
+/// world.create_entity().attach(2_u32).attach('b');
+///
+/// let count = world.query::<(&u32,WithBundle<(char,f32)>)>().count();
+/// assert_eq!(count,1);
+/// ```
+pub struct WithBundle<B> {
+    _marker : PhantomData<B>
+}
+
+pub struct WithBundleIter<'a> {
+    index : usize,
+    // the smallest storage is at first
+    borrows : Vec<StorageReadGuard<'a>>
+}
+
+impl<'a,B : Bundle> Queryable<'a> for WithBundle<B> {
+    type Item = ();
+
+    fn component_types(types : &mut Vec<TypeId>) {
+        let mut bundle_types = vec![];
+        B::component_types(&mut bundle_types);
+        types.extend(bundle_types.into_iter().map(|(type_id,_)|type_id));
+    }
+
+    fn query(world : &'a World) -> Box<dyn QueryIterator<Item = Self::Item> + 'a> {
+        let mut types = vec![];
+        B::component_types(&mut types);
+        let mut borrows = types.into_iter()
+            .map(|(type_id,_)| world.raw_storage_read(type_id)
+                .expect("Queryable for WithBundle: Component was not registered in world"))
+            .collect::<Vec<_>>();
+        // iterate the smallest storage
+        borrows.sort_by_key(|storage|storage.count());
+        Box::new(WithBundleIter {
+            index : 0,
+            borrows
+        })
+    }
+}
+
+impl<'a> WithBundleIter<'a> {
+    fn has_all(&self,id : EntityId) -> bool {
+        self.borrows.iter()
+            .all(|storage|storage.has(id))
+    }
+}
+
+impl<'a> Iterator for WithBundleIter<'a> {
+    type Item = ();
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_id()
+            .map(|(_,item)|item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.borrows.first()
+            .map(|storage|storage.count() - self.index)
+            .unwrap_or(0);
+        (0,Some(rem))
+    }
+}
+
+impl<'a> QueryIterator for WithBundleIter<'a> {
+    fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        self.peek_from_id(id)
+    }
+
+    fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        loop {
+            let id = self.borrows.first()?.id(self.index)?;
+            self.index += 1;
+            if self.has_all(id) {
+                return Some((id,()));
+            }
+        }
+    }
+}
+
+impl<'a> QueryGet for WithBundleIter<'a> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if self.has_all(id) {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use std::marker::PhantomData;
-use crate::{entity::EntityId, world::World};
-use super::{QueryIterator, Queryable};
+use std::{any::TypeId, iter::FusedIterator, marker::PhantomData};
+use crate::{entity::EntityId, error::QueryError, world::World};
+use super::{PresenceBits, QueryGet, QueryIterator, Queryable};
 
+/// Exclude the entities matched by ```T```.
+/// # Details
+/// ```(A,Without<B>)``` and ```(Without<B>,A)``` iterate ```A``` and skip the entities in ```B```.
+/// ```A``` can be any [Queryable] including group queries,
+/// so ```(FullOwning<&A,&B>,Without<&C>)``` still iterates the packed part of group.
 pub struct Without<T>{
     _marker : PhantomData<T>
 }
@@ -9,25 +14,48 @@ pub struct Without<T>{
 impl<'a,A : 'a + Queryable<'a>,B : 'a + Queryable<'a>> Queryable<'a> for (Without<A>,B) {
     type Item = <B as Queryable<'a>>::Item;
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
-        Box::new(WithoutIterLeft{
-            iter_a,
-            iter_b
-        })
+        Box::new(WithoutIterLeft::new(iter_a,iter_b))
     }
 }
 
 pub struct WithoutIterLeft<A,B>{
     iter_a : A,
-    iter_b : B
+    iter_b : B,
+    // the IDs in B but not in A if both have presence bitmaps
+    bits : Option<PresenceBits>
+}
+
+impl<A : QueryIterator,B : QueryIterator> WithoutIterLeft<A,B> {
+    pub(in crate) fn new(iter_a : A,iter_b : B) -> Self {
+        let bits = PresenceBits::and_not(iter_b.presence(),iter_a.presence());
+        WithoutIterLeft{
+            iter_a,
+            iter_b,
+            bits
+        }
+    }
 }
 
 impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithoutIterLeft<A,B> {
     type Item = B::Item;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,b)) = self.iter_b.next_with_id() {
             if let None = self.iter_a.from_id(id) {
                 return Some(b);
@@ -35,10 +63,25 @@ impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithoutIterLeft<A,B> {
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        (0,self.iter_b.size_hint().1)
+    }
 }
 
+impl<A : QueryIterator + FusedIterator,B : QueryIterator + FusedIterator> FusedIterator for WithoutIterLeft<A,B> {}
+
 impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterLeft<A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+            return self.iter_b.from_id(id);
+        }
         if let None = self.iter_a.from_id(id) {
             if let Some(b) = self.iter_b.from_id(id) {
                 return Some(b)
@@ -48,6 +91,12 @@ impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterLeft<A
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if let Some(bits) = &mut self.bits {
+            let index = bits.next()?;
+            // the bitmap ensures B has the entity
+            let id = self.iter_b.entity_of(index)?;
+            return self.iter_b.from_id(id).map(|item|(id,item));
+        }
         while let Some((id,b)) = self.iter_b.next_with_id() {
             if let None = self.iter_a.from_id(id) {
                 return Some((id,b));
@@ -55,6 +104,29 @@ impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterLeft<A
         }
         None
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_b.entity_of(index)
+    }
+}
+
+impl<A : QueryGet,B : QueryGet> QueryGet for WithoutIterLeft<A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+            return self.iter_b.peek_from_id(id);
+        }
+        if self.iter_a.peek_from_id(id).is_some() {
+            return None;
+        }
+        self.iter_b.peek_from_id(id)
+    }
 }
 
 
@@ -64,25 +136,48 @@ impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterLeft<A
 impl<'a,A : 'a + Queryable<'a>,B : 'a + Queryable<'a>> Queryable<'a> for (A,Without<B>) {
     type Item = <A as Queryable<'a>>::Item;
 
+    fn component_types(types : &mut Vec<TypeId>) {
+        A::component_types(types);
+        B::component_types(types);
+    }
+
+    fn check(world : &World) -> Result<(),QueryError> {
+        A::check(world)?;
+        B::check(world)
+    }
+
     fn query(world : &'a World) -> Box<(dyn QueryIterator<Item = Self::Item> + 'a)> {
         let iter_a = world.query::<A>();
         let iter_b = world.query::<B>();
-        Box::new(WithoutIterRight{
-            iter_a,
-            iter_b
-        })
+        Box::new(WithoutIterRight::new(iter_a,iter_b))
     }
 }
 
 pub struct WithoutIterRight<A,B>{
     iter_a : A,
-    iter_b : B
+    iter_b : B,
+    // the IDs in A but not in B if both have presence bitmaps
+    bits : Option<PresenceBits>
+}
+
+impl<A : QueryIterator,B : QueryIterator> WithoutIterRight<A,B> {
+    pub(in crate) fn new(iter_a : A,iter_b : B) -> Self {
+        let bits = PresenceBits::and_not(iter_a.presence(),iter_b.presence());
+        WithoutIterRight{
+            iter_a,
+            iter_b,
+            bits
+        }
+    }
 }
 
 impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithoutIterRight<A,B> {
     type Item = A::Item;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.bits.is_some() {
+            return self.next_with_id().map(|(_,item)|item);
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let None = self.iter_b.from_id(id) {
                 return Some(a);
@@ -90,10 +185,25 @@ impl<'a,A : QueryIterator,B : QueryIterator> Iterator for WithoutIterRight<A,B>
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(bits) = &self.bits {
+            return (bits.len(),Some(bits.len()));
+        }
+        (0,self.iter_a.size_hint().1)
+    }
 }
 
+impl<A : QueryIterator + FusedIterator,B : QueryIterator + FusedIterator> FusedIterator for WithoutIterRight<A,B> {}
+
 impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterRight<A,B> {
     fn from_id(&mut self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+            return self.iter_a.from_id(id);
+        }
         if let None = self.iter_b.from_id(id) {
             if let Some(a) = self.iter_a.from_id(id) {
                 return Some(a)
@@ -103,6 +213,12 @@ impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterRight<
     }
 
     fn next_with_id(&mut self) -> Option<(EntityId,Self::Item)> {
+        if let Some(bits) = &mut self.bits {
+            let index = bits.next()?;
+            // the bitmap ensures A has the entity
+            let id = self.iter_a.entity_of(index)?;
+            return self.iter_a.from_id(id).map(|item|(id,item));
+        }
         while let Some((id,a)) = self.iter_a.next_with_id() {
             if let None = self.iter_b.from_id(id) {
                 return Some((id,a));
@@ -110,4 +226,221 @@ impl<'a,A : QueryIterator,B : QueryIterator> QueryIterator for WithoutIterRight<
         }
         None
     }
+
+    fn presence(&self) -> Option<&[u64]> {
+        self.bits.as_ref().map(PresenceBits::bitmap)
+    }
+
+    fn entity_of(&self,index : usize) -> Option<EntityId> {
+        self.iter_a.entity_of(index)
+    }
+}
+
+impl<A : QueryGet,B : QueryGet> QueryGet for WithoutIterRight<A,B> {
+    fn peek_from_id(&self,id : EntityId) -> Option<Self::Item> {
+        if let Some(bits) = &self.bits {
+            if !bits.contains(id) {
+                return None;
+            }
+            return self.iter_a.peek_from_id(id);
+        }
+        if self.iter_b.peek_from_id(id).is_some() {
+            return None;
+        }
+        self.iter_a.peek_from_id(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, non_owning, partial_owning, FullOwning, NonOwning, PartialOwning}, query::{QueryIterator, Queryable, WithId, Without}, world::World};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>()
+            .register::<bool>();
+        for i in 0..20_u32 {
+            let id = world.create_entity().into_id();
+            if i % 4 != 0 {
+                world.attach_component(id,i);
+                world.attach_component(id,'a');
+                world.attach_component(id,i as u64);
+                world.attach_component(id,i as i8);
+                world.attach_component(id,i as i16);
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,true);
+            }
+        }
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<i8,i16>());
+        world
+    }
+
+    // The expected IDs which have all components but bool
+    fn expected(world : &World) -> Vec<EntityId> {
+        world.query::<(&u32,Without<&bool>)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>()
+    }
+
+    fn sorted<T : Ord>(mut ids : Vec<T>) -> Vec<T> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn group_without_test() {
+        let world = world();
+        let expected = sorted(expected(&world));
+        assert_eq!(expected.len(),10);
+
+        let iter = world.query::<(FullOwning<&u32,&char>,Without<&bool>)>();
+        // the group drives the iteration
+        assert_eq!(iter.size_hint(),(0,Some(15)));
+        let ids = iter.with_id().map(|(id,_)|id).collect::<Vec<_>>();
+        // the packed order of group is kept
+        let packed = world.query::<FullOwning<&u32,&char>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .filter(|id|expected.contains(id))
+            .collect::<Vec<_>>();
+        assert_eq!(ids,packed);
+
+        let ids = world.query::<(PartialOwning<&u64,&i8>,Without<&bool>)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(ids),expected);
+
+        let ids = world.query::<(NonOwning<&i8,&i16>,Without<&bool>)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(ids),expected);
+
+        let ids = world.query::<(Without<&bool>,FullOwning<&u32,&char>)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(ids),expected);
+    }
+
+    #[test]
+    fn group_without_mut_test() {
+        let world = world();
+        for (x,_) in world.query::<(FullOwning<&mut u32,&char>,Without<&bool>)>() {
+            *x += 100;
+        }
+        for (_,y) in world.query::<(PartialOwning<&u64,&mut i8>,Without<&bool>)>() {
+            *y = -*y;
+        }
+        let items = world.query::<((&u32,&i8),Without<&bool>)>()
+            .map(|(x,y)|(*x,*y))
+            .collect::<Vec<_>>();
+        assert!(items.iter().all(|(x,y)|*x >= 100 && *y < 0));
+        // entities with bool are untouched
+        let mut iter = world.query::<FullOwning<&u32,&char>>();
+        let ids = world.query::<(&u32,&bool)>().with_id().map(|(id,_)|id).collect::<Vec<_>>();
+        assert_eq!(ids.len(),5);
+        assert!(ids.iter().all(|id|*iter.from_id(*id).unwrap().0 < 100));
+    }
+
+    // A:u32,B:char,C:u64 for every shape of nesting
+    fn nested_world(presence : bool) -> (World,Vec<EntityId>) {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        if presence {
+            world.enable_presence_bitmap::<u32>()
+                .enable_presence_bitmap::<char>()
+                .enable_presence_bitmap::<u64>();
+        }
+        let ids = (0..24_u32).map(|i| {
+            let id = world.create_entity().into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,i);
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,'b');
+            }
+            if i % 4 != 3 {
+                world.attach_component(id,i as u64);
+            }
+            id
+        }).collect::<Vec<_>>();
+        (world,ids)
+    }
+
+    // Check both next_with_id() and from_id() of Q match the entities filtered by f,
+    // f gets whether entity has A,B and C
+    fn check<'a,Q : Queryable<'a>>(world : &'a World,ids : &[EntityId],f : fn((bool,bool,bool)) -> bool) {
+        let expected = ids.iter()
+            .cloned()
+            .filter(|id| f((world.has_component::<u32>(*id),
+                            world.has_component::<char>(*id),
+                            world.has_component::<u64>(*id))))
+            .collect::<Vec<_>>();
+        let name = std::any::type_name::<Q>();
+        let iterated = world.query::<Q>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(iterated),expected,"next of {}",name);
+        let mut iter = world.query::<Q>();
+        let matched = ids.iter()
+            .cloned()
+            .filter(|id|iter.from_id(*id).is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(matched,expected,"from_id of {}",name);
+    }
+
+    fn check_nested(presence : bool) {
+        let (world,ids) = nested_world(presence);
+        check::<(&u32,Without<&char>)>(&world,&ids,|(a,b,_)|a && !b);
+        check::<(Without<&char>,&u32)>(&world,&ids,|(a,b,_)|a && !b);
+        check::<((&u32,Without<&char>),&u64)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<(&u64,(&u32,Without<&char>))>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<(&u64,(Without<&char>,&u32))>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((Without<&char>,&u32),&u64)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((&u32,Without<&char>),(&u64,Without<&char>))>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((&u32,Without<&char>),Without<&u64>)>(&world,&ids,|(a,b,c)|a && !b && !c);
+        check::<(Without<&u64>,(&u32,Without<&char>))>(&world,&ids,|(a,b,c)|a && !b && !c);
+        check::<(&u32,Without<(&char,&u64)>)>(&world,&ids,|(a,b,c)|a && !(b && c));
+        check::<(&u32,Without<(&char,Without<&u64>)>)>(&world,&ids,|(a,b,c)|a && (!b || c));
+        check::<((&mut u32,Without<&char>),&mut u64)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<(&mut u64,(Without<&char>,&mut u32))>(&world,&ids,|(a,b,c)|a && !b && c);
+    }
+
+    #[test]
+    fn nested_from_id_test() {
+        check_nested(false);
+        check_nested(true);
+    }
+
+    fn check_nested_group(presence : bool) {
+        let (mut world,ids) = nested_world(presence);
+        world.make_group(full_owning::<u32,u64>());
+        world.make_group(non_owning::<char,u64>());
+        check::<(FullOwning<&u32,&u64>,Without<&char>)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<(&u32,(FullOwning<&u32,&u64>,Without<&char>))>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((FullOwning<&u32,&u64>,Without<&char>),&u32)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((Without<&char>,FullOwning<&u32,&u64>),&u64)>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<(&u64,(&u32,Without<NonOwning<&char,&u64>>))>(&world,&ids,|(a,b,c)|a && !b && c);
+        check::<((&u32,Without<&char>),NonOwning<&char,&u64>)>(&world,&ids,|_|false);
+        check::<(Without<FullOwning<&u32,&u64>>,(&char,Without<&u32>))>(&world,&ids,|(a,b,_)|b && !a);
+    }
+
+    #[test]
+    fn nested_group_from_id_test() {
+        check_nested_group(false);
+        check_nested_group(true);
+    }
 }
@@ -1,5 +1,6 @@
 use std::{fmt::{Debug, Display}, marker::PhantomData, ops::{Deref, DerefMut}};
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
+use crate::lock::{ResourceReadGuard, ResourceWriteGuard};
+use crate::world::World;
 
 /// The resource trait 
 pub trait Resource : Send + Sync + 'static {}
@@ -15,13 +16,15 @@ impl dyn Resource {
 }
 
 /// A read lock gurad for resource
+/// # Details
+/// It's also known as [ResourceRef].
 pub struct ResourceRead<'a,T> {
-    lock : RwLockReadGuard<'a,Box<dyn Resource>>,
+    lock : ResourceReadGuard<'a>,
     _marker : PhantomData<T>
 }
 
 impl<'a,T : Resource> ResourceRead<'a,T> {
-    pub(in crate) fn new(lock : RwLockReadGuard<'a,Box<dyn Resource>>) -> Self {
+    pub(in crate) fn new(lock : ResourceReadGuard<'a>) -> Self {
         ResourceRead {
             lock,
             _marker : Default::default()
@@ -61,14 +64,16 @@ impl<'a,T : Resource + Display> Display for ResourceRead<'a,T> {
 
 
 /// A write lock gurad for resource
+/// # Details
+/// It's also known as [ResourceMut].
 pub struct ResourceWrite<'a,T> {
-    lock : RwLockWriteGuard<'a,Box<dyn Resource>>,
+    lock : ResourceWriteGuard<'a>,
     _marker : PhantomData<T>
 }
 
 
 impl<'a,T : Resource> ResourceWrite<'a,T> {
-    pub(in crate) fn new(lock : RwLockWriteGuard<'a,Box<dyn Resource>>) -> Self {
+    pub(in crate) fn new(lock : ResourceWriteGuard<'a>) -> Self {
         ResourceWrite{
             lock,
             _marker : Default::default()
@@ -110,4 +115,35 @@ impl<'a,T : Resource + Display> Display for ResourceWrite<'a,T> {
         };
         data.fmt(f)
     }
-} 
+}
+
+/// The read guard returned by [resource](crate::world::World::resource)
+pub type ResourceRef<'a,T> = ResourceRead<'a,T>;
+/// The write guard returned by [resource_mut](crate::world::World::resource_mut)
+pub type ResourceMut<'a,T> = ResourceWrite<'a,T>;
+
+/// A descriptor of resource which a system needs.
+/// # Details
+/// ```&R``` fetches a [ResourceRef] and ```&mut R``` fetches a [ResourceMut].
+/// Use it by [fetch_resource](crate::world::World::fetch_resource).
+pub trait ResourceParam<'a> {
+    type Guard : 'a;
+
+    fn fetch(world : &'a World) -> Option<Self::Guard>;
+}
+
+impl<'a,R : Resource> ResourceParam<'a> for &'a R {
+    type Guard = ResourceRef<'a,R>;
+
+    fn fetch(world : &'a World) -> Option<Self::Guard> {
+        world.resource::<R>()
+    }
+}
+
+impl<'a,R : Resource> ResourceParam<'a> for &'a mut R {
+    type Guard = ResourceMut<'a,R>;
+
+    fn fetch(world : &'a World) -> Option<Self::Guard> {
+        world.resource_mut::<R>()
+    }
+}
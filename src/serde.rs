@@ -0,0 +1,556 @@
+//! # Serde support
+//! Save a whole [World](crate::world::World) and load it back by [serde](::serde).
+//! # Details
+//! Because components are type-erased in world,
+//! every component to be saved must be registered to a [SerializeRegistry] with a stable name.
+//! The saved data contains:
+//! * All entities,including the removed IDs which will be reused,
+//!   so an [EntityId](crate::entity::EntityId) is still valid after loading.
+//! * The entities and data of every registered storage.
+//!
+//! The components not in registry are skipped.
+//!
+//! Components registered by [register_serializable](crate::world::World::register_serializable)
+//! are saved by ```World```'s [Serialize](::serde::Serialize) and loaded by [load](crate::world::World::load)
+//! without a registry,they are named by their type names.
+//! ```World``` cannot be [Deserialize](::serde::Deserialize) because the component types must be known,
+//! so [load](crate::world::World::load) fills a world with the components registered.
+//! [Groups](crate::group) and resources are not saved,make the groups again after loading.
+//! [EntityId](crate::entity::EntityId) is serialized as its [packed bits](crate::entity::EntityId::to_bits),
+//! so it can be a field of component.
+//! # Example
+//! ```
+//! use serde::{Serialize, Deserialize};
+//! use xecs::{World, group::{full_owning, FullOwning}, serde::SerializeRegistry};
+//! #[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+//! struct Position(f32,f32);
+//! #[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+//! struct Velocity(f32,f32);
+//!
+//! let mut registry = SerializeRegistry::new();
+//! registry.register::<Position>("game::Position")
+//!     .register::<Velocity>("game::Velocity");
+//!
+//! let mut world = World::new();
+//! world.register::<Position>().register::<Velocity>();
+//! let id = world.create_entity()
+//!     .attach(Position(1.0,2.0))
+//!     .attach(Velocity(0.5,0.0))
+//!     .into_id();
+//!
+//! let json = world.serialize_with(&registry,serde_json::value::Serializer).unwrap();
+//! let mut loaded = World::deserialize_with(&registry,json).unwrap();
+//! loaded.make_group(full_owning::<Position,Velocity>());
+//! assert_eq!(loaded.query::<FullOwning<&Position,&Velocity>>().next(),
+//!            Some((&Position(1.0,2.0),&Velocity(0.5,0.0))));
+//! assert!(loaded.is_alive(id));
+//! ```
+use std::{any::TypeId, fmt::Formatter};
+use ::serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeStruct},
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer
+};
+use crate::{
+    component::{Component, ComponentStorage},
+    entity::{EntityId, EntityManager},
+    sparse_set::SparseSet,
+    world::World
+};
+
+impl Serialize for EntityId {
+    fn serialize<S : Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.to_bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityId {
+    fn deserialize<D : Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u64::deserialize(deserializer)?;
+        EntityId::from_bits(bits)
+            .ok_or_else(|| de::Error::custom(format!("{} is not a valid entity ID",bits)))
+    }
+}
+
+#[derive(Clone)]
+struct Registration {
+    name : &'static str,
+    type_id : TypeId,
+    register : fn(&mut World),
+    serialize : for<'a> fn(&'a (dyn ComponentStorage + 'static)) -> Box<dyn erased_serde::Serialize + 'a>,
+    deserialize : fn(&mut dyn erased_serde::Deserializer<'_>,&mut (dyn ComponentStorage + 'static)) -> Result<(),erased_serde::Error>
+}
+
+/// The components which can be saved and loaded with their stable names.
+/// # Details
+/// The name is written to the saved data instead of type name,
+/// so it should not be changed when the component is renamed or moved.
+#[derive(Clone)]
+pub struct SerializeRegistry {
+    registrations : Vec<Registration>
+}
+
+impl Default for SerializeRegistry {
+    fn default() -> Self {
+        SerializeRegistry::new()
+    }
+}
+
+impl SerializeRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        SerializeRegistry {
+            registrations : vec![]
+        }
+    }
+
+    /// Register ```T``` with ```name```.
+    /// # Panics
+    /// * Panic if ```T``` or ```name``` has been registered.
+    pub fn register<T>(&mut self,name : &'static str) -> &mut Self
+        where T : Component + Serialize + DeserializeOwned {
+        assert!(self.registrations.iter().all(|registration|registration.type_id != TypeId::of::<T>()),
+                "SerializeRegistry:Cannot register a component twice");
+        assert!(self.get(name).is_none(),
+                "SerializeRegistry:Cannot register a name twice");
+        self.registrations.push(Registration {
+            name,
+            type_id : TypeId::of::<T>(),
+            register : register_storage::<T>,
+            serialize : serialize_storage::<T>,
+            deserialize : deserialize_storage::<T>
+        });
+        self
+    }
+
+    /// Get the names of all registered components in the order of registering
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.registrations
+            .iter()
+            .map(|registration|registration.name)
+    }
+
+    fn get(&self,name : &str) -> Option<&Registration> {
+        self.registrations
+            .iter()
+            .find(|registration|registration.name == name)
+    }
+}
+
+fn register_storage<T : Component>(world : &mut World) {
+    if !world.has_registered::<T>() {
+        world.register::<T>();
+    }
+}
+
+fn serialize_storage<'a,T : Component + Serialize>(storage : &'a (dyn ComponentStorage + 'static)) -> Box<dyn erased_serde::Serialize + 'a> {
+    // Safety:
+    // the storage is chosen by the type ID of T
+    let sparse_set = unsafe { storage.downcast_ref::<SparseSet<EntityId,T>>() };
+    Box::new((sparse_set.entities(),sparse_set.data()))
+}
+
+fn deserialize_storage<T : Component + DeserializeOwned>(deserializer : &mut dyn erased_serde::Deserializer<'_>,
+                                                         storage : &mut (dyn ComponentStorage + 'static)) -> Result<(),erased_serde::Error> {
+    let (entities,data) : (Vec<EntityId>,Vec<T>) = erased_serde::deserialize(deserializer)?;
+    if entities.len() != data.len() {
+        return Err(de::Error::custom("the count of entities and data are mismatched"));
+    }
+    // Safety:
+    // the storage is chosen by the type ID of T
+    let sparse_set = unsafe { storage.downcast_mut::<SparseSet<EntityId,T>>() };
+    for (id,data) in entities.iter().zip(data) {
+        if sparse_set.exist(*id) {
+            return Err(de::Error::custom(format!("the entity {} is duplicated",id)));
+        }
+        sparse_set.add(*id,data);
+    }
+    Ok(())
+}
+
+/// The entities of world
+#[derive(Serialize,Deserialize)]
+struct EntitiesData {
+    alive : Vec<EntityId>,
+    // in the order they will be reused
+    removed : Vec<EntityId>,
+    max_id : Option<usize>
+}
+
+struct ComponentsRef<'a> {
+    world : &'a World,
+    registry : &'a SerializeRegistry
+}
+
+impl Serialize for ComponentsRef<'_> {
+    fn serialize<S : Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let storages = self.registry.registrations
+            .iter()
+            .filter_map(|registration| {
+                self.world.raw_storage_read(registration.type_id)
+                    .map(|storage|(registration,storage))
+            })
+            .collect::<Vec<_>>();
+        let mut map = serializer.serialize_map(Some(storages.len()))?;
+        for (registration,storage) in &storages {
+            let data = (registration.serialize)(&***storage);
+            map.serialize_entry(registration.name,&*data)?;
+        }
+        map.end()
+    }
+}
+
+struct StorageSeed<'a> {
+    registration : &'a Registration,
+    world : &'a World
+}
+
+impl<'de> DeserializeSeed<'de> for StorageSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        // Unwrap here never fails
+        // all components in registry are registered before loading
+        let mut storage = self.world.raw_storage_write(self.registration.type_id).unwrap();
+        if !storage.is_empty() {
+            return Err(de::Error::custom(format!("the component {} is duplicated",self.registration.name)));
+        }
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.registration.deserialize)(&mut deserializer,&mut **storage)
+            .map_err(de::Error::custom)
+    }
+}
+
+struct ComponentsSeed<'a> {
+    registry : &'a SerializeRegistry,
+    world : &'a World
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a map of components")
+    }
+
+    fn visit_map<A : MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            let registration = self.registry.get(&name)
+                .ok_or_else(|| de::Error::custom(format!("the component {} is not registered",name)))?;
+            map.next_value_seed(StorageSeed {
+                registration,
+                world : self.world
+            })?;
+        }
+        Ok(())
+    }
+}
+
+const FIELDS : &[&str] = &["entities","components"];
+
+struct WorldVisitor<'a> {
+    registry : &'a SerializeRegistry
+}
+
+impl WorldVisitor<'_> {
+    fn new_world(&self) -> World {
+        let mut world = World::new();
+        for registration in &self.registry.registrations {
+            (registration.register)(&mut world);
+        }
+        world
+    }
+
+    fn finish<E : de::Error>(&self,mut world : World,entities : EntitiesData) -> Result<World,E> {
+        let manager = EntityManager::from_raw(entities.alive,&entities.removed,entities.max_id)
+            .ok_or_else(|| de::Error::custom("the indices of entities are invalid"))?;
+        world.set_entity_manager(manager);
+        for registration in &self.registry.registrations {
+            // Unwrap here never fails
+            // all components in registry are registered before loading
+            let storage = world.raw_storage_read(registration.type_id).unwrap();
+            let dead = (0..storage.count())
+                .filter_map(|index|storage.id(index))
+                .find(|id|!world.is_alive(*id));
+            if let Some(id) = dead {
+                return Err(de::Error::custom(format!("the entity {} of component {} is not alive",id,registration.name)));
+            }
+        }
+        Ok(world)
+    }
+}
+
+impl<'de> Visitor<'de> for WorldVisitor<'_> {
+    type Value = World;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a world")
+    }
+
+    fn visit_seq<A : SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let world = self.new_world();
+        let entities = seq.next_element::<EntitiesData>()?
+            .ok_or_else(|| de::Error::invalid_length(0,&self))?;
+        seq.next_element_seed(ComponentsSeed {
+                registry : self.registry,
+                world : &world
+            })?
+            .ok_or_else(|| de::Error::invalid_length(1,&self))?;
+        self.finish(world,entities)
+    }
+
+    fn visit_map<A : MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let world = self.new_world();
+        let mut entities = None;
+        let mut components = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "entities" => {
+                    if entities.is_some() {
+                        return Err(de::Error::duplicate_field("entities"));
+                    }
+                    entities = Some(map.next_value::<EntitiesData>()?);
+                },
+                "components" => {
+                    if components {
+                        return Err(de::Error::duplicate_field("components"));
+                    }
+                    map.next_value_seed(ComponentsSeed {
+                        registry : self.registry,
+                        world : &world
+                    })?;
+                    components = true;
+                },
+                _ => return Err(de::Error::unknown_field(&key,FIELDS))
+            }
+        }
+        let entities = entities.ok_or_else(|| de::Error::missing_field("entities"))?;
+        if !components {
+            return Err(de::Error::missing_field("components"));
+        }
+        self.finish(world,entities)
+    }
+}
+
+/// Save all entities and the components registered by [register_serializable](World::register_serializable)
+impl Serialize for World {
+    fn serialize<S : Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with(self.serializable(),serializer)
+    }
+}
+
+impl World {
+    /// Save all entities and the components in ```registry``` by ```serializer```.
+    /// # Details
+    /// The components which are not registered in world are skipped.
+    /// See [serde](crate::serde) for what is saved.
+    pub fn serialize_with<S : Serializer>(&self,registry : &SerializeRegistry,serializer : S) -> Result<S::Ok,S::Error> {
+        let entities = {
+            let manager = self.entity_manager_read();
+            EntitiesData {
+                alive : manager.entities().to_vec(),
+                removed : manager.removed(),
+                max_id : manager.max_id()
+            }
+        };
+        let mut state = serializer.serialize_struct("World",2)?;
+        state.serialize_field("entities",&entities)?;
+        state.serialize_field("components",&ComponentsRef {
+            world : self,
+            registry
+        })?;
+        state.end()
+    }
+
+    /// Load the entities and components saved by [serialize](Serialize::serialize) into this world.
+    /// # Details
+    /// The components are chosen by [register_serializable](World::register_serializable),
+    /// the saved IDs are kept and the groups of this world are rebuilt.
+    /// So a world with all components registered and groups made can be used as a template of loading.
+    /// # Errors
+    /// See [deserialize_with](World::deserialize_with),the world is not changed if it fails.
+    /// # Panics
+    /// * Panic if world has any entity.
+    /// # Example
+    /// ```
+    /// use xecs::{World, group::{full_owning, FullOwning}};
+    /// #[derive(Debug,Clone,Copy,PartialEq,serde::Serialize,serde::Deserialize)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.register_serializable::<Health>()
+    ///     .register_serializable::<char>();
+    /// let id = world.create_entity().attach(Health(10)).attach('a').into_id();
+    /// let json = serde_json::to_string(&world).unwrap();
+    ///
+    /// let mut loaded = World::new();
+    /// loaded.register_serializable::<Health>()
+    ///     .register_serializable::<char>();
+    /// loaded.make_group(full_owning::<Health,char>());
+    /// loaded.load(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+    /// assert_eq!(loaded.query::<FullOwning<&Health,&char>>().next(),Some((&Health(10),&'a')));
+    /// assert!(loaded.is_alive(id));
+    /// ```
+    pub fn load<'de,D : Deserializer<'de>>(&mut self,deserializer : D) -> Result<(),D::Error> {
+        assert!(self.entity_manager_read().entities().is_empty(),
+                "World:Cannot load because world has entities");
+        let loaded = World::deserialize_with(self.serializable(),deserializer)?;
+        self.absorb_loaded(loaded);
+        Ok(())
+    }
+
+    /// Load a world saved by [serialize_with](World::serialize_with).
+    /// # Details
+    /// All components in ```registry``` are registered in the new world.
+    /// # Errors
+    /// * A component in data is not in ```registry```.
+    /// * The entities are invalid,or an entity of component is not alive.
+    pub fn deserialize_with<'de,D : Deserializer<'de>>(registry : &SerializeRegistry,deserializer : D) -> Result<World,D::Error> {
+        deserializer.deserialize_struct("World",FIELDS,WorldVisitor { registry })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::{entity::EntityId, group::{full_owning, FullOwning}, query::WithId, world::World};
+    use super::SerializeRegistry;
+
+    fn registry() -> SerializeRegistry {
+        let mut registry = SerializeRegistry::new();
+        registry.register::<u32>("u32")
+            .register::<char>("char");
+        registry
+    }
+
+    fn items(world : &World) -> Vec<(EntityId,u32,Option<char>)> {
+        let mut items = world.query::<&u32>()
+            .with_id()
+            .map(|(id,x)|(id,*x,world.get::<char>(id).map(|c|*c)))
+            .collect::<Vec<_>>();
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        world.make_group(full_owning::<u32,char>());
+        let ids = (0..20_u32).map(|i| {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 3 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.remove_entity(ids[5]);
+        world.remove_entity(ids[9]);
+        world.remove_entity(ids[6]);
+        let reused = world.create_entity().attach(100_u32).into_id();
+        assert_eq!(reused.index(),ids[6].index());
+
+        let registry = registry();
+        let value = world.serialize_with(&registry,serde_json::value::Serializer).unwrap();
+        let text = serde_json::to_string(&value).unwrap();
+        let mut loaded = World::deserialize_with(&registry,&mut serde_json::Deserializer::from_str(&text)).unwrap();
+
+        assert_eq!(loaded.entity_manager_read().entities(),world.entity_manager_read().entities());
+        assert_eq!(items(&loaded),items(&world));
+        assert!(!loaded.has_registered::<u64>());
+        assert!(!loaded.is_alive(ids[6]));
+        assert!(loaded.is_alive(reused));
+
+        // the removed IDs are reused in the same order
+        let a = world.create_entity().into_id();
+        let b = loaded.create_entity().into_id();
+        assert_eq!(a,b);
+        assert_eq!(b.index(),ids[9].index());
+        assert_eq!(b.generation(),ids[9].generation().next().unwrap());
+
+        loaded.make_group(full_owning::<u32,char>());
+        let mut grouped = loaded.query::<FullOwning<&u32,&char>>()
+            .map(|(x,_)|*x)
+            .collect::<Vec<_>>();
+        grouped.sort();
+        assert_eq!(grouped,vec![0,3,12,15,18]);
+    }
+
+    #[test]
+    fn load_test() {
+        let template = || {
+            let mut world = World::new();
+            world.register_serializable::<u32>()
+                .register_serializable::<char>()
+                .register::<u64>();
+            world.make_group(full_owning::<u32,char>());
+            world
+        };
+        let world = template();
+        let ids = (0..10_u32).map(|i| {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.remove_entity(ids[4]);
+        let text = serde_json::to_string(&world).unwrap();
+        assert!(text.contains(std::any::type_name::<char>()));
+
+        let mut loaded = template();
+        assert!(loaded.load(&mut serde_json::Deserializer::from_str("{}")).is_err());
+        assert!(loaded.entity_manager_read().entities().is_empty());
+        loaded.load(&mut serde_json::Deserializer::from_str(&text)).unwrap();
+
+        assert_eq!(loaded.entity_manager_read().entities(),world.entity_manager_read().entities());
+        assert_eq!(items(&loaded),items(&world));
+        assert_eq!(loaded.query::<&u64>().count(),0);
+        let mut grouped = loaded.query::<FullOwning<&u32,&char>>()
+            .map(|(x,_)|*x)
+            .collect::<Vec<_>>();
+        grouped.sort();
+        assert_eq!(grouped,vec![0,2,6,8]);
+        assert_eq!(loaded.create_entity().into_id(),world.create_entity().into_id());
+    }
+
+    #[test]
+    fn error_test() {
+        let registry = registry();
+        let load = |value : serde_json::Value| World::deserialize_with(&registry,value).map(|_|());
+
+        let entities = json!({
+            "alive" : [1_u64 << 32 | 1,1_u64 << 32 | 2],
+            "removed" : [],
+            "max_id" : null
+        });
+        assert!(load(json!({ "entities" : entities, "components" : { "u32" : [[1_u64 << 32 | 2],[7]] } })).is_ok());
+        // the world can be a sequence too
+        assert!(load(json!([entities,{ "u32" : [[1_u64 << 32 | 2],[7]] }])).is_ok());
+        // unknown component
+        assert!(load(json!({ "entities" : entities, "components" : { "f32" : [[],[]] } })).is_err());
+        // the entity of component is not alive
+        assert!(load(json!({ "entities" : entities, "components" : { "u32" : [[2_u64 << 32 | 2],[7]] } })).is_err());
+        // mismatched count
+        assert!(load(json!({ "entities" : entities, "components" : { "u32" : [[1_u64 << 32 | 2],[7,8]] } })).is_err());
+        // the index 2 is missing
+        let entities = json!({ "alive" : [1_u64 << 32 | 1,1_u64 << 32 | 3], "removed" : [], "max_id" : null });
+        assert!(load(json!({ "entities" : entities, "components" : {} })).is_err());
+        // invalid ID
+        let entities = json!({ "alive" : [1], "removed" : [], "max_id" : null });
+        assert!(load(json!({ "entities" : entities, "components" : {} })).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! # Snapshot
+//! Copy a whole [World](crate::world::World) and restore it later,it's useful for rollback.
+//! # Details
+//! Because components are type-erased in world,
+//! every component to be copied must be registered to a [CloneRegistry].
+//! A [WorldSnapshot] contains:
+//! * All entities,including the removed IDs which will be reused,
+//!   so the entities created after restoring get the same IDs again.
+//! * The storages of every registered component in their current order.
+//! * The data of all [groups](crate::group),so the queries yield the same items in the same order.
+//! * The [parents](crate::world::World::set_parent) of entities.
+//!
+//! The components not in registry are not copied,they are cleared when restoring.
+//! Resources are not copied.
+//! # Example
+//! ```
+//! use xecs::{World, snapshot::CloneRegistry};
+//! #[derive(Debug,Clone,Copy,PartialEq)]
+//! struct Position(f32);
+//!
+//! let mut registry = CloneRegistry::new();
+//! registry.register::<Position>();
+//!
+//! let mut world = World::new();
+//! world.register::<Position>();
+//! let id = world.create_entity().attach(Position(1.0)).into_id();
+//! let snapshot = world.snapshot(&registry);
+//!
+//! world.get_mut::<Position>(id).unwrap().0 = 2.0;
+//! world.remove_entity(id);
+//! assert!(!world.is_alive(id));
+//!
+//! world.restore(&snapshot);
+//! assert!(world.is_alive(id));
+//! assert_eq!(world.get::<Position>(id).as_deref(),Some(&Position(1.0)));
+//! ```
+use std::{any::TypeId, collections::HashMap};
+use crate::{
+    component::{Component, ComponentStorage, StorageKind},
+    entity::{EntityId, EntityManager},
+    group::Group,
+    hierarchy::Hierarchy,
+    sparse_set::SparseSet
+};
+
+type CloneFn = fn(&(dyn ComponentStorage + 'static)) -> Box<dyn ComponentStorage>;
+
+fn clone_storage<T : Component + Clone>(storage : &(dyn ComponentStorage + 'static)) -> Box<dyn ComponentStorage> {
+    // Safety:
+    // storage is SparseSet<EntityId,T> because it's registered with T
+    let sparse_set = unsafe {
+        storage.downcast_ref::<SparseSet<EntityId,T>>()
+    };
+    Box::new(sparse_set.clone())
+}
+
+/// The components which can be copied by [snapshot](crate::world::World::snapshot)
+#[derive(Clone)]
+pub struct CloneRegistry {
+    clones : HashMap<TypeId,CloneFn>
+}
+
+impl Default for CloneRegistry {
+    fn default() -> Self {
+        CloneRegistry::new()
+    }
+}
+
+impl CloneRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        CloneRegistry {
+            clones : HashMap::new()
+        }
+    }
+
+    /// Register ```T```.
+    /// # Panics
+    /// * Panic if ```T``` has been registered.
+    pub fn register<T : Component + Clone>(&mut self) -> &mut Self {
+        let old = self.clones.insert(TypeId::of::<T>(),clone_storage::<T>);
+        assert!(old.is_none(),"CloneRegistry:Cannot register a component twice");
+        self
+    }
+
+    /// Check if ```T``` is registered
+    pub fn has_registered<T : Component>(&self) -> bool {
+        self.clones.contains_key(&TypeId::of::<T>())
+    }
+
+    pub(in crate) fn get(&self,type_id : TypeId) -> Option<CloneFn> {
+        self.clones.get(&type_id).copied()
+    }
+}
+
+pub(in crate) struct StorageSnapshot {
+    pub(in crate) type_id : TypeId,
+    pub(in crate) name : &'static str,
+    pub(in crate) kind : StorageKind,
+    pub(in crate) storage : Box<dyn ComponentStorage>,
+    pub(in crate) clone : CloneFn
+}
+
+impl StorageSnapshot {
+    pub(in crate) fn clone_storage(&self) -> Box<dyn ComponentStorage> {
+        (self.clone)(&*self.storage)
+    }
+}
+
+/// A copy of [World](crate::world::World) made by [snapshot](crate::world::World::snapshot)
+/// # Details
+/// It can be [restored](crate::world::World::restore) many times.
+pub struct WorldSnapshot {
+    pub(in crate) entity_manager : EntityManager,
+    pub(in crate) storages : Vec<StorageSnapshot>,
+    pub(in crate) groups : Vec<Group>,
+    pub(in crate) bindings : HashMap<EntityId,Vec<EntityId>>,
+    pub(in crate) hierarchy : Hierarchy
+}
+
+impl WorldSnapshot {
+    /// Get the count of entities in snapshot
+    pub fn entity_count(&self) -> usize {
+        self.entity_manager.len()
+    }
+
+    /// Check if ```T``` is copied in snapshot
+    pub fn has_component<T : Component>(&self) -> bool {
+        self.storage(TypeId::of::<T>()).is_some()
+    }
+
+    pub(in crate) fn storage(&self,type_id : TypeId) -> Option<&StorageSnapshot> {
+        self.storages.iter()
+            .find(|storage|storage.type_id == type_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, group::{full_owning, non_owning, FullOwning, NonOwning}, query::WithId, world::World};
+    use super::CloneRegistry;
+
+    #[derive(Debug,Clone,PartialEq)]
+    struct Name(String);
+
+    type Items = (
+        Vec<(EntityId,(u32,char))>,
+        Vec<(EntityId,(u32,u8))>,
+        Vec<(EntityId,(u32,Name))>
+    );
+
+    // the query results in their order
+    fn items(world : &World) -> Items {
+        (
+            world.query::<FullOwning<&u32,&char>>()
+                .with_id()
+                .map(|(id,(x,c))|(id,(*x,*c)))
+                .collect(),
+            world.query::<NonOwning<&u32,&u8>>()
+                .with_id()
+                .map(|(id,(x,y))|(id,(*x,*y)))
+                .collect(),
+            world.query::<(&u32,&Name)>()
+                .with_id()
+                .map(|(id,(x,name))|(id,(*x,name.clone())))
+                .collect()
+        )
+    }
+
+    #[test]
+    fn snapshot_restore_test() {
+        let mut registry = CloneRegistry::new();
+        registry.register::<u32>()
+            .register::<char>()
+            .register::<u8>()
+            .register::<Name>();
+        assert!(registry.has_registered::<Name>());
+        assert!(!registry.has_registered::<u64>());
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u8>()
+            .register::<Name>()
+            .register::<u64>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(non_owning::<u32,u8>());
+        let ids = (0..20_u32).map(|i| {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,Name(i.to_string()));
+            }
+            if i % 5 != 0 {
+                world.attach_component(id,i as u8);
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.remove_entity(ids[4]);
+        world.remove_entity(ids[7]);
+
+        let snapshot = world.snapshot(&registry);
+        let before = items(&world);
+        assert_eq!(snapshot.entity_count(),18);
+        assert!(snapshot.has_component::<Name>());
+        assert!(!snapshot.has_component::<u64>());
+
+        // spawn,despawn and modify
+        let spawned = world.create_entity().attach(100_u32).attach('b').into_id();
+        world.remove_entity(ids[0]);
+        world.remove_entity(ids[3]);
+        world.detach_component::<char>(ids[2]);
+        world.attach_component(ids[5],Name("new".to_string()));
+        world.attach_component(ids[10],10_u8);
+        for x in world.query::<&mut u32>() {
+            *x += 1;
+        }
+        assert_ne!(items(&world),before);
+
+        world.restore(&snapshot);
+        assert_eq!(items(&world),before);
+        assert!(world.is_alive(ids[0]));
+        assert!(!world.is_alive(ids[4]));
+        assert!(!world.is_alive(spawned));
+        // u64 is not in registry
+        assert_eq!(world.query::<&u64>().count(),0);
+
+        // the same IDs are allocated again
+        let a = world.create_entity().attach(1_u32).into_id();
+        assert_eq!(a,spawned);
+
+        // restore again
+        world.restore(&snapshot);
+        assert_eq!(items(&world),before);
+        assert!(!world.is_alive(a));
+    }
+
+    #[test]
+    fn restore_epoch_test() {
+        let mut registry = CloneRegistry::new();
+        registry.register::<u32>();
+        let mut world = World::new();
+        world.register::<u32>();
+        world.create_entity().attach(1_u32);
+
+        let epoch = world.epoch();
+        let snapshot = world.snapshot(&registry);
+        world.create_entity().attach(2_u32);
+        world.restore(&snapshot);
+        // the storages are the same as the epoch,but they were replaced
+        assert!(world.check_epoch(&epoch).is_err());
+
+        let epoch = world.epoch();
+        world.restore(&snapshot);
+        assert!(world.check_epoch(&epoch).is_err());
+    }
+}
@@ -1,23 +1,89 @@
-use std::num::NonZeroUsize;
+use std::{cell::UnsafeCell, fmt::{Debug, Formatter}, iter::{Copied, Zip}, num::NonZeroUsize, ops::Range, slice, sync::atomic::{AtomicBool, Ordering}};
+use crate::query::bit_of;
+
+/// The second dense buffer of a double-buffered sparse set.
+/// # Details
+/// It's aligned with ```data``` by every structural change.
+/// It can be borrowed mutably through ```&SparseSet```,
+/// so it can be written while other queries are reading the front buffer.
+/// The ```borrowed``` flag ensures there is at most one borrow.
+pub(in crate) struct BackBuffer<T> {
+    data : UnsafeCell<Vec<T>>,
+    borrowed : AtomicBool,
+    clone : fn(&T) -> T
+}
+
+// Safety:
+// data is only accessed by &mut BackBuffer or by the one who sets borrowed flag
+unsafe impl<T : Send> Send for BackBuffer<T> {}
+unsafe impl<T : Send + Sync> Sync for BackBuffer<T> {}
+
+impl<T> BackBuffer<T> {
+    fn data_mut(&mut self) -> &mut Vec<T> {
+        self.data.get_mut()
+    }
+}
+
+impl<T> Clone for BackBuffer<T> {
+    fn clone(&self) -> Self {
+        assert!(!self.borrowed.load(Ordering::Acquire),
+                "SparseSet:Cannot clone back buffer while it is borrowed");
+        // Safety:
+        // it's not borrowed,checked above
+        let data = unsafe { &*self.data.get() };
+        BackBuffer {
+            data : UnsafeCell::new(data.iter().map(self.clone).collect()),
+            borrowed : AtomicBool::new(false),
+            clone : self.clone
+        }
+    }
+}
+
+impl<T> Debug for BackBuffer<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackBuffer")
+            .field("borrowed",&self.borrowed.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
 
 #[derive(Debug,Clone)]
 pub(in crate) struct SparseSet<E,T>
-    where E : Copy + Into<usize>,
+    where E : Copy + Into<usize> + PartialEq,
           T : Sized{
     pub (in crate) indices : Vec<Option<NonZeroUsize>>,
     pub (in crate) entities :  Vec<E>,
-    pub (in crate) data : Vec<T>
+    pub (in crate) data : Vec<T>,
+    /// Increased by every structural change
+    pub (in crate) version : u64,
+    /// The back buffer if it's double-buffered
+    pub (in crate) back : Option<BackBuffer<T>>,
+    /// The change tick of every data,0 means never changed
+    pub (in crate) ticks : Vec<u64>,
+    /// The tick when every data was added,0 means unknown
+    pub (in crate) added : Vec<u64>,
+    /// The version when every data was added,
+    /// it's different every time an entity gets the data again
+    pub (in crate) stamps : Vec<u64>,
+    /// The presence bitmap indexed by entity if it's enabled
+    pub (in crate) presence : Option<Vec<u64>>
 }
 
 impl<E,T> SparseSet<E,T>
-    where E : Copy + Into<usize>,
+    where E : Copy + Into<usize> + PartialEq,
           T : Sized {
 
     pub fn new() -> Self {
         SparseSet{
             indices: vec![],
             entities: vec![],
-            data: vec![]
+            data: vec![],
+            version: 0,
+            back: None,
+            ticks: vec![],
+            added: vec![],
+            stamps: vec![],
+            presence: None
         }
     }
 
@@ -25,6 +91,16 @@ impl<E,T> SparseSet<E,T>
         self.indices.clear();
         self.entities.clear();
         self.data.clear();
+        self.ticks.clear();
+        self.added.clear();
+        self.stamps.clear();
+        if let Some(back) = &mut self.back {
+            back.data_mut().clear();
+        }
+        if let Some(presence) = &mut self.presence {
+            presence.clear();
+        }
+        self.version += 1;
     }
 
     pub fn add(&mut self,entity : E,data : T) {
@@ -36,20 +112,54 @@ impl<E,T> SparseSet<E,T>
         if let Some(index) = self.indices[entity_] {
             //already exists
             //overwrite
-            self.data[index.get() - 1] = data;
+            let index = index.get() - 1;
+            if self.entities[index] != entity {
+                // the stored entity is another generation of this index,
+                // so this is a new component of entity
+                self.entities[index] = entity;
+                self.ticks[index] = 0;
+                self.added[index] = 0;
+                self.version += 1;
+                self.stamps[index] = self.version;
+            }
+            if let Some(back) = &mut self.back {
+                back.data_mut()[index] = (back.clone)(&data);
+            }
+            self.data[index] = data;
         }else{
             //not yet exist
             self.indices[entity_] = NonZeroUsize::new(self.entities.len() + 1);
             self.entities.push(entity);
+            if let Some(back) = &mut self.back {
+                let value = (back.clone)(&data);
+                back.data_mut().push(value);
+            }
             self.data.push(data);
+            self.ticks.push(0);
+            self.added.push(0);
+            self.set_presence(entity_,true);
+            self.version += 1;
+            self.stamps.push(self.version);
         }
     }
 
+    /// Append ```entities``` with their ```data``` to the end.
+    /// # Details
+    /// The entities must be distinct and not in sparse set,
+    /// they are not checked like [add](SparseSet::add) for speed.
     pub fn add_batch(&mut self,entities : &[E],mut data : Vec<T>) {
         assert_eq!(entities.len(),data.len());
+        self.version += 1;
         let start_index = self.entities.len();
         // copy data to dense
         self.entities.extend_from_slice(entities);
+        if let Some(back) = &mut self.back {
+            let clone = back.clone;
+            back.data_mut().extend(data.iter().map(clone));
+        }
+        self.ticks.resize(self.ticks.len() + data.len(),0);
+        self.added.resize(self.added.len() + data.len(),0);
+        self.stamps.resize(self.stamps.len() + data.len(),self.version);
         self.data.append(&mut data);
         // store data in sparse
         for (index,entity) in entities.iter().enumerate() {
@@ -62,22 +172,56 @@ impl<E,T> SparseSet<E,T>
             self.indices[entity_] = Some(unsafe {
                 NonZeroUsize::new_unchecked(start_index + index + 1)
             });
+            self.set_presence(entity_,true);
         }
     }
 
     pub fn remove(&mut self,entity : E) -> Option<T> {
+        let index = self.get_index(entity)?;
         let entity : usize = entity.into();
-        if self.indices.len() < entity {
-            return None;
+        self.indices.swap(self.entities[index].into(),(*self.entities.last().unwrap()).into());
+        self.indices[entity] = None;
+        self.set_presence(entity,false);
+        self.entities.swap_remove(index);
+        self.version += 1;
+        if let Some(back) = &mut self.back {
+            back.data_mut().swap_remove(index);
         }
-        if let Some(index) = self.indices[entity] {
-            let index = index.get() - 1;
-            self.indices.swap(self.entities[index].into(),(*self.entities.last().unwrap()).into());
-            self.indices[entity] = None;
-            self.entities.swap_remove(index);
-            return Some(self.data.swap_remove(index));
+        self.ticks.swap_remove(index);
+        self.added.swap_remove(index);
+        self.stamps.swap_remove(index);
+        Some(self.data.swap_remove(index))
+    }
+
+    /// Keep the items which ```f``` returns ```true```,remove the others.
+    /// # Details
+    /// The items are visited from the back,
+    /// so every removed item is swapped with an item which has been kept.
+    pub fn retain<F : FnMut(E,&mut T) -> bool>(&mut self,mut f : F) {
+        for index in (0..self.len()).rev() {
+            let entity = self.entities[index];
+            if !f(entity,&mut self.data[index]) {
+                self.remove(entity);
+            }
+        }
+    }
+
+    /// Remove all items and yield them in dense order.
+    /// # Details
+    /// All items are removed even if the iterator is dropped before the end.
+    pub fn drain(&mut self) -> impl Iterator<Item = (E,T)> + '_ {
+        self.indices.clear();
+        self.ticks.clear();
+        self.added.clear();
+        self.stamps.clear();
+        if let Some(back) = &mut self.back {
+            back.data_mut().clear();
         }
-        None
+        if let Some(presence) = &mut self.presence {
+            presence.clear();
+        }
+        self.version += 1;
+        self.entities.drain(..).zip(self.data.drain(..))
     }
 
     pub(in crate) fn swap_by_index(&mut self,index_a : usize,index_b : usize) {
@@ -93,6 +237,13 @@ impl<E,T> SparseSet<E,T>
         self.indices.swap(entity_a,entity_b);
         self.entities.swap(index_a,index_b);
         self.data.swap(index_a,index_b);
+        self.ticks.swap(index_a,index_b);
+        self.added.swap(index_a,index_b);
+        self.stamps.swap(index_a,index_b);
+        if let Some(back) = &mut self.back {
+            back.data_mut().swap(index_a,index_b);
+        }
+        self.version += 1;
     }
 
     #[allow(unused)]
@@ -111,6 +262,19 @@ impl<E,T> SparseSet<E,T>
         self.indices.swap(entity_a,entity_b);
         self.entities.swap(index_a,index_b);
         self.data.swap(index_a,index_b);
+        self.ticks.swap(index_a,index_b);
+        self.added.swap(index_a,index_b);
+        self.stamps.swap(index_a,index_b);
+        if let Some(back) = &mut self.back {
+            back.data_mut().swap(index_a,index_b);
+        }
+        self.version += 1;
+    }
+
+    /// Get the structural version,
+    /// it's increased when an entity is added,removed or moved
+    pub fn version(&self) -> u64 {
+        self.version
     }
 
     pub fn len(&self) -> usize {
@@ -118,23 +282,36 @@ impl<E,T> SparseSet<E,T>
     }
 
     pub fn exist(&self,entity : E) -> bool {
-        let entity : usize = entity.into();
-        if entity < self.indices.len()  {
-            self.indices[entity].is_some()
-        }else{
-            false
+        self.get_index(entity).is_some()
+    }
+
+    /// Check if every entity in ```entities``` exists,
+    /// and write the results to ```out```.
+    /// # Panics
+    /// Panic if ```entities.len()``` is not equal to ```out.len()```
+    pub fn contains_many(&self,entities : &[E],out : &mut [bool]) {
+        assert_eq!(entities.len(),out.len(),
+                   "SparseSet:Cannot check entities because the length of output is mismatched");
+        let len = self.indices.len();
+        if len == 0 {
+            out.fill(false);
+            return;
+        }
+        let indices = self.indices.as_slice();
+        for (entity,out) in entities.iter().zip(out.iter_mut()) {
+            let index : usize = (*entity).into();
+            // clamp the entity instead of branching,
+            // so the bounds check can be hoisted out of the loop.
+            let clamped = index.min(len - 1);
+            *out = (index < len) & indices[clamped]
+                .map(|dense|self.entities[dense.get() - 1] == *entity)
+                .unwrap_or(false);
         }
     }
 
     pub fn get(&self,entity : E) -> Option<&T> {
-        let entity : usize = entity.into();
-        if entity< self.indices.len() {
-            if let Some(index) = self.indices[entity] {
-                let index = index.get() - 1;
-                return Some(&self.data[index])
-            }
-        }
-        None
+        let index = self.get_index(entity)?;
+        Some(&self.data[index])
     }
 
     pub unsafe fn get_unchecked(&self,entity : E) -> &T {
@@ -144,14 +321,8 @@ impl<E,T> SparseSet<E,T>
     }
 
     pub fn get_mut(&mut self,entity : E) -> Option<&mut T> {
-        let entity : usize = entity.into();
-        if entity < self.indices.len() {
-            if let Some(index) = self.indices[entity] {
-                let index = index.get() - 1;
-                return Some(&mut self.data[index])
-            }
-        }
-        None
+        let index = self.get_index(entity)?;
+        Some(&mut self.data[index])
     }
 
     pub unsafe fn get_unchecked_mut(&mut self,entity : E) -> &mut T {
@@ -160,20 +331,56 @@ impl<E,T> SparseSet<E,T>
         self.data.get_unchecked_mut(index - 1)
     }
 
+    /// Get the [Entry] of ```entity``` for in-place manipulation
+    pub fn entry(&mut self,entity : E) -> Entry<'_,E,T> {
+        match self.get_index(entity) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                sparse_set : self,
+                entity,
+                index
+            }),
+            None => Entry::Vacant(VacantEntry {
+                sparse_set : self,
+                entity
+            })
+        }
+    }
+
+    /// Get the index of ```entity``` in dense arrays.
+    /// # Details
+    /// Return ```None``` if the stored entity is not equal to ```entity```,
+    /// like a stale [EntityId](crate::entity::EntityId) whose index was reused.
     pub fn get_index(&self,entity : E) -> Option<usize> {
-        let entity : usize = entity.into();
-        if entity < self.indices.len() {
-            if let Some(index) = self.indices[entity] {
-                return Some(index.get() - 1);
-            }
+        let index = (*self.indices.get(entity.into())?)?.get() - 1;
+        if self.entities[index] == entity {
+            Some(index)
+        } else {
+            None
         }
-        None
+    }
+
+    /// Get the entity stored at ```index``` of sparse array.
+    /// # Details
+    /// It restores the entity from an index,like the bits of [presence](SparseSet::presence).
+    pub(in crate) fn entity_at(&self,index : usize) -> Option<E> {
+        let index = (*self.indices.get(index)?)?.get();
+        Some(self.entities[index - 1])
     }
 
     pub fn is_empty(&self) -> bool {
         self.entities.len() == 0
     }
 
+    /// Get the indices in ```range``` sorted by their data with ```cmp```,the sort is stable.
+    /// # Details
+    /// Nothing is moved,rearrange the items by [permute].
+    pub(in crate) fn sorted_indices<F>(&self,range : Range<usize>,mut cmp : F) -> Vec<usize>
+        where F : FnMut(&T,&T) -> std::cmp::Ordering {
+        let mut indices = range.collect::<Vec<_>>();
+        indices.sort_by(|a,b| cmp(&self.data[*a],&self.data[*b]));
+        indices
+    }
+
     #[allow(unused)]
     pub fn indices(&self) -> &[Option<NonZeroUsize>] {
         self.indices.as_slice()
@@ -195,11 +402,364 @@ impl<E,T> SparseSet<E,T>
     pub fn data_mut(&mut self) -> &mut [T] {
         self.data.as_mut_slice()
     }
+
+    /// Iterate the entities with their data in dense order
+    pub fn iter(&self) -> Iter<'_,E,T> {
+        self.entities.iter().copied().zip(self.data.iter())
+    }
+
+    /// Iterate the entities with their mutable data in dense order
+    pub fn iter_mut(&mut self) -> IterMut<'_,E,T> {
+        self.entities.iter().copied().zip(self.data.iter_mut())
+    }
+
+    /// Get the change tick of data at ```index```
+    pub(in crate) fn tick(&self,index : usize) -> Option<u64> {
+        self.ticks.get(index).cloned()
+    }
+
+    /// Mark the data at ```index``` is changed at ```tick```
+    /// # Panics
+    /// Panic if ```index``` is out of range
+    pub(in crate) fn mark_changed(&mut self,index : usize,tick : u64) {
+        self.ticks[index] = tick;
+    }
+
+    /// Get the tick when data at ```index``` was added
+    pub(in crate) fn added_tick(&self,index : usize) -> Option<u64> {
+        self.added.get(index).cloned()
+    }
+
+    /// Get the version when ```entity``` got its data
+    pub(in crate) fn stamp(&self,entity : E) -> Option<u64> {
+        let index = self.get_index(entity)?;
+        self.stamps.get(index).cloned()
+    }
+
+    /// Mark the data at ```index``` is added at ```tick```
+    /// # Panics
+    /// Panic if ```index``` is out of range
+    pub(in crate) fn mark_added_at(&mut self,index : usize,tick : u64) {
+        self.added[index] = tick;
+    }
+
+    /// Mark the data before ```end``` are changed at ```tick```
+    /// # Panics
+    /// Panic if ```end``` is out of range
+    #[cfg(feature = "rayon")]
+    pub(in crate) fn mark_changed_until(&mut self,end : usize,tick : u64) {
+        self.ticks[..end].fill(tick);
+    }
+
+    /// Make this sparse set double-buffered,
+    /// the back buffer is initialized by cloning the data
+    pub(in crate) fn enable_back_buffer(&mut self,clone : fn(&T) -> T) {
+        if self.back.is_none() {
+            self.back = Some(BackBuffer {
+                data : UnsafeCell::new(self.data.iter().map(clone).collect()),
+                borrowed : AtomicBool::new(false),
+                clone
+            });
+        }
+    }
+
+    /// Swap the front buffer and back buffer in O(1)
+    /// # Panics
+    /// Panic if it's not double-buffered
+    pub(in crate) fn swap_buffers(&mut self) {
+        let back = self.back.as_mut()
+            .expect("SparseSet:Cannot swap buffers because it is not double-buffered");
+        std::mem::swap(&mut self.data,back.data_mut());
+    }
+
+    /// Borrow the back buffer mutably,
+    /// return the pointer to the first element.
+    /// # Details
+    /// [release_back](SparseSet::release_back) must be called after using.
+    /// The pointer is valid until the sparse set is changed structurally.
+    /// # Panics
+    /// * Panic if it's not double-buffered.
+    /// * Panic if the back buffer has been borrowed.
+    pub(in crate) fn borrow_back(&self) -> *mut T {
+        let back = self.back.as_ref()
+            .expect("SparseSet:Cannot borrow back buffer because it is not double-buffered");
+        assert!(back.borrowed.compare_exchange(false,true,Ordering::Acquire,Ordering::Relaxed).is_ok(),
+                "SparseSet:Cannot borrow back buffer twice");
+        // Safety:
+        // the borrowed flag ensures nobody else is accessing data
+        unsafe { (*back.data.get()).as_mut_ptr() }
+    }
+
+    pub(in crate) fn release_back(&self) {
+        if let Some(back) = &self.back {
+            back.borrowed.store(false,Ordering::Release);
+        }
+    }
+
+    /// Maintain a presence bitmap indexed by entity,
+    /// the bit of entity is set if it's in sparse set
+    pub(in crate) fn enable_presence(&mut self) {
+        if self.presence.is_none() {
+            self.presence = Some(vec![]);
+            for index in 0..self.entities.len() {
+                let entity : usize = self.entities[index].into();
+                self.set_presence(entity,true);
+            }
+        }
+    }
+
+    /// Get the presence bitmap,return ```None``` if it's not enabled
+    pub(in crate) fn presence(&self) -> Option<&[u64]> {
+        self.presence.as_deref()
+    }
+
+    fn set_presence(&mut self,entity : usize,present : bool) {
+        if let Some(presence) = &mut self.presence {
+            let (word,mask) = bit_of(entity);
+            if presence.len() <= word {
+                if !present {
+                    return;
+                }
+                presence.resize(word + 1,0);
+            }
+            if present {
+                presence[word] |= mask;
+            } else {
+                presence[word] &= !mask;
+            }
+        }
+    }
+
+    /// Get the back buffer
+    #[cfg(test)]
+    pub(in crate) fn back_data(&mut self) -> Option<&[T]> {
+        self.back.as_mut().map(|back|back.data_mut().as_slice())
+    }
+}
+
+/// A view into a single entity in a sparse set,
+/// it's constructed from [entry](SparseSet::entry).
+/// # Details
+/// It works like the ```Entry``` of ```HashMap```,
+/// the entity is looked up only once.
+pub(in crate) enum Entry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    Occupied(OccupiedEntry<'a,E,T>),
+    Vacant(VacantEntry<'a,E,T>)
+}
+
+/// An entity which is in sparse set
+pub(in crate) struct OccupiedEntry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    sparse_set : &'a mut SparseSet<E,T>,
+    entity : E,
+    // the index in dense arrays
+    index : usize
+}
+
+/// An entity which is not in sparse set
+pub(in crate) struct VacantEntry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    sparse_set : &'a mut SparseSet<E,T>,
+    entity : E
+}
+
+#[allow(unused)]
+impl<'a,E,T> Entry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    /// Get the entity of this entry
+    pub fn entity(&self) -> E {
+        match self {
+            Entry::Occupied(entry) => entry.entity,
+            Entry::Vacant(entry) => entry.entity
+        }
+    }
+
+    /// Insert ```default``` if it's vacant,
+    /// and get the mutable reference of data
+    pub fn or_insert(self,default : T) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default)
+        }
+    }
+
+    /// Insert the result of ```default``` if it's vacant,
+    /// and get the mutable reference of data
+    pub fn or_insert_with<F : FnOnce() -> T>(self,default : F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default())
+        }
+    }
+
+    /// Modify the data by ```f``` if it's occupied
+    pub fn and_modify<F : FnOnce(&mut T)>(self,f : F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            },
+            Entry::Vacant(entry) => Entry::Vacant(entry)
+        }
+    }
+}
+
+#[allow(unused)]
+impl<'a,E,T : Default> Entry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    /// Insert the default value if it's vacant,
+    /// and get the mutable reference of data
+    pub fn or_default(self) -> &'a mut T {
+        self.or_insert_with(T::default)
+    }
+}
+
+#[allow(unused)]
+impl<'a,E,T> OccupiedEntry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    /// Get the entity of this entry
+    pub fn entity(&self) -> E {
+        self.entity
+    }
+
+    /// Get the reference of data
+    pub fn get(&self) -> &T {
+        // Safety:
+        // index is from the sparse array,it's in range of data
+        unsafe { self.sparse_set.data.get_unchecked(self.index) }
+    }
+
+    /// Get the mutable reference of data
+    pub fn get_mut(&mut self) -> &mut T {
+        // Safety:
+        // index is from the sparse array,it's in range of data
+        unsafe { self.sparse_set.data.get_unchecked_mut(self.index) }
+    }
+
+    /// Convert the entry into the mutable reference of data with the lifetime of sparse set
+    pub fn into_mut(self) -> &'a mut T {
+        // Safety:
+        // entry is occupied,so entity is in sparse set
+        unsafe { self.sparse_set.get_unchecked_mut(self.entity) }
+    }
+
+    /// Overwrite the data and return the old one
+    pub fn insert(&mut self,data : T) -> T {
+        if let Some(back) = &mut self.sparse_set.back {
+            back.data_mut()[self.index] = (back.clone)(&data);
+        }
+        std::mem::replace(self.get_mut(),data)
+    }
+
+    /// Remove the entity from sparse set and return its data
+    pub fn remove(self) -> T {
+        // Unwrap here never fails
+        // entry is occupied
+        self.sparse_set.remove(self.entity).unwrap()
+    }
+}
+
+#[allow(unused)]
+impl<'a,E,T> VacantEntry<'a,E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    /// Get the entity of this entry
+    pub fn entity(&self) -> E {
+        self.entity
+    }
+
+    /// Insert ```data``` to the entity and get the mutable reference of it
+    pub fn insert(self,data : T) -> &'a mut T {
+        self.sparse_set.add(self.entity,data);
+        // Safety:
+        // entity was added above
+        unsafe { self.sparse_set.get_unchecked_mut(self.entity) }
+    }
+}
+
+impl<E,T> Extend<(E,T)> for SparseSet<E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    /// Add all items like [add](SparseSet::add),
+    /// the data of existing entities are overwritten.
+    fn extend<I : IntoIterator<Item = (E,T)>>(&mut self,iter : I) {
+        let iter = iter.into_iter();
+        // reserve the lower bound,it's exact for most collections
+        let (lower,_) = iter.size_hint();
+        self.entities.reserve(lower);
+        self.data.reserve(lower);
+        self.ticks.reserve(lower);
+        self.added.reserve(lower);
+        self.stamps.reserve(lower);
+        for (entity,data) in iter {
+            self.add(entity,data);
+        }
+    }
+}
+
+/// The iterator of [iter](SparseSet::iter)
+pub(in crate) type Iter<'a,E,T> = Zip<Copied<slice::Iter<'a,E>>,slice::Iter<'a,T>>;
+/// The iterator of [iter_mut](SparseSet::iter_mut)
+pub(in crate) type IterMut<'a,E,T> = Zip<Copied<slice::Iter<'a,E>>,slice::IterMut<'a,T>>;
+
+impl<'a,E,T> IntoIterator for &'a SparseSet<E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    type Item = (E,&'a T);
+    type IntoIter = Iter<'a,E,T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a,E,T> IntoIterator for &'a mut SparseSet<E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    type Item = (E,&'a mut T);
+    type IntoIter = IterMut<'a,E,T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<E,T> FromIterator<(E,T)> for SparseSet<E,T>
+    where E : Copy + Into<usize> + PartialEq {
+    fn from_iter<I : IntoIterator<Item = (E,T)>>(iter : I) -> Self {
+        let mut sparse_set = SparseSet::new();
+        sparse_set.extend(iter);
+        sparse_set
+    }
+}
+
+/// Rearrange items by ```swap```,so the item at ```order[i]``` is moved to ```start + i```.
+/// # Details
+/// ```order``` must be a permutation of ```start..start + order.len()```,
+/// every item is swapped at most once.
+pub(in crate) fn permute<F : FnMut(usize,usize)>(start : usize,order : &[usize],mut swap : F) {
+    let mut visited = vec![false;order.len()];
+    for first in 0..order.len() {
+        if visited[first] {
+            continue;
+        }
+        // follow the cycle from first,
+        // the item needed by current is always at order[current]
+        let mut current = first;
+        visited[current] = true;
+        loop {
+            let next = order[current] - start;
+            if visited[next] {
+                break;
+            }
+            swap(start + current,start + next);
+            visited[next] = true;
+            current = next;
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests{
-    use crate::sparse_set::SparseSet;
+    use std::collections::HashMap;
+    use crate::{entity::{EntityId, Generation}, sparse_set::{permute, Entry, SparseSet}};
 
     #[test]
     fn basic_test(){
@@ -257,6 +817,26 @@ mod tests{
         println!("{:?}",s1);
     }
 
+    #[test]
+    fn contains_many_test() {
+        let mut s = SparseSet::new();
+        let mut out = [true;4];
+        s.contains_many(&[0_usize,1,2,100],&mut out);
+        assert_eq!(out,[false;4]);
+
+        s.add(2_usize,'a');
+        s.add(5,'b');
+        s.add(3,'c');
+        s.remove(3);
+        let entities = [5_usize,0,3,2,6,1_000_000,4];
+        let mut out = [false;7];
+        s.contains_many(&entities,&mut out);
+        assert_eq!(out,[true,false,false,true,false,false,false]);
+        for (entity,out) in entities.iter().zip(out.iter()) {
+            assert_eq!(s.exist(*entity),*out);
+        }
+    }
+
     #[test]
     fn batch() {
         let mut s = SparseSet::new();
@@ -270,4 +850,247 @@ mod tests{
         s.add_batch(&entities,data);
         println!("{:?}",s);
     }
+
+    #[test]
+    fn back_buffer_test() {
+        let mut s = SparseSet::new();
+        s.add(3_usize,'a');
+        s.enable_back_buffer(Clone::clone);
+        assert_eq!(s.back_data(),Some(&['a'][..]));
+        s.add(5,'b');
+        s.add_batch(&[6,2],vec!['c','d']);
+        assert_eq!(s.back_data(),Some(&['a','b','c','d'][..]));
+
+        let ptr = s.borrow_back();
+        // Safety:
+        // the back buffer is borrowed and has 4 elements
+        unsafe { *ptr.add(1) = 'x'; }
+        s.release_back();
+        s.swap_buffers();
+        assert_eq!(s.data(),&['a','x','c','d']);
+        assert_eq!(s.back_data(),Some(&['a','b','c','d'][..]));
+
+        s.swap_by_index(0,3);
+        s.remove(6);
+        assert_eq!(s.entities(),&[2,5,3]);
+        assert_eq!(s.data(),&['d','x','a']);
+        assert_eq!(s.back_data(),Some(&['d','b','a'][..]));
+        s.add(5,'y');
+        assert_eq!(s.back_data(),Some(&['d','y','a'][..]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn borrow_back_twice_test() {
+        let mut s = SparseSet::new();
+        s.add(1_usize,'a');
+        s.enable_back_buffer(Clone::clone);
+        s.borrow_back();
+        s.borrow_back();
+    }
+
+
+    #[test]
+    fn ticks_test() {
+        let mut s = SparseSet::new();
+        s.add(3_usize,'a');
+        s.add_batch(&[5,6],vec!['b','c']);
+        assert_eq!(s.ticks,vec![0,0,0]);
+        assert_eq!(s.added,vec![0,0,0]);
+        s.mark_changed(2,7);
+        s.mark_added_at(1,4);
+        s.swap_by_index(0,2);
+        assert_eq!(s.tick(0),Some(7));
+        s.swap_by_index(1,2);
+        assert_eq!(s.added_tick(2),Some(4));
+        s.remove(6);
+        assert_eq!(s.entities(),&[5,3]);
+        assert_eq!(s.ticks,vec![0,0]);
+        assert_eq!(s.added,vec![4,0]);
+        assert_eq!(s.tick(2),None);
+    }
+
+
+    #[test]
+    fn entry_test() {
+        let mut s = SparseSet::new();
+        s.add(3_usize,1_u32);
+
+        *s.entry(3).or_insert(10) += 1;
+        *s.entry(5).or_insert(10) += 1;
+        assert_eq!(s.get(3),Some(&2));
+        assert_eq!(s.get(5),Some(&11));
+
+        let mut called = false;
+        s.entry(5).or_insert_with(|| { called = true; 0 });
+        assert!(!called);
+        assert_eq!(*s.entry(7).or_insert_with(|| 70),70);
+        assert_eq!(*s.entry(8).or_default(),0);
+
+        s.entry(3).and_modify(|x|*x *= 100).or_insert(0);
+        s.entry(9).and_modify(|x|*x *= 100).or_insert(9);
+        assert_eq!(s.get(3),Some(&200));
+        assert_eq!(s.get(9),Some(&9));
+        assert_eq!(s.entities(),&[3,5,7,8,9]);
+        assert_eq!(s.ticks.len(),5);
+
+        match s.entry(5) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.entity(),5);
+                assert_eq!(*entry.get(),11);
+                assert_eq!(entry.insert(12),11);
+                assert_eq!(entry.remove(),12);
+            },
+            Entry::Vacant(_) => panic!("5 is in sparse set")
+        }
+        match s.entry(5) {
+            Entry::Occupied(_) => panic!("5 was removed"),
+            Entry::Vacant(entry) => assert_eq!(entry.entity(),5)
+        }
+        assert!(!s.exist(5));
+        assert_eq!(s.entities(),&[3,9,7,8]);
+
+        // the back buffer is kept aligned
+        s.enable_back_buffer(Clone::clone);
+        if let Entry::Occupied(mut entry) = s.entry(3) {
+            entry.insert(300);
+        }
+        s.entry(20).or_insert(2000);
+        s.swap_buffers();
+        assert_eq!(s.get(3),Some(&300));
+        assert_eq!(s.get(20),Some(&2000));
+    }
+
+    #[test]
+    fn retain_drain_test() {
+        let mut sparse_set : SparseSet<usize,u32> = SparseSet::new();
+        sparse_set.enable_presence();
+        for i in 0..10 {
+            sparse_set.add(i,i as u32 * 10);
+        }
+        sparse_set.retain(|entity,data| {
+            *data += 1;
+            entity % 3 != 0
+        });
+        assert_eq!(sparse_set.len(),6);
+        for i in 0..10 {
+            assert_eq!(sparse_set.exist(i),i % 3 != 0);
+            if i % 3 != 0 {
+                assert_eq!(sparse_set.get(i),Some(&(i as u32 * 10 + 1)));
+            }
+        }
+        assert_eq!(sparse_set.presence().unwrap()[0],0b0110110110);
+        for (index,entity) in sparse_set.entities().iter().enumerate() {
+            assert_eq!(sparse_set.get_index(*entity),Some(index));
+        }
+
+        let mut drained = sparse_set.drain().take(2).collect::<Vec<_>>();
+        assert_eq!(drained.len(),2);
+        assert!(sparse_set.is_empty());
+        assert!((0..10).all(|i|!sparse_set.exist(i)));
+        assert!(sparse_set.presence().unwrap().iter().all(|bits|*bits == 0));
+        sparse_set.add(4,4);
+        sparse_set.add(7,7);
+        drained = sparse_set.drain().collect();
+        assert_eq!(drained,vec![(4,4),(7,7)]);
+        assert_eq!(sparse_set.len(),0);
+    }
+
+    #[test]
+    fn sort_test() {
+        let mut s = SparseSet::new();
+        let data = [5_u32,3,9,3,1,7,0,3];
+        for (entity,x) in data.iter().enumerate() {
+            s.add(entity + 1,*x);
+        }
+        s.mark_changed(2,42);
+        s.enable_back_buffer(Clone::clone);
+
+        // keep the first 2 items
+        let order = s.sorted_indices(2..s.len(),|a,b|a.cmp(b));
+        permute(2,&order,|a,b|s.swap_by_index(a,b));
+        assert_eq!(s.data(),&[5,3,0,1,3,3,7,9]);
+        // the sort is stable
+        assert_eq!(s.entities(),&[1,2,7,5,4,8,6,3]);
+        assert_eq!(s.back_data().unwrap(),&[5,3,0,1,3,3,7,9]);
+        assert_eq!(s.tick(7),Some(42));
+        for (entity,x) in data.iter().enumerate() {
+            assert_eq!(s.get(entity + 1),Some(x));
+        }
+    }
+
+    #[test]
+    fn stale_test() {
+        let mut s = SparseSet::new();
+        let old = EntityId::new(3).unwrap();
+        let new = EntityId::from_parts(3,Generation::FIRST.next().unwrap()).unwrap();
+        let other = EntityId::new(4).unwrap();
+        s.add(old,'a');
+        s.add(other,'b');
+        s.mark_added_at(0,5);
+
+        // the index is same but generation is not
+        assert!(!s.exist(new));
+        assert_eq!(s.get(new),None);
+        assert_eq!(s.get_mut(new),None);
+        assert_eq!(s.get_index(new),None);
+        assert_eq!(s.remove(new),None);
+        let mut out = [true;2];
+        s.contains_many(&[new,other],&mut out);
+        assert_eq!(out,[false,true]);
+
+        // adding the new generation replaces the stale one
+        s.add(new,'c');
+        assert_eq!(s.entities(),&[new,other]);
+        assert_eq!(s.added_tick(0),Some(0));
+        assert!(!s.exist(old));
+        assert_eq!(s.get(new),Some(&'c'));
+        assert_eq!(s.remove(old),None);
+        assert_eq!(s.remove(new),Some('c'));
+        assert_eq!(s.entities(),&[other]);
+    }
+
+    #[test]
+    fn from_iter_extend_test() {
+        let map = [(5_usize,'a'),(3,'b'),(8,'c')]
+            .into_iter()
+            .collect::<HashMap<_,_>>();
+        let mut s = map.clone().into_iter().collect::<SparseSet<usize,char>>();
+        assert_eq!(s.len(),3);
+        let round_trip = s.iter()
+            .map(|(entity,data)|(entity,*data))
+            .collect::<HashMap<_,_>>();
+        assert_eq!(round_trip,map);
+
+        // existing entities are overwritten like add
+        s.extend(vec![(3,'x'),(1,'y'),(3,'z')]);
+        assert_eq!(s.len(),4);
+        assert_eq!(s.get(3),Some(&'z'));
+        assert_eq!(s.get(1),Some(&'y'));
+        assert_eq!(s.get(5),Some(&'a'));
+        assert_eq!(s.entities().len(),s.data().len());
+    }
+
+    #[test]
+    fn iter_test() {
+        let mut s = SparseSet::<usize,char>::new();
+        s.add(5,'a');
+        s.add(3,'b');
+        s.add(8,'c');
+        s.remove(5);
+        assert_eq!(s.iter().collect::<Vec<_>>(),vec![(8,&'c'),(3,&'b')]);
+
+        for (entity,data) in s.iter_mut() {
+            if entity == 3 {
+                *data = 'x';
+            }
+        }
+        for (_,data) in &mut s {
+            *data = data.to_ascii_uppercase();
+        }
+        let items = (&s).into_iter()
+            .map(|(entity,data)|(entity,*data))
+            .collect::<Vec<_>>();
+        assert_eq!(items,vec![(8,'C'),(3,'X')]);
+    }
 }
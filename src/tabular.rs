@@ -0,0 +1,556 @@
+//! # Tabular component I/O
+//! Export components to CSV and import them back.
+//! # Details
+//! Every component in a table must implement [TabularComponent].
+//! A component is flattened into some columns named ```Component.field```,
+//! ```Component``` is [TabularComponent::table_name] and ```field``` is one of [TabularComponent::fields].
+//! # Example
+//! ```
+//! use xecs::{World, tabular::{TabularComponent, TabularFieldError, parse_field}};
+//! #[derive(Debug,Clone,PartialEq)]
+//! struct Name(String);
+//! #[derive(Debug,Clone,PartialEq)]
+//! struct Health(u32);
+//! impl TabularComponent for Name {
+//!     fn fields() -> &'static [&'static str] { &["value"] }
+//!     fn to_fields(&self) -> Vec<String> { vec![self.0.clone()] }
+//!     fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError> {
+//!         Ok(Name(parse_field(values,0)?))
+//!     }
+//! }
+//! impl TabularComponent for Health {
+//!     fn fields() -> &'static [&'static str] { &["hp"] }
+//!     fn to_fields(&self) -> Vec<String> { vec![self.0.to_string()] }
+//!     fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError> {
+//!         Ok(Health(parse_field(values,0)?))
+//!     }
+//! }
+//!
+//! let mut world = World::new();
+//! world.register::<Name>().register::<Health>();
+//! world.create_entity().attach(Name("slime".to_string())).attach(Health(10));
+//!
+//! let mut csv = vec![];
+//! world.export_table::<(&Name,&Health)>(&mut csv).unwrap();
+//! assert_eq!(String::from_utf8(csv).unwrap(),"Name.value,Health.hp\nslime,10\n");
+//!
+//! let report = world.import_table::<(Name,Health)>("Name.value,Health.hp\nslime,25\nbat,5\n".as_bytes(),"Name").unwrap();
+//! assert_eq!(report.updated.len(),1);
+//! assert_eq!(report.spawned.len(),1);
+//! ```
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+    io::{self, Read, Write},
+    str::FromStr
+};
+use crate::{
+    bundle::Bundle,
+    component::Component,
+    entity::EntityId,
+    query::Queryable,
+    world::World
+};
+
+/// A component which can be flattened into some columns
+pub trait TabularComponent : Component + Sized {
+    /// The name of this component in header.
+    /// # Details
+    /// It's the last segment of type name by default.
+    fn table_name() -> &'static str {
+        std::any::type_name::<Self>()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default()
+    }
+    /// The names of fields,they are the columns of this component
+    fn fields() -> &'static [&'static str];
+    /// Convert this component to strings in the order of [fields](TabularComponent::fields)
+    fn to_fields(&self) -> Vec<String>;
+    /// Parse this component from strings in the order of [fields](TabularComponent::fields)
+    fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError>;
+}
+
+/// The error when a field cannot be parsed
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TabularFieldError {
+    /// The index of field in [fields](TabularComponent::fields)
+    pub index : usize,
+    /// The reason
+    pub message : String
+}
+
+/// Parse the field at ```index``` by [FromStr]
+pub fn parse_field<V>(values : &[&str],index : usize) -> Result<V,TabularFieldError>
+    where V : FromStr,
+          V::Err : Display {
+    let value = values.get(index)
+        .ok_or_else(|| TabularFieldError {
+            index,
+            message : "value is missing".to_string()
+        })?;
+    value.parse::<V>()
+        .map_err(|err| TabularFieldError {
+            index,
+            message : err.to_string()
+        })
+}
+
+fn push_header<T : TabularComponent>(header : &mut Vec<String>) {
+    let name = T::table_name();
+    header.extend(T::fields().iter().map(|field|format!("{}.{}",name,field)));
+}
+
+/// A query whose items can be written as rows.
+/// # Details
+/// It's implemented for ```&T``` and tuples of them,the arities are from 2 to 8.
+pub trait TabularQuery<'a> : Queryable<'a> {
+    /// Push the columns to ```header```
+    fn header(header : &mut Vec<String>);
+    /// Push the values of ```item``` to ```row```
+    fn write_item(item : &Self::Item,row : &mut Vec<String>);
+}
+
+impl<'a,T : TabularComponent> TabularQuery<'a> for &'a T {
+    fn header(header : &mut Vec<String>) {
+        push_header::<T>(header);
+    }
+
+    fn write_item(item : &Self::Item,row : &mut Vec<String>) {
+        row.extend(item.to_fields());
+    }
+}
+
+/// A bundle which can be read from rows.
+/// # Details
+/// It's implemented for tuples of [TabularComponent]s,the arities are from 1 to 8.
+pub trait TabularBundle : Bundle {
+    /// Push the columns to ```header```
+    fn header(header : &mut Vec<String>);
+    /// Parse the bundle from ```row``` whose columns are in the order of [header](TabularBundle::header).
+    /// # Details
+    /// ```columns``` is the header,it's used in error.
+    fn parse(row : &[&str],columns : &[String]) -> Result<Self,RowErrorKind>;
+    /// Get the key string of component named ```key```
+    fn key_of(&self,key : &str) -> Option<String>;
+    /// Get the key strings of all entities which have component named ```key```
+    fn keys_in(world : &World,key : &str) -> Option<HashMap<String,EntityId>>;
+    /// Convert to type-erased components with their type IDs
+    fn into_components(self) -> Vec<(TypeId,crate::bundle::AnyComponent)>;
+}
+
+fn key_string<T : TabularComponent>(component : &T) -> String {
+    // unit separator never appears in a csv field of designers
+    component.to_fields().join("\u{1f}")
+}
+
+fn parse_component<T : TabularComponent>(row : &[&str],
+                                         columns : &[String],
+                                         offset : &mut usize) -> Result<T,RowErrorKind> {
+    let count = T::fields().len();
+    let start = *offset;
+    *offset += count;
+    if row.len() < start + count {
+        return Err(RowErrorKind::MissingColumn(columns[row.len().max(start)].clone()));
+    }
+    T::from_fields(&row[start..start + count])
+        .map_err(|err| RowErrorKind::InvalidValue {
+            column : columns[start + err.index].clone(),
+            value : row.get(start + err.index).map(|value|value.to_string()).unwrap_or_default(),
+            message : err.message
+        })
+}
+
+macro_rules! impl_tabular_query {
+    ($($name:ident $index:tt),*) => {
+        impl<'a,$($name : TabularQuery<'a> + 'a),*> TabularQuery<'a> for ($($name,)*) {
+            fn header(header : &mut Vec<String>) {
+                $($name::header(header);)*
+            }
+
+            fn write_item(item : &Self::Item,row : &mut Vec<String>) {
+                $($name::write_item(&item.$index,row);)*
+            }
+        }
+    };
+}
+
+macro_rules! impl_tabular_bundle {
+    ($($name:ident $index:tt),*) => {
+        impl<$($name : TabularComponent),*> TabularBundle for ($($name,)*) {
+            fn header(header : &mut Vec<String>) {
+                $(push_header::<$name>(header);)*
+            }
+
+            fn parse(row : &[&str],columns : &[String]) -> Result<Self,RowErrorKind> {
+                let mut offset = 0;
+                Ok(($(parse_component::<$name>(row,columns,&mut offset)?,)*))
+            }
+
+            fn key_of(&self,key : &str) -> Option<String> {
+                $(if $name::table_name() == key {
+                    return Some(key_string(&self.$index));
+                })*
+                None
+            }
+
+            fn keys_in(world : &World,key : &str) -> Option<HashMap<String,EntityId>> {
+                $(if $name::table_name() == key {
+                    let mut iter = world.query::<&$name>();
+                    let mut keys = HashMap::new();
+                    while let Some((id,component)) = iter.next_with_id() {
+                        keys.insert(key_string(component),id);
+                    }
+                    return Some(keys);
+                })*
+                None
+            }
+
+            fn into_components(self) -> Vec<(TypeId,crate::bundle::AnyComponent)> {
+                vec![$((TypeId::of::<$name>(),Box::new(self.$index)),)*]
+            }
+        }
+    };
+}
+
+impl_tabular_query!(A 0,B 1);
+impl_tabular_query!(A 0,B 1,C 2);
+impl_tabular_query!(A 0,B 1,C 2,D 3);
+impl_tabular_query!(A 0,B 1,C 2,D 3,E 4);
+impl_tabular_query!(A 0,B 1,C 2,D 3,E 4,F 5);
+impl_tabular_query!(A 0,B 1,C 2,D 3,E 4,F 5,G 6);
+impl_tabular_query!(A 0,B 1,C 2,D 3,E 4,F 5,G 6,H 7);
+
+impl_tabular_bundle!(A 0);
+impl_tabular_bundle!(A 0,B 1);
+impl_tabular_bundle!(A 0,B 1,C 2);
+impl_tabular_bundle!(A 0,B 1,C 2,D 3);
+impl_tabular_bundle!(A 0,B 1,C 2,D 3,E 4);
+impl_tabular_bundle!(A 0,B 1,C 2,D 3,E 4,F 5);
+impl_tabular_bundle!(A 0,B 1,C 2,D 3,E 4,F 5,G 6);
+impl_tabular_bundle!(A 0,B 1,C 2,D 3,E 4,F 5,G 6,H 7);
+
+/// The reason why a row was skipped
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum RowErrorKind {
+    /// The row is too short to have this column
+    MissingColumn(String),
+    /// The value of column cannot be parsed
+    InvalidValue {
+        column : String,
+        value : String,
+        message : String
+    }
+}
+
+/// A row which was skipped in import
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct RowError {
+    /// The number of row,starts from 1 and the header is not counted
+    pub row : usize,
+    /// The reason
+    pub kind : RowErrorKind
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            RowErrorKind::MissingColumn(column) =>
+                write!(f,"Row {}:Column '{}' is missing",self.row,column),
+            RowErrorKind::InvalidValue { column, value, message } =>
+                write!(f,"Row {}:Cannot parse '{}' in column '{}':{}",self.row,value,column,message)
+        }
+    }
+}
+
+impl Error for RowError {}
+
+/// The result of [import_table](crate::world::World::import_table)
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct ImportReport {
+    /// The entities whose components were overwritten
+    pub updated : Vec<EntityId>,
+    /// The entities which were spawned
+    pub spawned : Vec<EntityId>,
+    /// The rows which were skipped
+    pub errors : Vec<RowError>
+}
+
+fn write_record<W : Write>(writer : &mut W,record : &[String]) -> io::Result<()> {
+    let line = record.iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"",field.replace('"',"\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(writer,"{}",line)
+}
+
+fn read_records(input : &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => quoted = false,
+                _ => field.push(c)
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {},
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            },
+            _ => field.push(c)
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+impl World {
+    /// Write the items of query ```Q``` to ```writer``` as CSV.
+    /// # Details
+    /// The first line is header,then one row per entity.
+    /// Return the count of rows.
+    /// # Panics
+    /// * Panic if any component of ```Q``` is not registered.
+    pub fn export_table<'a,Q : TabularQuery<'a>>(&'a self,mut writer : impl Write) -> io::Result<usize> {
+        let mut header = vec![];
+        Q::header(&mut header);
+        write_record(&mut writer,&header)?;
+        let mut count = 0;
+        let mut row = vec![];
+        for item in self.query::<Q>() {
+            row.clear();
+            Q::write_item(&item,&mut row);
+            write_record(&mut writer,&row)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Read CSV from ```reader``` and upsert the bundle ```B``` of every row.
+    /// # Details
+    /// The entity whose component named ```keyed_by``` equals to the row's is overwritten,
+    /// otherwise a new entity is spawned.
+    /// Malformed rows are skipped and collected in [ImportReport::errors].
+    /// Columns are matched by names in header,their order in CSV doesn't matter.
+    /// # Errors
+    /// * Return ```InvalidInput``` if no component of ```B``` is named ```keyed_by```.
+    /// * Return ```InvalidData``` if input is not UTF-8.
+    /// # Panics
+    /// * Panic if any component of ```B``` is not registered.
+    pub fn import_table<B : TabularBundle>(&self,mut reader : impl Read,keyed_by : &str) -> io::Result<ImportReport> {
+        let mut keys = B::keys_in(self,keyed_by)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("World:Cannot import table because no component is named '{}'",keyed_by)))?;
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        let mut records = read_records(&input).into_iter();
+        let mut columns = vec![];
+        B::header(&mut columns);
+        // the position of every column in csv
+        let positions = records.next()
+            .map(|header| columns.iter()
+                .map(|column|header.iter().position(|name|name == column))
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut report = ImportReport::default();
+        for (index,record) in records.enumerate() {
+            // reorder the row as the columns of bundle,stop at the first missing column
+            let row = positions.iter()
+                .map_while(|position|position.and_then(|position|record.get(position)))
+                .map(|value|value.as_str())
+                .collect::<Vec<_>>();
+            let bundle = match B::parse(&row,&columns) {
+                Ok(bundle) => bundle,
+                Err(kind) => {
+                    report.errors.push(RowError { row : index + 1, kind });
+                    continue;
+                }
+            };
+            // unwrap never fails because keys_in has found the key component
+            let key = bundle.key_of(keyed_by).unwrap();
+            let id = match keys.get(&key) {
+                Some(&id) => {
+                    report.updated.push(id);
+                    id
+                },
+                None => {
+                    let id = self.create_entity().into_id();
+                    keys.insert(key,id);
+                    report.spawned.push(id);
+                    id
+                }
+            };
+            for (type_id,component) in bundle.into_components() {
+                self.attach_any(id,type_id,component);
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::World;
+    use super::{parse_field, RowError, RowErrorKind, TabularComponent, TabularFieldError};
+
+    #[derive(Debug,Clone,PartialEq)]
+    struct Name(String);
+    #[derive(Debug,Clone,PartialEq)]
+    struct Health(u32);
+    #[derive(Debug,Clone,PartialEq)]
+    struct Speed {
+        walk : f32,
+        run : f32
+    }
+
+    impl TabularComponent for Name {
+        fn fields() -> &'static [&'static str] { &["value"] }
+        fn to_fields(&self) -> Vec<String> { vec![self.0.clone()] }
+        fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError> {
+            Ok(Name(parse_field(values,0)?))
+        }
+    }
+
+    impl TabularComponent for Health {
+        fn fields() -> &'static [&'static str] { &["hp"] }
+        fn to_fields(&self) -> Vec<String> { vec![self.0.to_string()] }
+        fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError> {
+            Ok(Health(parse_field(values,0)?))
+        }
+    }
+
+    impl TabularComponent for Speed {
+        fn fields() -> &'static [&'static str] { &["walk","run"] }
+        fn to_fields(&self) -> Vec<String> { vec![self.walk.to_string(),self.run.to_string()] }
+        fn from_fields(values : &[&str]) -> Result<Self,TabularFieldError> {
+            Ok(Speed {
+                walk : parse_field(values,0)?,
+                run : parse_field(values,1)?
+            })
+        }
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.register::<Name>()
+            .register::<Health>()
+            .register::<Speed>();
+        world
+    }
+
+    fn rows(world : &World) -> Vec<(String,u32,f32,f32)> {
+        let mut rows = world.query::<(&Name,&Health,&Speed)>()
+            .map(|(name,health,speed)|(name.0.clone(),health.0,speed.walk,speed.run))
+            .collect::<Vec<_>>();
+        rows.sort_by(|a,b|a.0.cmp(&b.0));
+        rows
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let world = new_world();
+        world.create_entity()
+            .attach(Name("slime, green".to_string()))
+            .attach(Health(10))
+            .attach(Speed { walk : 1.0, run : 1.5 });
+        world.create_entity()
+            .attach(Name("\"boss\"".to_string()))
+            .attach(Health(500))
+            .attach(Speed { walk : 0.5, run : 2.0 });
+        world.create_entity()
+            .attach(Name("ghost".to_string()));
+
+        let mut csv = vec![];
+        let count = world.export_table::<(&Name,&Health,&Speed)>(&mut csv).unwrap();
+        assert_eq!(count,2);
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with("Name.value,Health.hp,Speed.walk,Speed.run\n"));
+
+        let other = new_world();
+        let report = other.import_table::<(Name,Health,Speed)>(csv.as_slice(),"Name").unwrap();
+        assert_eq!(report.spawned.len(),2);
+        assert!(report.errors.is_empty());
+        assert_eq!(rows(&world),rows(&other));
+    }
+
+    #[test]
+    fn upsert_test() {
+        let world = new_world();
+        let slime = world.create_entity()
+            .attach(Name("slime".to_string()))
+            .attach(Health(10))
+            .into_id();
+        // columns in a different order
+        let csv = "Health.hp,Speed.run,Name.value,Speed.walk\n\
+                   20,3,slime,1\n\
+                   5,4,bat,2\n";
+        let report = world.import_table::<(Name,Health,Speed)>(csv.as_bytes(),"Name").unwrap();
+        assert_eq!(report.updated,vec![slime]);
+        assert_eq!(report.spawned.len(),1);
+        assert_eq!(rows(&world),vec![
+            ("bat".to_string(),5,2.0,4.0),
+            ("slime".to_string(),20,1.0,3.0)
+        ]);
+        assert_eq!(world.components_of(report.spawned[0]).len(),3);
+
+        let err = world.import_table::<(Name,Health)>(csv.as_bytes(),"Speed").unwrap_err();
+        assert_eq!(err.kind(),std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn error_report_test() {
+        let world = new_world();
+        let csv = "Name.value,Health.hp,Speed.walk,Speed.run\n\
+                   slime,10,1,2\n\
+                   bat,ten,1,2\n\
+                   ghost,3\n\
+                   rat,1,fast,2\n";
+        let report = world.import_table::<(Name,Health,Speed)>(csv.as_bytes(),"Name").unwrap();
+        assert_eq!(report.spawned.len(),1);
+        assert_eq!(report.errors.len(),3);
+        assert!(matches!(&report.errors[0],
+            RowError { row : 2, kind : RowErrorKind::InvalidValue { column, value, .. } }
+                if column == "Health.hp" && value == "ten"));
+        assert_eq!(report.errors[1],RowError {
+            row : 3,
+            kind : RowErrorKind::MissingColumn("Speed.walk".to_string())
+        });
+        assert!(matches!(&report.errors[2],
+            RowError { row : 4, kind : RowErrorKind::InvalidValue { column, .. } }
+                if column == "Speed.walk"));
+        assert_eq!(rows(&world),vec![("slime".to_string(),10,1.0,2.0)]);
+
+        // header without a column
+        let csv = "Name.value,Health.hp\nslime,10\n";
+        let report = world.import_table::<(Name,Health,Speed)>(csv.as_bytes(),"Name").unwrap();
+        assert_eq!(report.errors,vec![RowError {
+            row : 1,
+            kind : RowErrorKind::MissingColumn("Speed.walk".to_string())
+        }]);
+    }
+}
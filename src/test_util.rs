@@ -0,0 +1,474 @@
+//! # Test utilities
+//! A deterministic scenario generator and golden-file harness for query results.
+//! It's only available with ```test-util``` feature.
+//! ## Scenario
+//! [ScenarioBuilder](crate::test_util::ScenarioBuilder) builds a world from a seed,
+//! mutates it by random operations and snapshots the digests of all query shapes
+//! after every N operations.
+//! ```no_run
+//! use xecs::{group::full_owning, test_util::{OpMix, ScenarioBuilder}};
+//! ScenarioBuilder::seed(1234)
+//!     .entities(10_000)
+//!     .component::<u32>(0.7)
+//!     .component::<u64>(0.3)
+//!     .group(full_owning::<u32,u64>())
+//!     .ops(5_000,OpMix { attach : 0.3, detach : 0.2, despawn : 0.1, spawn : 0.4 })
+//!     .run()
+//!     .check_golden("tests/golden/full_owning.txt");
+//! ```
+//! ## Digest format
+//! The digest of a query shape is the 64-bit FNV-1a hash of all ```(id,value)```
+//! pairs sorted by id.The digest file has one line for each snapshot:
+//! ```text
+//! ops=<count> <shape>=<digest> <shape>=<digest> ...
+//! ```
+//! For every group,the digest of group query is checked to be equal to
+//! the digest of the plain tuple query before it's recorded.
+//! ## Golden files
+//! [check_golden](crate::test_util::Scenario::check_golden) compares the digests with a golden file.
+//! Set the ```XECS_UPDATE_GOLDEN``` environment variable to regenerate golden files.
+use std::{fmt::Write, hash::{Hash, Hasher}, path::Path};
+use crate::{component::Component, entity::EntityId, group::{FullOwning, NonOwning, PartialOwning}, query::{QueryIterator, WithId}, world::World};
+
+/// The environment variable to regenerate golden files
+pub const UPDATE_GOLDEN_ENV : &str = "XECS_UPDATE_GOLDEN";
+
+/// A xorshift64* random number generator
+#[derive(Debug,Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Create a generator from ```seed```
+    pub fn new(seed : u64) -> Self {
+        // state cannot be zero
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Get next random ```u64```
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Get a random number in ```[0,1)```
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Get a random number in ```[0,n)```
+    /// # Panics
+    /// Panic if ```n``` is zero
+    pub fn below(&mut self,n : usize) -> usize {
+        assert!(n > 0,"Rng:Cannot generate a number below zero");
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// A 64-bit FNV-1a hasher,which is stable across platforms and compiler versions
+#[derive(Debug,Clone)]
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16)
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32)
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64)
+    }
+}
+
+/// The component can be generated randomly in scenario
+pub trait ScenarioComponent : Component + Copy + Hash {
+    /// Generate a random component
+    fn random(rng : &mut Rng) -> Self;
+}
+
+macro_rules! impl_scenario_component {
+    ($($ty:ty),*) => {
+        $(impl ScenarioComponent for $ty {
+            fn random(rng : &mut Rng) -> Self {
+                rng.next_u64() as $ty
+            }
+        })*
+    };
+}
+
+impl_scenario_component!(u8,u16,u32,u64,usize,i8,i16,i32,i64,isize);
+
+impl ScenarioComponent for bool {
+    fn random(rng : &mut Rng) -> Self {
+        rng.next_u64() & 1 == 1
+    }
+}
+
+impl ScenarioComponent for char {
+    fn random(rng : &mut Rng) -> Self {
+        (b'a' + rng.below(26) as u8) as char
+    }
+}
+
+/// Get the digest of a query result
+fn digest<T,I>(iter : I) -> u64
+    where T : Hash,
+          I : QueryIterator<Item = T> {
+    let mut items = iter.with_id()
+        .map(|(id,item)| {
+            let mut hasher = FnvHasher::default();
+            item.hash(&mut hasher);
+            (id,hasher.finish())
+        })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|(id,_)|*id);
+    let mut hasher = FnvHasher::default();
+    for (id,hash) in items {
+        hasher.write_usize(id.index());
+        hasher.write_u64(hash);
+    }
+    hasher.finish()
+}
+
+/// The group can be used in scenario
+pub trait ScenarioGroup {
+    /// The name of group in digest
+    fn name() -> String;
+    /// Make group in world
+    fn make(world : &mut World);
+    /// Get the digest of group query
+    fn group_digest(world : &World) -> u64;
+    /// Get the digest of plain tuple query which has the same result as group query
+    fn tuple_digest(world : &World) -> u64;
+}
+
+macro_rules! impl_scenario_group {
+    ($group:ident,$name:literal,$ctor:ident) => {
+        impl<A : ScenarioComponent,B : ScenarioComponent> ScenarioGroup for $group<A,B> {
+            fn name() -> String {
+                format!("{}<{},{}>",$name,std::any::type_name::<A>(),std::any::type_name::<B>())
+            }
+
+            fn make(world : &mut World) {
+                world.make_group(crate::group::$ctor::<A,B>());
+            }
+
+            fn group_digest(world : &World) -> u64 {
+                // ref the components by value to hash them in the same way as tuple query
+                let mut items = world.query::<$group<&A,&B>>()
+                    .with_id()
+                    .map(|(id,(a,b))|(id,(*a,*b)))
+                    .collect::<Vec<_>>();
+                items.sort_by_key(|(id,_)|*id);
+                digest_sorted(items)
+            }
+
+            fn tuple_digest(world : &World) -> u64 {
+                let mut items = world.query::<(&A,&B)>()
+                    .with_id()
+                    .map(|(id,(a,b))|(id,(*a,*b)))
+                    .collect::<Vec<_>>();
+                items.sort_by_key(|(id,_)|*id);
+                digest_sorted(items)
+            }
+        }
+    };
+}
+
+impl_scenario_group!(FullOwning,"full_owning",full_owning);
+impl_scenario_group!(PartialOwning,"partial_owning",partial_owning);
+impl_scenario_group!(NonOwning,"non_owning",non_owning);
+
+/// Get the digest of items sorted by id
+fn digest_sorted<T : Hash>(items : Vec<(EntityId,T)>) -> u64 {
+    let mut hasher = FnvHasher::default();
+    for (id,item) in items {
+        let mut item_hasher = FnvHasher::default();
+        item.hash(&mut item_hasher);
+        hasher.write_usize(id.index());
+        hasher.write_u64(item_hasher.finish());
+    }
+    hasher.finish()
+}
+
+/// The weights of random operations
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct OpMix {
+    /// Attach a random component to a random entity
+    pub attach : f64,
+    /// Detach a random component from a random entity
+    pub detach : f64,
+    /// Remove a random entity
+    pub despawn : f64,
+    /// Create an entity with random components
+    pub spawn : f64
+}
+
+impl Default for OpMix {
+    fn default() -> Self {
+        OpMix {
+            attach : 0.3,
+            detach : 0.2,
+            despawn : 0.1,
+            spawn : 0.4
+        }
+    }
+}
+
+struct ComponentSpec {
+    name : &'static str,
+    density : f64,
+    register : fn(&mut World),
+    attach : fn(&World,EntityId,&mut Rng),
+    detach : fn(&World,EntityId),
+    digest : fn(&World) -> u64
+}
+
+struct GroupSpec {
+    name : String,
+    make : fn(&mut World),
+    group_digest : fn(&World) -> u64,
+    tuple_digest : fn(&World) -> u64
+}
+
+/// A builder to build a deterministic [Scenario](crate::test_util::Scenario)
+pub struct ScenarioBuilder {
+    seed : u64,
+    entities : usize,
+    components : Vec<ComponentSpec>,
+    groups : Vec<GroupSpec>,
+    ops : usize,
+    mix : OpMix,
+    snapshot_every : Option<usize>
+}
+
+impl ScenarioBuilder {
+    /// Create a builder with ```seed```
+    pub fn seed(seed : u64) -> Self {
+        ScenarioBuilder {
+            seed,
+            entities : 0,
+            components : vec![],
+            groups : vec![],
+            ops : 0,
+            mix : OpMix::default(),
+            snapshot_every : None
+        }
+    }
+
+    /// Set the count of initial entities
+    pub fn entities(mut self,count : usize) -> Self {
+        self.entities = count;
+        self
+    }
+
+    /// Add a component type,```density``` is the probability of an entity having this component
+    pub fn component<T : ScenarioComponent>(mut self,density : f64) -> Self {
+        self.components.push(ComponentSpec {
+            name : std::any::type_name::<T>(),
+            density,
+            register : |world| { world.register::<T>(); },
+            attach : |world,id,rng| world.attach_component(id,T::random(rng)),
+            detach : |world,id| { world.detach_component::<T>(id); },
+            digest : |world| digest(world.query::<&T>())
+        });
+        self
+    }
+
+    /// Add a group,the group is made after initial entities are created
+    pub fn group<G : ScenarioGroup>(mut self,_group : G) -> Self {
+        self.groups.push(GroupSpec {
+            name : G::name(),
+            make : G::make,
+            group_digest : G::group_digest,
+            tuple_digest : G::tuple_digest
+        });
+        self
+    }
+
+    /// Set the count of random operations and their weights
+    pub fn ops(mut self,count : usize,mix : OpMix) -> Self {
+        self.ops = count;
+        self.mix = mix;
+        self
+    }
+
+    /// Snapshot after every ```count``` operations,the default is a tenth of all operations
+    pub fn snapshot_every(mut self,count : usize) -> Self {
+        self.snapshot_every = Some(count);
+        self
+    }
+
+    /// Build the world and run all operations
+    /// # Panics
+    /// * Panic if a group query is inconsistent with its plain tuple query
+    pub fn run(self) -> Scenario {
+        let mut rng = Rng::new(self.seed);
+        let mut world = World::new();
+        for component in &self.components {
+            (component.register)(&mut world);
+        }
+        let mut alive = vec![];
+        for _ in 0..self.entities {
+            alive.push(self.spawn(&world,&mut rng));
+        }
+        for group in &self.groups {
+            (group.make)(&mut world);
+        }
+
+        let every = self.snapshot_every
+            .unwrap_or(self.ops / 10)
+            .max(1);
+        let mut snapshots = vec![self.snapshot(&world,0)];
+        let total = self.mix.attach + self.mix.detach + self.mix.despawn + self.mix.spawn;
+        for op in 1..=self.ops {
+            let roll = rng.next_f64() * total;
+            if roll < self.mix.spawn || alive.is_empty() {
+                alive.push(self.spawn(&world,&mut rng));
+            } else if roll < self.mix.spawn + self.mix.despawn {
+                let id = alive.swap_remove(rng.below(alive.len()));
+                world.remove_entity(id);
+            } else if !self.components.is_empty() {
+                let id = alive[rng.below(alive.len())];
+                let component = &self.components[rng.below(self.components.len())];
+                if roll < self.mix.spawn + self.mix.despawn + self.mix.attach {
+                    (component.attach)(&world,id,&mut rng);
+                } else {
+                    (component.detach)(&world,id);
+                }
+            }
+            if op % every == 0 {
+                snapshots.push(self.snapshot(&world,op));
+            }
+        }
+        Scenario {
+            world,
+            snapshots
+        }
+    }
+
+    fn spawn(&self,world : &World,rng : &mut Rng) -> EntityId {
+        let id = world.create_entity().into_id();
+        for component in &self.components {
+            if rng.next_f64() < component.density {
+                (component.attach)(world,id,rng);
+            }
+        }
+        id
+    }
+
+    fn snapshot(&self,world : &World,ops : usize) -> String {
+        let mut line = format!("ops={}",ops);
+        for component in &self.components {
+            // write! to String never fails
+            write!(line," &{}={:016x}",component.name,(component.digest)(world)).unwrap();
+        }
+        for group in &self.groups {
+            let digest = (group.group_digest)(world);
+            assert_eq!(digest,(group.tuple_digest)(world),
+                       "Scenario:Group {} is inconsistent with storages after {} operations",
+                       group.name,ops);
+            write!(line," {}={:016x}",group.name,digest).unwrap();
+        }
+        line
+    }
+}
+
+/// The result of running a [ScenarioBuilder](crate::test_util::ScenarioBuilder)
+pub struct Scenario {
+    world : World,
+    snapshots : Vec<String>
+}
+
+impl Scenario {
+    /// Get the world after all operations
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Get the digest lines of all snapshots
+    pub fn snapshots(&self) -> &[String] {
+        &self.snapshots
+    }
+
+    /// Get the digest text which is stored in golden file
+    pub fn digest_text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.snapshots {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Compare the digests with the golden file in ```path```.
+    /// # Details
+    /// If ```XECS_UPDATE_GOLDEN``` environment variable is set,
+    /// the golden file is regenerated instead.
+    /// # Panics
+    /// * Panic if the digests are different from golden file.
+    /// * Panic if golden file cannot be read or written.
+    pub fn check_golden<P : AsRef<Path>>(&self,path : P) {
+        let path = path.as_ref();
+        let text = self.digest_text();
+        if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .expect("Scenario:Cannot create the directory of golden file");
+            }
+            std::fs::write(path,text)
+                .expect("Scenario:Cannot write golden file");
+            return;
+        }
+        let golden = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Scenario:Cannot read golden file {}: {}. Set {} to generate it",
+                                         path.display(),err,UPDATE_GOLDEN_ENV));
+        for (index,(expected,actual)) in golden.lines().zip(text.lines()).enumerate() {
+            assert_eq!(expected,actual,
+                       "Scenario:Snapshot {} is different from golden file {}",index,path.display());
+        }
+        assert_eq!(golden.lines().count(),text.lines().count(),
+                   "Scenario:The count of snapshots is different from golden file {}",path.display());
+    }
+}
@@ -0,0 +1,466 @@
+//! # Transaction
+//! A [Transaction](crate::transaction::Transaction) batches structural changes of
+//! [World](crate::world::World) and makes them all-or-nothing.
+//! ## Details
+//! All operations are applied to world immediately, so the reads inside
+//! the transaction see the changes.Transaction only records the data
+//! needed to undo these operations.
+//! * [commit](crate::transaction::Transaction::commit) drops the undo log.
+//! * [rollback](crate::transaction::Transaction::rollback) or dropping
+//! a transaction without commit undoes all operations in reverse order.
+//!
+//! Groups are maintained as usual during both applying and undoing.
+//! The [bindings](crate::world::World::bind_to) and [parents](crate::world::World::set_parent)
+//! of despawned entities are kept until commit,
+//! then the bound entities are removed and the links are cleaned up
+//! in the order of despawning.
+use std::{any::{Any, TypeId}, mem::{self, ManuallyDrop}};
+use crate::{component::{Component, ErasedValue}, entity::EntityId, error::HierarchyError, world::World};
+
+/// Type-erased component
+type AnyComponent = Box<dyn Any + Send + Sync>;
+
+/// The data to undo an operation
+enum Undo {
+    /// An entity was created
+    Create(EntityId),
+    /// A new component was attached
+    Attach {
+        id : EntityId,
+        type_id : TypeId
+    },
+    /// A component was replaced in place
+    Replace {
+        id : EntityId,
+        type_id : TypeId,
+        old : AnyComponent,
+        tick : u64
+    },
+    /// A component was detached
+    Detach {
+        id : EntityId,
+        type_id : TypeId,
+        component : AnyComponent
+    },
+    /// An entity was removed with all its components
+    Remove {
+        id : EntityId,
+        components : Vec<(TypeId,AnyComponent)>
+    },
+    /// The parent of an entity was replaced
+    SetParent {
+        child : EntityId,
+        old : Option<EntityId>
+    }
+}
+
+/// A guard to make structural changes of [World](crate::world::World) atomically.
+/// # Details
+/// Only one transaction can be alive in a world at a time.
+pub struct Transaction<'a> {
+    world : &'a World,
+    log : Vec<Undo>
+}
+
+impl<'a> Transaction<'a> {
+    pub(in crate) fn new(world : &'a World) -> Self {
+        Transaction {
+            world,
+            log : vec![]
+        }
+    }
+
+    /// Get the world of this transaction
+    pub fn world(&self) -> &'a World {
+        self.world
+    }
+
+    /// Create an entity without any component,return its ID.
+    pub fn create_entity(&mut self) -> EntityId {
+        let id = self.world.create_entity().into_id();
+        self.log.push(Undo::Create(id));
+        id
+    }
+
+    /// Insert a component to an entity,the old component is replaced.
+    /// # Details
+    /// Like [add_component](crate::world::World::add_component),
+    /// the old component is overwritten in place and only marked as changed,
+    /// rollback writes it back in place with its old change tick.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// * Panic if ```entity_id``` not exist.
+    pub fn insert_component<T : Component>(&mut self,entity_id : EntityId,component : T) {
+        assert!(self.world.exist(entity_id),
+                "Transaction:Cannot insert component to a non-existence entity");
+        let type_id = TypeId::of::<T>();
+        let has = self.world.raw_storage_read(type_id)
+            .expect("Transaction:Cannot insert component because components has not been registered.")
+            .has(entity_id);
+        if has {
+            let tick = self.world.change_tick();
+            // Unwrap here never fails
+            // entity has the component
+            let (old,tick) = self.world.replace_any(entity_id,type_id,Box::new(component),tick).unwrap();
+            self.log.push(Undo::Replace {
+                id : entity_id,
+                type_id,
+                old,
+                tick
+            });
+        } else {
+            let mut component = ManuallyDrop::new(component);
+            // Safety:
+            // component is never used again
+            self.world.attach_erased(entity_id,unsafe { ErasedValue::new(&mut component) });
+            self.log.push(Undo::Attach {
+                id : entity_id,
+                type_id
+            });
+        }
+    }
+
+    /// Remove a component from an entity.
+    /// # Details
+    /// Return ```None``` if entity doesn't have this component,
+    /// otherwise return ```Some(component)```.
+    /// ```T``` must be ```Clone``` because there are 2 owners of the component:
+    /// the transaction keeps the removed component to attach it back on rollback,
+    /// and the caller gets a clone.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// * Panic if ```entity_id``` not exist.
+    pub fn remove_component<T : Component + Clone>(&mut self,entity_id : EntityId) -> Option<T> {
+        assert!(self.world.exist(entity_id),
+                "Transaction:Cannot remove component from a non-existence entity");
+        let type_id = TypeId::of::<T>();
+        let component = self.world.detach_any(entity_id,type_id)?;
+        // unwrap never fails because the storage of type_id is SparseSet<EntityId,T>
+        let cloned = component.downcast_ref::<T>().unwrap().clone();
+        self.log.push(Undo::Detach {
+            id : entity_id,
+            type_id,
+            component
+        });
+        Some(cloned)
+    }
+
+    /// Despawn an entity,remove it and all of its components.
+    /// # Details
+    /// The entities [bound](crate::world::World::bind_to) to it are removed
+    /// when the transaction is committed.
+    /// # Panics
+    /// * Panic if ```entity_id``` not exist.
+    pub fn despawn(&mut self,entity_id : EntityId) {
+        assert!(self.world.exist(entity_id),
+                "Transaction:Cannot despawn a non-exists entity");
+        let components = self.world.detach_all(entity_id);
+        self.world.remove_entity(entity_id);
+        self.log.push(Undo::Remove {
+            id : entity_id,
+            components
+        });
+    }
+
+    /// Set ```parent``` as the parent of ```child```.
+    /// # Details
+    /// See [World::set_parent](crate::world::World::set_parent).
+    /// # Errors
+    /// * [EntityNotFound](crate::HierarchyError::EntityNotFound) if ```child``` or ```parent``` is not alive.
+    /// * [Cycle](crate::HierarchyError::Cycle) if ```parent``` is ```child``` or a descendant of ```child```.
+    pub fn set_parent(&mut self,child : EntityId,parent : EntityId) -> Result<(),HierarchyError> {
+        let old = self.world.raw_parent(child);
+        self.world.set_parent(child,parent)?;
+        self.log.push(Undo::SetParent {
+            child,
+            old
+        });
+        Ok(())
+    }
+
+    /// Finish the transaction and keep all changes.
+    /// # Details
+    /// The removal of despawned entities is finished after the transaction ends,
+    /// so the entities bound to them are removed in a normal way.
+    pub fn commit(mut self) {
+        let world = self.world;
+        let log = mem::take(&mut self.log);
+        // end the transaction
+        drop(self);
+        for undo in log {
+            if let Undo::Remove { id, components } = undo {
+                world.finish_removal(id,components);
+            }
+        }
+    }
+
+    /// Undo all changes in reverse order.
+    pub fn rollback(self) {
+        // Drop does the rollback
+    }
+
+    fn undo(&mut self) {
+        let world = self.world;
+        while let Some(undo) = self.log.pop() {
+            match undo {
+                Undo::Create(id) => {
+                    world.remove_entity(id);
+                },
+                Undo::Attach { id, type_id } => {
+                    world.detach_any(id,type_id);
+                },
+                Undo::Replace { id, type_id, old, tick } => {
+                    world.replace_any(id,type_id,old,tick);
+                },
+                Undo::Detach { id, type_id, component } => {
+                    world.attach_any(id,type_id,component);
+                },
+                Undo::Remove { id, components } => {
+                    world.restore_entity(id);
+                    for (type_id,component) in components {
+                        world.attach_any(id,type_id,component);
+                    }
+                },
+                Undo::SetParent { child, old } => {
+                    world.restore_parent(child,old);
+                },
+            }
+        }
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        self.undo();
+        self.world.end_transaction();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{entity::EntityId, error::{HierarchyError, XecsError}, group::{full_owning, partial_owning, FullOwning, PartialOwning}, lifetime::BoundTo, query::{Added, Changed, WithId}, world::World};
+
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Item(&'static str);
+    #[derive(Debug,Clone,Copy,PartialEq)]
+    struct Gold(u32);
+
+    fn world() -> (World,EntityId,EntityId) {
+        let mut world = World::new();
+        world.register::<Item>()
+            .register::<Gold>()
+            .register::<char>();
+        let a = world.create_entity()
+            .attach(Item("sword"))
+            .attach(Gold(10))
+            .into_id();
+        let b = world.create_entity()
+            .attach(Gold(100))
+            .attach('b')
+            .into_id();
+        world.make_group(full_owning::<Item,Gold>());
+        world.make_group(partial_owning::<char,Gold>());
+        (world,a,b)
+    }
+
+    type Row = (EntityId,Option<Item>,Option<Gold>,Option<char>);
+
+    fn snapshot(world : &World) -> Vec<Row> {
+        let mut ids = world.query::<&Gold>()
+            .with_id()
+            .map(|(id,_)|id)
+            .chain(world.query::<&Item>().with_id().map(|(id,_)|id))
+            .chain(world.query::<&char>().with_id().map(|(id,_)|id))
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        ids.into_iter()
+            .map(|id|(id,
+                      world.entity_component_read::<Item>(id).map(|c|*c),
+                      world.entity_component_read::<Gold>(id).map(|c|*c),
+                      world.entity_component_read::<char>(id).map(|c|*c)))
+            .collect()
+    }
+
+    /// Check the groups are consistent with the storages
+    fn check_consistency(world : &World) {
+        let mut expected = world.query::<(&Item,&Gold)>()
+            .map(|(item,gold)|(item.0,gold.0))
+            .collect::<Vec<_>>();
+        let mut grouped = world.query::<FullOwning<&Item,&Gold>>()
+            .map(|(item,gold)|(item.0,gold.0))
+            .collect::<Vec<_>>();
+        expected.sort();
+        grouped.sort();
+        assert_eq!(expected,grouped);
+
+        let mut expected = world.query::<(&char,&Gold)>()
+            .map(|(c,gold)|(*c,gold.0))
+            .collect::<Vec<_>>();
+        let mut grouped = world.query::<PartialOwning<&char,&Gold>>()
+            .map(|(c,gold)|(*c,gold.0))
+            .collect::<Vec<_>>();
+        expected.sort();
+        grouped.sort();
+        assert_eq!(expected,grouped);
+    }
+
+    #[test]
+    fn commit_test() {
+        let (world,a,b) = world();
+        let mut tx = world.begin_transaction();
+        let item = tx.remove_component::<Item>(a).unwrap();
+        tx.insert_component(b,item);
+        tx.insert_component(a,Gold(60));
+        tx.insert_component(b,Gold(50));
+        let c = tx.create_entity();
+        tx.insert_component(c,'c');
+        // reads inside transaction see the changes
+        assert_eq!(tx.world().entity_component_read::<Item>(b).unwrap().0,"sword");
+        tx.commit();
+
+        assert_eq!(snapshot(&world),vec![
+            (a,None,Some(Gold(60)),None),
+            (b,Some(Item("sword")),Some(Gold(50)),Some('b')),
+            (c,None,None,Some('c'))
+        ]);
+        check_consistency(&world);
+
+        // a new transaction can be started after commit
+        let tx = world.begin_transaction();
+        tx.rollback();
+    }
+
+    #[test]
+    fn rollback_test() {
+        let (world,a,b) = world();
+        let c = world.create_entity().attach('c').attach(Gold(1)).into_id();
+        let before = snapshot(&world);
+
+        {
+            let mut tx = world.begin_transaction();
+            let item = tx.remove_component::<Item>(a).unwrap();
+            tx.insert_component(b,item);
+            tx.insert_component(b,Gold(0));
+            tx.despawn(c);
+            let d = tx.create_entity();
+            tx.insert_component(d,Gold(7));
+            tx.insert_component(d,'d');
+            check_consistency(tx.world());
+            // validation fails here, dropping the transaction rolls back
+        }
+
+        assert_eq!(snapshot(&world),before);
+        assert!(world.exist(c));
+        check_consistency(&world);
+
+        let mut tx = world.begin_transaction();
+        tx.despawn(a);
+        tx.insert_component(b,Item("shield"));
+        tx.rollback();
+
+        assert_eq!(snapshot(&world),before);
+        check_consistency(&world);
+    }
+
+    #[test]
+    fn replace_in_place_test() {
+        let (world,a,b) = world();
+        let c = world.create_entity().attach(Item("axe")).attach(Gold(5)).into_id();
+        let order = |world : &World| {
+            (world.query::<FullOwning<&Item,&Gold>>().with_id().map(|(id,_)|id).collect::<Vec<_>>(),
+             world.query::<&Gold>().with_id().map(|(id,_)|id).collect::<Vec<_>>())
+        };
+        let before = order(&world);
+        let ptr = world.component_ptr::<Gold>(a).unwrap();
+        world.tick();
+        world.tick();
+
+        let mut tx = world.begin_transaction();
+        tx.insert_component(a,Gold(60));
+        tx.insert_component(b,Gold(0));
+        assert_eq!(order(&world),before);
+        // replacing is a change,not an addition
+        assert_eq!(world.query::<Added<Gold>>().count(),0);
+        assert_eq!(world.query::<Changed<Gold>>().count(),2);
+        assert_eq!(*world.resolve(&ptr).unwrap(),Gold(60));
+        tx.rollback();
+
+        assert_eq!(order(&world),before);
+        assert_eq!(world.query::<Added<Gold>>().count(),0);
+        assert_eq!(world.query::<Changed<Gold>>().count(),0);
+        assert_eq!(*world.resolve(&ptr).unwrap(),Gold(10));
+        assert_eq!(world.get::<Gold>(b).as_deref(),Some(&Gold(100)));
+        assert!(world.exist(c));
+        check_consistency(&world);
+    }
+
+    #[test]
+    fn despawn_commit_test() {
+        let (mut world,a,b) = world();
+        world.register::<BoundTo>();
+        let bound = world.create_entity().into_id();
+        world.bind_to(bound,a).unwrap();
+        let child = world.create_entity().into_id();
+        world.set_parent(child,b).unwrap();
+
+        // the bindings and links are kept for rollback
+        let mut tx = world.begin_transaction();
+        tx.despawn(a);
+        tx.despawn(b);
+        assert!(world.exist(bound));
+        tx.rollback();
+        assert!(world.exist(a));
+        assert_eq!(world.parent(child),Some(b));
+        world.remove_entity(a);
+        assert!(!world.exist(bound));
+
+        let bound = world.create_entity().into_id();
+        world.bind_to(bound,b).unwrap();
+        let mut tx = world.begin_transaction();
+        tx.despawn(b);
+        tx.commit();
+        assert!(!world.exist(bound));
+        assert_eq!(world.parent(child),None);
+        assert_eq!(world.children(b).count(),0);
+    }
+
+    #[test]
+    fn set_parent_test() {
+        let (world,a,b) = world();
+        let c = world.create_entity().into_id();
+        world.set_parent(c,a).unwrap();
+
+        let mut tx = world.begin_transaction();
+        assert_eq!(tx.set_parent(c,b),Ok(()));
+        assert_eq!(tx.set_parent(a,c),Ok(()));
+        assert_eq!(tx.set_parent(b,a),Err(HierarchyError::Cycle { child : b, parent : a }));
+        assert_eq!(world.children(b).collect::<Vec<_>>(),vec![c]);
+        tx.rollback();
+        assert_eq!(world.parent(c),Some(a));
+        assert_eq!(world.parent(a),None);
+        assert_eq!(world.children(b).count(),0);
+
+        let mut tx = world.begin_transaction();
+        tx.set_parent(c,b).unwrap();
+        tx.commit();
+        assert_eq!(world.parent(c),Some(b));
+    }
+
+    #[test]
+    fn try_begin_transaction_test() {
+        let (world,_,_) = world();
+        let tx = world.try_begin_transaction().unwrap();
+        assert!(matches!(world.try_begin_transaction(),Err(XecsError::NestedTransaction)));
+        tx.rollback();
+        assert!(world.try_begin_transaction().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "another transaction is alive")]
+    fn nested_transaction_test() {
+        let (world,_,_) = world();
+        let _tx = world.begin_transaction();
+        let _nested = world.begin_transaction();
+    }
+}
@@ -1,21 +1,61 @@
-use crate::component::{Component, ComponentRead, ComponentStorage, ComponentWrite, StorageRead, StorageWrite};
-use crate::entity::{Entity, EntityId, EntityManager, Entities};
-use crate::group::Group;
-use crate::query::{QueryIterator, Queryable};
-use crate::resource::{Resource, ResourceRead, ResourceWrite};
-use crate::sparse_set::SparseSet;
-use std::any::TypeId;
-use std::collections::HashMap;
+use crate::component::{AnyComponentRead, Component, ComponentPtr, ComponentRead, ComponentStorage, ComponentWrite, ErasedValue, IdsSnapshot, MultiStorageSwap, StorageKind, StorageRead, StorageWrite};
+use crate::bundle::{Bundle, RemoveBundleError};
+use crate::command::Commands;
+use crate::events::{Event, EventIter, EventReader, Events};
+use crate::hierarchy::Hierarchy;
+use crate::error::{GroupError, HierarchyError, QueryError, XecsError};
+use crate::epoch::{Epoch, EpochChanged};
+use crate::finalizer::{Finalizer, FinalizerCtx};
+use crate::entity::{Entity, EntityId, EntityIdExhausted, EntityManager, EntityStats, Entities};
+use crate::group::{full_owning, Group, GroupHandle, GroupQueryable, LockedGroup};
+use crate::query::{DoubleEndedQueryable, DynQueryIter, QueryIterator, Queryable};
+use crate::lifetime::{BoundTo, Lifetime};
+#[cfg(feature = "rayon")]
+use crate::query::{ParQuery, ParQueryable};
+use crate::resource::{Resource, ResourceMut, ResourceParam, ResourceRead, ResourceRef, ResourceWrite};
+use crate::sparse_set::{self, permute, SparseSet};
+use crate::snapshot::{CloneRegistry, StorageSnapshot, WorldSnapshot};
+use crate::transaction::Transaction;
+#[cfg(feature = "serde")]
+use crate::serde::SerializeRegistry;
+use std::any::{Any, TypeId};
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::{Arc, Weak};
 use std::fmt::{Debug, Formatter};
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::lock::{EntityManagerReadGuard, GroupReadGuard, GroupWriteGuard, RwLock, StorageReadGuard, StorageWriteGuard};
+
+/// The structural version and the weak IDs snapshot of each storage
+type IdsSnapshotCache = HashMap<TypeId,(u64,Weak<[EntityId]>)>;
 
 /// World is the core of XECS.It manages all components and entities
 pub struct World {
     entity_manager: RwLock<EntityManager>,
     // Box<SparseSet<EntityId,Component>>
     components: HashMap<TypeId,RwLock<Box<dyn ComponentStorage>>>,
+    // All registered components sorted by TypeId
+    component_index: Vec<(TypeId,&'static str,StorageKind)>,
     groups: Vec<RwLock<Group>>,
-    resources : HashMap<TypeId,RwLock<Box<dyn Resource>>>
+    // bumped when any group is removed,the handles of groups are invalidated
+    groups_generation : u64,
+    resources : HashMap<TypeId,RwLock<Box<dyn Resource>>>,
+    in_transaction : AtomicBool,
+    // sorted by priority
+    finalizers : Vec<Finalizer>,
+    // the cached IDs snapshots,they are released when no consumer holds them
+    ids_snapshots : RwLock<IdsSnapshotCache>,
+    // the current frame for change detection,it starts from 1
+    change_tick : AtomicU64,
+    // the entities bound to each target by BoundTo,
+    // they are checked by handle before despawned
+    bindings : RwLock<HashMap<EntityId,Vec<EntityId>>>,
+    // the parent and children of entities
+    hierarchy : RwLock<Hierarchy>,
+    // update the buffers of every registered event type
+    event_updates : Vec<fn(&World)>,
+    // the components saved by World::serialize
+    #[cfg(feature = "serde")]
+    serializable : SerializeRegistry
 }
 
 impl World {
@@ -24,11 +64,41 @@ impl World {
         World {
             entity_manager: RwLock::new(EntityManager::new()),
             components: Default::default(),
+            component_index: Default::default(),
             groups: Default::default(),
-            resources : Default::default()
+            groups_generation : 0,
+            resources : Default::default(),
+            in_transaction : AtomicBool::new(false),
+            finalizers : vec![],
+            ids_snapshots : Default::default(),
+            change_tick : AtomicU64::new(1),
+            bindings : Default::default(),
+            hierarchy : Default::default(),
+            event_updates : vec![],
+            #[cfg(feature = "serde")]
+            serializable : SerializeRegistry::new()
         }
     }
 
+    /// Go to the next frame of change detection.
+    /// # Details
+    /// [Changed<T>](crate::query::Changed) and [Added<T>](crate::query::Added) yield the components
+    /// which were changed or added in last frame or current frame.
+    /// Return the new tick.
+    pub fn increment_tick(&self) -> u64 {
+        self.change_tick.fetch_add(1,Ordering::AcqRel) + 1
+    }
+
+    /// Same as [increment_tick](crate::world::World::increment_tick)
+    pub fn tick(&self) -> u64 {
+        self.increment_tick()
+    }
+
+    /// Get the current tick of change detection
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick.load(Ordering::Acquire)
+    }
+
     /// Register resource in world 
     pub fn register_resource<R : Resource>(&mut self,resource : R) {
         let type_id = TypeId::of::<R>();
@@ -51,20 +121,370 @@ impl World {
         Some(ResourceWrite::new(lock))
     }
 
-    /// Register a component.
+    /// Insert a resource into world.
+    /// # Details
+    /// Return the old resource if there is one.
+    pub fn insert_resource<R : Resource>(&mut self,resource : R) -> Option<R> {
+        let type_id = TypeId::of::<R>();
+        let old = self.resources.insert(type_id,RwLock::new(Box::new(resource)))?;
+        let ptr = Box::into_raw(old.into_inner()) as *mut R;
+        // Safety:
+        // the resource is stored by its TypeId,so it's R
+        Some(*unsafe { Box::from_raw(ptr) })
+    }
+
+    /// Get a read guard of resource.
+    /// # Details
+    /// Return ```None``` if the resource is not in world.
+    /// # Panics
+    /// * Panic in ```refcell``` feature if the resource is borrowed mutably.
+    pub fn resource<R : Resource>(&self) -> Option<ResourceRef<'_,R>> {
+        self.resource_read::<R>()
+    }
+
+    /// Get a write guard of resource.
+    /// # Details
+    /// Return ```None``` if the resource is not in world.
+    /// # Panics
+    /// * Panic in ```refcell``` feature if the resource is borrowed.
+    pub fn resource_mut<R : Resource>(&self) -> Option<ResourceMut<'_,R>> {
+        self.resource_write::<R>()
+    }
+
+    /// Apply the [Commands](crate::command::Commands) resource and clear it.
+    /// # Details
+    /// Nothing happens if there is no [Commands](crate::command::Commands) resource.
+    /// The mutations recorded while flushing are kept for the next flush.
+    pub fn flush_commands(&mut self) {
+        let mut commands = match self.resource_mut::<Commands>() {
+            Some(mut commands) => std::mem::take(&mut *commands),
+            None => return
+        };
+        commands.flush(self);
+    }
+
+    /// Apply a standalone [Commands](crate::command::Commands) in recording order.
+    /// # Details
+    /// Call it after all iterators are dropped,
+    /// it's the same as ```commands.flush(world)```.
+    pub fn apply_commands(&mut self,mut commands : Commands) {
+        commands.flush(self);
+    }
+
+    /// Register the [Events<T>](crate::events::Events) resource to send events of type ```T```.
+    /// # Details
+    /// Nothing happens if ```T``` has been registered.
+    pub fn register_event<T : Event>(&mut self) -> &mut Self {
+        if !self.resources.contains_key(&TypeId::of::<Events<T>>()) {
+            self.insert_resource(Events::<T>::new());
+            self.event_updates.push(|world|{
+                // Unwrap here never fails
+                // the resource is inserted above and never removed by world
+                world.resource_mut::<Events<T>>().unwrap().update();
+            });
+        }
+        self
+    }
+
+    /// Send an event to all [EventReader](crate::events::EventReader)s of ```T```
+    /// # Panics
+    /// * Panic if ```T``` is not registered by [register_event](World::register_event).
+    pub fn send_event<T : Event>(&self,event : T) {
+        self.resource_mut::<Events<T>>()
+            .expect("World:Cannot send event because event has not been registered.")
+            .send(event);
+    }
+
+    /// Read the events which ```reader``` has not read.
+    /// # Details
+    /// ```reader``` is moved to the end,so every event is read once by each reader.
+    /// The [Events<T>](crate::events::Events) resource is locked until the iterator is dropped.
     /// # Panics
-    /// Panic if component is registered.
+    /// * Panic if ```T``` is not registered by [register_event](World::register_event).
+    pub fn read_events<T : Event>(&self,reader : &mut EventReader<T>) -> EventIter<'_,T> {
+        let lock = self.resources.get(&TypeId::of::<Events<T>>())
+            .expect("World:Cannot read events because event has not been registered.")
+            .read();
+        EventIter::new(lock,reader)
+    }
+
+    /// Update the [Events](crate::events::Events) of all registered types,it should be called once per frame.
+    /// # Details
+    /// The events sent before last update are dropped.
+    pub fn update_events(&mut self) {
+        for update in &self.event_updates {
+            update(self);
+        }
+    }
+
+    /// Fetch a resource by a [descriptor](crate::resource::ResourceParam),
+    /// ```&R``` or ```&mut R```
+    pub fn fetch_resource<'a,P : ResourceParam<'a>>(&'a self) -> Option<P::Guard> {
+        P::fetch(self)
+    }
+
+    /// Register a component.
+    /// # Details
+    /// Nothing happens if the component is registered,
+    /// so different systems can register the same component.
+    /// The storage kind of a registered component is not changed.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// world.create_entity().attach(1_u32);
+    /// world.register::<u32>();
+    /// assert_eq!(world.query::<&u32>().count(),1);
+    /// ```
     pub fn register<T: Component>(&mut self) -> &mut Self {
-        assert!(!self.has_registered::<T>(),
-                "World:Cannot register a component twice");
+        if self.has_registered::<T>() {
+            return self;
+        }
         let type_id = TypeId::of::<T>();
         self.components.insert(
             type_id,
             RwLock::new(Box::new(SparseSet::<EntityId, T>::new())),
         );
+        // keep the index sorted,
+        // so the inspection APIs do not depend on the order of HashMap
+        let index = self.component_index
+            .binary_search_by_key(&type_id,|(type_id,_,_)|*type_id)
+            .unwrap_err();
+        self.component_index.insert(index,(type_id,std::any::type_name::<T>(),StorageKind::SparseSet));
+        self
+    }
+
+    /// Register a component,fail if it's registered.
+    /// # Errors
+    /// * [ComponentAlreadyRegistered](crate::XecsError::ComponentAlreadyRegistered) if ```T``` is registered.
+    pub fn try_register<T : Component>(&mut self) -> Result<&mut Self,XecsError> {
+        if self.has_registered::<T>() {
+            return Err(XecsError::ComponentAlreadyRegistered(std::any::type_name::<T>()));
+        }
+        Ok(self.register::<T>())
+    }
+
+    /// Register all components of a [Bundle](crate::bundle::Bundle).
+    /// # Details
+    /// The components which have been registered are skipped,
+    /// so bundles can share components.
+    pub fn register_bundle<B : Bundle>(&mut self) -> &mut Self {
+        B::register(self);
+        self
+    }
+
+    /// Register a component which is saved when world is serialized.
+    /// # Details
+    /// The component is saved with its type name,
+    /// use [SerializeRegistry](crate::serde::SerializeRegistry) if the name should be stable.
+    /// See [serde](crate::serde).
+    /// # Panics
+    /// Panic if component is registered as serializable.
+    #[cfg(feature = "serde")]
+    pub fn register_serializable<T>(&mut self) -> &mut Self
+        where T : Component + ::serde::Serialize + ::serde::de::DeserializeOwned {
+        self.register::<T>();
+        self.serializable.register::<T>(std::any::type_name::<T>());
+        self
+    }
+
+    /// Register a double-buffered component.
+    /// # Details
+    /// The storage keeps two dense buffers:
+    /// * ```&T``` and ```&mut T``` access the front buffer.
+    /// * [Next<T>](crate::query::Next) accesses the back buffer.
+    ///
+    /// [swap_buffers](World::swap_buffers) flips them in O(1).
+    /// A new component is cloned to initialize both buffers.  
+    /// Double-buffered component cannot be owned by any group.
+    /// # Panics
+    /// * Panic if ```T``` is already registered.
+    pub fn register_double_buffered<T : Component + Clone>(&mut self) -> &mut Self {
+        // a registered storage may be owned by groups
+        assert!(!self.has_registered::<T>(),
+                "World:Cannot register a double-buffered component twice");
+        self.register::<T>();
+        let type_id = TypeId::of::<T>();
+        {
+            // unwrap never fails because T is registered above
+            let mut storage = self.raw_storage_write(type_id).unwrap();
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            let sparse_set = unsafe {
+                storage.downcast_mut::<SparseSet<EntityId,T>>()
+            };
+            sparse_set.enable_back_buffer(T::clone);
+        }
+        // unwrap never fails because T is registered above
+        let index = self.component_index
+            .binary_search_by_key(&type_id,|(type_id,_,_)|*type_id)
+            .unwrap();
+        self.component_index[index].2 = StorageKind::DoubleBuffered;
+        self
+    }
+
+    /// Swap the front buffer and back buffer of a double-buffered component in O(1).
+    /// # Details
+    /// The values written by [Next<T>](crate::query::Next) become visible to ```&T```,
+    /// and the back buffer holds the values of last frame.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// * Panic if ```T``` is not [double-buffered](World::register_double_buffered).
+    pub fn swap_buffers<T : Component>(&self) {
+        let mut storage = self.raw_storage_write(TypeId::of::<T>())
+            .expect("World:Cannot swap buffers because components has not been registered.");
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe {
+            storage.downcast_mut::<SparseSet<EntityId,T>>()
+        };
+        sparse_set.swap_buffers();
+    }
+
+    /// Sort the storage of ```T``` by ```cmp```,then ```world.query::<&T>()``` yields in the sorted order.
+    /// # Details
+    /// The sort is stable.  
+    /// If ```T``` is owned by a group,the entities in group and the others are sorted separately,
+    /// so the group is kept.
+    /// The other storages owned by a [full-owning group](crate::group::FullOwning) are rearranged together,
+    /// so the group query yields in the sorted order too.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// #[derive(Debug,PartialEq)]
+    /// struct Sprite { z : i32 }
+    ///
+    /// let mut world = World::new();
+    /// world.register::<Sprite>();
+    /// for z in [3,-1,2] {
+    ///     world.create_entity().attach(Sprite { z });
+    /// }
+    /// world.sort_storage_by::<Sprite,_>(|a,b|a.z.cmp(&b.z));
+    /// let z = world.query::<&Sprite>()
+    ///     .map(|sprite|sprite.z)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(z,vec![-1,2,3]);
+    /// ```
+    pub fn sort_storage_by<T : Component,F : FnMut(&T,&T) -> std::cmp::Ordering>(&mut self,mut cmp : F) {
+        let type_id = TypeId::of::<T>();
+        assert!(self.has_registered::<T>(),
+                "World:Cannot sort storage because components has not been registered.");
+        // the length of group part and the storages aligned with T in group
+        let (group_len,others) = self.find_owner(type_id)
+            .map(|group| {
+                let others = group.owning()
+                    .into_iter()
+                    .filter(|other|*other != type_id)
+                    .collect::<Vec<_>>();
+                (group.len(),others)
+            })
+            .unwrap_or((0,vec![]));
+        // Unwrap here never fails
+        // T is registered and the components of group must be registered
+        let mut storage = self.raw_storage_write(type_id).unwrap();
+        let mut others = others.into_iter()
+            .map(|other|self.raw_storage_write(other).unwrap())
+            .collect::<Vec<_>>();
+        let (in_group,out_group) = {
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            let sparse_set = unsafe { storage.downcast_mut::<SparseSet<EntityId,T>>() };
+            let count = sparse_set.len();
+            (sparse_set.sorted_indices(0..group_len,&mut cmp),
+             sparse_set.sorted_indices(group_len..count,&mut cmp))
+        };
+        let mut storages = std::iter::once(&mut storage)
+            .chain(others.iter_mut())
+            .map(|storage|&mut ***storage as &mut dyn ComponentStorage)
+            .collect::<Vec<_>>();
+        let mut swap = MultiStorageSwap::new(&mut storages);
+        permute(0,&in_group,|index_a,index_b|swap.swap(index_a,index_b));
+        // the entities out of group are not aligned with others
+        permute(group_len,&out_group,|index_a,index_b|storage.swap_by_index(index_a,index_b));
+    }
+
+    /// Make the storage of ```T``` maintain a presence bitmap.
+    /// # Details
+    /// The bitmap is indexed by entity ID,the bit is set when the entity has ```T```.
+    /// When all storages in a tuple query or [Without](crate::query::Without) have bitmaps,
+    /// the matched IDs are computed word by word before any component is fetched.
+    /// It's faster for sparse intersections of large storages.  
+    /// It costs about ```max_id / 8``` bytes,see [presence_bitmap_bytes](World::presence_bitmap_bytes).
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// world.enable_presence_bitmap::<u32>()
+    ///     .enable_presence_bitmap::<char>();
+    /// world.create_entity().attach(1_u32).attach('a');
+    /// world.create_entity().attach(2_u32);
+    ///
+    /// assert_eq!(world.query::<(&u32,&char)>().count(),1);
+    /// assert_eq!(world.presence_bitmap_bytes::<u32>(),Some(8));
+    /// ```
+    pub fn enable_presence_bitmap<T : Component>(&mut self) -> &mut Self {
+        {
+            let mut storage = self.raw_storage_write(TypeId::of::<T>())
+                .expect("World:Cannot enable presence bitmap because components has not been registered.");
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            let sparse_set = unsafe {
+                storage.downcast_mut::<SparseSet<EntityId,T>>()
+            };
+            sparse_set.enable_presence();
+        }
         self
     }
 
+    /// Get the memory used by the presence bitmap of ```T``` in bytes.
+    /// # Details
+    /// Return ```None``` if ```T``` is not registered or the bitmap is not enabled.
+    pub fn presence_bitmap_bytes<T : Component>(&self) -> Option<usize> {
+        self.raw_storage_read(TypeId::of::<T>())?
+            .presence()
+            .map(std::mem::size_of_val)
+    }
+
+    fn is_double_buffered(&self,type_id : TypeId) -> bool {
+        self.component_index
+            .binary_search_by_key(&type_id,|(type_id,_,_)|*type_id)
+            .map(|index|self.component_index[index].2 == StorageKind::DoubleBuffered)
+            .unwrap_or(false)
+    }
+
+    /// Get all registered components.
+    /// # Details
+    /// The components are sorted by ```TypeId```, the order is stable
+    /// no matter which order the components are registered in.
+    pub fn registered_components(&self) -> impl Iterator<Item = (TypeId,&'static str,StorageKind)> + '_ {
+        self.component_index
+            .iter()
+            .cloned()
+    }
+
+    /// Get the components owned by ```entity_id``` with their type names.
+    /// # Details
+    /// The components are sorted by ```TypeId```, the same order
+    /// as [registered_components](crate::world::World::registered_components).
+    pub fn components_of(&self,entity_id : EntityId) -> Vec<(TypeId,&'static str)> {
+        self.component_index
+            .iter()
+            .filter(|(type_id,_,_)| {
+                // unwrap never fails because index only contains registered components
+                self.raw_storage_read(*type_id)
+                    .unwrap()
+                    .has(entity_id)
+            })
+            .map(|(type_id,name,_)|(*type_id,*name))
+            .collect()
+    }
+
     /// Check if component is registered.
     pub fn has_registered<T: Component>(&self) -> bool {
         let type_id = TypeId::of::<T>();
@@ -73,6 +493,8 @@ impl World {
 
     /// Create an entity without any component in World,
     ///  return an [Entity](crate::entity::Entity).
+    /// # Panics
+    /// * Panic if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
     pub fn create_entity(&self) -> Entity<'_> {
         let id = {
             let mut entity_manager = self.entity_manager.write();
@@ -81,9 +503,32 @@ impl World {
         self.entity(id).unwrap()
     }
 
+    /// Create an entity with all components of a [Bundle](crate::bundle::Bundle),
+    ///  return an [Entity](crate::entity::Entity).
+    /// # Panics
+    /// * Panic if any component of ```B``` has not been registered.
+    /// * Panic if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
+    pub fn create_entity_with_bundle<B : Bundle>(&self,bundle : B) -> Entity<'_> {
+        self.create_entity().attach_bundle(bundle)
+    }
+
+    /// Create an entity without any component in World,
+    ///  return an [Entity](crate::entity::Entity).
+    /// # Details
+    /// Return ```Err``` if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
+    pub fn try_create_entity(&self) -> Result<Entity<'_>,EntityIdExhausted> {
+        let id = {
+            let mut entity_manager = self.entity_manager.write();
+            entity_manager.try_allocate()?
+        };
+        Ok(self.entity(id).unwrap())
+    }
+
     /// Create count of entities
     /// # Details
     /// This funtionn ensures tbe entity id is continuous.
+    /// # Panics
+    /// * Panic if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
     pub fn create_entities(&self,count: usize) -> Entities<'_> {
         let ids = {
             let mut entity_manager = self.entity_manager.write();
@@ -93,25 +538,399 @@ impl World {
         Entities::new(self,ids,entity_manager)
     }
 
+    /// Create count of entities
+    /// # Details
+    /// This funtionn ensures tbe entity id is continuous.
+    /// Return ```Err``` if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
+    pub fn try_create_entities(&self,count: usize) -> Result<Entities<'_>,EntityIdExhausted> {
+        let ids = {
+            let mut entity_manager = self.entity_manager.write();
+            entity_manager.try_allocate_n(count)?
+        };
+        let entity_manager = self.entity_manager.read();
+        Ok(Entities::new(self,ids,entity_manager))
+    }
+
+    /// Move all entities and components of ```other``` into this world.
+    /// # Details
+    /// Every entity of ```other``` gets a new ID in this world,
+    /// the returned map is from the IDs in ```other``` to the new IDs.
+    /// Components holding ```EntityId``` are not remapped,fix them up with the map.  
+    /// The components not registered in this world are registered with the storage kind in ```other```.
+    /// The groups of this world are rebuilt,
+    /// the groups,resources and finalizers of ```other``` are dropped.
+    /// The [parents](World::set_parent) and [bindings](World::bind_to) in ```other``` are kept with the new IDs.
+    /// # Panics
+    /// * Panic if the entity ID reaches the [maximum](crate::world::World::set_max_entity_id).
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// world.create_entity().attach(1_u32);
+    ///
+    /// let mut level = World::new();
+    /// level.register::<u32>().register::<char>();
+    /// let id = level.create_entity().attach(2_u32).attach('a').into_id();
+    ///
+    /// let ids = world.merge(level);
+    /// assert_eq!(world.get::<u32>(ids[&id]).as_deref(),Some(&2));
+    /// assert_eq!(world.get::<char>(ids[&id]).as_deref(),Some(&'a'));
+    /// assert_eq!(world.query::<&u32>().count(),2);
+    /// ```
+    pub fn merge(&mut self,other : World) -> HashMap<EntityId,EntityId> {
+        let ids = {
+            let other_entities = other.entity_manager_read();
+            let mut entity_manager = self.entity_manager.write();
+            other_entities.entities()
+                .iter()
+                .map(|id|(*id,entity_manager.allocate()))
+                .collect::<HashMap<_,_>>()
+        };
+        self.absorb_components(other,|id|ids.get(&id).copied());
+        ids
+    }
+
+    /// Move all components of ```other``` to ```map(id)``` in this world,then rebuild the groups.
+    /// # Details
+    /// The components not registered in this world are registered.
+    /// ```map``` returns ```None``` for the removed entities of ```other```,
+    /// the [BoundTo](crate::BoundTo)s and parents linking to them are dropped.
+    /// The bindings and parents are remapped by ```map```.
+    fn absorb_components<F : Fn(EntityId) -> Option<EntityId>>(&mut self,mut other : World,map : F) {
+        let tick = self.change_tick();
+        let links = other.hierarchy.read().links();
+        let mut bound = vec![];
+        for (type_id,name,kind) in std::mem::take(&mut other.component_index) {
+            // Unwrap here never fails
+            // index only contains registered components
+            let mut storage = other.components.remove(&type_id).unwrap().into_inner();
+            // all components are taken out before inserting,
+            // so the new IDs never collide with the old IDs in the same storage
+            let components = storage.drain()
+                .into_iter()
+                .filter_map(|(id,mut component)| {
+                    let id = map(id)?;
+                    if type_id == TypeId::of::<BoundTo>() {
+                        // Unwrap here never fails
+                        // the storage of BoundTo only contains BoundTo
+                        let bound_to = component.downcast_mut::<BoundTo>().unwrap();
+                        let target = map(bound_to.target())?;
+                        *bound_to = BoundTo::new(target);
+                        bound.push((id,target));
+                    }
+                    Some((id,component))
+                })
+                .collect::<Vec<_>>();
+            if let Some(target) = self.components.get(&type_id) {
+                let mut target = target.write();
+                for (id,component) in components {
+                    target.insert_any(id,component);
+                    target.mark_added(id,tick);
+                }
+            } else {
+                // the emptied storage is reused,so the type is not needed to create it
+                for (id,component) in components {
+                    storage.insert_any(id,component);
+                    storage.mark_added(id,tick);
+                }
+                self.components.insert(type_id,RwLock::new(storage));
+                let index = self.component_index
+                    .binary_search_by_key(&type_id,|(type_id,_,_)|*type_id)
+                    .unwrap_err();
+                self.component_index.insert(index,(type_id,name,kind));
+            }
+        }
+        for group in &self.groups {
+            self.make_group_data(&mut group.write());
+        }
+        {
+            let mut bindings = self.bindings.write();
+            for (id,target) in bound {
+                bindings.entry(target)
+                    .or_default()
+                    .push(id);
+            }
+        }
+        let mut hierarchy = self.hierarchy.write();
+        for (child,parent) in links {
+            // the links of removed entities are skipped
+            if let (Some(child),Some(parent)) = (map(child),map(parent)) {
+                hierarchy.set_parent(child,parent);
+            }
+        }
+    }
+
+    /// Move the entities and components of a loaded world into this world,the IDs are kept
+    #[cfg(feature = "serde")]
+    pub(in crate) fn absorb_loaded(&mut self,mut loaded : World) {
+        self.entity_manager = std::mem::replace(&mut loaded.entity_manager,RwLock::new(EntityManager::new()));
+        self.absorb_components(loaded,Some);
+    }
+
+    /// Remove all entities but keep the registrations,groups,resources and finalizers.
+    /// # Details
+    /// All components are dropped and all groups become empty.
+    /// The IDs are released,so the next created entities reuse their indices
+    /// with the next generation like [remove_entity](crate::world::World::remove_entity).  
+    /// The [despawn finalizers](crate::finalizer) don't run.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// let id = world.create_entity().attach(1_u32).into_id();
+    ///
+    /// world.clear_entities();
+    /// assert_eq!(world.query::<&u32>().count(),0);
+    /// assert!(!world.is_alive(id));
+    ///
+    /// let new_id = world.create_entity().attach(2_u32).into_id();
+    /// assert_eq!(new_id.index(),id.index());
+    /// assert_ne!(new_id,id);
+    /// ```
+    pub fn clear_entities(&mut self) {
+        for storage in self.components.values() {
+            storage.write().clear();
+        }
+        for group in &self.groups {
+            self.make_group_data(&mut group.write());
+        }
+        {
+            let mut entity_manager = self.entity_manager.write();
+            let entities = entity_manager.entities().to_vec();
+            for entity_id in entities {
+                entity_manager.remove(entity_id);
+            }
+        }
+        self.bindings.write().clear();
+        self.hierarchy.write().clear();
+    }
+
+    /// Remove all components ```T``` but keep the entities.
+    /// # Details
+    /// The groups containing ```T``` become empty.
+    /// The [despawn finalizers](crate::finalizer) don't run.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn clear_storage<T : Component>(&mut self) {
+        assert!(self.has_registered::<T>(),
+                "World:Cannot clear storage because components has not been registered.");
+        let type_id = TypeId::of::<T>();
+        // Unwrap here never fails
+        // assert before ensures this
+        self.raw_storage_write(type_id).unwrap().clear();
+        for group in &self.groups {
+            let mut group = group.write();
+            if group.types().contains(&type_id) {
+                self.make_group_data(&mut group);
+            }
+        }
+    }
+
+    /// Copy the entities,groups and components in ```registry``` into a [WorldSnapshot](crate::snapshot::WorldSnapshot).
+    /// # Details
+    /// See [snapshot](crate::snapshot) for what is copied.
+    /// # Panics
+    /// * Panic if any storage is borrowed mutably.
+    pub fn snapshot(&self,registry : &CloneRegistry) -> WorldSnapshot {
+        let entity_manager = self.entity_manager_read().clone();
+        let storages = self.component_index.iter()
+            .filter_map(|&(type_id,name,kind)| {
+                let clone = registry.get(type_id)?;
+                // Unwrap here never fails
+                // index only contains registered components
+                let storage = self.raw_storage_read(type_id).unwrap();
+                Some(StorageSnapshot {
+                    type_id,
+                    name,
+                    kind,
+                    storage : clone(&**storage),
+                    clone
+                })
+            })
+            .collect();
+        let groups = self.groups.iter()
+            .map(|group|group.read().clone())
+            .collect();
+        WorldSnapshot {
+            entity_manager,
+            storages,
+            groups,
+            bindings : self.bindings.read().clone(),
+            hierarchy : self.hierarchy.read().clone()
+        }
+    }
+
+    /// Restore the world to ```snapshot```.
+    /// # Details
+    /// The components not in snapshot are cleared,
+    /// the components which are not registered but in snapshot are registered.
+    /// The groups made after snapshot are rebuilt,
+    /// and the groups removed after snapshot are not made again.
+    /// Resources are not changed and the [despawn finalizers](crate::finalizer) don't run.  
+    /// The structural versions of all storages are increased,
+    /// so every [Epoch](crate::epoch::Epoch) taken before restoring is changed.
+    pub fn restore(&mut self,snapshot : &WorldSnapshot) {
+        self.entity_manager = RwLock::new(snapshot.entity_manager.clone());
+        let versions = self.components.iter()
+            .map(|(type_id,storage)|(*type_id,storage.read().version()))
+            .collect::<HashMap<_,_>>();
+        for (type_id,storage) in &self.components {
+            let mut storage = storage.write();
+            match snapshot.storage(*type_id) {
+                Some(copied) => *storage = copied.clone_storage(),
+                None => storage.clear()
+            }
+        }
+        for copied in &snapshot.storages {
+            if let Entry::Vacant(entry) = self.components.entry(copied.type_id) {
+                entry.insert(RwLock::new(copied.clone_storage()));
+                let index = self.component_index
+                    .binary_search_by_key(&copied.type_id,|(type_id,_,_)|*type_id)
+                    .unwrap_err();
+                self.component_index.insert(index,(copied.type_id,copied.name,copied.kind));
+            }
+        }
+        for group in &self.groups {
+            let mut group = group.write();
+            let copied = snapshot.groups.iter()
+                .find(|copied|**copied == *group);
+            let all_copied = group.types()
+                .iter()
+                .all(|type_id|snapshot.storage(*type_id).is_some());
+            match copied {
+                Some(copied) if all_copied => *group = copied.clone(),
+                _ => self.make_group_data(&mut group)
+            }
+        }
+        *self.bindings.write() = snapshot.bindings.clone();
+        *self.hierarchy.write() = snapshot.hierarchy.clone();
+        // the versions of storages never go back,
+        // otherwise an epoch taken before restoring may be reported unchanged
+        for (type_id,storage) in &self.components {
+            let mut storage = storage.write();
+            let version = versions.get(type_id)
+                .copied()
+                .unwrap_or(0)
+                .max(storage.version());
+            storage.set_version(version + 1);
+        }
+        self.ids_snapshots.write().clear();
+    }
+
+    /// Set the maximum entity ID.
+    /// # Details
+    /// Removed IDs are always reused before allocating a new ID,
+    /// so at most ```max_entity_id``` entities can be alive at the same time.
+    /// Creating more entities fails with [EntityIdExhausted](crate::entity::EntityIdExhausted).
+    /// # Panics
+    /// * Panic if an ID larger than ```max_entity_id``` has been allocated.
+    pub fn set_max_entity_id(&mut self,max_entity_id : usize) {
+        self.entity_manager
+            .write()
+            .set_max_id(max_entity_id);
+    }
+
+    /// Get the [statistics](crate::entity::EntityStats) of entities
+    pub fn entity_stats(&self) -> EntityStats {
+        self.entity_manager
+            .read()
+            .stats()
+    }
+
+    /// Get the count of alive entities
+    pub fn entity_count(&self) -> usize {
+        self.entity_manager
+            .read()
+            .entities()
+            .len()
+    }
+
+    /// Iterate the IDs of all alive entities.
+    /// # Details
+    /// The entities are locked for reading until the iterator is dropped,
+    /// don't create or remove entities during iterating.
+    pub fn iter_entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        let entity_manager = self.entity_manager.read();
+        (0..).map_while(move |index|entity_manager.entities().get(index).copied())
+    }
+
+    /// Iterate the IDs of entities which have component ```T```.
+    /// # Details
+    /// The storage of ```T``` is locked for reading until the iterator is dropped.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn entities_with<T : Component>(&self) -> impl Iterator<Item = EntityId> + '_ {
+        let storage = self.raw_storage_read(TypeId::of::<T>())
+            .expect("World:Cannot iterate entities because component has not been registered");
+        (0..).map_while(move |index|storage.id(index))
+    }
+
+    /// Register a [despawn finalizer](crate::finalizer) for component ```T```.
+    /// # Details
+    /// ```finalizer``` receives the component by value when its entity is removed.
+    /// Finalizers run in ascending ```priority```,the same priority runs in registration order.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn register_despawn_finalizer<T,F>(&mut self,priority : i32,finalizer : F) -> &mut Self
+        where T : Component,
+              F : FnMut(EntityId,T,&mut FinalizerCtx<'_>) + Send + Sync + 'static {
+        assert!(self.has_registered::<T>(),
+                "World:Cannot register a finalizer because components has not been registered.");
+        let index = self.finalizers
+            .partition_point(|finalizer|finalizer.priority() <= priority);
+        self.finalizers.insert(index,Finalizer::new(priority,finalizer));
+        self
+    }
+
     /// Remove entity and its components.
-    pub fn remove_entity(&self, entity_id: EntityId) {
-        assert!(self.exist(entity_id),
-                "World:Cannot remove a non-exists entity");
+    /// # Details
+    /// The [despawn finalizers](crate::finalizer) run before the components are removed.  
+    /// The groups are maintained before storages,
+    /// then the ID is released and will be reused by the next created entity.  
+    /// The entities [bound](World::bind_to) to it are removed after that.
+    /// In a [Transaction](crate::transaction::Transaction),
+    /// the bindings and [parents](World::set_parent) are kept for rollback
+    /// and cleaned up when the transaction is [committed](crate::transaction::Transaction::commit).  
+    /// Return ```false``` if the entity is not alive.
+    pub fn remove_entity(&self, entity_id: EntityId) -> bool {
+        if !self.exist(entity_id) {
+            return false;
+        }
+        self.unbind(entity_id);
+        let mut ctx = FinalizerCtx::new(self,entity_id);
+        for finalizer in &self.finalizers {
+            // unwrap never fails because finalizer is registered after component
+            let has = self.raw_storage_read(finalizer.type_id())
+                .unwrap()
+                .has(entity_id);
+            if has {
+                finalizer.run(self,entity_id,&mut ctx);
+            }
+        }
+        let deferred = ctx.into_deferred();
         // find all groups need remove 
         let mut groups = vec![];
         for group in &self.groups {
             let need_remove = {
                 let group = group.read();
-                let (type_a,type_b) = group.types();
-                let comp_a = self.raw_storage_read(type_a).unwrap();
-                let comp_b = self.raw_storage_read(type_b).unwrap();
-                group.in_group(entity_id, &comp_a, &comp_b)
+                let storages = group.types()
+                    .into_iter()
+                    .map(|type_id|self.raw_storage_read(type_id).unwrap())
+                    .collect::<Vec<_>>();
+                let storages = storages.iter()
+                    .map(|storage|&**storage)
+                    .collect::<Vec<_>>();
+                group.in_group(entity_id,&storages)
             };
             if need_remove {
                 groups.push(group.write());
             };
         }
-        // remove entity in group and its storages
+        // remove entity in all groups first,
+        // storages are removed after that because
+        // other groups sharing these storages still need them to check in_group
         for mut group in groups {
             match &mut *group{
                 Group::FullOwning(data) => {
@@ -119,15 +938,19 @@ impl World {
                     let mut comp_a = self.raw_storage_write(type_a).unwrap();
                     let mut comp_b = self.raw_storage_write(type_b).unwrap();
                     data.remove(entity_id,&mut comp_a,&mut comp_b);
-                    comp_a.remove(entity_id);
-                    comp_b.remove(entity_id);
+                },
+                Group::FullOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                    let mut comp_c = self.raw_storage_write(type_c).unwrap();
+                    data.remove(entity_id,&mut **comp_a,&mut **comp_b,&mut **comp_c);
                 },
                 Group::PartialOwning(data) => {
                     let (type_a,type_b) = data.types();
                     let mut comp_a = self.raw_storage_write(type_a).unwrap();
                     let comp_b = self.raw_storage_read(type_b).unwrap();
                     data.remove(entity_id,&mut comp_a,&comp_b);
-                    comp_a.remove(entity_id);
                 }
                 Group::NonOwning(data) => {
                     let (type_a,type_b) = data.types();
@@ -135,9 +958,16 @@ impl World {
                     let comp_b = self.raw_storage_read(type_b).unwrap();
                     data.remove(entity_id,&comp_a,&comp_b);
                 },
+                Group::NonOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let comp_a = self.raw_storage_read(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    let comp_c = self.raw_storage_read(type_c).unwrap();
+                    data.remove(entity_id,&**comp_a,&**comp_b,&**comp_c);
+                },
             }
         }
-        // remove entity in other storages
+        // remove entity in all storages
         let mut storages = vec![];
         for storage in self.components.values() {
             let need_remove = {
@@ -156,36 +986,415 @@ impl World {
             let mut entity_manager = self.entity_manager.write();
             entity_manager.remove(entity_id);
         }
+        for command in deferred {
+            command(self);
+        }
+        if !self.in_transaction.load(Ordering::Acquire) {
+            self.remove_bound(entity_id);
+            // the links are kept for rollback in transaction
+            self.hierarchy.write().remove(entity_id);
+        }
+        true
     }
 
-    /// Get lock guard of raw component storage,
-    /// return None if component is not registered.
-    pub(in crate) fn raw_storage_read(&self,id : TypeId) 
-        -> Option<RwLockReadGuard<'_,Box<dyn ComponentStorage>>> {
-        self.components
-            .get(&id)
-            .map(|rwlock|rwlock.read())
+    /// Set ```parent``` as the parent of ```child```.
+    /// # Details
+    /// The old parent of ```child``` is replaced.
+    /// The components are not changed,
+    /// the children of a removed entity are detached,see [despawn_recursive](World::despawn_recursive).
+    /// # Errors
+    /// * [EntityNotFound](crate::HierarchyError::EntityNotFound) if ```child``` or ```parent``` is not alive.
+    /// * [Cycle](crate::HierarchyError::Cycle) if ```parent``` is ```child``` or a descendant of ```child```.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let world = World::new();
+    /// let body = world.create_entity().into_id();
+    /// let arm = world.create_entity().into_id();
+    /// let hand = world.create_entity().into_id();
+    /// world.set_parent(arm,body).unwrap();
+    /// world.set_parent(hand,arm).unwrap();
+    /// assert!(world.set_parent(body,hand).is_err());
+    ///
+    /// world.despawn_recursive(arm);
+    /// assert!(!world.is_alive(hand));
+    /// assert_eq!(world.children(body).count(),0);
+    /// ```
+    pub fn set_parent(&self,child : EntityId,parent : EntityId) -> Result<(),HierarchyError> {
+        for entity_id in [child,parent] {
+            if !self.is_alive(entity_id) {
+                return Err(HierarchyError::EntityNotFound(entity_id));
+            }
+        }
+        let mut hierarchy = self.hierarchy.write();
+        if hierarchy.is_ancestor(child,parent) {
+            return Err(HierarchyError::Cycle { child, parent });
+        }
+        hierarchy.set_parent(child,parent);
+        Ok(())
     }
 
-    /// Get lock guard of raw component storage,
-    /// return None if component is not registered.
-    pub(in crate) fn raw_storage_write(&self,id : TypeId) 
-        -> Option<RwLockWriteGuard<'_,Box<dyn ComponentStorage>>> {
-        self.components
-            .get(&id)
-            .map(|rwlock|rwlock.write())
+    /// Detach ```child``` from its parent,return the old parent.
+    pub fn remove_parent(&self,child : EntityId) -> Option<EntityId> {
+        self.hierarchy.write()
+            .remove_parent(child)
+            .filter(|parent|self.is_alive(*parent))
     }
 
-    /// Attach a component to an entity.  
-    /// # Panics
-    /// * Panic if ```T``` is not registered.
-    /// * Panic if ```entity_id``` not exist.
-    pub fn attach_component<T: Component>(&self, entity_id: EntityId,component: T) {
-        self.entity(entity_id)
+    /// Get the parent of ```child```.
+    /// # Details
+    /// Return ```None``` if ```child``` has no parent.
+    pub fn parent(&self,child : EntityId) -> Option<EntityId> {
+        self.hierarchy.read()
+            .parent(child)
+            .filter(|parent|self.is_alive(*parent))
+    }
+
+    /// Get the children of ```parent``` in order of [set_parent](World::set_parent)
+    pub fn children(&self,parent : EntityId) -> impl Iterator<Item = EntityId> {
+        self.hierarchy.read()
+            .children(parent)
+            .iter()
+            .copied()
+            .filter(|child|self.is_alive(*child))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Remove ```entity_id``` with all of its descendants.
+    /// # Details
+    /// Return ```false``` if the entity is not alive.
+    pub fn despawn_recursive(&self,entity_id : EntityId) -> bool {
+        if !self.is_alive(entity_id) {
+            return false;
+        }
+        let descendants = self.hierarchy.read().descendants(entity_id);
+        self.remove_entity(entity_id);
+        for id in descendants {
+            self.remove_entity(id);
+        }
+        true
+    }
+
+    /// Bind ```entity_id``` to ```target```,
+    /// it will be removed when ```target``` is removed.
+    /// # Details
+    /// A [BoundTo](crate::BoundTo) is attached to ```entity_id```,the old binding is replaced.
+    /// The bindings cascade,
+    /// so an entity bound to a bound entity is removed with the whole chain.
+    /// # Errors
+    /// * [ComponentNotRegistered](crate::XecsError::ComponentNotRegistered) if [BoundTo](crate::BoundTo) is not registered.
+    /// * [EntityNotFound](crate::XecsError::EntityNotFound) if ```entity_id``` or ```target``` is not alive.
+    pub fn bind_to(&self,entity_id : EntityId,target : EntityId) -> Result<(),XecsError> {
+        self.check_structural::<BoundTo>(entity_id)?;
+        if !self.exist(target) {
+            return Err(XecsError::EntityNotFound(target));
+        }
+        self.unbind(entity_id);
+        self.attach_component(entity_id,BoundTo::new(target));
+        self.bindings.write()
+            .entry(target)
+            .or_default()
+            .push(entity_id);
+        Ok(())
+    }
+
+    /// Remove ```entity_id``` from the bindings of its target
+    fn unbind(&self,entity_id : EntityId) {
+        let target = match self.entity_component_read::<BoundTo>(entity_id) {
+            Some(bound_to) => bound_to.target(),
+            None => return
+        };
+        self.unbind_from(entity_id,target);
+    }
+
+    fn unbind_from(&self,entity_id : EntityId,target : EntityId) {
+        let mut bindings = self.bindings.write();
+        if let Some(bound) = bindings.get_mut(&target) {
+            bound.retain(|id|*id != entity_id);
+            if bound.is_empty() {
+                bindings.remove(&target);
+            }
+        }
+    }
+
+    /// Remove the entities bound to the removed ```target```
+    fn remove_bound(&self,target : EntityId) {
+        let bound = self.bindings.write()
+            .remove(&target)
+            .unwrap_or_default();
+        for id in bound {
+            // the entity may be bound to another target
+            let is_bound = self.entity_component_read::<BoundTo>(id)
+                .map(|bound_to|bound_to.target() == target)
+                .unwrap_or(false);
+            if is_bound {
+                self.remove_entity(id);
+            }
+        }
+    }
+
+    /// Finish the removal of ```entity_id``` which was removed in a committed
+    /// [Transaction](crate::transaction::Transaction).
+    /// # Details
+    /// ```components``` are the components kept by transaction.
+    /// After ```entity_id``` is removed from the bindings,
+    /// the [despawn finalizers](crate::finalizer) run on them in ascending priority
+    /// and the rest of them are dropped.
+    /// Then the bound entities are removed and the links of hierarchy are cleaned up
+    /// like [remove_entity](World::remove_entity).
+    pub(in crate) fn finish_removal(&self,entity_id : EntityId,mut components : Vec<(TypeId,Box<dyn Any + Send + Sync>)>) {
+        let bound_to = components.iter()
+            .find(|(type_id,_)|*type_id == TypeId::of::<BoundTo>())
+            .and_then(|(_,component)|component.downcast_ref::<BoundTo>())
+            .map(|bound_to|bound_to.target());
+        if let Some(target) = bound_to {
+            self.unbind_from(entity_id,target);
+        }
+        let mut ctx = FinalizerCtx::new(self,entity_id);
+        for finalizer in &self.finalizers {
+            let position = components.iter()
+                .position(|(type_id,_)|*type_id == finalizer.type_id());
+            if let Some(position) = position {
+                let (_,component) = components.swap_remove(position);
+                finalizer.run_with(self,entity_id,component,&mut ctx);
+            }
+        }
+        let deferred = ctx.into_deferred();
+        drop(components);
+        for command in deferred {
+            command(self);
+        }
+        self.remove_bound(entity_id);
+        self.hierarchy.write().remove(entity_id);
+    }
+
+    /// Get the parent of ```child``` even if it's not alive
+    pub(in crate) fn raw_parent(&self,child : EntityId) -> Option<EntityId> {
+        self.hierarchy.read().parent(child)
+    }
+
+    /// Set the parent of ```child``` back to ```parent``` without any check
+    pub(in crate) fn restore_parent(&self,child : EntityId,parent : Option<EntityId>) {
+        let mut hierarchy = self.hierarchy.write();
+        match parent {
+            Some(parent) => hierarchy.set_parent(child,parent),
+            None => {
+                hierarchy.remove_parent(child);
+            }
+        }
+    }
+
+    /// Count down all [Lifetime](crate::Lifetime)s and remove the expired entities.
+    /// # Details
+    /// ```delta``` is the elapsed seconds,
+    /// a lifetime counted by frames is counted down by one.
+    /// The entities bound to the expired entities are removed too.  
+    /// Return the IDs of expired entities.
+    pub fn despawn_expired(&self,delta : f32) -> Vec<EntityId> {
+        let mut expired = vec![];
+        if self.has_registered::<Lifetime>() {
+            let mut iter = self.query::<&mut Lifetime>();
+            while let Some((id,lifetime)) = iter.next_with_id() {
+                if lifetime.advance(delta) {
+                    expired.push(id);
+                }
+            }
+        }
+        for id in &expired {
+            self.remove_entity(*id);
+        }
+        expired
+    }
+
+    /// Despawn an entity,remove it and all of its components.
+    /// # Details
+    /// It's an alias of [remove_entity](World::remove_entity).  
+    /// Return ```false``` if the entity is not alive.
+    pub fn despawn(&self,entity_id : EntityId) -> bool {
+        self.remove_entity(entity_id)
+    }
+
+    /// Begin a [Transaction](crate::transaction::Transaction) to make
+    /// structural changes atomically.
+    /// # Panics
+    /// * Panic if another transaction is alive.
+    pub fn begin_transaction(&self) -> Transaction<'_> {
+        match self.try_begin_transaction() {
+            Ok(transaction) => transaction,
+            Err(_) => panic!("World:Cannot begin a transaction because another transaction is alive")
+        }
+    }
+
+    /// Begin a [Transaction](crate::transaction::Transaction) to make
+    /// structural changes atomically.
+    /// # Errors
+    /// * [NestedTransaction](crate::XecsError::NestedTransaction) if another transaction is alive.
+    /// # Example
+    /// ```
+    /// use xecs::{World, XecsError};
+    /// let world = World::new();
+    /// let tx = world.try_begin_transaction().unwrap();
+    /// assert_eq!(world.try_begin_transaction().err(),Some(XecsError::NestedTransaction));
+    /// tx.commit();
+    /// assert!(world.try_begin_transaction().is_ok());
+    /// ```
+    pub fn try_begin_transaction(&self) -> Result<Transaction<'_>,XecsError> {
+        let nested = self.in_transaction.swap(true,Ordering::AcqRel);
+        if nested {
+            return Err(XecsError::NestedTransaction);
+        }
+        Ok(Transaction::new(self))
+    }
+
+    pub(in crate) fn end_transaction(&self) {
+        self.in_transaction.store(false,Ordering::Release);
+    }
+
+    /// Re-allocate a removed ```entity_id```.
+    /// # Panics
+    /// * Panic if the index of ```entity_id``` was reused.
+    pub(in crate) fn restore_entity(&self,entity_id : EntityId) {
+        let mut entity_manager = self.entity_manager.write();
+        let id = entity_manager.restore(entity_id);
+        assert_eq!(id,entity_id,
+                   "World:Cannot restore entity because its ID was reused");
+    }
+
+    /// Detach all components from ```entity_id``` and maintain the groups.
+    pub(in crate) fn detach_all(&self,entity_id : EntityId) -> Vec<(TypeId,Box<dyn Any + Send + Sync>)> {
+        let type_ids = self.components
+            .iter()
+            .filter(|(_,storage)| storage.read().has(entity_id))
+            .map(|(type_id,_)| *type_id)
+            .collect::<Vec<_>>();
+        type_ids.into_iter()
+            .filter_map(|type_id| {
+                self.detach_any(entity_id,type_id)
+                    .map(|component|(type_id,component))
+            })
+            .collect()
+    }
+
+    /// Get lock guard of raw component storage,
+    /// return None if component is not registered.
+    pub(in crate) fn raw_storage_read(&self,id : TypeId) 
+        -> Option<StorageReadGuard<'_>> {
+        self.components
+            .get(&id)
+            .map(|rwlock|rwlock.read())
+    }
+
+    /// Get lock guard of raw component storage,
+    /// return None if component is not registered.
+    pub(in crate) fn raw_storage_write(&self,id : TypeId) 
+        -> Option<StorageWriteGuard<'_>> {
+        self.components
+            .get(&id)
+            .map(|rwlock|rwlock.write())
+    }
+
+    /// Attach a component to an entity.  
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// * Panic if ```entity_id``` not exist.
+    pub fn attach_component<T: Component>(&self, entity_id: EntityId,component: T) {
+        self.entity(entity_id)
             .expect("World: Cannot attach component to a non-existence entity")
             .attach(component);
     }
 
+    /// Attach a type-erased component to an entity without heap allocation.
+    /// # Details
+    /// The storage is chosen by the [type ID](crate::component::ErasedValue::type_id) of ```value```.
+    /// # Panics
+    /// * Panic if the type of ```value``` is not registered.
+    /// * Panic if ```entity_id``` not exist.
+    pub fn attach_erased(&self,entity_id : EntityId,value : ErasedValue<'_>) {
+        assert!(self.exist(entity_id),
+                "World:Cannot attach component to a non-existence entity");
+        let type_id = value.type_id();
+        {
+            let mut storage = self.raw_storage_write(type_id)
+                .expect("World:Cannot attach component because components has not been registered.");
+            let added = !storage.has(entity_id);
+            // insert_erased never fails because the storage is chosen by type ID
+            storage.insert_erased(entity_id,value);
+            if added {
+                storage.mark_added(entity_id,self.change_tick());
+            }
+        }
+        self.add_to_groups(entity_id,type_id);
+    }
+
+    /// Attach ```components``` to ```entities``` in one pass.
+    /// # Details
+    /// The components are appended to storage by one batch,
+    /// and the groups containing ```T``` are made again once instead of adding every entity.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    /// * Panic if ```components.len()``` is not equal to ```entities.len()```.
+    /// * Panic if any entity does not exist,already has ```T``` or appears twice.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// let ids = (0..3).map(|_|world.create_entity().into_id()).collect::<Vec<_>>();
+    /// world.batch_attach(&ids,vec![1_u32,2,3]);
+    /// assert_eq!(world.get::<u32>(ids[1]).as_deref(),Some(&2));
+    /// ```
+    pub fn batch_attach<T : Component>(&self,entities : &[EntityId],components : Vec<T>) {
+        assert_eq!(entities.len(),components.len(),
+                   "World:Cannot attach components because the count of entities and components are mismatched");
+        let type_id = TypeId::of::<T>();
+        {
+            let mut storage = self.raw_storage_write(type_id)
+                .expect("World:Cannot attach component because components has not been registered.");
+            {
+                let entity_manager = self.entity_manager_read();
+                let mut seen = std::collections::HashSet::with_capacity(entities.len());
+                for &id in entities {
+                    assert!(entity_manager.has(id),
+                            "World:Cannot attach component to a non-existence entity");
+                    assert!(!storage.has(id) && seen.insert(id),
+                            "World:Cannot batch attach component to an entity twice");
+                }
+            }
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            let sparse_set = unsafe {
+                storage.downcast_mut::<SparseSet<EntityId,T>>()
+            };
+            sparse_set.add_batch(entities,components);
+            let tick = self.change_tick();
+            for &id in entities {
+                sparse_set.mark_added(id,tick);
+            }
+        }
+        for mut group in self.groups(type_id) {
+            self.make_group_data(&mut group);
+        }
+    }
+
+    /// Create ```count``` entities with the component ```make(i)``` for the ```i```th entity.
+    /// # Details
+    /// The components are attached by [batch_attach](World::batch_attach).
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn batch_create_with<T : Component,F : Fn(usize) -> T>(&self,count : usize,make : F) -> Vec<EntityId> {
+        let ids = self.create_entities(count).into_ids();
+        // the indices of created entities have never been recycled
+        // Unwrap here never fails
+        // the indices are allocated by EntityManager
+        let ids = (ids.start.index()..ids.end.index())
+            .map(|index|EntityId::new(index).unwrap())
+            .collect::<Vec<_>>();
+        let components = (0..count).map(make).collect::<Vec<_>>();
+        self.batch_attach(&ids,components);
+        ids
+    }
+
     /// Detach a component from an entity.
     /// # Details
     /// Return ```None``` if entity doesn't have this component,  
@@ -199,12 +1408,191 @@ impl World {
             .detach::<T>()
     }
 
+    /// Remove a component from an entity and return it.
+    /// # Details
+    /// Unlike [detach_component](World::detach_component),it doesn't panic on dead entity.
+    /// Every group which owns or references ```T``` is maintained.  
+    /// Return ```None``` if entity is not alive or doesn't have this component.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn remove_component<T : Component>(&self,entity_id : EntityId) -> Option<T> {
+        if !self.exist(entity_id) {
+            return None;
+        }
+        self.detach_any(entity_id,TypeId::of::<T>())
+            // downcast never fails because it comes from storage of T
+            .map(|component| *component.downcast::<T>().unwrap())
+    }
+
+    /// Add a component to an alive entity,the old one is replaced.
+    /// # Details
+    /// Every group which is interested in ```T``` is maintained.
+    /// The old component is overwritten in place like [attach](crate::entity::Entity::attach),
+    /// so the entity keeps its position in storage and groups,
+    /// and the component is only marked as changed.
+    /// # Errors
+    /// * [ComponentNotRegistered](crate::XecsError::ComponentNotRegistered) if ```T``` is not registered.
+    /// * [EntityNotFound](crate::XecsError::EntityNotFound) if entity is not alive.
+    pub fn add_component<T : Component>(&self,entity_id : EntityId,component : T) -> Result<(),XecsError> {
+        self.check_structural::<T>(entity_id)?;
+        let type_id = TypeId::of::<T>();
+        let added = {
+            // Unwrap never fails because check_structural ensures this
+            let mut storage = self.raw_storage_write(type_id).unwrap();
+            // Safety:
+            // storage is SparseSet<EntityId,T>
+            let sparse_set = unsafe {
+                storage.downcast_mut::<SparseSet<EntityId,T>>()
+            };
+            let tick = self.change_tick();
+            match sparse_set.entry(entity_id) {
+                sparse_set::Entry::Occupied(mut entry) => {
+                    entry.insert(component);
+                    // Unwrap here never fails
+                    // entry is occupied
+                    let index = sparse_set.get_index(entity_id).unwrap();
+                    sparse_set.mark_changed(index,tick);
+                    false
+                },
+                sparse_set::Entry::Vacant(entry) => {
+                    entry.insert(component);
+                    sparse_set.mark_added(entity_id,tick);
+                    true
+                }
+            }
+        };
+        if added {
+            self.add_to_groups(entity_id,type_id);
+        }
+        Ok(())
+    }
+
+    /// Remove a component from an alive entity and return it.
+    /// # Details
+    /// It's the fallible version of [remove_component](World::remove_component).
+    /// # Errors
+    /// * [ComponentNotRegistered](crate::XecsError::ComponentNotRegistered) if ```T``` is not registered.
+    /// * [EntityNotFound](crate::XecsError::EntityNotFound) if entity is not alive.
+    /// * [ComponentNotFound](crate::XecsError::ComponentNotFound) if entity doesn't have ```T```.
+    pub fn try_remove_component<T : Component>(&self,entity_id : EntityId) -> Result<T,XecsError> {
+        self.check_structural::<T>(entity_id)?;
+        self.remove_component::<T>(entity_id)
+            .ok_or(XecsError::ComponentNotFound {
+                entity_id,
+                component : std::any::type_name::<T>()
+            })
+    }
+
+    fn check_structural<T : Component>(&self,entity_id : EntityId) -> Result<(),XecsError> {
+        if !self.has_registered::<T>() {
+            return Err(XecsError::ComponentNotRegistered(std::any::type_name::<T>()));
+        }
+        if !self.exist(entity_id) {
+            return Err(XecsError::EntityNotFound(entity_id));
+        }
+        Ok(())
+    }
+
+    /// Check if entity has all components of bundle ```B```.
+    /// # Details
+    /// Return ```false``` if entity doesn't exist.
+    /// # Panics
+    /// * Panic if any component of ```B``` is not registered.
+    pub fn has_bundle<B : Bundle>(&self,entity_id : EntityId) -> bool {
+        self.exist(entity_id) && self.missing_of::<B>(entity_id).is_empty()
+    }
+
+    /// Get the type IDs and names of components in bundle ```B``` which entity doesn't have
+    fn missing_of<B : Bundle>(&self,entity_id : EntityId) -> Vec<(TypeId,&'static str)> {
+        let mut types = vec![];
+        B::component_types(&mut types);
+        types.into_iter()
+            .filter(|(type_id,_)| {
+                !self.raw_storage_read(*type_id)
+                    .expect("World:Cannot check bundle because components has not been registered.")
+                    .has(entity_id)
+            })
+            .collect()
+    }
+
+    /// Remove all components of bundle ```B``` from an entity and return them.
+    /// # Details
+    /// It's all-or-nothing,nothing is removed if any component is missing.
+    /// Every affected group is maintained only once.
+    /// # Panics
+    /// * Panic if any component of ```B``` is not registered.
+    pub fn remove_bundle<B : Bundle>(&self,entity_id : EntityId) -> Result<B,RemoveBundleError> {
+        if !self.exist(entity_id) {
+            return Err(RemoveBundleError { missing : vec![] });
+        }
+        let missing = self.missing_of::<B>(entity_id);
+        if !missing.is_empty() {
+            return Err(RemoveBundleError {
+                missing : missing.into_iter().map(|(_,name)|name).collect()
+            });
+        }
+        let mut types = vec![];
+        B::component_types(&mut types);
+        let type_ids = types.iter()
+            .map(|(type_id,_)|*type_id)
+            .collect::<Vec<_>>();
+        self.remove_from_groups_of(entity_id,&type_ids);
+        let components = type_ids.into_iter()
+            .map(|type_id| {
+                // unwrap never fails because the components are checked above
+                self.raw_storage_write(type_id)
+                    .unwrap()
+                    .remove_any(entity_id)
+                    .unwrap()
+            })
+            .collect();
+        // from_components never fails because the components have the types of bundle
+        Ok(B::from_components(components).unwrap())
+    }
+
     /// Check if ```entity_id``` exists in World.
     pub fn exist(&self, entity_id: EntityId) -> bool {
         let entity_manager = self.entity_manager.read();
         entity_manager.has(entity_id)
     }
 
+    /// Retain the IDs which have component ```T``` in ```ids```.
+    /// # Details
+    /// This function checks all IDs in one batch,
+    /// it's faster than calling [exist](crate::component::StorageRead::exist) one by one.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn filter_ids_with<T : Component>(&self,ids : &mut Vec<EntityId>) {
+        let storage = self.raw_storage_read(TypeId::of::<T>())
+            .expect("World:Cannot filter IDs because component has not been registered");
+        // Safety:
+        // storage is SparseSet<EntityId,T>
+        let sparse_set = unsafe {
+            storage.downcast_ref::<SparseSet<EntityId,T>>()
+        };
+        let mut mask = vec![false;ids.len()];
+        sparse_set.contains_many(ids,&mut mask);
+        let mut mask = mask.into_iter();
+        // unwrap never fails because mask has the same length as ids
+        ids.retain(|_| mask.next().unwrap());
+    }
+
+    pub(in crate) fn entity_manager_read(&self) -> EntityManagerReadGuard<'_> {
+        self.entity_manager.read()
+    }
+
+    /// Get the components saved by [register_serializable](World::register_serializable)
+    #[cfg(feature = "serde")]
+    pub(in crate) fn serializable(&self) -> &SerializeRegistry {
+        &self.serializable
+    }
+
+    /// Replace the entity manager,it's used to load a world
+    #[cfg(feature = "serde")]
+    pub(in crate) fn set_entity_manager(&mut self,entity_manager : EntityManager) {
+        self.entity_manager = RwLock::new(entity_manager);
+    }
+
     /// Get the component storage's read guard
     pub fn components_read<T : Component>(&self) -> Option<StorageRead<'_,T>> {
         let type_id = TypeId::of::<T>();
@@ -212,6 +1600,35 @@ impl World {
         Some(StorageRead::from_lock(lock))
     }
 
+    /// Get a [snapshot](crate::component::IdsSnapshot) of the entity IDs which have component ```T```.
+    /// # Details
+    /// The snapshot is cached and reused until the storage is structurally changed,
+    /// the cache doesn't keep the snapshot alive.
+    /// # Panics
+    /// * Panic if ```T``` is not registered.
+    pub fn ids_snapshot<T : Component>(&self) -> IdsSnapshot {
+        let type_id = TypeId::of::<T>();
+        let storage = self.components_read::<T>()
+            .expect("World:Cannot get IDs snapshot because components has not been registered.");
+        let version = storage.version();
+        let cached = self.ids_snapshots
+            .read()
+            .get(&type_id)
+            .filter(|(cached_version,_)|*cached_version == version)
+            .and_then(|(_,ids)|ids.upgrade());
+        let ids = match cached {
+            Some(ids) => ids,
+            None => {
+                let ids : Arc<[EntityId]> = storage.ids().into();
+                self.ids_snapshots
+                    .write()
+                    .insert(type_id,(version,Arc::downgrade(&ids)));
+                ids
+            }
+        };
+        IdsSnapshot::new(type_id,version,ids)
+    }
+
     /// Get the component storage's write guard
     pub fn components_write<T : Component>(&self) -> Option<StorageWrite<'_,T>> {
         let type_id = TypeId::of::<T>();
@@ -219,6 +1636,31 @@ impl World {
         Some(StorageWrite::from_lock(lock))
     }
 
+    /// Get a component of an entity directly without building a query.
+    /// # Details
+    /// The returned [ComponentRead](crate::component::ComponentRead) holds the read lock of storage.  
+    /// Return ```None``` if ```T``` is not registered or entity doesn't have this component.
+    pub fn get<T : Component>(&self,entity_id : EntityId) -> Option<ComponentRead<'_,T>> {
+        self.entity_component_read::<T>(entity_id)
+    }
+
+    /// Get a mutable component of an entity directly without building a query.
+    /// # Details
+    /// The returned [ComponentWrite](crate::component::ComponentWrite) holds the write lock of storage.  
+    /// Return ```None``` if ```T``` is not registered or entity doesn't have this component.
+    pub fn get_mut<T : Component>(&self,entity_id : EntityId) -> Option<ComponentWrite<'_,T>> {
+        self.entity_component_write::<T>(entity_id)
+    }
+
+    /// Check if entity has component ```T```.
+    /// # Details
+    /// Return ```false``` if ```T``` is not registered.
+    pub fn has_component<T : Component>(&self,entity_id : EntityId) -> bool {
+        self.raw_storage_read(TypeId::of::<T>())
+            .map(|storage|storage.has(entity_id))
+            .unwrap_or(false)
+    }
+
     /// Get the read guard of component of an entity
     pub fn entity_component_read<T : Component>(&self,id : EntityId) -> Option<ComponentRead<'_,T>> {
         let lock = self.components_read::<T>()?;
@@ -236,7 +1678,60 @@ impl World {
         let lock = self.components_write::<T>()?;
         if lock.exist(id) {
             Some(unsafe {
-                ComponentWrite::new(id,lock)
+                ComponentWrite::new(id,lock,self.change_tick())
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Check if ```entity_id``` refers to an alive entity.
+    /// # Details
+    /// Return ```false``` if the entity was removed,
+    /// even if its index was reused by another entity.
+    pub fn is_alive(&self,entity_id : EntityId) -> bool {
+        self.exist(entity_id)
+    }
+
+    /// Get a [ComponentPtr](crate::component::ComponentPtr) to the component of an entity.
+    /// # Details
+    /// Return ```None``` if entity does not exist or it doesn't have this component.
+    pub fn component_ptr<T : Component>(&self,id : EntityId) -> Option<ComponentPtr<T>> {
+        if !self.exist(id) {
+            return None;
+        }
+        let stamp = self.components_read::<T>()?.stamp(id)?;
+        Some(ComponentPtr::new(id,stamp))
+    }
+
+    /// Get the read guard of component from a [ComponentPtr](crate::component::ComponentPtr).
+    /// # Details
+    /// Return ```None``` if the pointer is stale.
+    pub fn resolve<T : Component>(&self,ptr : &ComponentPtr<T>) -> Option<ComponentRead<'_,T>> {
+        if !self.is_alive(ptr.id) {
+            return None;
+        }
+        let lock = self.components_read::<T>()?;
+        if lock.stamp(ptr.id) == Some(ptr.stamp) {
+            Some(unsafe {
+                ComponentRead::new(ptr.id,lock)
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get the write guard of component from a [ComponentPtr](crate::component::ComponentPtr).
+    /// # Details
+    /// Return ```None``` if the pointer is stale.
+    pub fn resolve_mut<T : Component>(&self,ptr : &ComponentPtr<T>) -> Option<ComponentWrite<'_,T>> {
+        if !self.is_alive(ptr.id) {
+            return None;
+        }
+        let lock = self.components_write::<T>()?;
+        if lock.stamp(ptr.id) == Some(ptr.stamp) {
+            Some(unsafe {
+                ComponentWrite::new(ptr.id,lock,self.change_tick())
             })
         } else {
             None
@@ -257,37 +1752,94 @@ impl World {
     /// ## Panics
     /// * Panic if ```group``` is the same as another group in [World](crate::world::World).
     /// * Panic if component is owned by another group.
+    /// * Panic if component is double-buffered and owned by ```group```.
     pub fn make_group<G : Into<Group> + 'static + Copy>(&mut self, group: G) {
-        assert!(!self.has_group(group),
-                "World: Cannot make group because world has a same group");
+        if let Err(error) = self.try_make_group(group) {
+            panic!("World: Cannot make group because {}",error);
+        }
+    }
+
+    /// Make a [group](crate::group) to accelerate the iteration.
+    /// # Details
+    /// A component storage can only be owned by one group,
+    /// but [non-owning groups](crate::group::NonOwning) can be made over any storages.
+    /// # Errors
+    /// * [ExistingGroup](crate::error::GroupError::ExistingGroup) if ```group``` is already in world.
+    /// * [OwnedByAnotherGroup](crate::error::GroupError::OwnedByAnotherGroup)
+    ///   if a component owned by ```group``` is owned by another group.
+    /// * [DoubleBuffered](crate::error::GroupError::DoubleBuffered)
+    ///   if a component owned by ```group``` is double-buffered.
+    /// # Example
+    /// ```
+    /// use xecs::{World, GroupError, group::{full_owning, non_owning}};
+    /// use std::any::TypeId;
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>().register::<u64>();
+    /// assert_eq!(world.try_make_group(full_owning::<u32,char>()),Ok(()));
+    /// assert_eq!(world.try_make_group(full_owning::<u32,char>()),Err(GroupError::ExistingGroup));
+    /// assert_eq!(world.try_make_group(full_owning::<u64,u32>()),
+    ///            Err(GroupError::OwnedByAnotherGroup(TypeId::of::<u32>())));
+    /// assert_eq!(world.try_make_group(non_owning::<u32,u64>()),Ok(()));
+    /// ```
+    pub fn try_make_group<G : Into<Group> + 'static + Copy>(&mut self, group: G) -> Result<(),GroupError> {
+        if self.has_group(group) {
+            return Err(GroupError::ExistingGroup);
+        }
         let group = group.into();
-        assert!(
-            {
-                let mut ok = true;
-                'outer: for world_group in &self.groups {
-                    let world_group = world_group.read();
-                    for owning_type in world_group.owning() {
-                        if group.owned(owning_type) {
-                            ok = false;
-                            break 'outer;
-                        }
-                    }
-                }
-                ok
-            },
-            "World: Cannot make group because component was owned by another group"
-        );
+        for world_group in &self.groups {
+            let world_group = world_group.read();
+            if let Some(owned_type) = world_group.owning()
+                .into_iter()
+                .find(|owning_type|group.owned(*owning_type)) {
+                return Err(GroupError::OwnedByAnotherGroup(owned_type));
+            }
+        }
+        if let Some(type_id) = group.owning()
+            .into_iter()
+            .find(|type_id|self.is_double_buffered(*type_id)) {
+            return Err(GroupError::DoubleBuffered(type_id));
+        }
 
         self.groups.push(RwLock::new(group));
+        // Unwrap here never fails
+        // group was just pushed
         let group = self.groups.last().unwrap();
-        let mut group = group.write();
-        match &mut *group{
+        self.make_group_data(&mut group.write());
+        Ok(())
+    }
+
+    /// Re-make a [group](crate::group) from the current storages.
+    /// # Details
+    /// The group is rebuilt from scratch as [make_group](World::make_group) does.
+    /// Groups are maintained by every attach and detach,
+    /// it's useful to repair a group after the storages are rearranged by
+    /// [swap_aligned_unchecked](World::swap_aligned_unchecked).
+    /// # Panics
+    /// * Panic if ```group``` is not in [World](crate::world::World).
+    pub fn remake_group<G : Into<Group> + 'static>(&mut self,group : G) {
+        let group = group.into();
+        let world_group = self.groups
+            .iter()
+            .find(|world_group| world_group.read().eq(&group))
+            .expect("World:Cannot remake group because group is not in world");
+        self.make_group_data(&mut world_group.write());
+    }
+
+    fn make_group_data(&self,group : &mut Group) {
+        match group {
             Group::FullOwning(data) => {
                 let (type_a,type_b) = data.types();
                 let mut comp_a = self.raw_storage_write(type_a).unwrap();
                 let mut comp_b = self.raw_storage_write(type_b).unwrap();
                 data.make(&mut comp_a,&mut comp_b);
             },
+            Group::FullOwning3(data) => {
+                let (type_a,type_b,type_c) = data.types();
+                let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                let mut comp_c = self.raw_storage_write(type_c).unwrap();
+                data.make(&mut **comp_a,&mut **comp_b,&mut **comp_c);
+            },
             Group::PartialOwning(data) => {
                 let (type_a,type_b) = data.types();
                 let mut comp_a = self.raw_storage_write(type_a).unwrap();
@@ -300,43 +1852,347 @@ impl World {
                 let comp_b = self.raw_storage_read(type_b).unwrap();
                 data.make(&comp_a,&comp_b);
             },
+            Group::NonOwning3(data) => {
+                let (type_a,type_b,type_c) = data.types();
+                let comp_a = self.raw_storage_read(type_a).unwrap();
+                let comp_b = self.raw_storage_read(type_b).unwrap();
+                let comp_c = self.raw_storage_read(type_c).unwrap();
+                data.make(&**comp_a,&**comp_b,&**comp_c);
+            },
         }
     }
 
-    /// Check if (group)[crate::group] exists in [World](crate::world::World).
-    /// Return true if group is same as another group in World.
-    pub(in crate) fn has_group<G : Into<Group> + 'static>(&self, group: G) -> bool {
+    /// Remove a [group](crate::group) from [World](crate::world::World).
+    /// # Details
+    /// Return ```false``` if the group is not in world.
+    /// All [GroupHandle](crate::group::GroupHandle)s are invalidated.
+    pub fn remove_group<G : Into<Group> + 'static>(&mut self,group : G) -> bool {
         let group = group.into();
-        for world_group in &self.groups {
-            let world_group = world_group.read();
-            if world_group.eq(&group) {
-                return true;
-            }
+        let index = self.groups
+            .iter()
+            .position(|world_group| world_group.read().eq(&group));
+        if let Some(index) = index {
+            self.groups.remove(index);
+            self.groups_generation += 1;
+            true
+        } else {
+            false
         }
-        false
     }
 
-    pub(in crate) fn group<G : Into<Group> + 'static>(&self, group: G) -> RwLockReadGuard<Group> {
+    /// Resolve a [group](crate::group) to a [GroupHandle](crate::group::GroupHandle).
+    /// # Details
+    /// Return ```None``` if the group is not in world.
+    /// The handle skips the lookup of group in [query_group](crate::world::World::query_group).
+    pub fn group_handle<G : Into<Group> + 'static + Copy>(&self,group : G) -> Option<GroupHandle<G>> {
         let group = group.into();
         self.groups
             .iter()
-            .find(|world_group| {
-                let world_group = world_group.read();
-                world_group.eq(&group)
-            })
-            // unwrap here
-            // existence will be ensured by an outside function
-            .unwrap()
-            .read()
+            .position(|world_group| world_group.read().eq(&group))
+            .map(|index| GroupHandle::new(index,self.groups_generation))
     }
 
-    pub(in crate) fn groups(&self,type_id : TypeId) -> Vec<RwLockWriteGuard<'_,Group>> {
+    /// Query a group by a [GroupHandle](crate::group::GroupHandle).
+    /// # Details
+    /// It's the same as [query](crate::world::World::query) with a group query,
+    /// but the group is not looked up again.
+    /// Return ```None``` if ```handle``` is stale.
+    /// # Panics
+    /// * Panic if component was not registered.
+    /// # Example
+    /// ```
+    /// use xecs::{World, group::{full_owning, FullOwning}};
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// world.make_group(full_owning::<u32,char>());
+    /// world.create_entity().attach(1_u32).attach('a');
+    ///
+    /// let handle = world.group_handle(full_owning::<u32,char>()).unwrap();
+    /// for (x,_) in world.query_group::<FullOwning<&mut u32,&char>>(&handle).unwrap() {
+    ///     *x += 1;
+    /// }
+    /// assert_eq!(world.query_group::<FullOwning<&u32,&char>>(&handle).unwrap().next(),Some((&2,&'a')));
+    /// ```
+    pub fn query_group<'a,Q : GroupQueryable<'a>>(&'a self,handle : &GroupHandle<Q::Group>) -> Option<Box<dyn QueryIterator<Item = Q::Item> + 'a>> {
+        if handle.generation() != self.groups_generation {
+            return None;
+        }
+        // the index is valid if no group was removed
+        let group = self.groups.get(handle.index())?.read();
+        Some(Q::query_in(self,LockedGroup(group)))
+    }
+
+    /// Get the length of [full-owning group](crate::group::FullOwning) of ```A``` and ```B```.
+    /// # Details
+    /// Return ```None``` if the group is not in [World](crate::world::World).
+    pub fn group_len_of<A : Component,B : Component>(&self) -> Option<usize> {
+        self.group_len(full_owning::<A,B>())
+    }
+
+    /// Get how many entities are in ```group```.
+    /// # Details
+    /// Return ```None``` if the group is not in [World](crate::world::World).
+    /// # Example
+    /// ```
+    /// use xecs::{World, group::non_owning};
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// assert_eq!(world.group_len(non_owning::<u32,char>()),None);
+    ///
+    /// world.make_group(non_owning::<u32,char>());
+    /// world.create_entity().attach(1_u32).attach('a');
+    /// world.create_entity().attach(2_u32);
+    /// assert_eq!(world.group_len(non_owning::<u32,char>()),Some(1));
+    /// ```
+    pub fn group_len<G : Into<Group> + 'static>(&self,group : G) -> Option<usize> {
+        self.find_group(group)
+            .map(|world_group| world_group.len())
+    }
+
+    /// Check if the entity ```id``` is in ```group```.
+    /// # Details
+    /// The read locks of group and its storages are taken internally.  
+    /// Return ```None``` if the group is not in [World](crate::world::World).
+    /// # Panics
+    /// * Panic if any storage of group is borrowed mutably with ```refcell``` feature.
+    pub fn group_contains<G : Into<Group> + 'static>(&self,group : G,id : EntityId) -> Option<bool> {
+        let world_group = self.find_group(group)?;
+        // Unwrap here never fails
+        // the components of group must be registered before making group
+        let storages = world_group.types()
+            .into_iter()
+            .map(|type_id| self.raw_storage_read(type_id).unwrap())
+            .collect::<Vec<_>>();
+        let storages = storages.iter()
+            .map(|storage| &**storage)
+            .collect::<Vec<_>>();
+        Some(world_group.in_group(id,&storages))
+    }
+
+    /// Swap ```index_a``` and ```index_b``` in all ```storages``` together.
+    /// # Details
+    /// All indices are validated before any swap by [MultiStorageSwap](crate::component::MultiStorageSwap).
+    /// In debug build,it asserts that all storages are owned by the same group.
+    /// # Panics
+    /// * Panic if any component is not registered or listed twice.
+    /// * Panic if any index is out of bounds,no storage is swapped in this case.
+    /// # Safety
+    /// * The swap must keep the groups aligned,
+    ///   e.g. both indices are inside the group which owns all ```storages```.
+    pub unsafe fn swap_aligned_unchecked(&self,storages : &[TypeId],index_a : usize,index_b : usize) {
+        debug_assert!(storages.is_empty() || self.groups.iter().any(|group| {
+            let group = group.read();
+            storages.iter().all(|type_id| group.owned(*type_id))
+        }),"World:Cannot swap storages which are not owned by the same group");
+        for (i,type_id) in storages.iter().enumerate() {
+            assert!(!storages[..i].contains(type_id),
+                    "World:Cannot swap a storage twice");
+        }
+        let mut guards = storages.iter()
+            .map(|type_id| self.raw_storage_write(*type_id)
+                .expect("World:Cannot swap storages because components has not been registered."))
+            .collect::<Vec<_>>();
+        let mut storages = guards.iter_mut()
+            .map(|guard| &mut ***guard as &mut dyn ComponentStorage)
+            .collect::<Vec<_>>();
+        MultiStorageSwap::new(&mut storages).swap(index_a,index_b);
+    }
+
+    /// Check if (group)[crate::group] exists in [World](crate::world::World).
+    /// Return true if group is same as another group in World.
+    pub(in crate) fn has_group<G : Into<Group> + 'static>(&self, group: G) -> bool {
+        let group = group.into();
+        for world_group in &self.groups {
+            let world_group = world_group.read();
+            if world_group.eq(&group) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Lock the group for query in one lookup.
+    /// # Panics
+    /// * Panic with ```message``` and the types of group if group is not in world.
+    pub(in crate) fn lock_group<G : Into<Group> + 'static>(&self,group : G,message : &str) -> GroupReadGuard<'_> {
+        let group = group.into();
+        match self.find_group_data(&group) {
+            Some(world_group) => world_group,
+            None => panic!("{}: {}",message,self.missing_group(&group))
+        }
+    }
+
+    /// Find the group which owns ```type_id``` and lock it for reading
+    fn find_owner(&self,type_id : TypeId) -> Option<GroupReadGuard<'_>> {
+        self.groups
+            .iter()
+            .map(|world_group|world_group.read())
+            .find(|world_group|world_group.owned(type_id))
+    }
+
+    /// Find ```group``` in world and lock it for reading
+    fn find_group<G : Into<Group> + 'static>(&self,group : G) -> Option<GroupReadGuard<'_>> {
+        self.find_group_data(&group.into())
+    }
+
+    fn find_group_data(&self,group : &Group) -> Option<GroupReadGuard<'_>> {
+        self.groups
+            .iter()
+            .map(|world_group|world_group.read())
+            .find(|world_group|world_group.eq(group))
+    }
+
+    /// Add ```id``` to all groups which contain ```type_id```
+    pub(in crate) fn add_to_groups(&self,id : EntityId,type_id : TypeId) {
+        for mut group in self.groups(type_id) {
+            match &mut *group {
+                Group::FullOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                    data.add(id,&mut comp_a,&mut comp_b);
+                },
+                Group::FullOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                    let mut comp_c = self.raw_storage_write(type_c).unwrap();
+                    data.add(id,&mut **comp_a,&mut **comp_b,&mut **comp_c);
+                },
+                Group::PartialOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    data.add(id,&mut comp_a,&comp_b);
+                },
+                Group::NonOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let comp_a = self.raw_storage_read(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    data.add(id,&comp_a,&comp_b);
+                }
+                Group::NonOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let comp_a = self.raw_storage_read(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    let comp_c = self.raw_storage_read(type_c).unwrap();
+                    data.add(id,&**comp_a,&**comp_b,&**comp_c);
+                }
+            }
+        }
+    }
+
+    /// Remove ```id``` from all groups which contain ```type_id```
+    pub(in crate) fn remove_from_groups(&self,id : EntityId,type_id : TypeId) {
+        self.remove_from_groups_of(id,&[type_id]);
+    }
+
+    /// Remove ```id``` from all groups which contain any of ```types```,
+    /// each group is maintained only once
+    pub(in crate) fn remove_from_groups_of(&self,id : EntityId,types : &[TypeId]) {
+        for mut group in self.groups_of(types) {
+            match &mut *group {
+                Group::FullOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                    data.remove(id,&mut comp_a,&mut comp_b);
+                },
+                Group::FullOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let mut comp_b = self.raw_storage_write(type_b).unwrap();
+                    let mut comp_c = self.raw_storage_write(type_c).unwrap();
+                    data.remove(id,&mut **comp_a,&mut **comp_b,&mut **comp_c);
+                },
+                Group::PartialOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let mut comp_a = self.raw_storage_write(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    data.remove(id,&mut comp_a,&comp_b);
+                },
+                Group::NonOwning(data) => {
+                    let (type_a,type_b) = data.types();
+                    let comp_a = self.raw_storage_read(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    data.remove(id,&comp_a,&comp_b);
+                }
+                Group::NonOwning3(data) => {
+                    let (type_a,type_b,type_c) = data.types();
+                    let comp_a = self.raw_storage_read(type_a).unwrap();
+                    let comp_b = self.raw_storage_read(type_b).unwrap();
+                    let comp_c = self.raw_storage_read(type_c).unwrap();
+                    data.remove(id,&**comp_a,&**comp_b,&**comp_c);
+                }
+            }
+        }
+    }
+
+    /// Attach a type-erased component to ```id``` and maintain the groups.
+    /// Return the old component if ```id``` already has one.
+    /// # Panics
+    /// * Panic if the component is not registered.
+    pub(in crate) fn attach_any(&self,
+                                id : EntityId,
+                                type_id : TypeId,
+                                component : Box<dyn Any + Send + Sync>) -> Option<Box<dyn Any + Send + Sync>> {
+        let old = self.detach_any(id,type_id);
+        {
+            let mut storage = self.raw_storage_write(type_id)
+                .expect("World:Cannot attach component because components has not been registered.");
+            storage.insert_any(id,component);
+            if old.is_none() {
+                storage.mark_added(id,self.change_tick());
+            }
+        }
+        self.add_to_groups(id,type_id);
+        old
+    }
+
+    /// Overwrite the type-erased component of ```id``` in place and set its change tick to ```tick```,
+    /// see [replace_any](crate::component::ComponentStorage::replace_any).
+    /// # Details
+    /// The groups are not changed because ```id``` keeps the component.
+    /// # Panics
+    /// * Panic if the component is not registered.
+    pub(in crate) fn replace_any(&self,
+                                 id : EntityId,
+                                 type_id : TypeId,
+                                 component : Box<dyn Any + Send + Sync>,
+                                 tick : u64) -> Option<(Box<dyn Any + Send + Sync>,u64)> {
+        self.raw_storage_write(type_id)
+            .expect("World:Cannot replace component because components has not been registered.")
+            .replace_any(id,component,tick)
+    }
+
+    /// Detach a type-erased component from ```id``` and maintain the groups.
+    /// # Panics
+    /// * Panic if the component is not registered.
+    pub(in crate) fn detach_any(&self,id : EntityId,type_id : TypeId) -> Option<Box<dyn Any + Send + Sync>> {
+        let has = self.raw_storage_read(type_id)
+            .expect("World:Cannot detach component because components has not been registered.")
+            .has(id);
+        if !has {
+            return None;
+        }
+        self.remove_from_groups(id,type_id);
+        let mut storage = self.raw_storage_write(type_id).unwrap();
+        storage.remove_any(id)
+    }
+
+    pub(in crate) fn groups(&self,type_id : TypeId) -> Vec<GroupWriteGuard<'_>> {
+        self.groups_of(&[type_id])
+    }
+
+    /// Get the groups which contain any of ```types```
+    pub(in crate) fn groups_of(&self,types : &[TypeId]) -> Vec<GroupWriteGuard<'_>> {
         let mut groups = vec![];
         for group in &self.groups {
             let need_add = {
                 let group = group.read();
-                let (type_id_a,type_id_b) = group.types();
-                type_id_a == type_id || type_id_b == type_id
+                group.types()
+                    .iter()
+                    .any(|type_id|types.contains(type_id))
             };
             if need_add {
                 groups.push(group.write())
@@ -346,10 +2202,305 @@ impl World {
     }
 
     /// [Query](crate::query) entities with conditions
+    /// # Panics
+    /// * Panic if [try_query](crate::world::World::try_query) fails.
     pub fn query<'a, T: Queryable<'a>>(
         &'a self,
     ) -> Box<dyn QueryIterator<Item = <T as Queryable>::Item> + 'a> {
-        <T as Queryable<'a>>::query(self)
+        self.try_query::<T>()
+            .unwrap_or_else(|error|panic!("World:Cannot query because {}",error))
+    }
+
+    /// [Query](crate::query) entities with conditions without panic.
+    /// # Errors
+    /// * [UnregisteredComponent](crate::error::QueryError::UnregisteredComponent)
+    ///   if any component of ```T``` is not registered.
+    /// * [MissingGroup](crate::error::QueryError::MissingGroup) if the group of ```T``` is not in world.
+    /// * [StorageBorrowConflict](crate::error::QueryError::StorageBorrowConflict)
+    ///   if any storage of ```T``` is locked for writing by another query.
+    /// # Example
+    /// ```
+    /// use xecs::{World, QueryError, group::{full_owning, FullOwning}};
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// assert!(matches!(world.try_query::<&char>(),Err(QueryError::UnregisteredComponent(_))));
+    ///
+    /// world.register::<char>();
+    /// assert!(matches!(world.try_query::<FullOwning<&u32,&char>>(),Err(QueryError::MissingGroup { .. })));
+    ///
+    /// let iter = world.try_query::<&mut u32>().unwrap();
+    /// assert!(matches!(world.try_query::<&u32>(),Err(QueryError::StorageBorrowConflict)));
+    /// drop(iter);
+    /// world.make_group(full_owning::<u32,char>());
+    /// assert!(world.try_query::<FullOwning<&u32,&char>>().is_ok());
+    /// ```
+    pub fn try_query<'a, T: Queryable<'a>>(
+        &'a self,
+    ) -> Result<Box<dyn QueryIterator<Item = <T as Queryable<'a>>::Item> + 'a>,QueryError> {
+        T::check(self)?;
+        let mut types = vec![];
+        T::component_types(&mut types);
+        self.check_borrow(&types)?;
+        Ok(<T as Queryable<'a>>::query(self))
+    }
+
+    /// [Query](crate::query) entities as a [DoubleEndedIterator](std::iter::DoubleEndedIterator).
+    /// # Details
+    /// See [DoubleEndedQueryable](crate::query::DoubleEndedQueryable) for what can be queried.
+    /// # Panics
+    /// * Panic if any component of ```T``` is not registered.
+    /// * Panic if the group of ```T``` is not in world.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>();
+    /// for i in 0..5_u32 {
+    ///     world.create_entity().attach(i);
+    /// }
+    /// let items = world.query_double_ended::<&u32>()
+    ///     .rev()
+    ///     .copied()
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(items,vec![4,3,2,1,0]);
+    /// ```
+    pub fn query_double_ended<'a,T : DoubleEndedQueryable<'a>>(&'a self) -> T::Iter {
+        T::query_double_ended(self)
+    }
+
+    /// [Query](crate::query) the components of one entity.
+    /// # Details
+    /// It's a shortcut of ```world.query::<T>().from_id(entity_id)```,
+    /// only the storages of ```T``` are locked and nothing is iterated.  
+    /// It borrows world mutably,because the locks are released before the item is returned.  
+    /// Return ```None``` if any component of ```T``` is not registered,
+    /// or the entity doesn't match ```T```.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// world.create_entity().attach(1_u32);
+    /// let id = world.create_entity().attach(2_u32).attach('a').into_id();
+    ///
+    /// if let Some((x,_)) = world.query_one::<(&mut u32,&char)>(id) {
+    ///     *x += 1;
+    /// }
+    /// assert_eq!(world.query_one::<&u32>(id),Some(&3));
+    /// ```
+    pub fn query_one<'a,T : Queryable<'a>>(&'a mut self,entity_id : EntityId) -> Option<<T as Queryable<'a>>::Item> {
+        let world : &'a World = self;
+        world.try_query::<T>().ok()?.from_id(entity_id)
+    }
+
+    /// Query the entities with all components of ```type_ids``` which are chosen at runtime.
+    /// # Details
+    /// It yields the ID with the type-erased components in the order of ```type_ids```,
+    /// nothing is yielded if ```type_ids``` is empty.
+    /// The storages are locked for reading until the iterator is dropped.
+    /// # Errors
+    /// * [UnregisteredComponent](crate::error::QueryError::UnregisteredComponent)
+    ///   if any type of ```type_ids``` is not registered.
+    /// * [StorageBorrowConflict](crate::error::QueryError::StorageBorrowConflict)
+    ///   if any storage is locked for writing.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// use std::any::TypeId;
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// world.create_entity().attach(1_u32).attach('a');
+    /// world.create_entity().attach(2_u32);
+    ///
+    /// let types = [TypeId::of::<char>(),TypeId::of::<u32>()];
+    /// let items = world.query_dynamic(&types)
+    ///     .unwrap()
+    ///     .map(|(_,components)| {
+    ///         (components[0].downcast_ref::<char>().copied(),components[1].downcast_ref::<u32>().copied())
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(items,vec![(Some('a'),Some(1))]);
+    /// assert!(world.query_dynamic(&[TypeId::of::<u64>()]).is_err());
+    /// ```
+    pub fn query_dynamic(&self,type_ids : &[TypeId]) -> Result<DynQueryIter<'_>,QueryError> {
+        self.check_registered(type_ids)?;
+        self.check_borrow(type_ids)?;
+        let borrows = type_ids.iter()
+            // Unwrap here never fails
+            // all types are checked before
+            .map(|type_id|self.raw_storage_read(*type_id).unwrap())
+            .collect::<Vec<_>>();
+        Ok(DynQueryIter::new(borrows))
+    }
+
+    /// Get the type-erased component of ```type_id``` from an entity.
+    /// # Details
+    /// Return ```Ok(None)``` if the entity doesn't have this component.
+    /// # Errors
+    /// * [UnregisteredComponent](crate::error::QueryError::UnregisteredComponent)
+    ///   if ```type_id``` is not registered.
+    /// * [StorageBorrowConflict](crate::error::QueryError::StorageBorrowConflict)
+    ///   if the storage is locked for writing.
+    pub fn get_component_dynamic(&self,entity_id : EntityId,type_id : TypeId) -> Result<Option<AnyComponentRead<'_>>,QueryError> {
+        self.check_registered(&[type_id])?;
+        self.check_borrow(&[type_id])?;
+        // Unwrap here never fails
+        // type is checked before
+        let storage = self.raw_storage_read(type_id).unwrap();
+        if storage.has(entity_id) {
+            // Safety:
+            // the entity is in storage
+            Ok(Some(unsafe { AnyComponentRead::new(entity_id,storage) }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check if all ```types``` are registered
+    pub(in crate) fn check_registered(&self,types : &[TypeId]) -> Result<(),QueryError> {
+        match types.iter().find(|type_id|!self.components.contains_key(type_id)) {
+            Some(type_id) => Err(QueryError::UnregisteredComponent(*type_id)),
+            None => Ok(())
+        }
+    }
+
+    /// Check if ```group``` is in world
+    pub(in crate) fn check_group<G : Into<Group> + 'static>(&self,group : G) -> Result<(),QueryError> {
+        let group = group.into();
+        if self.groups.iter().any(|world_group|world_group.read().eq(&group)) {
+            Ok(())
+        } else {
+            Err(self.missing_group(&group))
+        }
+    }
+
+    fn missing_group(&self,group : &Group) -> QueryError {
+        let types = group.types();
+        let name_of = |type_id : TypeId| {
+            self.component_index
+                .binary_search_by_key(&type_id,|(type_id,_,_)|*type_id)
+                .map(|index|self.component_index[index].1)
+                .unwrap_or("<unregistered>")
+        };
+        QueryError::MissingGroup {
+            types : (types[0],types[1]),
+            names : (name_of(types[0]),name_of(types[1]))
+        }
+    }
+
+    /// Check if no storage of ```types``` is locked for writing,
+    /// unregistered types are skipped
+    fn check_borrow(&self,types : &[TypeId]) -> Result<(),QueryError> {
+        let conflict = types.iter()
+            .filter_map(|type_id|self.components.get(type_id))
+            .any(|storage|storage.try_read().is_none());
+        if conflict {
+            Err(QueryError::StorageBorrowConflict)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Query ```T``` in parallel.
+    /// # Details
+    /// The storages are locked until [ParQuery](crate::query::ParQuery) is dropped.
+    /// See [ParQueryable](crate::query::ParQueryable) for what can be queried.
+    /// # Panics
+    /// * Panic if any component of ```T``` is not registered.
+    /// * Panic if the group of ```T``` is not in world.
+    #[cfg(feature = "rayon")]
+    pub fn par_query<'a,T : ParQueryable<'a>>(&'a self) -> ParQuery<'a,T> {
+        ParQuery::new(self)
+    }
+
+    /// [Query](crate::query) entities and get the [Epoch](crate::epoch::Epoch)
+    /// of exactly the storages touched by query.
+    /// # Details
+    /// It's useful to make two passes over the same query,
+    /// [check_epoch](crate::world::World::check_epoch) between the passes
+    /// reports the storages which were structurally changed.
+    /// # Example
+    /// ```
+    /// use xecs::World;
+    /// let mut world = World::new();
+    /// world.register::<u32>().register::<char>();
+    /// world.create_entity().attach(1_u32);
+    ///
+    /// let (iter,epoch) = world.query_with_epoch::<&u32>();
+    /// let count = iter.count();
+    /// // an unrelated storage is changed
+    /// world.create_entity().attach('a');
+    /// assert!(world.check_epoch(&epoch).is_ok());
+    /// assert_eq!(world.query::<&u32>().count(),count);
+    /// ```
+    pub fn query_with_epoch<'a,T : Queryable<'a>>(&'a self)
+        -> (Box<dyn QueryIterator<Item = <T as Queryable<'a>>::Item> + 'a>,Epoch) {
+        let mut types = vec![];
+        T::component_types(&mut types);
+        let epoch = self.epoch_of(&types);
+        (self.query::<T>(),epoch)
+    }
+
+    /// Get the [Epoch](crate::epoch::Epoch) of all registered storages.
+    pub fn epoch(&self) -> Epoch {
+        let types = self.component_index
+            .iter()
+            .map(|(type_id,_,_)|*type_id)
+            .collect::<Vec<_>>();
+        self.epoch_of(&types)
+    }
+
+    fn epoch_of(&self,types : &[TypeId]) -> Epoch {
+        let mut versions : Vec<(TypeId,u64)> = vec![];
+        for type_id in types {
+            if versions.iter().any(|(recorded,_)|recorded == type_id) {
+                continue;
+            }
+            // unregistered component can be queried by Option<&T>
+            if let Some(storage) = self.raw_storage_read(*type_id) {
+                versions.push((*type_id,storage.version()));
+            }
+        }
+        Epoch::new(versions)
+    }
+
+    /// Check if the storages in ```epoch``` were structurally changed.
+    /// # Details
+    /// Return the type IDs and names of changed storages in error.  
+    /// **It blocks(or panics with ```refcell``` feature) if a query
+    /// which writes these storages is alive.**
+    pub fn check_epoch(&self,epoch : &Epoch) -> Result<(),EpochChanged> {
+        let changed_storages = epoch.versions()
+            .iter()
+            .filter(|(type_id,version)| {
+                self.raw_storage_read(*type_id)
+                    .map(|storage|storage.version())
+                    != Some(*version)
+            })
+            .map(|(type_id,_)| {
+                let name = self.component_index
+                    .binary_search_by_key(type_id,|(type_id,_,_)|*type_id)
+                    .map(|index|self.component_index[index].1)
+                    .unwrap_or("<unregistered>");
+                (*type_id,name)
+            })
+            .collect::<Vec<_>>();
+        if changed_storages.is_empty() {
+            Ok(())
+        } else {
+            Err(EpochChanged {
+                changed_storages
+            })
+        }
+    }
+
+    /// Assert that the storages in ```epoch``` were not structurally changed.
+    /// # Panics
+    /// Panic if any storage was changed,the message names the changed storages.
+    pub fn assert_epoch(&self,epoch : &Epoch) {
+        if let Err(err) = self.check_epoch(epoch) {
+            panic!("World:{}",err);
+        }
     }
 
 }
@@ -369,10 +2520,14 @@ impl Debug for World {
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
-    use crate::component::Component;
-    use crate::entity::EntityId;
-    use crate::group::{full_owning, non_owning, partial_owning};
-    use crate::query::WithId;
+    use crate::component::{Component, StorageKind};
+    use crate::entity::{EntityId, EntityIdExhausted, Generation};
+    use crate::error::XecsError;
+    use std::any::TypeId;
+    use crate::group::{full_owning, full_owning3, non_owning, partial_owning, FullOwning, FullOwning3, GroupIndexed, NonOwning, PartialOwning};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use crate::query::{Added, QueryIterator, WithId};
     use crate::world::World;
 
     #[test]
@@ -402,6 +2557,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn register_test() {
+        let mut world = World::new();
+        world.register::<char>().register_double_buffered::<u32>();
+        let id = world.create_entity().attach('a').attach(1_u32).into_id();
+
+        // registering again keeps the storage and its kind
+        world.register::<char>().register::<u32>();
+        assert_eq!(world.get::<char>(id).as_deref(),Some(&'a'));
+        assert_eq!(world.registered_components().count(),2);
+        assert!(world.registered_components()
+            .any(|(_,_,kind)|kind == StorageKind::DoubleBuffered));
+
+        assert_eq!(world.try_register::<char>().err(),
+                   Some(XecsError::ComponentAlreadyRegistered(std::any::type_name::<char>())));
+        assert!(world.try_register::<u64>().is_ok());
+        assert!(world.has_registered::<u64>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn register_double_buffered_panic_test() {
+        let mut world = World::new();
+        world.register::<u32>().register_double_buffered::<u32>();
+    }
+
     #[test]
     fn group_test() {
         let mut world = World::new();
@@ -472,6 +2653,203 @@ mod tests {
         println!();
     }
 
+    #[test]
+    fn group_indexed_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<u64>();
+
+        assert_eq!(world.group_len_of::<u32,u64>(),None);
+        for i in 0..20_u32 {
+            let entity = world.create_entity().attach(i);
+            if i % 3 != 0 {
+                entity.attach(i as u64 * 10);
+            }
+        }
+        world.make_group(full_owning::<u32,u64>());
+        world.create_entity().attach(100_u64).attach(100_u32);
+        let len = world.group_len_of::<u32,u64>().unwrap();
+        assert_eq!(len,14);
+
+        // accumulate by index
+        let mut side = vec![0_u64;len];
+        for (index,a,b) in world.query::<GroupIndexed<&u32,&u64>>() {
+            side[index] += *a as u64 + *b;
+        }
+        // accumulate by id
+        let mut map = HashMap::new();
+        for (id,(a,b)) in world.query::<(&u32,&u64)>().with_id() {
+            *map.entry(id).or_insert(0) += *a as u64 + *b;
+        }
+        let mut indices = vec![];
+        for (id,(index,_,b)) in world.query::<GroupIndexed<&u32,&mut u64>>().with_id() {
+            assert_eq!(side[index],map[&id]);
+            *b += 1;
+            indices.push((id,index));
+        }
+        assert_eq!(indices.len(),len);
+        // indices are stable without structural changes
+        let mut query = world.query::<GroupIndexed<&mut u32,&u64>>();
+        for (id,index) in &indices {
+            let (index_,_,_) = query.from_id(*id).unwrap();
+            assert_eq!(*index,index_);
+        }
+        assert!(query.from_id(EntityId::new(1).unwrap()).is_none());
+    }
+
+    #[test]
+    fn group_incremental_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>();
+        let a = world.create_entity().attach(1_u32).attach(1_u64).into_id();
+        world.create_entity().attach('x');
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<u32,u64>());
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),0);
+
+        // B is attached to an entity which already has A
+        world.attach_component(a,'a');
+        world.attach_component(a,1_i8);
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().with_id().map(|(id,_)|id).collect::<Vec<_>>(),vec![a]);
+        assert_eq!(world.query::<PartialOwning<&u64,&i8>>().count(),1);
+        assert_eq!(world.group_len_of::<u32,char>(),Some(1));
+
+        // the components attached in batch
+        let ids = world.create_entities(10)
+            .attach((0..10).collect::<Vec<u32>>())
+            .attach(vec!['b';10])
+            .attach(vec![0_u64;10])
+            .into_ids();
+        assert_eq!(world.group_len_of::<u32,char>(),Some(11));
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),11);
+        for id in ids.start.index()..ids.end.index() {
+            if id % 2 == 0 {
+                world.detach_component::<char>(EntityId::new(id).unwrap());
+            }
+        }
+        assert_eq!(world.group_len_of::<u32,char>(),Some(6));
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),
+                   world.query::<(&u32,&char)>().count());
+
+        // remaking keeps the same members
+        let mut before = world.query::<FullOwning<&u32,&char>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        world.remake_group(full_owning::<u32,char>());
+        world.remake_group(non_owning::<u32,u64>());
+        let mut after = world.query::<FullOwning<&u32,&char>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        before.sort();
+        after.sort();
+        assert_eq!(before,after);
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),11);
+    }
+
+    #[test]
+    #[should_panic]
+    fn remake_group_panic_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        world.remake_group(full_owning::<u32,char>());
+    }
+
+    #[test]
+    fn make_group_conflict_test() {
+        use crate::error::GroupError;
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        for i in 0..10_u32 {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+        }
+        world.make_group(full_owning::<u32,char>());
+        assert_eq!(world.try_make_group(full_owning::<u32,u64>()),
+                   Err(GroupError::OwnedByAnotherGroup(TypeId::of::<u32>())));
+        assert_eq!(world.try_make_group(partial_owning::<char,u64>()),
+                   Err(GroupError::OwnedByAnotherGroup(TypeId::of::<char>())));
+        assert_eq!(world.try_make_group(full_owning3::<u64,char,u32>()),
+                   Err(GroupError::OwnedByAnotherGroup(TypeId::of::<u32>())));
+        assert_eq!(world.try_make_group(full_owning::<u32,char>()),
+                   Err(GroupError::ExistingGroup));
+        // the rejected groups left nothing behind
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),5);
+
+        assert_eq!(world.try_make_group(non_owning::<u32,u64>()),Ok(()));
+        assert_eq!(world.try_make_group(non_owning::<char,u64>()),Ok(()));
+        assert_eq!(world.try_make_group(partial_owning::<u64,u32>()),Ok(()));
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),10);
+        assert_eq!(world.query::<NonOwning<&char,&u64>>().count(),5);
+        assert_eq!(world.query::<PartialOwning<&u64,&u32>>().count(),10);
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),5);
+    }
+
+    #[test]
+    #[should_panic(expected = "owned by another group")]
+    fn make_group_conflict_panic_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(full_owning::<u32,u64>());
+    }
+
+    #[test]
+    #[should_panic(expected = "Group of u64 and char is not in world")]
+    fn missing_group_panic_test() {
+        let mut world = World::new();
+        world.register::<u64>().register::<char>();
+        world.query::<PartialOwning<&u64,&char>>();
+    }
+
+    #[test]
+    fn try_query_test() {
+        use std::any::TypeId;
+        use crate::error::QueryError;
+        use crate::group::{full_owning3, FullOwning3};
+        use crate::query::Without;
+
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        world.create_entity().attach(1_u32).attach('a');
+
+        assert_eq!(world.try_query::<(&u32,&mut u64)>().err(),
+                   Some(QueryError::UnregisteredComponent(TypeId::of::<u64>())));
+        assert_eq!(world.try_query::<(&u32,Option<&u64>)>().unwrap().count(),1);
+        assert_eq!(world.try_query::<(&u32,Without<&u64>)>().err(),
+                   Some(QueryError::UnregisteredComponent(TypeId::of::<u64>())));
+
+        world.register::<u64>();
+        let missing = QueryError::MissingGroup {
+            types : (TypeId::of::<u32>(),TypeId::of::<char>()),
+            names : ("u32","char")
+        };
+        assert_eq!(world.try_query::<(NonOwning<&u32,&char>,Without<&u64>)>().err(),Some(missing));
+        assert_eq!(world.try_query::<FullOwning3<&u32,&char,&u64>>().err(),Some(missing));
+        assert_eq!(missing.to_string(),"Group of u32 and char is not in world");
+        world.make_group(full_owning3::<u32,char,u64>());
+        assert_eq!(world.try_query::<FullOwning3<&u32,&char,&u64>>().unwrap().count(),0);
+
+        {
+            let _iter = world.try_query::<&mut char>().unwrap();
+            assert_eq!(world.try_query::<(&u32,Option<&char>)>().err(),Some(QueryError::StorageBorrowConflict));
+            assert!(world.try_query::<&u32>().is_ok());
+        }
+        assert_eq!(world.try_query::<(&u32,&char)>().unwrap().count(),1);
+    }
+
     #[test]
     fn debug_trait_test() {
         let mut world = World::new();
@@ -513,6 +2891,35 @@ mod tests {
         assert_eq!(&world.resource_read::<Test>().unwrap().name,"affff");
     }
 
+    #[test]
+    fn insert_resource_test() {
+        #[derive(Debug,PartialEq)]
+        struct Time(f32);
+        #[derive(Debug,PartialEq)]
+        struct Gravity(f32);
+
+        let mut world = World::new();
+        assert!(world.resource::<Time>().is_none());
+        assert_eq!(world.insert_resource(Time(0.0)),None);
+        assert_eq!(world.insert_resource(Time(1.0)),Some(Time(0.0)));
+        world.insert_resource(Gravity(-9.8));
+
+        *world.resource_mut::<Time>().unwrap() = Time(2.0);
+        assert_eq!(*world.resource::<Time>().unwrap(),Time(2.0));
+        {
+            // resources are locked independently
+            let time = world.fetch_resource::<&Time>().unwrap();
+            let mut gravity = world.fetch_resource::<&mut Gravity>().unwrap();
+            gravity.0 *= time.0;
+        }
+        assert_eq!(*world.resource::<Gravity>().unwrap(),Gravity(-19.6));
+        // two readers at the same time
+        let a = world.resource::<Time>().unwrap();
+        let b = world.fetch_resource::<&Time>().unwrap();
+        assert_eq!(a.0,b.0);
+        assert!(world.fetch_resource::<&mut u32>().is_none());
+    }
+
     #[test]
     fn entity_component_test() {
         let mut world = World::new();
@@ -537,6 +2944,16 @@ mod tests {
             let v = world.entity_component_read::<u32>(id).unwrap();
             assert_eq!(*v,3);
         }
+
+        *world.get_mut::<u32>(id).unwrap() += 1;
+        assert_eq!(*world.get::<u32>(id).unwrap(),4);
+        assert!(world.has_component::<u32>(id));
+        world.detach_component::<u32>(id);
+        assert!(world.get::<u32>(id).is_none());
+        assert!(world.get_mut::<u32>(id).is_none());
+        assert!(!world.has_component::<u32>(id));
+        assert!(!world.has_component::<char>(id));
+        assert!(world.get::<char>(id).is_none());
     }
 
     #[test]
@@ -568,13 +2985,430 @@ mod tests {
     }
 
     #[test]
-    fn enitites_test() {
+    fn iter_entities_test() {
         let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..10_u32).map(|i| {
+            let id = world.create_entity().into_id();
+            if i % 3 == 0 {
+                world.attach_component(id,i);
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.remove_entity(ids[3]);
+        world.remove_entity(ids[4]);
 
-        world.register::<u32>();
-        world.register::<char>();
+        assert_eq!(world.entity_count(),8);
+        let mut alive = world.iter_entities().collect::<Vec<_>>();
+        alive.sort();
+        let expected = ids.iter()
+            .enumerate()
+            .filter(|(i,_)|*i != 3 && *i != 4)
+            .map(|(_,id)|*id)
+            .collect::<Vec<_>>();
+        assert_eq!(alive,expected);
 
-        world.create_entity()
+        let mut with = world.entities_with::<u32>().collect::<Vec<_>>();
+        with.sort();
+        assert_eq!(with,vec![ids[0],ids[6],ids[9]]);
+        assert_eq!(world.entities_with::<char>().count(),0);
+        // lazy,the storage can be read during iterating
+        for id in world.entities_with::<u32>() {
+            assert!(world.get::<u32>(id).is_some());
+        }
+    }
+
+    #[test]
+    fn filter_ids_with_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>();
+
+        let id1 = world.create_entity().attach(1_u32).attach('a').into_id();
+        let id2 = world.create_entity().attach(2_u32).into_id();
+        let id3 = world.create_entity().attach('c').into_id();
+        world.detach_component::<char>(id1);
+        // this id has never been allocated
+        let id_far = EntityId::new(1000).unwrap();
+
+        let mut ids = vec![id3,id1,id_far,id2];
+        world.filter_ids_with::<u32>(&mut ids);
+        assert_eq!(ids,vec![id1,id2]);
+
+        let mut ids = vec![id3,id1,id_far,id2];
+        world.filter_ids_with::<char>(&mut ids);
+        assert_eq!(ids,vec![id3]);
+    }
+
+    #[test]
+    fn components_of_test() {
+        let mut world1 = World::new();
+        world1.register::<u32>()
+            .register::<char>()
+            .register::<String>();
+        let mut world2 = World::new();
+        world2.register::<String>()
+            .register::<u32>()
+            .register::<char>();
+
+        let types1 = world1.registered_components().collect::<Vec<_>>();
+        let types2 = world2.registered_components().collect::<Vec<_>>();
+        assert_eq!(types1,types2);
+        assert!(types1.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let id1 = world1.create_entity().attach('a').attach(1_u32).attach(String::new()).into_id();
+        let id2 = world2.create_entity().attach(String::new()).attach('a').attach(1_u32).into_id();
+        let components = world1.components_of(id1);
+        assert_eq!(components,world2.components_of(id2));
+        assert_eq!(components,
+                   types1.iter().map(|(type_id,name,_)|(*type_id,*name)).collect::<Vec<_>>());
+
+        // the index is updated when a new type is registered
+        world1.register::<u8>();
+        world1.create_entity().attach(2_u8);
+        let types = world1.registered_components().collect::<Vec<_>>();
+        assert_eq!(types.len(),4);
+        assert!(types.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(types.iter().any(|(_,name,_)| *name == "u8"));
+        world1.attach_component(id1,3_u8);
+        assert_eq!(world1.components_of(id1),
+                   types.iter().map(|(type_id,name,_)|(*type_id,*name)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn component_ptr_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>();
+
+        let id = world.create_entity().attach(57_u32).into_id();
+        assert!(world.component_ptr::<char>(id).is_none());
+        let ptr = world.component_ptr::<u32>(id).unwrap();
+        assert_eq!(*world.resolve(&ptr).unwrap(),57);
+        *world.resolve_mut(&ptr).unwrap() = 58;
+        assert_eq!(*world.resolve(&ptr).unwrap(),58);
+
+        // replaced in place
+        world.attach_component(id,59_u32);
+        assert_eq!(*world.resolve(&ptr).unwrap(),59);
+
+        // lost the component
+        world.detach_component::<u32>(id);
+        assert!(world.resolve(&ptr).is_none());
+        // the re-attached component is not the pointed one
+        world.attach_component(id,60_u32);
+        assert!(world.resolve(&ptr).is_none());
+        assert!(world.resolve_mut(&ptr).is_none());
+        let ptr = world.component_ptr::<u32>(id).unwrap();
+        assert_eq!(*world.resolve(&ptr).unwrap(),60);
+
+        // the id is reused by another entity
+        world.remove_entity(id);
+        assert!(world.resolve(&ptr).is_none());
+        let new_id = world.create_entity().attach(60_u32).into_id();
+        assert_eq!(new_id.index(),id.index());
+        assert_ne!(new_id,id);
+        assert!(world.resolve(&ptr).is_none());
+        assert!(world.resolve_mut(&ptr).is_none());
+        // raw id lookup rejects the stale generation
+        assert!(world.entity_component_read::<u32>(ptr.id()).is_none());
+        assert_eq!(*world.entity_component_read::<u32>(new_id).unwrap(),60);
+    }
+
+    #[test]
+    fn group_len_contains_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<bool>();
+        let ids = (0..10_u32).map(|i| {
+            let id = world.create_entity().attach(i).attach(i as u64).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,true);
+            }
+            id
+        }).collect::<Vec<_>>();
+        assert_eq!(world.group_len(full_owning::<u32,char>()),None);
+        assert_eq!(world.group_contains(full_owning::<u32,char>(),ids[0]),None);
+
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,bool>());
+        world.make_group(non_owning::<char,bool>());
+        assert_eq!(world.group_len(full_owning::<u32,char>()),Some(5));
+        assert_eq!(world.group_len_of::<u32,char>(),Some(5));
+        assert_eq!(world.group_len(partial_owning::<u64,bool>()),Some(4));
+        assert_eq!(world.group_len(non_owning::<char,bool>()),Some(2));
+        assert_eq!(world.group_len(full_owning::<char,u32>()),None);
+
+        assert_eq!(world.group_contains(full_owning::<u32,char>(),ids[4]),Some(true));
+        assert_eq!(world.group_contains(full_owning::<u32,char>(),ids[5]),Some(false));
+        assert_eq!(world.group_contains(partial_owning::<u64,bool>(),ids[9]),Some(true));
+        assert_eq!(world.group_contains(non_owning::<char,bool>(),ids[6]),Some(true));
+        assert_eq!(world.group_contains(non_owning::<char,bool>(),ids[4]),Some(false));
+
+        world.detach_component::<char>(ids[4]);
+        world.remove_entity(ids[6]);
+        assert_eq!(world.group_len(full_owning::<u32,char>()),Some(3));
+        assert_eq!(world.group_len(non_owning::<char,bool>()),Some(1));
+        assert_eq!(world.group_contains(full_owning::<u32,char>(),ids[4]),Some(false));
+        assert_eq!(world.group_contains(non_owning::<char,bool>(),ids[6]),Some(false));
+    }
+
+    #[test]
+    fn query_one_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        let ids = (0..10_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+            }
+            id
+        }).collect::<Vec<_>>();
+
+        assert_eq!(world.query_one::<&u32>(ids[3]),Some(&3));
+        assert_eq!(world.query_one::<(&u32,&char)>(ids[3]),None);
+        *world.query_one::<&mut u32>(ids[4]).unwrap() = 40;
+        assert_eq!(world.query_one::<(&u32,&char)>(ids[4]),Some((&40,&'a')));
+        assert_eq!(world.query_one::<&u64>(ids[4]),None);
+
+        world.make_group(full_owning::<u32,char>());
+        assert_eq!(world.query_one::<FullOwning<&u32,&char>>(ids[4]),Some((&40,&'a')));
+        assert_eq!(world.query_one::<FullOwning<&u32,&char>>(ids[5]),None);
+
+        world.remove_entity(ids[4]);
+        let new_id = world.create_entity().attach(100_u32).into_id();
+        assert_eq!(new_id.index(),ids[4].index());
+        assert_eq!(world.query_one::<&u32>(ids[4]),None);
+        assert_eq!(world.query_one::<&u32>(new_id),Some(&100));
+    }
+
+    #[test]
+    fn query_dynamic_test() {
+        use crate::error::QueryError;
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        let ids = (0..8_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,char::from(b'a' + i as u8));
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.make_group(full_owning::<u32,char>());
+
+        let types = [TypeId::of::<u32>(),TypeId::of::<char>()];
+        let mut items = world.query_dynamic(&types)
+            .unwrap()
+            .map(|(id,components)| {
+                assert_eq!(components.len(),2);
+                (id,*components[0].downcast_ref::<u32>().unwrap(),*components[1].downcast_ref::<char>().unwrap())
+            })
+            .collect::<Vec<_>>();
+        items.sort();
+        let expected = (0..8_u32)
+            .filter(|i|i % 2 == 0)
+            .map(|i|(ids[i as usize],i,char::from(b'a' + i as u8)))
+            .collect::<Vec<_>>();
+        assert_eq!(items,expected);
+        assert_eq!(world.query_dynamic(&[TypeId::of::<u32>()]).unwrap().count(),8);
+        assert_eq!(world.query_dynamic(&[TypeId::of::<u64>(),TypeId::of::<u32>()]).unwrap().count(),0);
+        assert_eq!(world.query_dynamic(&[]).unwrap().count(),0);
+        assert_eq!(world.query_dynamic(&[TypeId::of::<i8>()]).err(),
+                   Some(QueryError::UnregisteredComponent(TypeId::of::<i8>())));
+
+        let component = world.get_component_dynamic(ids[3],TypeId::of::<u32>()).unwrap().unwrap();
+        assert_eq!(component.downcast_ref::<u32>(),Some(&3));
+        // read locks can be shared with the typed path
+        assert_eq!(world.query::<&u32>().count(),8);
+        {
+            let _write = world.query::<&mut char>();
+            assert_eq!(world.query_dynamic(&types).err(),Some(QueryError::StorageBorrowConflict));
+        }
+        drop(component);
+        assert!(world.get_component_dynamic(ids[3],TypeId::of::<char>()).unwrap().is_none());
+        assert!(world.get_component_dynamic(ids[3],TypeId::of::<i8>()).is_err());
+    }
+
+    #[test]
+    fn sort_storage_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>();
+        let values = [5_u32,9,1,7,3,8,2,6,0,4];
+        let ids = values.iter().enumerate().map(|(i,value)| {
+            let id = world.create_entity()
+                .attach(*value)
+                .attach(*value as u64)
+                .into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,char::from(b'a' + *value as u8));
+                world.attach_component(id,*value as i8);
+            }
+            id
+        }).collect::<Vec<_>>();
+
+        world.sort_storage_by::<u32,_>(|a,b|a.cmp(b));
+        let sorted = world.query::<&u32>().copied().collect::<Vec<_>>();
+        assert_eq!(sorted,(0..10).collect::<Vec<_>>());
+        for (i,id) in ids.iter().enumerate() {
+            assert_eq!(world.get::<u32>(*id).as_deref(),Some(&values[i]));
+        }
+
+        world.make_group(partial_owning::<u32,u64>());
+        world.sort_storage_by::<u32,_>(|a,b|b.cmp(a));
+        let sorted = world.query::<&u32>().copied().collect::<Vec<_>>();
+        assert_eq!(sorted,vec![9,8,7,6,5,4,3,2,1,0]);
+        assert_eq!(world.query::<PartialOwning<&u32,&u64>>()
+            .map(|(a,b)|{ assert_eq!(*a as u64,*b); *a })
+            .count(),10);
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>();
+        let ids = values.iter().enumerate().map(|(i,value)| {
+            let id = world.create_entity()
+                .attach(*value)
+                .attach(*value as u64)
+                .into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,char::from(b'a' + *value as u8));
+            }
+            id
+        }).collect::<Vec<_>>();
+        world.make_group(full_owning::<u32,char>());
+        world.sort_storage_by::<u32,_>(|a,b|a.cmp(b));
+        // entities in group go first,and the others are sorted separately
+        let sorted = world.query::<&u32>().copied().collect::<Vec<_>>();
+        assert_eq!(sorted,vec![0,1,2,3,5,4,6,7,8,9]);
+        let grouped = world.query::<FullOwning<&u32,&char>>()
+            .map(|(value,c)|{ assert_eq!(char::from(b'a' + *value as u8),*c); *value })
+            .collect::<Vec<_>>();
+        assert_eq!(grouped,vec![0,1,2,3,5]);
+        for (i,id) in ids.iter().enumerate() {
+            assert_eq!(world.group_contains(full_owning::<u32,char>(),*id),Some(i % 2 == 0));
+        }
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<u64>()
+            .register::<i8>();
+        for (i,value) in values.iter().enumerate() {
+            let id = world.create_entity()
+                .attach(*value)
+                .attach(*value as u64)
+                .into_id();
+            if i % 3 != 0 {
+                world.attach_component(id,*value as i8);
+            }
+        }
+        world.make_group(full_owning3::<u32,u64,i8>());
+        world.sort_storage_by::<u64,_>(|a,b|b.cmp(a));
+        let grouped = world.query::<FullOwning3<&u32,&u64,&i8>>()
+            .map(|(a,b,c)|{
+                assert_eq!(*a as u64,*b);
+                assert_eq!(*a as i8,*c);
+                *a
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(grouped,vec![9,8,6,3,1,0]);
+        let sorted = world.query::<&u64>().copied().collect::<Vec<_>>();
+        assert_eq!(sorted,vec![9,8,6,3,1,0,7,5,4,2]);
+    }
+
+    #[test]
+    fn generation_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+
+        world.create_entity().attach(1_u32);
+        world.create_entity().attach(2_u32);
+        world.create_entity().attach(3_u32);
+
+        let ids = world.query::<&u32>()
+            .with_id()
+            .map(|(id,data)|(*data,id))
+            .collect::<HashMap<_,_>>();
+        let id = ids[&2];
+        assert!(world.is_alive(id));
+        assert_eq!(id.generation(),Generation::FIRST);
+
+        // reuse the index
+        world.remove_entity(id);
+        let new_id = world.create_entity().attach(4_u32).into_id();
+        assert_eq!(new_id.index(),id.index());
+        assert_eq!(new_id.generation(),id.generation().next().unwrap());
+        assert!(!world.is_alive(id));
+        assert!(world.is_alive(new_id));
+        assert!(world.entity(id).is_none());
+        assert!(!world.remove_entity(id));
+        assert_eq!(*world.entity_component_read::<u32>(new_id).unwrap(),4);
+        assert!(world.is_alive(ids[&1]));
+
+        // the stale id never aliases the new entity
+        assert!(world.get::<u32>(id).is_none());
+        assert!(!world.has_component::<u32>(id));
+        assert_eq!(world.remove_component::<u32>(id),None);
+        assert_eq!(world.query::<&u32>().from_id(id),None);
+        assert_eq!(world.query::<&mut u32>().from_id(id),None);
+        assert_eq!(world.query::<&u32>().from_id(new_id),Some(&4));
+    }
+
+    #[test]
+    fn max_entity_id_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+
+        world.create_entities(2);
+        world.set_max_entity_id(4);
+        let stats = world.entity_stats();
+        assert_eq!(stats.alive,2);
+        assert_eq!(stats.high_water_mark,2);
+        assert_eq!(stats.headroom,Some(2));
+
+        assert!(world.try_create_entities(3).is_err());
+        let id = world.try_create_entity().unwrap().attach(3_u32).into_id();
+        world.try_create_entity().unwrap();
+        let err = world.try_create_entity().err().unwrap();
+        assert_eq!(err,EntityIdExhausted { max_entity_id : 4 });
+        assert_eq!(world.entity_stats().headroom,Some(0));
+
+        world.remove_entity(id);
+        let stats = world.entity_stats();
+        assert_eq!(stats.alive,3);
+        assert_eq!(stats.high_water_mark,4);
+        assert_eq!(stats.headroom,Some(1));
+        assert_eq!(world.try_create_entity().unwrap().into_id().index(),id.index());
+    }
+
+    #[test]
+    #[should_panic(expected = "Entity ID exhausted")]
+    fn max_entity_id_panic_test() {
+        let mut world = World::new();
+        world.set_max_entity_id(1);
+        world.create_entity();
+        world.create_entity();
+    }
+
+    #[test]
+    fn enitites_test() {
+        let mut world = World::new();
+
+        world.register::<u32>();
+        world.register::<char>();
+
+        world.create_entity()
             .attach(2_u32)
             .attach('a');
 
@@ -599,6 +3433,806 @@ mod tests {
         let id = world.create_entity().into_id();
 
         // check if id is reused
-        assert_eq!(id,EntityId::new(4).unwrap());
+        assert_eq!(id.index(),4);
+    }
+
+    #[test]
+    fn swap_aligned_test() {
+        let mut world = World::new();
+        world.register::<char>()
+            .register::<u32>()
+            .register::<f32>();
+        for (c,n) in [('a',1_u32),('b',2),('c',3)] {
+            world.create_entity().attach(c).attach(n).attach(n as f32);
+        }
+        world.make_group(full_owning::<char,u32>());
+        world.make_group(partial_owning::<f32,u32>());
+
+        unsafe {
+            world.swap_aligned_unchecked(&[TypeId::of::<char>(),TypeId::of::<u32>()],0,2);
+            // partial-owning group owns a single storage
+            world.swap_aligned_unchecked(&[TypeId::of::<f32>()],0,1);
+        }
+        assert_eq!(world.components_read::<char>().unwrap().data(),&['c','b','a']);
+        assert_eq!(world.components_read::<u32>().unwrap().data(),&[3,2,1]);
+        assert_eq!(world.components_read::<f32>().unwrap().data(),&[2.0,1.0,3.0]);
+
+        let mut items = world.query::<FullOwning<&char,&u32>>()
+            .map(|(c,n)|(*c,*n))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,vec![('a',1),('b',2),('c',3)]);
+        let mut items = world.query::<PartialOwning<&f32,&u32>>()
+            .map(|(f,n)|(*f as u32,*n))
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items,vec![(1,1),(2,2),(3,3)]);
+    }
+
+
+    #[test]
+    fn epoch_test() {
+        let mut world = World::new();
+        world.register::<char>()
+            .register::<u32>()
+            .register::<f32>();
+        let id = world.create_entity().attach('a').attach(1_u32).into_id();
+
+        let epoch = world.epoch();
+        assert!(world.check_epoch(&epoch).is_ok());
+        // modifying data is not a structural change
+        *world.entity_component_write::<u32>(id).unwrap() = 2;
+        world.attach_component(id,'b');
+        world.assert_epoch(&epoch);
+
+        let (iter,epoch) = world.query_with_epoch::<(&char,&mut u32)>();
+        assert_eq!(iter.count(),1);
+        // unrelated storage
+        world.attach_component(id,1.0_f32);
+        assert!(world.check_epoch(&epoch).is_ok());
+        // touched storage
+        world.create_entity().attach(3_u32);
+        let err = world.check_epoch(&epoch).unwrap_err();
+        assert_eq!(err.changed_storages,vec![(TypeId::of::<u32>(),"u32")]);
+        assert!(world.check_epoch(&world.epoch()).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Epoch changed in storages: char")]
+    fn assert_epoch_test() {
+        let mut world = World::new();
+        world.register::<char>();
+        let (_,epoch) = world.query_with_epoch::<&char>();
+        world.create_entity().attach('a');
+        world.assert_epoch(&epoch);
+    }
+
+
+    #[test]
+    fn ids_snapshot_test() {
+        let mut world = World::new();
+        world.register::<char>().register::<u32>();
+        let a = world.create_entity().attach('a').into_id();
+        let b = world.create_entity().attach('b').into_id();
+
+        let first = world.ids_snapshot::<char>();
+        let second = world.ids_snapshot::<char>();
+        assert!(Arc::ptr_eq(first.arc(),second.arc()));
+        assert_eq!(first.as_slice(),&[a,b]);
+        assert!(first.is_current(&world));
+
+        // unrelated storage doesn't invalidate the snapshot
+        world.attach_component(a,1_u32);
+        assert!(Arc::ptr_eq(first.arc(),world.ids_snapshot::<char>().arc()));
+
+        world.detach_component::<char>(a);
+        assert!(!first.is_current(&world));
+        let third = world.ids_snapshot::<char>();
+        assert!(!Arc::ptr_eq(first.arc(),third.arc()));
+        assert_eq!(first.as_slice(),&[a,b]);
+        assert_eq!(third.as_slice(),&[b]);
+        assert_ne!(first.version(),third.version());
+
+        // the cache doesn't keep snapshot alive
+        let weak = Arc::downgrade(third.arc());
+        drop(third);
+        assert_eq!(weak.strong_count(),0);
+        drop(first);
+        drop(second);
+        assert_eq!(world.ids_snapshot::<char>().as_slice(),&[b]);
+    }
+
+
+    #[test]
+    fn remove_bundle_test() {
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct WorldPosition(f32);
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct Rotation(f32);
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct PhysicsBody(u32);
+
+        let mut world = World::new();
+        world.register::<WorldPosition>()
+            .register::<Rotation>()
+            .register::<PhysicsBody>()
+            .register::<char>();
+        world.make_group(full_owning::<WorldPosition,Rotation>());
+        world.make_group(partial_owning::<PhysicsBody,char>());
+        let a = world.create_entity()
+            .attach(WorldPosition(1.0))
+            .attach(Rotation(2.0))
+            .attach(PhysicsBody(3))
+            .attach('a')
+            .into_id();
+        let b = world.create_entity()
+            .attach(WorldPosition(4.0))
+            .attach(Rotation(5.0))
+            .attach('b')
+            .into_id();
+        assert!(world.has_bundle::<(WorldPosition,Rotation,PhysicsBody)>(a));
+        assert!(!world.has_bundle::<(WorldPosition,Rotation,PhysicsBody)>(b));
+
+        // missing component,nothing is removed
+        let err = world.remove_bundle::<(WorldPosition,Rotation,PhysicsBody)>(b).unwrap_err();
+        assert_eq!(err.missing,vec![std::any::type_name::<PhysicsBody>()]);
+        assert_eq!(world.components_of(b).len(),3);
+        assert_eq!(world.group_len_of::<WorldPosition,Rotation>(),Some(2));
+
+        let bundle = world.remove_bundle::<(WorldPosition,Rotation,PhysicsBody)>(a).unwrap();
+        assert_eq!(bundle,(WorldPosition(1.0),Rotation(2.0),PhysicsBody(3)));
+        assert_eq!(world.components_of(a),vec![(TypeId::of::<char>(),"char")]);
+        assert_eq!(world.group_len_of::<WorldPosition,Rotation>(),Some(1));
+        let items = world.query::<FullOwning<&WorldPosition,&Rotation>>()
+            .map(|(p,r)|(*p,*r))
+            .collect::<Vec<_>>();
+        assert_eq!(items,vec![(WorldPosition(4.0),Rotation(5.0))]);
+        assert_eq!(world.query::<PartialOwning<&PhysicsBody,&char>>().count(),0);
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut world = World::new();
+        world.register::<u32>().register::<char>();
+        world.make_group(full_owning::<u32,char>());
+        let kept = (0..4_u32).map(|i| {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'w');
+            }
+            id
+        }).collect::<Vec<_>>();
+
+        let mut other = World::new();
+        other.register::<char>()
+            .register::<u32>()
+            .register::<u64>();
+        other.make_group(full_owning::<char,u64>());
+        let ids = (0..6_u32).map(|i| {
+            let id = other.create_entity().attach(100 + i).into_id();
+            if i % 2 == 1 {
+                other.attach_component(id,'o');
+            }
+            if i % 3 == 0 {
+                other.attach_component(id,i as u64);
+            }
+            id
+        }).collect::<Vec<_>>();
+        other.remove_entity(ids[4]);
+        let empty = other.create_entity().into_id();
+
+        let map = world.merge(other);
+        assert_eq!(map.len(),6);
+        assert!(!map.contains_key(&ids[4]));
+        assert!(world.is_alive(map[&empty]));
+        assert!(world.components_of(map[&empty]).is_empty());
+        for (i,id) in ids.iter().enumerate().filter(|(i,_)|*i != 4) {
+            let new_id = map[id];
+            assert!(!kept.contains(&new_id));
+            assert_eq!(world.get::<u32>(new_id).as_deref(),Some(&(100 + i as u32)));
+            assert_eq!(world.has_component::<char>(new_id),i % 2 == 1);
+            assert_eq!(world.get::<u64>(new_id).as_deref().copied(),Some(i as u64).filter(|_|i % 3 == 0));
+        }
+        for (i,id) in kept.iter().enumerate() {
+            assert_eq!(world.get::<u32>(*id).as_deref(),Some(&(i as u32)));
+        }
+        assert!(world.has_registered::<u64>());
+        assert_eq!(world.query::<&u64>().count(),2);
+
+        // the group of world is rebuilt,the group of other is dropped
+        let mut grouped = world.query::<FullOwning<&u32,&char>>()
+            .map(|(n,c)|(*n,*c))
+            .collect::<Vec<_>>();
+        grouped.sort();
+        assert_eq!(grouped,vec![(0,'w'),(2,'w'),(101,'o'),(103,'o'),(105,'o')]);
+        assert!(world.try_query::<FullOwning<&char,&u64>>().is_err());
+    }
+
+    #[test]
+    fn clear_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(non_owning::<u32,u64>());
+        let ids = (0..6_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach('a')
+                .attach(i as u64)
+                .into_id()
+        }).collect::<Vec<_>>();
+
+        world.clear_storage::<char>();
+        assert_eq!(world.query::<&char>().count(),0);
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),0);
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),6);
+        assert_eq!(world.entity_count(),6);
+        world.attach_component(ids[1],'b');
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),1);
+
+        world.clear_entities();
+        assert_eq!(world.entity_count(),0);
+        assert_eq!(world.query::<&u32>().count(),0);
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),0);
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),0);
+        assert!(ids.iter().all(|id|!world.is_alive(*id)));
+        assert!(world.has_registered::<char>());
+
+        // the indices are reused with the next generation
+        let new_ids = (0..6_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach('c')
+                .into_id()
+        }).collect::<Vec<_>>();
+        let mut indices = new_ids.iter().map(EntityId::index).collect::<Vec<_>>();
+        indices.sort();
+        assert_eq!(indices,ids.iter().map(EntityId::index).collect::<Vec<_>>());
+        assert!(new_ids.iter().all(|id|!ids.contains(id)));
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),6);
+        assert_eq!(world.query::<NonOwning<&u32,&u64>>().count(),0);
+    }
+
+    #[test]
+    fn bundle_test() {
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct Transform(f32);
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct Mesh(u32);
+        #[derive(Debug,Clone,Copy,PartialEq)]
+        struct Visibility(bool);
+
+        let mut world = World::new();
+        world.register::<Mesh>();
+        // registered components are skipped
+        world.register_bundle::<(Transform,Mesh,Visibility)>()
+            .register_bundle::<(Transform,char)>();
+        assert!(world.has_registered::<Transform>());
+        assert!(world.has_registered::<Visibility>());
+        assert!(world.has_registered::<char>());
+
+        world.make_group(full_owning::<Transform,Visibility>());
+        world.make_group(partial_owning::<Mesh,char>());
+        world.create_entity().attach(Transform(0.0));
+        let ids = (0..6).map(|i| {
+            if i % 2 == 0 {
+                world.create_entity_with_bundle((Transform(i as f32),Mesh(i),Visibility(true))).into_id()
+            } else {
+                world.create_entity()
+                    .attach_bundle((Mesh(i),'a'))
+                    .into_id()
+            }
+        }).collect::<Vec<_>>();
+        assert!(world.has_bundle::<(Transform,Mesh,Visibility)>(ids[0]));
+        assert!(!world.has_bundle::<(Transform,Mesh,Visibility)>(ids[1]));
+
+        let items = world.query::<FullOwning<&Transform,&Visibility>>()
+            .map(|(t,v)|(*t,*v))
+            .collect::<Vec<_>>();
+        assert_eq!(items,vec![
+            (Transform(0.0),Visibility(true)),
+            (Transform(2.0),Visibility(true)),
+            (Transform(4.0),Visibility(true))
+        ]);
+        assert_eq!(world.query::<PartialOwning<&Mesh,&char>>().count(),3);
+
+        world.entity(ids[1]).unwrap().attach_bundle((Transform(1.0),Visibility(false)));
+        assert_eq!(world.group_len_of::<Transform,Visibility>(),Some(4));
+        assert_eq!(world.group_contains(full_owning::<Transform,Visibility>(),ids[1]),Some(true));
+        assert_eq!(world.query::<FullOwning<&Transform,&Visibility>>()
+            .filter(|(t,v)|t.0 as u32 % 2 == 1 && !v.0)
+            .count(),1);
+    }
+
+    #[test]
+    fn with_bundle_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<f32>();
+        for i in 0..20_u32 {
+            let entity = world.create_entity().attach(i);
+            let entity = if i % 2 == 0 { entity.attach('a') } else { entity };
+            if i % 3 == 0 { entity.attach(i as f32); }
+        }
+        let expected = world.query::<(&u32,&char,&f32)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        let filtered = world.query::<(&u32,crate::query::WithBundle<(char,f32)>)>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        assert_eq!(filtered,expected);
+        let mut alone = world.query::<crate::query::WithBundle<(f32,char)>>()
+            .with_id()
+            .map(|(id,_)|id)
+            .collect::<Vec<_>>();
+        alone.sort();
+        assert_eq!(alone,expected);
+    }
+
+
+    #[test]
+    fn remove_component_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>()
+            .register::<i32>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<i16,i32>());
+        let ids = (0..5_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach('a')
+                .attach(i as u64)
+                .attach(i as i8)
+                .attach(i as i16)
+                .attach(i as i32)
+                .into_id()
+        }).collect::<Vec<_>>();
+
+        assert_eq!(world.remove_component::<u32>(ids[1]),Some(1));
+        assert_eq!(world.remove_component::<u32>(ids[1]),None);
+        assert_eq!(world.remove_component::<u64>(ids[2]),Some(2));
+        assert_eq!(world.remove_component::<i32>(ids[3]),Some(3));
+        assert_eq!(world.remove_component::<i32>(ids[3]),None);
+
+        assert_eq!(world.group_len_of::<u32,char>(),Some(4));
+        let full = world.query::<FullOwning<&u32,&char>>()
+            .map(|(a,_)|*a)
+            .collect::<Vec<_>>();
+        assert_eq!(full.len(),4);
+        assert!(!full.contains(&1));
+        assert_eq!(world.query::<(&u32,&char)>().count(),4);
+
+        assert!(world.query::<PartialOwning<&u64,&i8>>().all(|(a,_)|*a != 2));
+        assert_eq!(world.query::<PartialOwning<&u64,&i8>>().count(),4);
+        assert_eq!(world.query::<(&u64,&i8)>().count(),4);
+
+        assert!(world.query::<NonOwning<&i16,&i32>>().all(|(a,_)|*a != 3));
+        assert_eq!(world.query::<NonOwning<&i16,&i32>>().count(),4);
+
+        world.remove_entity(ids[4]);
+        assert_eq!(world.remove_component::<u32>(ids[4]),None);
     }
+
+
+    #[test]
+    fn despawn_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(non_owning::<u64,i8>());
+        let ids = (0..4_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach('a')
+                .attach(i as u64)
+                .attach(i as i8)
+                .into_id()
+        }).collect::<Vec<_>>();
+
+        assert!(world.despawn(ids[1]));
+        assert_eq!(world.entity_stats().alive,3);
+        assert!(!world.has_component::<u32>(ids[1]));
+        assert!(!world.has_component::<u64>(ids[1]));
+        assert_eq!(world.group_len_of::<u32,char>(),Some(3));
+        assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),3);
+        assert_eq!(world.query::<NonOwning<&u64,&i8>>().count(),3);
+        assert!(world.query::<&u32>().all(|x|*x != 1));
+
+        world.entity(ids[2]).unwrap().despawn();
+        assert_eq!(world.entity_stats().alive,2);
+
+        // the despawned IDs are reused
+        let high_water_mark = world.entity_stats().high_water_mark;
+        let a = world.create_entity().into_id();
+        let b = world.create_entity().into_id();
+        let mut reused = vec![a.index(),b.index()];
+        reused.sort();
+        assert_eq!(reused,vec![ids[1].index(),ids[2].index()]);
+        assert_eq!(world.entity_stats().high_water_mark,high_water_mark);
+        assert!(world.components_of(a).is_empty());
+    }
+
+
+    #[test]
+    fn add_component_test() {
+        use crate::XecsError;
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>()
+            .register::<i32>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<i16,i32>());
+        let ids = (0..6_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach(i as u64)
+                .attach(i as i16)
+                .into_id()
+        }).collect::<Vec<_>>();
+        assert_eq!(world.group_len_of::<u32,char>(),Some(0));
+
+        let check = |world : &World| {
+            let full = world.query::<FullOwning<&u32,&char>>().count();
+            assert_eq!(world.group_len_of::<u32,char>(),Some(full));
+            assert_eq!(world.query::<(&u32,&char)>().count(),full);
+            assert_eq!(world.query::<PartialOwning<&u64,&i8>>().count(),
+                       world.query::<(&u64,&i8)>().count());
+            assert_eq!(world.query::<NonOwning<&i16,&i32>>().count(),
+                       world.query::<(&i16,&i32)>().count());
+        };
+
+        for (index,id) in ids.iter().enumerate() {
+            if index % 2 == 0 {
+                world.add_component(*id,'a').unwrap();
+            }
+            if index % 3 == 0 {
+                world.add_component(*id,index as i8).unwrap();
+                world.add_component(*id,index as i32).unwrap();
+            }
+            check(&world);
+        }
+        assert_eq!(world.group_len_of::<u32,char>(),Some(3));
+        // replace an existing component
+        world.add_component(ids[0],'b').unwrap();
+        assert_eq!(*world.get::<char>(ids[0]).unwrap(),'b');
+        assert_eq!(world.group_len_of::<u32,char>(),Some(3));
+        check(&world);
+
+        assert_eq!(world.try_remove_component::<char>(ids[2]),Ok('a'));
+        assert_eq!(world.try_remove_component::<i8>(ids[3]),Ok(3));
+        assert_eq!(world.try_remove_component::<i32>(ids[0]),Ok(0));
+        check(&world);
+        assert_eq!(world.query::<(&u64,&i8)>().count(),1);
+        assert_eq!(world.query::<(&i16,&i32)>().count(),1);
+
+        assert_eq!(world.try_remove_component::<char>(ids[2]),Err(XecsError::ComponentNotFound {
+            entity_id : ids[2],
+            component : "char"
+        }));
+        assert_eq!(world.add_component(ids[0],1.0_f32),Err(XecsError::ComponentNotRegistered("f32")));
+        assert_eq!(world.try_remove_component::<f32>(ids[0]),Err(XecsError::ComponentNotRegistered("f32")));
+        world.remove_entity(ids[5]);
+        assert_eq!(world.add_component(ids[5],'c'),Err(XecsError::EntityNotFound(ids[5])));
+        assert_eq!(world.try_remove_component::<u32>(ids[5]),Err(XecsError::EntityNotFound(ids[5])));
+        check(&world);
+    }
+
+    #[test]
+    fn add_component_in_place_test() {
+        use crate::query::Changed;
+
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>();
+        world.make_group(full_owning::<u32,char>());
+        let ids = (0..4_u32).map(|i| {
+            world.create_entity()
+                .attach(i)
+                .attach('a')
+                .into_id()
+        }).collect::<Vec<_>>();
+        let order = |world : &World| {
+            (world.query::<&u32>().with_id().map(|(id,_)|id).collect::<Vec<_>>(),
+             world.query::<FullOwning<&u32,&char>>().with_id().map(|(id,_)|id).collect::<Vec<_>>())
+        };
+        let before = order(&world);
+        world.tick();
+        world.tick();
+
+        world.add_component(ids[1],10_u32).unwrap();
+        assert_eq!(order(&world),before);
+        assert_eq!(world.get::<u32>(ids[1]).as_deref(),Some(&10));
+        // replacing is a change,not an addition
+        assert_eq!(world.query::<Changed<u32>>().with_id().map(|(id,_)|id).collect::<Vec<_>>(),vec![ids[1]]);
+        assert_eq!(world.query::<Added<u32>>().count(),0);
+    }
+
+
+    #[test]
+    fn remove_entity_recycle_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<char,i16>());
+        let create = |world : &World,i : u32| {
+            world.create_entity()
+                .attach(i)
+                .attach('a')
+                .attach(i as u64)
+                .attach(i as i8)
+                .attach(i as i16)
+                .into_id()
+        };
+        let check = |world : &World,count : usize| {
+            assert_eq!(world.group_len_of::<u32,char>(),Some(count));
+            assert_eq!(world.query::<FullOwning<&u32,&char>>().count(),count);
+            assert_eq!(world.query::<PartialOwning<&u64,&i8>>().count(),count);
+            assert_eq!(world.query::<NonOwning<&char,&i16>>().count(),count);
+            assert_eq!(world.query::<(&u32,&u64,&i16)>().count(),count);
+            for (id,(a,b)) in world.query::<(&u32,&u64)>().with_id() {
+                assert_eq!(*a as u64,*b);
+                assert_eq!(*world.get::<i16>(id).unwrap(),*a as i16);
+            }
+        };
+        let mut ids = (0..10).map(|i|create(&world,i)).collect::<Vec<_>>();
+        for round in 0..3_u32 {
+            for index in [7,2,5,0] {
+                assert!(world.remove_entity(ids[index]));
+                assert!(!world.remove_entity(ids[index]));
+            }
+            check(&world,6);
+            let mut removed = [7,2,5,0].iter().map(|index|ids[*index].index()).collect::<Vec<_>>();
+            for index in [7,2,5,0] {
+                ids[index] = create(&world,100 * (round + 1) + index as u32);
+            }
+            let mut recycled = [7,2,5,0].iter().map(|index|ids[*index].index()).collect::<Vec<_>>();
+            removed.sort();
+            recycled.sort();
+            assert_eq!(removed,recycled);
+            assert_eq!(world.entity_stats().high_water_mark,10);
+            check(&world,10);
+        }
+    }
+
+    #[test]
+    fn group_handle_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>()
+            .register::<i8>()
+            .register::<i16>();
+        for i in 0..30_u32 {
+            let id = world.create_entity().attach(i).into_id();
+            if i % 2 == 0 {
+                world.attach_component(id,'a');
+                world.attach_component(id,i as u64);
+            }
+            if i % 3 == 0 {
+                world.attach_component(id,i as i8);
+                world.attach_component(id,i as i16);
+            }
+        }
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(partial_owning::<u64,i8>());
+        world.make_group(non_owning::<i8,i16>());
+        assert!(world.group_handle(full_owning::<char,u32>()).is_none());
+
+        let full = world.group_handle(full_owning::<u32,char>()).unwrap();
+        let partial = world.group_handle(partial_owning::<u64,i8>()).unwrap();
+        let non = world.group_handle(non_owning::<i8,i16>()).unwrap();
+
+        for (x,_) in world.query_group::<FullOwning<&mut u32,&char>>(&full).unwrap() {
+            *x += 100;
+        }
+        for (_,y) in world.query_group::<PartialOwning<&u64,&mut i8>>(&partial).unwrap() {
+            *y = -*y;
+        }
+        for (_,z) in world.query_group::<NonOwning<&i8,&mut i16>>(&non).unwrap() {
+            *z += 1;
+        }
+
+        // the handle path is the same as the descriptor path
+        let by_handle = world.query_group::<FullOwning<&u32,&char>>(&full).unwrap()
+            .with_id()
+            .collect::<Vec<_>>();
+        let by_descriptor = world.query::<FullOwning<&u32,&char>>()
+            .with_id()
+            .collect::<Vec<_>>();
+        assert_eq!(by_handle.len(),15);
+        assert!(by_handle.iter().all(|(_,(x,_))|**x >= 100));
+        assert_eq!(by_handle,by_descriptor);
+
+        let by_handle = world.query_group::<PartialOwning<&u64,&i8>>(&partial).unwrap()
+            .with_id()
+            .collect::<Vec<_>>();
+        let by_descriptor = world.query::<PartialOwning<&u64,&i8>>()
+            .with_id()
+            .collect::<Vec<_>>();
+        assert_eq!(by_handle.len(),5);
+        assert!(by_handle.iter().all(|(_,(_,y))|**y <= 0));
+        assert_eq!(by_handle,by_descriptor);
+
+        let by_handle = world.query_group::<NonOwning<&i8,&i16>>(&non).unwrap()
+            .with_id()
+            .collect::<Vec<_>>();
+        let by_descriptor = world.query::<NonOwning<&i8,&i16>>()
+            .with_id()
+            .collect::<Vec<_>>();
+        assert_eq!(by_handle.len(),10);
+        assert!(by_handle.iter().all(|(_,(y,z))|**z == (**y as i16).abs() + 1));
+        assert_eq!(by_handle,by_descriptor);
+    }
+
+    #[test]
+    fn group_handle_stale_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        for i in 0..10_u32 {
+            world.create_entity().attach(i).attach('a').attach(i as u64);
+        }
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(non_owning::<u32,u64>());
+        let full = world.group_handle(full_owning::<u32,char>()).unwrap();
+        let non = world.group_handle(non_owning::<u32,u64>()).unwrap();
+
+        // making a group keeps the handles
+        world.make_group(non_owning::<u64,char>());
+        assert_eq!(world.query_group::<NonOwning<&u32,&u64>>(&non).unwrap().count(),10);
+
+        assert!(world.remove_group(full_owning::<u32,char>()));
+        assert!(!world.remove_group(full_owning::<u32,char>()));
+        assert!(world.group_handle(full_owning::<u32,char>()).is_none());
+        assert!(world.query_group::<FullOwning<&u32,&char>>(&full).is_none());
+        // the index of non-owning group is shifted
+        assert!(world.query_group::<NonOwning<&u32,&u64>>(&non).is_none());
+        let non = world.group_handle(non_owning::<u32,u64>()).unwrap();
+        assert_eq!(world.query_group::<NonOwning<&u32,&u64>>(&non).unwrap().count(),10);
+
+        // the storages are still usable
+        world.create_entity().attach(10_u32).attach('b');
+        assert_eq!(world.query::<(&u32,&char)>().count(),11);
+
+        // a re-made group needs a new handle
+        world.make_group(full_owning::<u32,u64>());
+        assert!(world.query_group::<FullOwning<&u32,&char>>(&full).is_none());
+        let handle = world.group_handle(full_owning::<u32,u64>()).unwrap();
+        assert_eq!(world.query_group::<FullOwning<&u32,&u64>>(&handle).unwrap().count(),10);
+    }
+
+    #[test]
+    #[ignore]
+    fn group_handle_bench_test() {
+        use std::time::Instant;
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u8>()
+            .register::<u16>()
+            .register::<i32>()
+            .register::<i64>();
+        // some groups before the queried one
+        world.make_group(non_owning::<u8,u16>());
+        world.make_group(non_owning::<u8,i32>());
+        world.make_group(non_owning::<u8,i64>());
+        world.make_group(non_owning::<u16,i32>());
+        world.make_group(non_owning::<u16,i64>());
+        world.make_group(non_owning::<i32,i64>());
+        world.make_group(full_owning::<u32,char>());
+        for i in 0..100_u32 {
+            world.create_entity().attach(i).attach('a');
+        }
+        let rounds = 100_000;
+
+        let start = Instant::now();
+        let mut count = 0;
+        for _ in 0..rounds {
+            count += world.query::<FullOwning<&u32,&char>>().size_hint().0;
+        }
+        let descriptor_time = start.elapsed();
+
+        let handle = world.group_handle(full_owning::<u32,char>()).unwrap();
+        let start = Instant::now();
+        for _ in 0..rounds {
+            count -= world.query_group::<FullOwning<&u32,&char>>(&handle).unwrap().size_hint().0;
+        }
+        let handle_time = start.elapsed();
+
+        assert_eq!(count,0);
+        println!("descriptor:{:?} handle:{:?}",descriptor_time,handle_time);
+    }
+
+    #[test]
+    fn batch_attach_test() {
+        let mut world = World::new();
+        world.register::<u32>()
+            .register::<char>()
+            .register::<u64>();
+        world.make_group(full_owning::<u32,char>());
+        world.make_group(non_owning::<u32,u64>());
+        let ids = world.batch_create_with(10,|i|i as u32);
+        assert_eq!(ids.len(),10);
+        assert!(ids.iter().all(|id|world.is_alive(*id)));
+        assert_eq!(world.get::<u32>(ids[7]).as_deref(),Some(&7));
+
+        let odd = ids.iter().copied().skip(1).step_by(2).collect::<Vec<_>>();
+        world.batch_attach(&odd,vec!['a';5]);
+        world.batch_attach(&ids[..4],vec![0_u64;4]);
+        let mut full = world.query::<FullOwning<&u32,&char>>()
+            .map(|(x,_)|*x)
+            .collect::<Vec<_>>();
+        full.sort();
+        assert_eq!(full,vec![1,3,5,7,9]);
+        let mut non = world.query::<NonOwning<&u32,&u64>>()
+            .map(|(x,_)|*x)
+            .collect::<Vec<_>>();
+        non.sort();
+        assert_eq!(non,vec![0,1,2,3]);
+        // the batch is marked as added
+        assert_eq!(world.query::<Added<u64>>().count(),4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn batch_attach_twice_test() {
+        let mut world = World::new();
+        world.register::<u32>();
+        let ids = world.batch_create_with(3,|i|i as u32);
+        world.batch_attach(&ids[1..],vec![1_u32,2]);
+    }
+
+    #[test]
+    #[ignore]
+    fn batch_attach_bench_test() {
+        use std::time::Instant;
+        let count = 1_000_000;
+        let build = || {
+            let mut world = World::new();
+            world.register::<u32>()
+                .register::<char>();
+            world.make_group(non_owning::<u32,char>());
+            world
+        };
+
+        let world = build();
+        let start = Instant::now();
+        for i in 0..count {
+            world.create_entity().attach(i as u32);
+        }
+        let single_time = start.elapsed();
+
+        let world = build();
+        let start = Instant::now();
+        world.batch_create_with(count,|i|i as u32);
+        let batch_time = start.elapsed();
+
+        assert_eq!(world.query::<&u32>().count(),count);
+        println!("single:{:?} batch:{:?}",single_time,batch_time);
+    }
+
 }
@@ -0,0 +1,56 @@
+use std::{alloc::{GlobalAlloc, Layout, System}, cell::Cell, mem::ManuallyDrop};
+use xecs::{ErasedValue, World};
+
+/// Count the heap allocations of current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS : Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count|count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr,layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR : CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count|count.get())
+}
+
+#[test]
+fn attach_erased_without_allocation() {
+    let mut world = World::new();
+    world.register::<u64>();
+    let ids = (0..1000)
+        .map(|_|world.create_entity().attach(0_u64).into_id())
+        .collect::<Vec<_>>();
+    // keep the capacity of storage, so the inserts below don't grow it
+    for id in &ids {
+        world.detach_component::<u64>(*id);
+    }
+
+    let before = allocations();
+    for (value,id) in ids.iter().enumerate() {
+        let mut value = ManuallyDrop::new(value as u64);
+        world.attach_erased(*id,unsafe { ErasedValue::new(&mut value) });
+    }
+    assert_eq!(allocations() - before,0);
+
+    // replacing existing components doesn't allocate either
+    let before = allocations();
+    for id in &ids {
+        let mut value = ManuallyDrop::new(7_u64);
+        world.attach_erased(*id,unsafe { ErasedValue::new(&mut value) });
+    }
+    assert_eq!(allocations() - before,0);
+    assert!(world.query::<&u64>().all(|value|*value == 7));
+}
@@ -0,0 +1,59 @@
+use xecs::{group::{full_owning, non_owning, partial_owning}, test_util::{OpMix, ScenarioBuilder}};
+
+const MIX : OpMix = OpMix {
+    attach : 0.3,
+    detach : 0.2,
+    despawn : 0.1,
+    spawn : 0.4
+};
+
+#[test]
+fn full_owning_scenario() {
+    ScenarioBuilder::seed(1234)
+        .entities(10_000)
+        .component::<u32>(0.7)
+        .component::<u64>(0.3)
+        .group(full_owning::<u32,u64>())
+        .ops(5_000,MIX)
+        .snapshot_every(500)
+        .run()
+        .check_golden("tests/golden/full_owning.txt");
+}
+
+#[test]
+fn partial_owning_scenario() {
+    ScenarioBuilder::seed(5678)
+        .entities(10_000)
+        .component::<i32>(0.5)
+        .component::<char>(0.6)
+        .group(partial_owning::<i32,char>())
+        .ops(5_000,MIX)
+        .snapshot_every(500)
+        .run()
+        .check_golden("tests/golden/partial_owning.txt");
+}
+
+#[test]
+fn non_owning_scenario() {
+    ScenarioBuilder::seed(9012)
+        .entities(10_000)
+        .component::<u16>(0.4)
+        .component::<i64>(0.8)
+        .group(non_owning::<u16,i64>())
+        .ops(5_000,MIX)
+        .snapshot_every(500)
+        .run()
+        .check_golden("tests/golden/non_owning.txt");
+}
+
+#[test]
+fn same_seed_same_digests() {
+    let build = || ScenarioBuilder::seed(42)
+        .entities(1_000)
+        .component::<u32>(0.5)
+        .component::<bool>(0.5)
+        .group(full_owning::<u32,bool>())
+        .ops(1_000,MIX)
+        .run();
+    assert_eq!(build().digest_text(),build().digest_text());
+}